@@ -0,0 +1,113 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use dxf_welder::dxf::*;
+use dxf_welder::dxf_process::{layer_chains, DxfConfig};
+use dxf_welder::report::ChainReport;
+use std::hint::black_box;
+
+// tessellation resolution of the synthetic inputs, finer than the weld resolution
+const CHORD_ERROR: f64 = 0.001;
+
+fn p(x: f64, y: f64) -> Point {
+    Point { x, y }
+}
+
+fn flattened(entities: Vec<Entity>) -> Drawing {
+    let mut drawing = Drawing::new(entities);
+    drawing.flatten(CHORD_ERROR);
+    drawing
+}
+
+// a grid of circles of varying radius
+fn circles() -> Drawing {
+    flattened((0..100).map(|i| Entity::Circle {
+        center: p((i % 10) as f64 * 30.0, (i / 10) as f64 * 30.0),
+        radius: 2.0 + (i % 7) as f64 * 2.0,
+    }).collect())
+}
+
+// wavy cubic splines, where arcs only fit short stretches
+fn splines() -> Drawing {
+    flattened((0..20).map(|row| Entity::Polyline {
+        curve_type: 6,
+        vertices: (0..40).map(|i| p(i as f64 * 5.0, row as f64 * 20.0 + if i % 2 == 0 { 4.0 } else { -4.0 })).collect(),
+        widths: vec![],
+    }).collect())
+}
+
+// arcs whose points are jittered by up to 0.02, from a fixed-seed generator
+fn noisy_arcs() -> Drawing {
+    let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+    let mut jitter = move || {
+        seed = seed.wrapping_mul(6_364_136_223_846_793_005).wrapping_add(1_442_695_040_888_963_407);
+        ((seed >> 11) as f64 / (1u64 << 53) as f64 - 0.5) * 0.04
+    };
+    let mut entities = vec![];
+    for i in 0..50 {
+        let center = p((i % 10) as f64 * 50.0, (i / 10) as f64 * 50.0);
+        let points = (0..=200).map(|j| {
+            let point = center.polar(20.0, 180.0 - j as f64 * 0.9);
+            p(point.x + jitter(), point.y + jitter())
+        }).collect::<Vec<_>>();
+        entities.extend(points.windows(2).map(|pair| Entity::Line(pair[0].clone(), pair[1].clone())));
+    }
+    Drawing::new(entities)
+}
+
+// long collinear runs, merged into single lines without any circle fitting
+fn straight_runs() -> Drawing {
+    let mut entities = vec![];
+    for row in 0..20 {
+        let y = row as f64 * 10.0;
+        entities.extend((0..500).map(|i| Entity::Line(p(i as f64, y), p(i as f64 + 1.0, y))));
+    }
+    Drawing::new(entities)
+}
+
+fn fixture(name: &str) -> Drawing {
+    let path = format!("{}/benches/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    Drawing::parse(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+fn inputs() -> Vec<(&'static str, Drawing)> {
+    vec![
+        ("circles", circles()),
+        ("splines", splines()),
+        ("noisy_arcs", noisy_arcs()),
+        ("straight_runs", straight_runs()),
+        ("plate.dxf", fixture("plate.dxf")),
+    ]
+}
+
+fn bench_phases(c: &mut Criterion) {
+    let config = DxfConfig::default();
+    for (name, drawing) in inputs() {
+        let text = drawing.to_string();
+        let chains = drawing.layers.iter().map(|layer| layer_chains(&layer.entities).unwrap()).collect::<Vec<_>>();
+        let welded = config.process_drawing(drawing.clone(), &mut Default::default()).unwrap();
+
+        c.bench_with_input(BenchmarkId::new("parse", name), &text, |b, text| b.iter(|| Drawing::parse(black_box(text)).unwrap()));
+        c.bench_with_input(BenchmarkId::new("chain", name), &drawing, |b, drawing| b.iter(|| {
+            drawing.layers.iter().map(|layer| layer_chains(black_box(&layer.entities)).unwrap().len()).sum::<usize>()
+        }));
+        c.bench_with_input(BenchmarkId::new("fit", name), &chains, |b, chains| b.iter(|| {
+            chains.iter().flat_map(|layer| layer.iter())
+                .map(|chain| config.process_chain(black_box(chain), &mut ChainReport::default()).unwrap().len())
+                .sum::<usize>()
+        }));
+        c.bench_with_input(BenchmarkId::new("write", name), &welded, |b, welded| b.iter(|| black_box(welded).to_string()));
+    }
+}
+
+// coordinates as exporters usually write them, with a few decimals, which the number parser has a
+// fast path for
+fn bench_decimals(c: &mut Criterion) {
+    let round = |v: f64| (v * 1e4).round() / 1e4;
+    let text = Drawing::new((0..20_000).map(|i| {
+        let angle = i as f64 * 0.37;
+        Entity::Line(p(round(angle.cos() * 500.0), round(angle.sin() * 500.0)), p(round(i as f64 * 0.1234), round(-angle)))
+    }).collect()).to_string();
+    c.bench_function("parse/decimals", |b| b.iter(|| Drawing::parse(black_box(&text)).unwrap()));
+}
+
+criterion_group!(benches, bench_phases, bench_decimals);
+criterion_main!(benches);
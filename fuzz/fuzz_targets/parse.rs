@@ -0,0 +1,8 @@
+#![no_main]
+use dxf_welder::dxf::Drawing;
+use libfuzzer_sys::fuzz_target;
+
+// any input may be rejected, but none may panic
+fuzz_target!(|data: &[u8]| {
+    let _ = Drawing::parse_bytes(data);
+});
@@ -0,0 +1,21 @@
+#ifndef DXF_WELDER_H
+#define DXF_WELDER_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/*
+ * Welds the DXF text `in` using the JSON options `opts_json` (NULL for defaults).
+ * Returns 0 and sets *out on success, or returns -1 and sets *err to an error message.
+ * Strings returned through `out` and `err` must be released with dxf_weld_free.
+ */
+int dxf_weld(const char* in, const char* opts_json, char** out, char** err);
+
+void dxf_weld_free(char* text);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
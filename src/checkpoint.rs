@@ -0,0 +1,178 @@
+use crate::dxf::*;
+use crate::dxf_process::DxfConfig;
+use crate::output::write_atomic;
+use crate::pass::Pass;
+use crate::report::{ChainReport, WeldReport};
+use crate::result::*;
+use serde::{Serialize, Deserialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::Hasher;
+use std::path::Path;
+
+// the welded output and reports of one layer so far
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct LayerProgress {
+    pub name: String,
+    // input arcs and chains welded, in the order they are welded
+    pub pieces: usize,
+    pub entities: Vec<Entity>,
+    pub chains: Vec<ChainReport>,
+    pub finished: bool,
+}
+
+// Progress of a weld that can be saved and picked up again by `DxfConfig::resume_drawing`, so an
+// interrupted run over a huge drawing does not start over.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Checkpoint {
+    // identifies the drawing and settings the progress belongs to
+    pub key: String,
+    pub layers: Vec<LayerProgress>,
+    // every layer is welded
+    pub finished: bool,
+}
+
+// feeds serialized bytes straight into a hasher
+struct HashWriter<'a>(&'a mut DefaultHasher);
+
+impl std::io::Write for HashWriter<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Checkpoint {
+    pub fn new(key: String) -> Checkpoint {
+        Checkpoint {
+            key,
+            ..Default::default()
+        }
+    }
+
+    // a hash of the settings and the drawing about to be welded
+    pub fn key(config: &DxfConfig, drawing: &Drawing) -> Result<String> {
+        let mut hasher = DefaultHasher::new();
+        serde_json::to_writer(HashWriter(&mut hasher), config)?;
+        serde_json::to_writer(HashWriter(&mut hasher), drawing)?;
+        Ok(format!("{:016x}", hasher.finish()))
+    }
+
+    // None when there is no checkpoint at `path`
+    pub fn load(path: &Path) -> Result<Option<Checkpoint>> {
+        match std::fs::read(path) {
+            Ok(saved) => Ok(Some(serde_json::from_slice(&saved)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_atomic(path, &serde_json::to_vec(self)?, false)
+    }
+}
+
+impl DxfConfig {
+    // Welds like `process_drawing`, skipping the layers and chains `checkpoint` already holds and
+    // recording each chain welded in it before calling `save`. `checkpoint.finished` is set for the
+    // last call, after which the output is moved out of the checkpoint into the returned drawing.
+    pub fn resume_drawing(&self, mut drawing: Drawing, report: &mut WeldReport, checkpoint: &mut Checkpoint, save: &mut dyn FnMut(&Checkpoint) -> Result<()>) -> Result<Drawing> {
+        self.before_weld.run(&mut drawing, report)?;
+        self.check_zero_length(&mut drawing, report)?;
+        self.limits.check_entities(drawing.entity_count())?;
+        report.input_entities += drawing.entity_count();
+        report.input_length += drawing.length();
+        if checkpoint.layers.len() > drawing.layers.len() {
+            return Err(weld_err!("checkpoint has more layers than the drawing"));
+        }
+        // blocks are welded again on every resume, being written ahead of the checkpointed layers
+        let mut offset = self.process_blocks(&mut drawing.blocks, report)?;
+        for (i, layer) in drawing.layers.iter().enumerate() {
+            if i == checkpoint.layers.len() {
+                checkpoint.layers.push(LayerProgress {
+                    name: layer.name.clone(),
+                    ..Default::default()
+                });
+            }
+            if checkpoint.layers[i].name != layer.name {
+                return Err(weld_err!("checkpoint does not match the drawing at layer {}", layer.name));
+            }
+            if !checkpoint.layers[i].finished {
+                let progress = &checkpoint.layers[i];
+                let (start, skip) = (offset + progress.entities.len(), progress.pieces);
+                self.weld_layer(layer, start, skip, None, &mut report.timings, &mut |output, chain_report| {
+                    let progress = &mut checkpoint.layers[i];
+                    progress.pieces += 1;
+                    progress.entities.extend(output);
+                    progress.chains.extend(chain_report);
+                    save(checkpoint)
+                })?;
+                checkpoint.layers[i].finished = true;
+            }
+            offset += checkpoint.layers[i].entities.len();
+        }
+        checkpoint.finished = true;
+        save(checkpoint)?;
+        report.output_entities += offset;
+        let layers = drawing.layers.into_iter().zip(checkpoint.layers.iter_mut()).map(|(layer, progress)| {
+            report.chains.append(&mut progress.chains);
+            Layer {
+                entities: std::mem::take(&mut progress.entities),
+                ..layer
+            }
+        }).collect();
+        let mut welded = Drawing { layers, ..drawing };
+        report.output_length += welded.length();
+        self.after_weld.run(&mut welded, report)?;
+        Ok(welded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resume() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let mut entities = vec![];
+        for i in 0..3 {
+            let center = p(i as f64 * 30.0, 0.0);
+            let arc = (0..=8).map(|step| center.polar(10.0, 80.0 - step as f64 * 10.0)).collect::<Vec<_>>();
+            entities.extend(arc.windows(2).map(|pair| Entity::Line(pair[0].clone(), pair[1].clone())));
+        }
+        let drawing = Drawing::new(entities);
+        let config = DxfConfig::default();
+        let expected = config.process_drawing(drawing.clone(), &mut WeldReport::default()).unwrap();
+
+        // stop after the second chain, as if the run was killed
+        let key = Checkpoint::key(&config, &drawing).unwrap();
+        let mut checkpoint = Checkpoint::new(key.clone());
+        let mut saved = None;
+        let interrupted = config.resume_drawing(drawing.clone(), &mut WeldReport::default(), &mut checkpoint, &mut |progress| {
+            saved = Some(progress.clone());
+            if progress.layers[0].pieces == 2 { Err(weld_err!("interrupted")) } else { Ok(()) }
+        });
+        assert!(interrupted.is_err());
+        let mut checkpoint = saved.unwrap();
+        assert_eq!(checkpoint.key, key);
+
+        let mut welded = 0;
+        let mut report = WeldReport::default();
+        let resumed = config.resume_drawing(drawing.clone(), &mut report, &mut checkpoint, &mut |progress| {
+            welded += !progress.finished as usize;
+            Ok(())
+        }).unwrap();
+        assert_eq!(welded, 1);
+        assert_eq!(resumed, expected);
+        assert_eq!(report.chains.len(), 3);
+        assert_eq!(report.arcs().map(|arc| arc.entity).collect::<Vec<_>>(), vec![0, 1, 2]);
+
+        let mut other = drawing;
+        other.transform(&crate::transform::Transform::scale(2.0));
+        assert_ne!(Checkpoint::key(&config, &other).unwrap(), key);
+    }
+}
@@ -0,0 +1,560 @@
+use crate::dxf::*;
+use crate::path::find;
+use crate::report::HealReport;
+use std::collections::{BTreeMap, BTreeSet};
+use std::f64::consts::PI;
+
+// lines whose directions differ by less than this (radians) are candidates for overlap
+const ANGLE_TOLERANCE: f64 = 1e-6;
+
+struct Segment {
+    from: Point,
+    to: Point,
+    theta: f64,
+}
+
+impl Segment {
+    fn new(from: Point, to: Point) -> Segment {
+        let (mut dx, mut dy) = (to.x - from.x, to.y - from.y);
+        if dx < 0.0 || (dx == 0.0 && dy < 0.0) {
+            dx = -dx;
+            dy = -dy;
+        }
+        Segment {
+            from,
+            to,
+            theta: dy.atan2(dx),
+        }
+    }
+}
+
+// removes overlap within one set of collinear segments, returning the kept segments and how many pieces were dropped
+fn dedupe_collinear(segments: &[&Segment], tolerance: f64) -> (Vec<Entity>, usize) {
+    let origin = &segments[0].from;
+    let (ux, uy) = (segments[0].theta.cos(), segments[0].theta.sin());
+    let param = |p: &Point| (p.x - origin.x) * ux + (p.y - origin.y) * uy;
+
+    let mut breaks = segments.iter()
+        .flat_map(|s| vec![(param(&s.from), s.from.clone()), (param(&s.to), s.to.clone())])
+        .collect::<Vec<_>>();
+    breaks.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+    breaks.dedup_by(|b, a| b.0 - a.0 <= tolerance);
+
+    let mut kept = vec![];
+    let mut dropped = 0;
+    for pair in breaks.windows(2) {
+        let mid = (pair[0].0 + pair[1].0) / 2.0;
+        let mut covering = segments.iter().filter(|s| {
+            let (a, b) = (param(&s.from), param(&s.to));
+            a.min(b) < mid && mid < a.max(b)
+        });
+        let first = match covering.next() {
+            Some(first) => first,
+            None => continue,
+        };
+        dropped += covering.count();
+        // keep the direction of the first covering segment so chains still follow through
+        if param(&first.from) <= param(&first.to) {
+            kept.push(Entity::Line(pair[0].1.clone(), pair[1].1.clone()));
+        } else {
+            kept.push(Entity::Line(pair[1].1.clone(), pair[0].1.clone()));
+        }
+    }
+    (kept, dropped)
+}
+
+fn remove_layer_overlaps(layer: &mut Layer, tolerance: f64) -> usize {
+    let mut segments = vec![];
+    let mut others = vec![];
+    for entity in layer.entities.drain(..) {
+        match entity {
+            Entity::Line(from, to) => segments.push(Segment::new(from, to)),
+            other => others.push(other),
+        }
+    }
+    segments.sort_by(|a, b| a.theta.partial_cmp(&b.theta).unwrap());
+
+    // group by direction, joining the vertical seam where theta wraps from pi/2 to -pi/2
+    let mut direction_groups: Vec<Vec<&Segment>> = vec![];
+    for segment in segments.iter() {
+        match direction_groups.last_mut() {
+            Some(group) if segment.theta - group.last().unwrap().theta <= ANGLE_TOLERANCE => group.push(segment),
+            _ => direction_groups.push(vec![segment]),
+        }
+    }
+    if direction_groups.len() > 1 {
+        let first_theta = direction_groups[0][0].theta;
+        let last_theta = direction_groups.last().unwrap().last().unwrap().theta;
+        if first_theta + PI - last_theta <= ANGLE_TOLERANCE {
+            let last = direction_groups.pop().unwrap();
+            direction_groups[0].extend(last);
+        }
+    }
+
+    let mut dropped = 0;
+    for group in direction_groups.iter() {
+        // then by perpendicular offset from the origin
+        let (nx, ny) = (-group[0].theta.sin(), group[0].theta.cos());
+        let mut by_offset = group.iter().map(|s| (s.from.x * nx + s.from.y * ny, *s)).collect::<Vec<_>>();
+        by_offset.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let mut start = 0;
+        for i in 1..=by_offset.len() {
+            if i < by_offset.len() && by_offset[i].0 - by_offset[i - 1].0 <= tolerance {
+                continue;
+            }
+            let collinear = by_offset[start..i].iter().map(|(_, s)| *s).collect::<Vec<_>>();
+            if collinear.len() == 1 {
+                layer.entities.push(Entity::Line(collinear[0].from.clone(), collinear[0].to.clone()));
+            } else {
+                let (kept, removed) = dedupe_collinear(&collinear, tolerance);
+                layer.entities.extend(kept);
+                dropped += removed;
+            }
+            start = i;
+        }
+    }
+    layer.entities.extend(others);
+    dropped
+}
+
+// Removes doubled-up lines: collinear LINEs within `tolerance` of each other that overlap fully or
+// partially are cut at every endpoint and each piece is kept once. Returns the number of duplicate
+// pieces removed. Layers are handled separately.
+pub fn remove_overlaps(drawing: &mut Drawing, tolerance: f64) -> usize {
+    drawing.layers.iter_mut().map(|layer| remove_layer_overlaps(layer, tolerance)).sum()
+}
+
+fn remove_layer_small_features(layer: &mut Layer, min_size: f64, chord_error: f64) -> usize {
+    let outlines = layer.entities.iter().map(|e| e.tessellate(chord_error)).collect::<Vec<_>>();
+    // join entities sharing an endpoint, counting how often each endpoint is used
+    let mut parents = (0..outlines.len()).collect::<Vec<_>>();
+    let mut endpoints: BTreeMap<Point, (usize, usize)> = BTreeMap::new();
+    for (i, outline) in outlines.iter().enumerate() {
+        if outline.is_empty() {
+            continue;
+        }
+        for point in [outline.first().unwrap(), outline.last().unwrap()].iter() {
+            let entry = endpoints.entry((*point).clone()).or_insert((i, 0));
+            entry.1 += 1;
+            let (a, b) = (find(&mut parents, entry.0), find(&mut parents, i));
+            parents[a] = b;
+        }
+    }
+    let mut open = vec![false; outlines.len()];
+    for (owner, count) in endpoints.values() {
+        if *count != 2 {
+            let root = find(&mut parents, *owner);
+            open[root] = true;
+        }
+    }
+
+    let mut loops: BTreeMap<usize, (Point, Point, f64)> = BTreeMap::new();
+    for (i, outline) in outlines.iter().enumerate() {
+        let root = find(&mut parents, i);
+        if open[root] || outline.is_empty() {
+            continue;
+        }
+        let (min, max, perimeter) = loops.entry(root).or_insert((outline[0].clone(), outline[0].clone(), 0.0));
+        for point in outline.iter() {
+            *min = Point { x: min.x.min(point.x), y: min.y.min(point.y) };
+            *max = Point { x: max.x.max(point.x), y: max.y.max(point.y) };
+        }
+        *perimeter += outline.windows(2).map(|pair| pair[0].dist(&pair[1])).sum::<f64>();
+    }
+    let small = loops.into_iter()
+        .filter(|(_, (min, max, perimeter))| (max.x - min.x).max(max.y - min.y) < min_size || *perimeter < min_size)
+        .map(|(root, _)| root)
+        .collect::<Vec<_>>();
+
+    let entities = std::mem::take(&mut layer.entities);
+    for (i, entity) in entities.into_iter().enumerate() {
+        if !small.contains(&find(&mut parents, i)) {
+            layer.entities.push(entity);
+        }
+    }
+    small.len()
+}
+
+// Drops closed loops (circles, closed polylines and rings of connected entities) whose bounding box or
+// perimeter is smaller than `min_size`, such as tessellation dust or specks from traced bitmaps. Returns
+// the number of loops removed.
+pub fn remove_small_features(drawing: &mut Drawing, min_size: f64, chord_error: f64) -> usize {
+    drawing.layers.iter_mut().map(|layer| remove_layer_small_features(layer, min_size, chord_error)).sum()
+}
+
+#[derive(Clone, Debug)]
+pub struct HealConfig {
+    // line endpoints closer than this are moved onto each other
+    pub snap_tolerance: f64,
+    // dangling endpoints up to this far apart are joined with a new line
+    pub max_gap: f64,
+}
+
+impl Default for HealConfig {
+    fn default() -> Self {
+        HealConfig {
+            snap_tolerance: 0.001,
+            max_gap: 0.05,
+        }
+    }
+}
+
+fn snap_layer(layer: &mut Layer, tolerance: f64) -> usize {
+    let cell = |v: f64| (v / tolerance).floor() as i64;
+    let mut grid: BTreeMap<(i64, i64), Vec<Point>> = BTreeMap::new();
+    let mut snapped = 0;
+    let mut snap = |point: &mut Point| {
+        let (cx, cy) = (cell(point.x), cell(point.y));
+        let existing = (cx - 1..=cx + 1)
+            .flat_map(|x| (cy - 1..=cy + 1).map(move |y| (x, y)))
+            .filter_map(|key| grid.get(&key))
+            .flatten()
+            .find(|other| other.dist(point) <= tolerance)
+            .cloned();
+        match existing {
+            Some(other) => {
+                if other.x != point.x || other.y != point.y {
+                    snapped += 1;
+                }
+                *point = other;
+            },
+            None => grid.entry((cx, cy)).or_default().push(point.clone()),
+        }
+    };
+    for entity in layer.entities.iter_mut() {
+        if let Entity::Line(from, to) = entity {
+            snap(from);
+            snap(to);
+        }
+    }
+    snapped
+}
+
+// Moves line endpoints within `tolerance` of an earlier endpoint onto it, so chains broken by tiny
+// gaps join up. Returns the number of endpoints moved.
+pub fn snap_endpoints(drawing: &mut Drawing, tolerance: f64) -> usize {
+    drawing.layers.iter_mut().map(|layer| snap_layer(layer, tolerance)).sum()
+}
+
+fn snap_layer_axes(layer: &mut Layer, max_degrees: f64) -> usize {
+    let mut ids: BTreeMap<Point, usize> = BTreeMap::new();
+    let mut points = vec![];
+    let mut id = |point: &Point| *ids.entry(point.clone()).or_insert_with(|| {
+        points.push(point.clone());
+        points.len() - 1
+    });
+    let lines = layer.entities.iter().filter_map(|entity| match entity {
+        Entity::Line(from, to) => Some((id(from), id(to))),
+        _ => None,
+    }).collect::<Vec<_>>();
+    // points joined by a nearly horizontal line share their y, and by a nearly vertical one their x
+    let mut rows = (0..points.len()).collect::<Vec<_>>();
+    let mut columns = rows.clone();
+    let mut straightened = 0;
+    for (a, b) in lines.iter() {
+        let (from, to) = (&points[*a], &points[*b]);
+        if from == to {
+            continue;
+        }
+        let angle = (to.y - from.y).atan2(to.x - from.x).to_degrees().rem_euclid(180.0);
+        let parents = if angle <= max_degrees || angle >= 180.0 - max_degrees {
+            straightened += (from.y != to.y) as usize;
+            &mut rows
+        } else if (angle - 90.0).abs() <= max_degrees {
+            straightened += (from.x != to.x) as usize;
+            &mut columns
+        } else {
+            continue;
+        };
+        let (a, b) = (find(parents, *a), find(parents, *b));
+        parents[a] = b;
+    }
+    // each group moves to its mean, which moves its points as little as possible
+    let means = |parents: &mut Vec<usize>, coordinate: fn(&Point) -> f64| {
+        let mut sums = vec![(0.0, 0usize); points.len()];
+        for (i, point) in points.iter().enumerate() {
+            let root = find(parents, i);
+            sums[root].0 += coordinate(point);
+            sums[root].1 += 1;
+        }
+        (0..points.len()).map(|i| {
+            let (sum, count) = sums[find(parents, i)];
+            sum / count as f64
+        }).collect::<Vec<_>>()
+    };
+    let (ys, xs) = (means(&mut rows, |point| point.y), means(&mut columns, |point| point.x));
+    let mut lines = lines.into_iter();
+    for entity in layer.entities.iter_mut() {
+        if let Entity::Line(from, to) = entity {
+            let (a, b) = lines.next().unwrap();
+            *from = Point { x: xs[a], y: ys[a] };
+            *to = Point { x: xs[b], y: ys[b] };
+        }
+    }
+    straightened
+}
+
+// Makes lines within `max_degrees` of horizontal or vertical exactly so, undoing the slight skew some
+// exporters introduce. Nearly horizontal lines joined end to end end up on one row at the mean of
+// their ends' y, and nearly vertical ones on one column, so connected lines stay connected. Returns
+// the number of lines straightened.
+pub fn snap_to_axes(drawing: &mut Drawing, max_degrees: f64) -> usize {
+    drawing.layers.iter_mut().map(|layer| snap_layer_axes(layer, max_degrees)).sum()
+}
+
+fn remove_layer_zero_length(layer: &mut Layer) -> usize {
+    let before = layer.entities.len();
+    layer.entities.retain(|entity| !matches!(entity, Entity::Line(from, to) if from == to));
+    before - layer.entities.len()
+}
+
+fn line_endpoint_counts(layer: &Layer) -> BTreeMap<Point, usize> {
+    let mut counts = BTreeMap::new();
+    for entity in layer.entities.iter() {
+        if let Entity::Line(from, to) = entity {
+            *counts.entry(from.clone()).or_insert(0) += 1;
+            *counts.entry(to.clone()).or_insert(0) += 1;
+        }
+    }
+    counts
+}
+
+// Pairs the dangling line ends on a layer up to `max_gap` apart, closest first and each end once,
+// leaving out the two ends of a lone line. Ends are looked up in a grid of `max_gap` cells.
+fn dangling_pairs(layer: &Layer, max_gap: f64) -> Vec<(f64, Point, Point)> {
+    if max_gap <= 0.0 {
+        return vec![];
+    }
+    let dangling = line_endpoint_counts(layer).into_iter().filter(|(_, count)| *count == 1).map(|(point, _)| point).collect::<Vec<_>>();
+    // the ends of a lone line are the line itself rather than a gap
+    let lone = layer.entities.iter().filter_map(|entity| match entity {
+        Entity::Line(from, to) => Some((dangling.binary_search(from).ok()?, dangling.binary_search(to).ok()?)),
+        _ => None,
+    }).flat_map(|(a, b)| [(a, b), (b, a)]).collect::<BTreeSet<_>>();
+    let cell = |v: f64| (v / max_gap).floor() as i64;
+    let mut grid: BTreeMap<(i64, i64), Vec<usize>> = BTreeMap::new();
+    for (i, point) in dangling.iter().enumerate() {
+        grid.entry((cell(point.x), cell(point.y))).or_default().push(i);
+    }
+    let mut candidates = vec![];
+    for (i, a) in dangling.iter().enumerate() {
+        let (cx, cy) = (cell(a.x), cell(a.y));
+        for j in (cx - 1..=cx + 1).flat_map(|x| (cy - 1..=cy + 1).map(move |y| (x, y))).filter_map(|key| grid.get(&key)).flatten() {
+            let gap = a.dist(&dangling[*j]);
+            if *j > i && gap <= max_gap && !lone.contains(&(i, *j)) {
+                candidates.push((gap, i, *j));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+    let mut paired = vec![false; dangling.len()];
+    candidates.into_iter().filter_map(|(gap, i, j)| {
+        if paired[i] || paired[j] {
+            return None;
+        }
+        paired[i] = true;
+        paired[j] = true;
+        Some((gap, dangling[i].clone(), dangling[j].clone()))
+    }).collect()
+}
+
+fn bridge_layer_gaps(layer: &mut Layer, max_gap: f64) -> usize {
+    let bridges = dangling_pairs(layer, max_gap).into_iter().map(|(_, a, b)| Entity::Line(a, b)).collect::<Vec<_>>();
+    let count = bridges.len();
+    layer.entities.extend(bridges);
+    count
+}
+
+fn layer_gaps(layer: &Layer, max_gap: f64) -> Vec<f64> {
+    dangling_pairs(layer, max_gap).into_iter().map(|(gap, _, _)| gap).collect()
+}
+
+// Sizes of the gaps of up to `max_gap` left between dangling line ends on each layer, which keep
+// chains from joining or closing. Ends are paired closest first and each one once, as `heal` bridges
+// them. Smallest first.
+pub fn gaps(drawing: &Drawing, max_gap: f64) -> Vec<f64> {
+    let mut gaps = drawing.layers.iter().flat_map(|layer| layer_gaps(layer, max_gap)).collect::<Vec<_>>();
+    gaps.sort_by(f64::total_cmp);
+    gaps
+}
+
+// reverses lines so each chain runs head to tail, listing the lines of a chain together
+fn orient_layer_chains(layer: &mut Layer) -> usize {
+    let counts = line_endpoint_counts(layer);
+    let mut lines = vec![];
+    let mut others = vec![];
+    for entity in layer.entities.drain(..) {
+        match entity {
+            Entity::Line(from, to) => lines.push(Some((from, to))),
+            other => others.push(other),
+        }
+    }
+    let mut by_point: BTreeMap<Point, Vec<usize>> = BTreeMap::new();
+    for (i, line) in lines.iter().enumerate() {
+        let (from, to) = line.as_ref().unwrap();
+        by_point.entry(from.clone()).or_default().push(i);
+        by_point.entry(to.clone()).or_default().push(i);
+    }
+    let mut reversed = 0;
+    // open chains are walked from their loose ends, then whatever is left is closed
+    let mut starts = (0..lines.len()).filter(|i| {
+        let (from, to) = lines[*i].as_ref().unwrap();
+        counts[from] != 2 || counts[to] != 2
+    }).collect::<Vec<_>>();
+    starts.extend(0..lines.len());
+    for start in starts {
+        let (from, to) = match lines[start].take() {
+            Some(line) => line,
+            None => continue,
+        };
+        let (from, to) = if counts[&from] != 2 || counts[&to] == 2 {
+            (from, to)
+        } else {
+            reversed += 1;
+            (to, from)
+        };
+        let mut cursor = to.clone();
+        layer.entities.push(Entity::Line(from, to));
+        while counts[&cursor] == 2 {
+            let next = match by_point[&cursor].iter().find(|i| lines[**i].is_some()) {
+                Some(next) => *next,
+                None => break,
+            };
+            let (from, to) = lines[next].take().unwrap();
+            let (from, to) = if from == cursor {
+                (from, to)
+            } else {
+                reversed += 1;
+                (to, from)
+            };
+            cursor = to.clone();
+            layer.entities.push(Entity::Line(from, to));
+        }
+    }
+    layer.entities.extend(others);
+    reversed
+}
+
+// Fixes common export defects in LINE geometry without welding: snaps nearly coincident endpoints,
+// drops zero-length and duplicate lines, bridges small gaps between dangling ends with new lines and
+// orients every chain head to tail.
+pub fn heal(drawing: &mut Drawing, config: &HealConfig) -> HealReport {
+    let mut report = HealReport::default();
+    for layer in drawing.layers.iter_mut() {
+        report.endpoints_snapped += snap_layer(layer, config.snap_tolerance);
+        report.zero_length_removed += remove_layer_zero_length(layer);
+        report.duplicates_removed += remove_layer_overlaps(layer, config.snap_tolerance);
+        report.gaps_bridged += bridge_layer_gaps(layer, config.max_gap);
+        report.lines_reversed += orient_layer_chains(layer);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remove_partial_overlap() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let mut drawing = Drawing::new(vec![
+            Entity::Line(p(0.0, 0.0), p(10.0, 0.0)),
+            Entity::Line(p(15.0, 0.0), p(5.0, 0.0)),
+            Entity::Line(p(0.0, 1.0), p(10.0, 1.0)),
+        ]);
+        assert_eq!(remove_overlaps(&mut drawing, 0.001), 1);
+        let entities = drawing.entities().collect::<Vec<_>>();
+        assert_eq!(entities.len(), 4);
+        for expected in [
+            Entity::Line(p(0.0, 0.0), p(5.0, 0.0)),
+            Entity::Line(p(5.0, 0.0), p(10.0, 0.0)),
+            Entity::Line(p(15.0, 0.0), p(10.0, 0.0)),
+            Entity::Line(p(0.0, 1.0), p(10.0, 1.0)),
+        ].iter() {
+            assert!(entities.contains(&expected), "missing {:?}", expected);
+        }
+    }
+
+    #[test]
+    fn test_remove_small_features() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let mut drawing = Drawing::new(vec![
+            Entity::Line(p(0.0, 0.0), p(0.1, 0.0)),
+            Entity::Line(p(0.1, 0.0), p(0.1, 0.1)),
+            Entity::Line(p(0.1, 0.1), p(0.0, 0.0)),
+            Entity::Circle { center: p(5.0, 5.0), radius: 0.05 },
+            Entity::Circle { center: p(5.0, 5.0), radius: 2.0 },
+            Entity::Line(p(10.0, 0.0), p(10.1, 0.0)),
+        ]);
+        assert_eq!(remove_small_features(&mut drawing, 0.5, 0.01), 2);
+        assert_eq!(drawing.entity_count(), 2);
+    }
+
+    #[test]
+    fn test_snap_to_axes() {
+        let p = |x: f64, y: f64| Point { x, y };
+        // a rectangle skewed by about 0.001 degrees, with a diagonal brace and a slanted line apart
+        let mut drawing = Drawing::new(vec![
+            Entity::Line(p(0.0, 0.0), p(100.0, 0.002)),
+            Entity::Line(p(100.0, 0.002), p(100.001, 50.0)),
+            Entity::Line(p(100.001, 50.0), p(0.0, 50.0)),
+            Entity::Line(p(0.0, 50.0), p(0.0, 0.0)),
+            Entity::Line(p(0.0, 0.0), p(100.001, 50.0)),
+            Entity::Line(p(200.0, 0.0), p(300.0, 1.0)),
+        ]);
+        assert_eq!(snap_to_axes(&mut drawing, 0.01), 2);
+        assert_eq!(drawing.layers[0].entities[..5], [
+            Entity::Line(p(0.0, 0.001), p(100.0005, 0.001)),
+            Entity::Line(p(100.0005, 0.001), p(100.0005, 50.0)),
+            Entity::Line(p(100.0005, 50.0), p(0.0, 50.0)),
+            Entity::Line(p(0.0, 50.0), p(0.0, 0.001)),
+            Entity::Line(p(0.0, 0.001), p(100.0005, 50.0)),
+        ]);
+        let exact = |entity: &Entity| matches!(entity, Entity::Line(from, to) if from.x == to.x || from.y == to.y);
+        assert!(drawing.layers[0].entities[..4].iter().all(exact));
+        assert_eq!(drawing.layers[0].entities[5], Entity::Line(p(200.0, 0.0), p(300.0, 1.0)));
+    }
+
+    #[test]
+    fn test_heal() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let mut drawing = Drawing::new(vec![
+            Entity::Line(p(0.0, 0.0), p(10.0, 0.0)),
+            Entity::Line(p(20.0, 0.0), p(10.0005, 0.0)),
+            Entity::Line(p(20.0, 0.0), p(20.0, 0.0)),
+            Entity::Line(p(20.01, 0.0), p(30.0, 0.0)),
+        ]);
+        let report = heal(&mut drawing, &HealConfig::default());
+        assert_eq!(report.endpoints_snapped, 1);
+        assert_eq!(report.zero_length_removed, 1);
+        assert_eq!(report.gaps_bridged, 1);
+        assert_eq!(report.lines_reversed, 1);
+        let entities = drawing.entities().collect::<Vec<_>>();
+        assert_eq!(entities.len(), 4);
+        for pair in entities.windows(2) {
+            match (pair[0], pair[1]) {
+                (Entity::Line(_, to), Entity::Line(from, _)) => assert_eq!(to, from),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_heal_settings() {
+        // a square drawn in microns, with gaps too wide for the default settings
+        let p = |x: f64, y: f64| Point { x, y };
+        let drawing = Drawing::new(vec![
+            Entity::Line(p(0.0, 0.0), p(1000.0, 0.0)),
+            Entity::Line(p(1000.0, 0.5), p(1000.0, 1000.0)),
+            Entity::Line(p(1000.0, 1000.0), p(0.0, 1000.0)),
+            Entity::Line(p(0.0, 999.998), p(0.0, 0.0)),
+        ]);
+        // the defaults only bridge the tiny gap and leave the square open
+        let report = heal(&mut drawing.clone(), &HealConfig::default());
+        assert_eq!((report.endpoints_snapped, report.gaps_bridged), (0, 1));
+        let mut healed = drawing;
+        let report = heal(&mut healed, &HealConfig { snap_tolerance: 0.01, max_gap: 1.0 });
+        assert_eq!((report.endpoints_snapped, report.gaps_bridged), (1, 1));
+        assert_eq!(gaps(&healed, 1.0), Vec::<f64>::new());
+    }
+}
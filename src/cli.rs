@@ -0,0 +1,580 @@
+use dxf_welder::weld_err;
+use dxf_welder::cleanup::HealConfig;
+use dxf_welder::dxf::{DxfVersion, HeaderOrigin};
+use dxf_welder::dxf_process::{Fitter, TextPolicy, ZeroLengthPolicy};
+use dxf_welder::preset::Preset;
+use dxf_welder::{gis, svg};
+use dxf_welder::result::*;
+use dxf_welder::transform::Transform;
+use std::collections::HashSet;
+
+#[derive(Debug, Default)]
+pub struct Options {
+    pub infiles: Vec<String>,
+    pub outfile: String,
+    pub mmap: bool,
+    pub preview: Option<String>,
+    pub report: Option<String>,
+    pub stats_json: Option<String>,
+    // print the time spent in each phase
+    pub stats: bool,
+    pub scale: Option<f64>,
+    pub rotate_deg: Option<f64>,
+    pub translate: Option<(f64, f64)>,
+    pub mirror_x: bool,
+    pub mirror_y: bool,
+    pub split_layers: bool,
+    pub split_colors: bool,
+    // columns and spacing
+    pub tile: Option<(usize, f64)>,
+    pub remove_overlaps: Option<f64>,
+    pub join_tolerance: Option<f64>,
+    // degrees off horizontal or vertical within which lines are straightened
+    pub snap_axes: Option<f64>,
+    pub min_feature_size: Option<f64>,
+    pub offset: Option<f64>,
+    pub optimize_start: bool,
+    pub holes_first: bool,
+    pub block_per_loop: bool,
+    pub split_parts: Option<PartOutput>,
+    // distance within which open geometry outside every loop joins a part
+    pub part_proximity: f64,
+    pub fail_on_intersection: bool,
+    pub fail_on_orphans: bool,
+    pub fail_if_compression_below: Option<f64>,
+    pub fail_if_deviation_above: Option<f64>,
+    pub heal_only: bool,
+    // see `HealConfig`, replacing the preset's or default values under --heal-only
+    pub snap_tolerance: Option<f64>,
+    pub max_gap: Option<f64>,
+    pub auto_resolution: bool,
+    pub max_deviation: Option<f64>,
+    // weld once per resolution, writing each output with the resolution as a suffix
+    pub resolutions: Option<Vec<f64>>,
+    pub fitter: Fitter,
+    pub fit_lines: bool,
+    pub merge_arcs: bool,
+    pub zero_length: ZeroLengthPolicy,
+    pub split_quadrants: bool,
+    pub no_circles: bool,
+    pub angle_precision: Option<usize>,
+    pub three_point_arcs: bool,
+    pub dxf_version: DxfVersion,
+    pub fragment: bool,
+    pub weld_blocks: bool,
+    pub debug_layer: Option<String>,
+    // share of the resolution an arc's residual must exceed to be marked
+    pub deviation_layer: Option<f64>,
+    // in bytes
+    pub max_memory: Option<usize>,
+    pub min_arc_segments: usize,
+    pub adaptive_min_segments: bool,
+    pub text_outlines: Option<TextPolicy>,
+    // drawing units per minute
+    pub feed_rate: Option<f64>,
+    // spacing of the grid output coordinates are snapped to
+    pub snap_grid: Option<f64>,
+    pub origin: Option<HeaderOrigin>,
+    pub max_points_per_fit: Option<usize>,
+    pub chunk_points: Option<usize>,
+    pub angular_tolerance: Option<f64>,
+    // indices of the only chains to weld, as listed by `info`
+    pub chains: Option<Vec<usize>>,
+    pub preset: Option<Preset>,
+    pub checkpoint: Option<String>,
+    pub backup: bool,
+    pub no_warn: bool,
+    // print no summary when the run ends
+    pub quiet: bool,
+    // print the summary as JSON on stdout instead
+    pub json: bool,
+    // read commas in DXF input as decimal separators
+    pub comma_decimals: bool,
+}
+
+fn flag_value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String> {
+    Ok(args.next().ok_or_else(|| weld_err!("missing value for {}", flag))?)
+}
+
+fn parse_number(value: &str, flag: &str) -> Result<f64> {
+    Ok(value.trim().parse::<f64>().map_err(|_| weld_err!("invalid number for {}: {}", flag, value))?)
+}
+
+// finite and above zero, as sizes and factors must be
+fn is_positive(value: f64) -> bool {
+    value.is_finite() && value > 0.0
+}
+
+fn parse_count(value: &str, flag: &str) -> Result<usize> {
+    Ok(value.trim().parse::<usize>().map_err(|_| weld_err!("invalid count for {}: {}", flag, value))?)
+}
+
+fn parse_pair(value: &str, flag: &str) -> Result<(f64, f64)> {
+    let mut parts = value.split(',');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(x), Some(y), None) => Ok((parse_number(x, flag)?, parse_number(y, flag)?)),
+        _ => Err(weld_err!("expected x,y for {}, got: {}", flag, value)),
+    }
+}
+
+// `4x10` is four columns with 10 units between parts
+fn parse_tile(value: &str) -> Result<(usize, f64)> {
+    let mut parts = value.splitn(2, 'x');
+    match (parts.next(), parts.next()) {
+        (Some(columns), Some(spacing)) => {
+            let columns = columns.trim().parse::<usize>().map_err(|_| weld_err!("invalid column count for --tile: {}", columns))?;
+            if columns == 0 {
+                return Err(weld_err!("--tile needs at least one column"));
+            }
+            let spacing = parse_number(spacing, "--tile")?;
+            if !spacing.is_finite() || spacing < 0.0 {
+                return Err(weld_err!("--tile spacing cannot be negative"));
+            }
+            Ok((columns, spacing))
+        },
+        _ => Err(weld_err!("expected COLSxSPACING for --tile, got: {}", value)),
+    }
+}
+
+fn parse_fitter(value: &str) -> Result<Fitter> {
+    match value {
+        "greedy" => Ok(Fitter::Greedy),
+        "ransac" => Ok(Fitter::Ransac),
+        _ => Err(weld_err!("expected greedy or ransac for --fitter, got: {}", value)),
+    }
+}
+
+fn parse_dxf_version(value: &str) -> Result<DxfVersion> {
+    match value.to_ascii_lowercase().as_str() {
+        "r12" => Ok(DxfVersion::R12),
+        "r2000" => Ok(DxfVersion::R2000),
+        _ => Err(weld_err!("expected r12 or r2000 for --dxf-version, got: {}", value)),
+    }
+}
+
+// `pass` to leave text unwelded, or the resolution to weld it at
+fn parse_text_policy(value: &str) -> Result<TextPolicy> {
+    match value {
+        "pass" => Ok(TextPolicy::PassThrough),
+        _ => match value.trim().parse::<f64>() {
+            Ok(resolution) if resolution > 0.0 => Ok(TextPolicy::Resolution(resolution)),
+            _ => Err(weld_err!("expected pass or a resolution for --text-outlines, got: {}", value)),
+        },
+    }
+}
+
+fn parse_zero_length(value: &str) -> Result<ZeroLengthPolicy> {
+    match value {
+        "drop" => Ok(ZeroLengthPolicy::Drop),
+        "error" => Ok(ZeroLengthPolicy::Error),
+        "keep" => Ok(ZeroLengthPolicy::Keep),
+        _ => Err(weld_err!("expected drop, error or keep for --zero-length, got: {}", value)),
+    }
+}
+
+// where --split-parts puts each part
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PartOutput {
+    Layers,
+    Blocks,
+}
+
+fn parse_part_output(value: &str) -> Result<PartOutput> {
+    match value {
+        "layers" => Ok(PartOutput::Layers),
+        "blocks" => Ok(PartOutput::Blocks),
+        _ => Err(weld_err!("expected layers or blocks for --split-parts, got: {}", value)),
+    }
+}
+
+fn parse_origin(value: &str) -> Result<HeaderOrigin> {
+    match value {
+        "ucs" => Ok(HeaderOrigin::Ucs),
+        "limits" => Ok(HeaderOrigin::Limits),
+        _ => Err(weld_err!("expected ucs or limits for --origin, got: {}", value)),
+    }
+}
+
+fn parse_preset(value: &str) -> Result<Preset> {
+    let names = Preset::ALL.iter().map(|preset| preset.name()).collect::<Vec<_>>();
+    Ok(Preset::from_name(value).ok_or_else(|| weld_err!("expected one of {} for --preset, got: {}", names.join(", "), value))?)
+}
+
+impl Options {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Options> {
+        let mut options = Options::default();
+        let mut positional = vec![];
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--preview" => options.preview = Some(flag_value(&mut args, &arg)?),
+                "--mmap" => options.mmap = true,
+                "--report" => options.report = Some(flag_value(&mut args, &arg)?),
+                "--stats" => options.stats = true,
+                "--stats-json" => options.stats_json = Some(flag_value(&mut args, &arg)?),
+                "--scale" => options.scale = Some(parse_number(&flag_value(&mut args, &arg)?, &arg)?),
+                "--rotate-deg" => options.rotate_deg = Some(parse_number(&flag_value(&mut args, &arg)?, &arg)?),
+                "--join-tolerance" => options.join_tolerance = Some(parse_number(&flag_value(&mut args, &arg)?, &arg)?),
+                "--snap-axes" => options.snap_axes = Some(parse_number(&flag_value(&mut args, &arg)?, &arg)?),
+                "--remove-overlaps" => options.remove_overlaps = Some(parse_number(&flag_value(&mut args, &arg)?, &arg)?),
+                "--min-feature-size" => options.min_feature_size = Some(parse_number(&flag_value(&mut args, &arg)?, &arg)?),
+                "--offset" => options.offset = Some(parse_number(&flag_value(&mut args, &arg)?, &arg)?),
+                "--tile" => options.tile = Some(parse_tile(&flag_value(&mut args, &arg)?)?),
+                "--fail-on-intersection" => options.fail_on_intersection = true,
+                "--fail-on-orphans" => options.fail_on_orphans = true,
+                "--fail-if-compression-below" => options.fail_if_compression_below = Some(parse_number(&flag_value(&mut args, &arg)?, &arg)?),
+                "--fail-if-deviation-above" => options.fail_if_deviation_above = Some(parse_number(&flag_value(&mut args, &arg)?, &arg)?),
+                "--auto-resolution" => options.auto_resolution = true,
+                "--max-deviation" => options.max_deviation = Some(parse_number(&flag_value(&mut args, &arg)?, &arg)?),
+                "--resolutions" => {
+                    let value = flag_value(&mut args, &arg)?;
+                    options.resolutions = Some(value.split(',').map(|resolution| parse_number(resolution, &arg)).collect::<Result<_>>()?);
+                },
+                "--backup" => options.backup = true,
+                "--adaptive-min-segments" => options.adaptive_min_segments = true,
+                "--origin" => options.origin = Some(parse_origin(&flag_value(&mut args, &arg)?)?),
+                "--snap-grid" => options.snap_grid = Some(parse_number(&flag_value(&mut args, &arg)?, &arg)?),
+                "--feed-rate" => options.feed_rate = Some(parse_number(&flag_value(&mut args, &arg)?, &arg)?),
+                "--text-outlines" => options.text_outlines = Some(parse_text_policy(&flag_value(&mut args, &arg)?)?),
+                "--block-per-loop" => options.block_per_loop = true,
+                "--split-parts" => options.split_parts = Some(parse_part_output(&flag_value(&mut args, &arg)?)?),
+                "--part-proximity" => options.part_proximity = parse_number(&flag_value(&mut args, &arg)?, &arg)?,
+                "--no-warn" => options.no_warn = true,
+                "--quiet" => options.quiet = true,
+                "--json" => options.json = true,
+                "--comma-decimals" => options.comma_decimals = true,
+                "--checkpoint" => options.checkpoint = Some(flag_value(&mut args, &arg)?),
+                "--preset" => options.preset = Some(parse_preset(&flag_value(&mut args, &arg)?)?),
+                "--fitter" => options.fitter = parse_fitter(&flag_value(&mut args, &arg)?)?,
+                "--fit-lines" => options.fit_lines = true,
+                "--merge-arcs" => options.merge_arcs = true,
+                "--zero-length" => options.zero_length = parse_zero_length(&flag_value(&mut args, &arg)?)?,
+                "--heal-only" => options.heal_only = true,
+                "--snap-tolerance" => options.snap_tolerance = Some(parse_number(&flag_value(&mut args, &arg)?, &arg)?),
+                "--max-gap" => options.max_gap = Some(parse_number(&flag_value(&mut args, &arg)?, &arg)?),
+                "--holes-first" => options.holes_first = true,
+                "--optimize-start" => options.optimize_start = true,
+                "--angle-precision" => {
+                    let value = flag_value(&mut args, &arg)?;
+                    options.angle_precision = Some(value.trim().parse::<usize>().map_err(|_| weld_err!("invalid decimal count for --angle-precision: {}", value))?);
+                },
+                "--three-point-arcs" => options.three_point_arcs = true,
+                "--fragment" => options.fragment = true,
+                "--weld-blocks" => options.weld_blocks = true,
+                "--dxf-version" => options.dxf_version = parse_dxf_version(&flag_value(&mut args, &arg)?)?,
+                "--debug-layer" => options.debug_layer = Some(flag_value(&mut args, &arg)?),
+                "--deviation-layer" => options.deviation_layer = Some(parse_number(&flag_value(&mut args, &arg)?, &arg)?),
+                "--max-memory" => {
+                    let megabytes = parse_number(&flag_value(&mut args, &arg)?, &arg)?;
+                    if !is_positive(megabytes) {
+                        return Err(weld_err!("{} must be positive", arg));
+                    }
+                    options.max_memory = Some((megabytes * 1024.0 * 1024.0) as usize);
+                },
+                "--min-arc-segments" => options.min_arc_segments = parse_count(&flag_value(&mut args, &arg)?, &arg)?,
+                "--chains" => {
+                    let value = flag_value(&mut args, &arg)?;
+                    options.chains = Some(value.split(',').map(|id| parse_count(id, &arg)).collect::<Result<_>>()?);
+                },
+                "--angular-tolerance" => options.angular_tolerance = Some(parse_number(&flag_value(&mut args, &arg)?, &arg)?),
+                "--max-points-per-fit" => options.max_points_per_fit = Some(parse_count(&flag_value(&mut args, &arg)?, &arg)?),
+                "--chunk-points" => options.chunk_points = Some(parse_count(&flag_value(&mut args, &arg)?, &arg)?),
+                "--split-quadrants" => options.split_quadrants = true,
+                "--no-circles" => options.no_circles = true,
+                "--split-layers" => options.split_layers = true,
+                "--split-colors" => options.split_colors = true,
+                "--mirror-x" => options.mirror_x = true,
+                "--mirror-y" => options.mirror_y = true,
+                "--translate" => options.translate = Some(parse_pair(&flag_value(&mut args, &arg)?, &arg)?),
+                flag if flag.starts_with("--") => return Err(weld_err!("unknown option: {}", flag)),
+                _ => positional.push(arg),
+            }
+        }
+        // every positional argument but the last is an input
+        options.outfile = positional.pop().ok_or_else(|| weld_err!("no output file"))?;
+        if positional.is_empty() {
+            return Err(weld_err!("no input file"));
+        }
+        options.infiles = positional;
+        if options.split_layers && options.split_colors {
+            return Err(weld_err!("--split-layers and --split-colors are mutually exclusive"));
+        }
+        if options.quiet && options.json {
+            return Err(weld_err!("--quiet and --json are mutually exclusive"));
+        }
+        if options.snap_grid.is_some_and(|grid| !is_positive(grid)) {
+            return Err(weld_err!("--snap-grid must be positive"));
+        }
+        if options.scale.is_some_and(|scale| !is_positive(scale)) {
+            return Err(weld_err!("--scale must be positive"));
+        }
+        for (value, flag) in [
+            (options.join_tolerance, "--join-tolerance"),
+            (options.snap_axes, "--snap-axes"),
+            (options.remove_overlaps, "--remove-overlaps"),
+            (options.min_feature_size, "--min-feature-size"),
+        ] {
+            if value.is_some_and(|value| !is_positive(value)) {
+                return Err(weld_err!("{} must be positive", flag));
+            }
+        }
+        // negative offsets shrink parts, so only zero and non-finite distances are refused
+        if options.offset.is_some_and(|distance| !distance.is_finite() || distance == 0.0) {
+            return Err(weld_err!("--offset must be a finite, non-zero distance"));
+        }
+        if options.feed_rate.is_some_and(|rate| rate <= 0.0) {
+            return Err(weld_err!("--feed-rate must be positive"));
+        }
+        if options.block_per_loop && !options.outfile.to_ascii_lowercase().ends_with(".dxf") {
+            return Err(weld_err!("--block-per-loop needs DXF output"));
+        }
+        if options.split_parts == Some(PartOutput::Blocks) && !options.outfile.to_ascii_lowercase().ends_with(".dxf") {
+            return Err(weld_err!("--split-parts blocks needs DXF output"));
+        }
+        if options.split_parts.is_some() && options.block_per_loop {
+            return Err(weld_err!("--split-parts and --block-per-loop are mutually exclusive"));
+        }
+        if options.part_proximity < 0.0 {
+            return Err(weld_err!("--part-proximity cannot be negative"));
+        }
+        for (value, flag) in [(options.snap_tolerance, "--snap-tolerance"), (options.max_gap, "--max-gap")] {
+            match value {
+                Some(value) if !is_positive(value) => return Err(weld_err!("{} must be positive", flag)),
+                Some(_) if !options.heal_only => return Err(weld_err!("{} needs --heal-only", flag)),
+                _ => (),
+            }
+        }
+        if options.checkpoint.is_some() && (options.heal_only || options.auto_resolution) {
+            return Err(weld_err!("--checkpoint cannot be combined with --heal-only or --auto-resolution"));
+        }
+        if let Some(resolutions) = &options.resolutions {
+            if resolutions.iter().any(|resolution| !is_positive(*resolution)) {
+                return Err(weld_err!("--resolutions must all be positive"));
+            }
+            if options.heal_only || options.auto_resolution || options.checkpoint.is_some() {
+                return Err(weld_err!("--resolutions cannot be combined with --heal-only, --auto-resolution or --checkpoint"));
+            }
+        }
+        Ok(options)
+    }
+}
+
+impl Options {
+    // The first option that needs the whole welded drawing in memory, which rules out streaming the
+    // output under --max-memory.
+    pub fn needs_whole_output(&self) -> Option<&'static str> {
+        let options = [
+            (self.preview.is_some(), "--preview"),
+            (self.report.is_some(), "--report"),
+            (self.split_layers, "--split-layers"),
+            (self.split_colors, "--split-colors"),
+            (self.offset.is_some(), "--offset"),
+            (self.optimize_start, "--optimize-start"),
+            (self.holes_first, "--holes-first"),
+            (self.block_per_loop, "--block-per-loop"),
+            (self.split_parts.is_some(), "--split-parts"),
+            (self.origin.is_some(), "--origin"),
+            (self.fail_on_intersection, "--fail-on-intersection"),
+            (self.fail_on_orphans, "--fail-on-orphans"),
+            (self.fail_if_compression_below.is_some(), "--fail-if-compression-below"),
+            (self.fail_if_deviation_above.is_some(), "--fail-if-deviation-above"),
+            (self.heal_only, "--heal-only"),
+            (self.auto_resolution, "--auto-resolution"),
+            (self.resolutions.is_some(), "--resolutions"),
+            (self.split_quadrants, "--split-quadrants"),
+            (self.no_circles, "--no-circles"),
+            (self.debug_layer.is_some(), "--debug-layer"),
+            (self.deviation_layer.is_some(), "--deviation-layer"),
+            (self.checkpoint.is_some(), "--checkpoint"),
+            (svg::is_svg_path(&self.outfile) || gis::is_geojson_path(&self.outfile) || gis::is_wkt_path(&self.outfile), "SVG/GeoJSON/WKT output"),
+        ];
+        options.iter().find(|(set, _)| *set).map(|(_, option)| *option)
+    }
+
+    // the preset's or default healing settings, with --snap-tolerance and --max-gap applied
+    pub fn heal_config(&self) -> HealConfig {
+        let defaults = self.preset.map(|preset| preset.heal_config()).unwrap_or_default();
+        HealConfig {
+            snap_tolerance: self.snap_tolerance.unwrap_or(defaults.snap_tolerance),
+            max_gap: self.max_gap.unwrap_or(defaults.max_gap),
+        }
+    }
+
+    // mirror, scale, then rotate about the origin, then translate, regardless of flag order
+    pub fn transform(&self) -> Transform {
+        let mut transform = Transform::identity();
+        if self.mirror_x {
+            transform = transform.then(&Transform::mirror_x());
+        }
+        if self.mirror_y {
+            transform = transform.then(&Transform::mirror_y());
+        }
+        if let Some(scale) = self.scale {
+            transform = transform.then(&Transform::scale(scale));
+        }
+        if let Some(degrees) = self.rotate_deg {
+            transform = transform.then(&Transform::rotate_degrees(degrees));
+        }
+        if let Some((x, y)) = self.translate {
+            transform = transform.then(&Transform::translate(x, y));
+        }
+        transform
+    }
+}
+
+// options for the `flatten` (or `unweld`) subcommand
+#[derive(Debug, Default)]
+pub struct FlattenOptions {
+    pub infile: String,
+    pub outfile: String,
+    pub resolution: Option<f64>,
+}
+
+impl FlattenOptions {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<FlattenOptions> {
+        let mut options = FlattenOptions::default();
+        let mut positional = vec![];
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--resolution" => options.resolution = Some(parse_number(&flag_value(&mut args, &arg)?, &arg)?),
+                flag if flag.starts_with("--") => return Err(weld_err!("unknown option: {}", flag)),
+                _ => positional.push(arg),
+            }
+        }
+        if positional.len() != 2 {
+            return Err(weld_err!("expected one input and one output file"));
+        }
+        options.outfile = positional.pop().unwrap();
+        options.infile = positional.pop().unwrap();
+        Ok(options)
+    }
+}
+
+// options for the `diff` subcommand
+#[derive(Debug, Default)]
+pub struct DiffOptions {
+    pub first: String,
+    pub second: String,
+    pub tolerance: Option<f64>,
+}
+
+impl DiffOptions {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<DiffOptions> {
+        let mut options = DiffOptions::default();
+        let mut positional = vec![];
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--tolerance" => options.tolerance = Some(parse_number(&flag_value(&mut args, &arg)?, &arg)?),
+                flag if flag.starts_with("--") => return Err(weld_err!("unknown option: {}", flag)),
+                _ => positional.push(arg),
+            }
+        }
+        if positional.len() != 2 {
+            return Err(weld_err!("expected two files to compare"));
+        }
+        options.second = positional.pop().unwrap();
+        options.first = positional.pop().unwrap();
+        Ok(options)
+    }
+}
+
+// `out.dxf` with suffix `cut` becomes `out_cut.dxf`; characters unsafe in file names are replaced
+pub fn suffixed_path(path: &str, suffix: &str) -> String {
+    let suffix = suffix.chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '_' }).collect::<String>();
+    let path = std::path::Path::new(path);
+    let stem = path.file_stem().map(|x| x.to_string_lossy()).unwrap_or_default();
+    let name = match path.extension() {
+        Some(extension) => format!("{}_{}.{}", stem, suffix, extension.to_string_lossy()),
+        None => format!("{}_{}", stem, suffix),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+// `suffixed_path` for each suffix, adding `_2`, `_3` and so on to suffixes whose file name is already
+// taken by an earlier one, such as `A B` after `A_B` or, for case-insensitive file systems, `a` after `A`
+pub fn suffixed_paths(path: &str, suffixes: &[&str]) -> Vec<String> {
+    let mut taken = HashSet::new();
+    suffixes.iter().map(|suffix| {
+        let mut path_for = suffixed_path(path, suffix);
+        let mut n = 1;
+        while !taken.insert(path_for.to_lowercase()) {
+            n += 1;
+            path_for = suffixed_path(path, &format!("{}_{}", suffix, n));
+        }
+        path_for
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suffixed_paths() {
+        assert_eq!(suffixed_path("dir/out.dxf", "cut 1"), "dir/out_cut_1.dxf");
+        assert_eq!(suffixed_paths("out.dxf", &["A B", "A_B", "a_b", "A_B_2", "C"]),
+            vec!["out_A_B.dxf", "out_A_B_2.dxf", "out_a_b_3.dxf", "out_A_B_2_2.dxf", "out_C.dxf"]);
+    }
+
+    #[test]
+    fn test_heal_flags() {
+        let parse = |args: &[&str]| Options::parse(args.iter().map(|arg| arg.to_string()));
+        let options = parse(&["--heal-only", "--preset", "plasma", "--snap-tolerance", "0.01", "in.dxf", "out.dxf"]).unwrap();
+        assert_eq!((options.heal_config().snap_tolerance, options.heal_config().max_gap), (0.01, 0.25));
+        assert!(parse(&["--max-gap", "1", "in.dxf", "out.dxf"]).is_err());
+        assert!(parse(&["--heal-only", "--max-gap", "NaN", "in.dxf", "out.dxf"]).is_err());
+        assert!(parse(&["--heal-only", "--snap-tolerance", "0", "in.dxf", "out.dxf"]).is_err());
+    }
+
+    #[test]
+    fn test_scale() {
+        let parse = |args: &[&str]| Options::parse(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(parse(&["--scale", "25.4", "in.dxf", "out.dxf"]).unwrap().scale, Some(25.4));
+        for scale in ["0", "-2", "NaN", "inf"] {
+            assert!(parse(&["--scale", scale, "in.dxf", "out.dxf"]).is_err(), "{}", scale);
+        }
+    }
+
+    #[test]
+    fn test_memory_and_resolutions() {
+        let parse = |args: &[&str]| Options::parse(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(parse(&["--max-memory", "1.5", "in.dxf", "out.dxf"]).unwrap().max_memory, Some(1536 * 1024));
+        for megabytes in ["0", "-1", "NaN", "inf"] {
+            assert!(parse(&["--max-memory", megabytes, "in.dxf", "out.dxf"]).is_err(), "{}", megabytes);
+        }
+        assert_eq!(parse(&["--resolutions", "0.01,0.1", "in.dxf", "out.dxf"]).unwrap().resolutions, Some(vec![0.01, 0.1]));
+        for resolutions in ["0.01,NaN", "0.01,0", "inf"] {
+            assert!(parse(&["--resolutions", resolutions, "in.dxf", "out.dxf"]).is_err(), "{}", resolutions);
+        }
+    }
+
+    #[test]
+    fn test_snap_grid() {
+        let parse = |args: &[&str]| Options::parse(args.iter().map(|arg| arg.to_string()));
+        assert_eq!(parse(&["--snap-grid", "0.01", "in.dxf", "out.dxf"]).unwrap().snap_grid, Some(0.01));
+        for grid in ["0", "-0.01", "NaN", "inf"] {
+            assert!(parse(&["--snap-grid", grid, "in.dxf", "out.dxf"]).is_err(), "{}", grid);
+        }
+    }
+
+    #[test]
+    fn test_cleanup_distances() {
+        let parse = |args: &[&str]| Options::parse(args.iter().map(|arg| arg.to_string()));
+        for flag in ["--join-tolerance", "--snap-axes", "--remove-overlaps", "--min-feature-size"] {
+            assert!(parse(&[flag, "0.01", "in.dxf", "out.dxf"]).is_ok(), "{}", flag);
+            for value in ["0", "-0.01", "NaN", "inf"] {
+                assert!(parse(&[flag, value, "in.dxf", "out.dxf"]).is_err(), "{} {}", flag, value);
+            }
+        }
+        assert_eq!(parse(&["--offset", "-0.1", "in.dxf", "out.dxf"]).unwrap().offset, Some(-0.1));
+        for distance in ["0", "NaN", "-inf"] {
+            assert!(parse(&["--offset", distance, "in.dxf", "out.dxf"]).is_err(), "{}", distance);
+        }
+        assert_eq!(parse(&["--tile", "3x0", "a.dxf", "b.dxf", "out.dxf"]).unwrap().tile, Some((3, 0.0)));
+        for tile in ["3x-1", "3xNaN", "3xinf"] {
+            assert!(parse(&["--tile", tile, "a.dxf", "b.dxf", "out.dxf"]).is_err(), "{}", tile);
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use crate::dxf::*;
+use crate::report::ChainReport;
+
+// A kind of curve that can replace a run of connected chain points, such as an arc. The fitters in
+// `DxfConfig::process_chain_with` decide which runs to try; a model only fits, measures and emits.
+//
+// Runs are handed to `fit` growing one point at a time from the same start until a fit fails, so a
+// model may keep state between calls to avoid refitting the whole run at every step.
+pub trait CurveModel {
+    type Model;
+
+    // a curve from the run's first to its last point staying within the resolution of every point
+    // and segment in between, or None
+    fn fit(&mut self, points: &[Point]) -> Option<Self::Model>;
+
+    // the largest distance of the run's points and segments from the curve, and where it occurs, or
+    // None if the curve does not fit the run
+    fn max_deviation(&self, model: &Self::Model, points: &[Point]) -> Option<(f64, Point)>;
+
+    // Degrees a run that has come back to its start sweeps around the curve, for telling a run that
+    // goes once round from one that only returns to where it began. Only runs sweeping a full turn
+    // are closed, and models without a sweep close every run that returns to its start.
+    fn sweep(&self, _model: &Self::Model, _points: &[Point]) -> Option<f64> {
+        None
+    }
+
+    // The entities replacing a run of `segments` segments, which is a closed loop when `closed` is
+    // set. Fitted entities are described in `report`, numbered from `first`.
+    fn to_entities(&self, model: Self::Model, deviation: (f64, Point), segments: usize, closed: bool, report: &mut ChainReport, first: usize) -> Vec<Entity>;
+}
@@ -0,0 +1,273 @@
+use crate::dxf::*;
+use serde::Serialize;
+use std::collections::HashMap;
+
+// finest grid cell, relative to the drawings' extent, used to look up segments for the Hausdorff distance
+const HAUSDORFF_CELLS: f64 = 512.0;
+
+// an entity found in only one of the two drawings compared
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Unmatched {
+    pub layer: String,
+    pub entity: Entity,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct DrawingDiff {
+    // entities of the first drawing with no match on the same layer of the second, and the other way round
+    pub removed: Vec<Unmatched>,
+    pub added: Vec<Unmatched>,
+    // the furthest either drawing strays from the other once tessellated, and where; infinite when
+    // only one of them is empty
+    pub hausdorff: f64,
+    pub hausdorff_at: Option<Point>,
+}
+
+impl DrawingDiff {
+    // whether every entity matched and the outlines stay within `tolerance` of each other
+    pub fn is_same(&self, tolerance: f64) -> bool {
+        self.removed.is_empty() && self.added.is_empty() && self.hausdorff <= tolerance
+    }
+}
+
+fn close(a: &Point, b: &Point, tolerance: f64) -> bool {
+    a.dist(b) <= tolerance
+}
+
+fn close_ends(a: &Entity, b: &Entity, tolerance: f64) -> bool {
+    match (a.start_point(), a.end_point(), b.start_point(), b.end_point()) {
+        (Some(a0), Some(a1), Some(b0), Some(b1)) => close(&a0, &b0, tolerance) && close(&a1, &b1, tolerance),
+        _ => false,
+    }
+}
+
+// Whether two entities are the same within `tolerance`. Lines and polylines may run either way; arcs
+// and ellipses are compared by their ends rather than their angles, which wrap around.
+fn same_entity(a: &Entity, b: &Entity, tolerance: f64) -> bool {
+    match (a, b) {
+        (Entity::Line(a0, a1), Entity::Line(b0, b1)) => {
+            (close(a0, b0, tolerance) && close(a1, b1, tolerance)) || (close(a0, b1, tolerance) && close(a1, b0, tolerance))
+        },
+        (Entity::Arc { center: ca, radius: ra, .. }, Entity::Arc { center: cb, radius: rb, .. }) => {
+            close(ca, cb, tolerance) && (ra - rb).abs() <= tolerance && close_ends(a, b, tolerance)
+        },
+        (Entity::Circle { center: ca, radius: ra }, Entity::Circle { center: cb, radius: rb }) => {
+            close(ca, cb, tolerance) && (ra - rb).abs() <= tolerance
+        },
+        (Entity::Ellipse { center: ca, major_axis: ma, ratio: ra, .. }, Entity::Ellipse { center: cb, major_axis: mb, ratio: rb, .. }) => {
+            let minor = |major: &Point, ratio: f64| (major.x * major.x + major.y * major.y).sqrt() * ratio;
+            close(ca, cb, tolerance) && close(ma, mb, tolerance) && (minor(ma, *ra) - minor(mb, *rb)).abs() <= tolerance && close_ends(a, b, tolerance)
+        },
+        (Entity::Polyline { curve_type: ta, vertices: va, .. }, Entity::Polyline { curve_type: tb, vertices: vb, .. }) => {
+            ta == tb && va.len() == vb.len() && (
+                va.iter().zip(vb.iter()).all(|(a, b)| close(a, b, tolerance))
+                || va.iter().zip(vb.iter().rev()).all(|(a, b)| close(a, b, tolerance))
+            )
+        },
+        _ => false,
+    }
+}
+
+// indices of the entities or segments in each grid cell
+type Grid = HashMap<(i64, i64), Vec<usize>>;
+
+fn cell_of(point: &Point, cell: f64) -> (i64, i64) {
+    ((point.x / cell).floor() as i64, (point.y / cell).floor() as i64)
+}
+
+// an entity's place for matching: the middle of its bounding box, which moves no further than the
+// entity does
+fn anchor(entity: &Entity) -> Option<Point> {
+    let (min, max) = entity.bounding_box()?;
+    Some(Point { x: (min.x + max.x) / 2.0, y: (min.y + max.y) / 2.0 })
+}
+
+fn unmatched_entities(a: &Drawing, b: &Drawing, tolerance: f64) -> (Vec<Unmatched>, Vec<Unmatched>) {
+    let cell = tolerance.max(1e-9);
+    let (mut removed, mut added) = (vec![], vec![]);
+    let mut grids: HashMap<&str, (Grid, Vec<bool>)> = HashMap::new();
+    for layer in b.layers.iter() {
+        let mut grid = Grid::new();
+        for (i, entity) in layer.entities.iter().enumerate() {
+            if let Some(point) = anchor(entity) {
+                grid.entry(cell_of(&point, cell)).or_default().push(i);
+            }
+        }
+        grids.insert(&layer.name, (grid, vec![false; layer.entities.len()]));
+    }
+    for layer in a.layers.iter() {
+        let others = b.layers.iter().find(|other| other.name == layer.name);
+        for entity in layer.entities.iter() {
+            let found = match (others, grids.get_mut(layer.name.as_str()), anchor(entity)) {
+                (Some(others), Some((grid, matched)), Some(point)) => {
+                    let (x, y) = cell_of(&point, cell);
+                    let candidates = (-1..=1).flat_map(|dx| (-1..=1).map(move |dy| (x + dx, y + dy)))
+                        .flat_map(|key| grid.get(&key).into_iter().flatten().copied())
+                        .collect::<Vec<_>>();
+                    let found = candidates.into_iter().find(|i| !matched[*i] && same_entity(entity, &others.entities[*i], tolerance));
+                    if let Some(i) = found {
+                        matched[i] = true;
+                    }
+                    found.is_some()
+                },
+                _ => false,
+            };
+            if !found {
+                removed.push(Unmatched { layer: layer.name.clone(), entity: entity.clone() });
+            }
+        }
+    }
+    for layer in b.layers.iter() {
+        let matched = &grids[layer.name.as_str()].1;
+        for (entity, _) in layer.entities.iter().zip(matched.iter()).filter(|(_, matched)| !**matched) {
+            added.push(Unmatched { layer: layer.name.clone(), entity: entity.clone() });
+        }
+    }
+    (removed, added)
+}
+
+fn segments(drawing: &Drawing, chord_error: f64) -> Vec<(Point, Point)> {
+    drawing.entities().flat_map(|entity| {
+        let points = entity.tessellate(chord_error);
+        points.windows(2).map(|pair| (pair[0].clone(), pair[1].clone())).collect::<Vec<_>>()
+    }).collect()
+}
+
+fn segment_distance(point: &Point, from: &Point, to: &Point) -> f64 {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let length = dx * dx + dy * dy;
+    let t = if length > 0.0 { (((point.x - from.x) * dx + (point.y - from.y) * dy) / length).clamp(0.0, 1.0) } else { 0.0 };
+    point.dist(&Point { x: from.x + t * dx, y: from.y + t * dy })
+}
+
+// segments bucketed by the grid cells their bounding boxes cover
+struct SegmentGrid<'a> {
+    cell: f64,
+    cells: Grid,
+    segments: &'a [(Point, Point)],
+    // the furthest ring of cells worth searching
+    rings: i64,
+}
+
+impl<'a> SegmentGrid<'a> {
+    fn new(segments: &'a [(Point, Point)], cell: f64, rings: i64) -> SegmentGrid<'a> {
+        let mut cells = Grid::new();
+        for (i, (from, to)) in segments.iter().enumerate() {
+            let (x0, y0) = cell_of(&Point { x: from.x.min(to.x), y: from.y.min(to.y) }, cell);
+            let (x1, y1) = cell_of(&Point { x: from.x.max(to.x), y: from.y.max(to.y) }, cell);
+            for x in x0..=x1 {
+                for y in y0..=y1 {
+                    cells.entry((x, y)).or_default().push(i);
+                }
+            }
+        }
+        SegmentGrid { cell, cells, segments, rings }
+    }
+
+    // searches rings of cells around the point until none closer than the nearest found can remain
+    fn distance(&self, point: &Point) -> f64 {
+        let (x, y) = cell_of(point, self.cell);
+        let mut nearest = f64::INFINITY;
+        for ring in 0..=self.rings {
+            for dx in -ring..=ring {
+                for dy in -ring..=ring {
+                    if dx.abs() != ring && dy.abs() != ring {
+                        continue;
+                    }
+                    for i in self.cells.get(&(x + dx, y + dy)).into_iter().flatten() {
+                        let (from, to) = &self.segments[*i];
+                        nearest = nearest.min(segment_distance(point, from, to));
+                    }
+                }
+            }
+            if nearest <= ring as f64 * self.cell {
+                break;
+            }
+        }
+        nearest
+    }
+}
+
+// the furthest any point of `from` is from `to`, sampling `from` at least every `step`
+fn directed_hausdorff(from: &[(Point, Point)], to: &SegmentGrid, step: f64) -> (f64, Option<Point>) {
+    let mut furthest = (0.0, None);
+    for (a, b) in from.iter() {
+        let count = (a.dist(b) / step).ceil().max(1.0) as usize;
+        for i in 0..=count {
+            let t = i as f64 / count as f64;
+            let point = Point { x: a.x + (b.x - a.x) * t, y: a.y + (b.y - a.y) * t };
+            let distance = to.distance(&point);
+            if distance > furthest.0 {
+                furthest = (distance, Some(point));
+            }
+        }
+    }
+    furthest
+}
+
+// Compares two drawings: entities are matched one to one on the same layer when they agree within
+// `tolerance`, and the Hausdorff distance is taken between the drawings with curves tessellated to
+// `tolerance`, so geometry that welded differently but traces the same outline can be told apart
+// from geometry that moved.
+pub fn diff(a: &Drawing, b: &Drawing, tolerance: f64) -> DrawingDiff {
+    let (removed, added) = unmatched_entities(a, b, tolerance);
+    let chord_error = tolerance.max(1e-9);
+    let (from, to) = (segments(a, chord_error), segments(b, chord_error));
+    let (hausdorff, hausdorff_at) = match (from.is_empty(), to.is_empty()) {
+        (true, true) => (0.0, None),
+        (true, false) | (false, true) => (f64::INFINITY, None),
+        (false, false) => {
+            let corners = from.iter().chain(to.iter()).flat_map(|(a, b)| [a, b]);
+            let (mut min, mut max) = ((f64::MAX, f64::MAX), (f64::MIN, f64::MIN));
+            for point in corners {
+                min = (min.0.min(point.x), min.1.min(point.y));
+                max = (max.0.max(point.x), max.1.max(point.y));
+            }
+            let extent = (max.0 - min.0).max(max.1 - min.1);
+            let cell = (extent / HAUSDORFF_CELLS).max(chord_error);
+            let rings = (extent / cell).ceil() as i64 + 1;
+            let forward = directed_hausdorff(&from, &SegmentGrid::new(&to, cell, rings), cell);
+            let backward = directed_hausdorff(&to, &SegmentGrid::new(&from, cell, rings), cell);
+            if backward.0 > forward.0 { backward } else { forward }
+        },
+    };
+    DrawingDiff {
+        removed,
+        added,
+        hausdorff,
+        hausdorff_at,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let a = Drawing::new(vec![
+            Entity::Line(p(0.0, 0.0), p(10.0, 0.0)),
+            Entity::Arc { center: p(10.0, 5.0), radius: 5.0, start_angle: 270.0, end_angle: 90.0 },
+        ]);
+        // the line runs the other way and the arc is split in two
+        let b = Drawing::new(vec![
+            Entity::Line(p(10.0, 0.0), p(0.0, 0.0001)),
+            Entity::Arc { center: p(10.0, 5.0), radius: 5.0, start_angle: 270.0, end_angle: 0.0 },
+            Entity::Arc { center: p(10.0, 5.0), radius: 5.0, start_angle: 0.0, end_angle: 90.0 },
+        ]);
+        let same = diff(&a, &a, 0.001);
+        assert!(same.is_same(0.001) && same.hausdorff < 1e-9);
+
+        let changed = diff(&a, &b, 0.001);
+        assert_eq!(changed.removed.len(), 1);
+        assert_eq!(changed.added.len(), 2);
+        assert!(changed.hausdorff < 0.001);
+
+        let mut moved = b.clone();
+        moved.layers[0].entities[0] = Entity::Line(p(10.0, 0.0), p(0.0, -0.5));
+        let changed = diff(&a, &moved, 0.001);
+        assert!((changed.hausdorff - 0.5).abs() < 1e-9);
+        assert_eq!(changed.hausdorff_at, Some(p(0.0, -0.5)));
+    }
+}
@@ -1,7 +1,8 @@
 use serde::{Serialize, Deserialize};
+use crate::tessellate::{ccw_sweep, is_clamped_uniform, nurbs_points, FINE_CHORD_ERROR};
+use crate::output::OutputBackend;
 use crate::result::*;
-use std::collections::VecDeque;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::cmp::Ordering;
 use std::fmt;
 
@@ -31,13 +32,14 @@ impl Eq for Point {}
 
 impl std::cmp::Ord for Point {
     fn cmp(&self, other: &Point) -> Ordering {
-        self.partial_cmp(other).unwrap_or(Ordering::Equal)
+        self.x.partial_cmp(&other.x).unwrap_or(Ordering::Equal)
+            .then(self.y.partial_cmp(&other.y).unwrap_or(Ordering::Equal))
     }
 }
 
 impl PartialOrd for Point {
     fn partial_cmp(&self, other: &Point) -> Option<Ordering> {
-        Some(self.x.partial_cmp(&other.x)?.then(self.y.partial_cmp(&other.y)?))
+        Some(self.cmp(other))
     }
 }
 
@@ -50,6 +52,63 @@ impl Point {
     pub fn angle(&self, other: &Point) -> f64 {
         (self.y - other.y).atan2(self.x - other.x)
     }
+
+    // point at `degrees` on the circle of `radius` centered here
+    pub fn polar(&self, radius: f64, degrees: f64) -> Point {
+        let radians = degrees.to_radians();
+        Point {
+            x: self.x + radius * radians.cos(),
+            y: self.y + radius * radians.sin(),
+        }
+    }
+}
+
+// wraps an angle in degrees into [0, 360)
+pub fn normalize_degrees(degrees: f64) -> f64 {
+    let normalized = degrees.rem_euclid(360.0);
+    // tiny negative angles round up to exactly 360
+    if normalized >= 360.0 {
+        0.0
+    } else {
+        normalized
+    }
+}
+
+// decimals shown for numbers in messages
+const SHOWN_DECIMALS: usize = 10;
+
+// at most SHOWN_DECIMALS decimals, without trailing zeros or a negative zero, for messages
+pub(crate) fn format_number(value: f64) -> String {
+    let mut text = format!("{:.*}", SHOWN_DECIMALS, value);
+    if text.contains('.') {
+        let len = text.trim_end_matches('0').trim_end_matches('.').len();
+        text.truncate(len);
+    }
+    if text == "-0" {
+        text.remove(0);
+    }
+    text
+}
+
+// The shortest decimal reading back as exactly `value`, for DXF coordinates, lengths and angles.
+// The digits depend on the value alone, so equal values are written the same on every platform.
+fn format_real(value: f64) -> String {
+    if value == 0.0 {
+        // no negative zero
+        return "0".to_string();
+    }
+    value.to_string()
+}
+
+// a normalized angle with `precision` decimals, or the shortest that reads back exactly
+fn format_angle(degrees: f64, precision: Option<usize>) -> String {
+    match precision {
+        Some(precision) => {
+            let factor = 10f64.powi(precision as i32);
+            format!("{:.*}", precision, normalize_degrees((degrees * factor).round() / factor))
+        },
+        None => format_real(normalize_degrees(degrees)),
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -65,6 +124,15 @@ pub enum Entity {
         center: Point,
         radius: f64,
     },
+    // `major_axis` is the end of the major axis relative to the center; parameters are in radians,
+    // counter-clockwise, with 0 to 2pi for a full ellipse
+    Ellipse {
+        center: Point,
+        major_axis: Point,
+        ratio: f64,
+        start_param: f64,
+        end_param: f64,
+    },
     Polyline {
         // curve_fit: bool,
         // spline_fit: bool,
@@ -76,113 +144,1640 @@ pub enum Entity {
         */
         curve_type: u32,
         vertices: Vec<Point>,
+        // the start and end width of the segment leaving each vertex, or empty for a polyline drawn
+        // with no width
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        widths: Vec<(f64, f64)>,
     }
 }
 
+// An arc by its start, a point halfway along and its end, running counter-clockwise like
+// `Entity::Arc`. Some CAM formats take arcs this way.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
-pub struct Drawing {
+pub struct ThreePointArc {
+    pub start: Point,
+    pub mid: Point,
+    pub end: Point,
+}
+
+impl ThreePointArc {
+    // the arc through the three points, or None if they are in a line
+    pub fn to_entity(&self) -> Option<Entity> {
+        // relative to the start, to keep the determinant well conditioned
+        let (bx, by) = (self.mid.x - self.start.x, self.mid.y - self.start.y);
+        let (cx, cy) = (self.end.x - self.start.x, self.end.y - self.start.y);
+        let d = 2.0 * (bx * cy - by * cx);
+        if d.abs() < 1e-12 {
+            return None;
+        }
+        let (b2, c2) = (bx * bx + by * by, cx * cx + cy * cy);
+        let center = Point {
+            x: self.start.x + (cy * b2 - by * c2) / d,
+            y: self.start.y + (bx * c2 - cx * b2) / d,
+        };
+        let angle = |point: &Point| normalize_degrees(point.angle(&center).to_degrees());
+        let (start_angle, end_angle) = if d > 0.0 { (angle(&self.start), angle(&self.end)) } else { (angle(&self.end), angle(&self.start)) };
+        Some(Entity::Arc {
+            radius: center.dist(&self.start),
+            center,
+            start_angle,
+            end_angle,
+        })
+    }
+}
+
+impl Entity {
+    // The counter-clockwise arc from `start` to `end` centered on the point of their bisector nearest
+    // `near`, the closest to an arc around `near` that still runs through both.
+    pub(crate) fn arc_between(start: &Point, end: &Point, near: &Point) -> Entity {
+        let middle = Point { x: (start.x + end.x) / 2.0, y: (start.y + end.y) / 2.0 };
+        let length = start.dist(end);
+        let normal = ((start.y - end.y) / length, (end.x - start.x) / length);
+        let offset = (near.x - middle.x) * normal.0 + (near.y - middle.y) * normal.1;
+        let center = Point { x: middle.x + normal.0 * offset, y: middle.y + normal.1 * offset };
+        Entity::Arc {
+            radius: center.dist(start),
+            start_angle: normalize_degrees(start.angle(&center).to_degrees()),
+            end_angle: normalize_degrees(end.angle(&center).to_degrees()),
+            center,
+        }
+    }
+
+    // an arc's three-point form, or None for other entities
+    pub fn three_point(&self) -> Option<ThreePointArc> {
+        match self {
+            Entity::Arc { center, radius, start_angle, end_angle } => {
+                let sweep = (end_angle - start_angle).rem_euclid(360.0);
+                Some(ThreePointArc {
+                    start: center.polar(*radius, *start_angle),
+                    mid: center.polar(*radius, start_angle + sweep / 2.0),
+                    end: center.polar(*radius, *end_angle),
+                })
+            },
+            _ => None,
+        }
+    }
+
+    // Where the entity starts and ends. Arcs and ellipses run counter-clockwise from their start
+    // angle or parameter; circles start and end at angle 0. None for an empty polyline.
+    pub fn start_point(&self) -> Option<Point> {
+        match self {
+            Entity::Line(from, _) => Some(from.clone()),
+            Entity::Arc { center, radius, start_angle, .. } => Some(center.polar(*radius, *start_angle)),
+            Entity::Circle { center, radius } => Some(center.polar(*radius, 0.0)),
+            Entity::Ellipse { center, major_axis, ratio, start_param, .. } => Some(ellipse_point(center, major_axis, *ratio, *start_param)),
+            Entity::Polyline { vertices, .. } => vertices.first().cloned(),
+        }
+    }
+
+    pub fn end_point(&self) -> Option<Point> {
+        match self {
+            Entity::Line(_, to) => Some(to.clone()),
+            Entity::Arc { center, radius, end_angle, .. } => Some(center.polar(*radius, *end_angle)),
+            Entity::Circle { center, radius } => Some(center.polar(*radius, 0.0)),
+            Entity::Ellipse { center, major_axis, ratio, end_param, .. } => Some(ellipse_point(center, major_axis, *ratio, *end_param)),
+            Entity::Polyline { vertices, .. } => vertices.last().cloned(),
+        }
+    }
+
+    // length along the entity; ellipses and spline-fit polylines are measured along their tessellation
+    pub fn length(&self) -> f64 {
+        match self {
+            Entity::Line(from, to) => from.dist(to),
+            Entity::Arc { radius, start_angle, end_angle, .. } => radius * ccw_sweep(*start_angle, *end_angle).to_radians(),
+            Entity::Circle { radius, .. } => radius * 2.0 * std::f64::consts::PI,
+            Entity::Ellipse { .. } | Entity::Polyline { .. } => {
+                self.tessellate(FINE_CHORD_ERROR).windows(2).map(|pair| pair[0].dist(&pair[1])).sum()
+            },
+        }
+    }
+
+    // the smallest and largest corner of the box around the entity, or None for an empty polyline
+    pub fn bounding_box(&self) -> Option<(Point, Point)> {
+        let points = match self {
+            Entity::Arc { center, radius, start_angle, end_angle } => {
+                let sweep = ccw_sweep(*start_angle, *end_angle);
+                // the ends, and wherever the arc crosses an axis through its center
+                let mut points = vec![center.polar(*radius, *start_angle), center.polar(*radius, *end_angle)];
+                points.extend((0..4).map(|i| i as f64 * 90.0).filter(|axis| (axis - start_angle).rem_euclid(360.0) <= sweep).map(|axis| center.polar(*radius, axis)));
+                points
+            },
+            Entity::Circle { center, radius } => vec![
+                Point { x: center.x - radius, y: center.y - radius },
+                Point { x: center.x + radius, y: center.y + radius },
+            ],
+            _ => self.tessellate(FINE_CHORD_ERROR),
+        };
+        let first = points.first()?.clone();
+        Some(points.iter().fold((first.clone(), first), |(min, max), p| (
+            Point { x: min.x.min(p.x), y: min.y.min(p.y) },
+            Point { x: max.x.max(p.x), y: max.y.max(p.y) },
+        )))
+    }
+
+    // Runs the entity the other way. Arcs, circles and ellipses are always stored counter-clockwise,
+    // so only lines and polylines change.
+    pub fn reverse(&mut self) {
+        match self {
+            Entity::Line(from, to) => std::mem::swap(from, to),
+            Entity::Polyline { vertices, widths, .. } => {
+                vertices.reverse();
+                // each segment's widths move to the vertex it now leaves from, start and end swapped
+                if let Some(last) = widths.pop() {
+                    widths.reverse();
+                    widths.iter_mut().for_each(|(start, end)| std::mem::swap(start, end));
+                    widths.push(last);
+                }
+            },
+            Entity::Arc { .. } | Entity::Circle { .. } | Entity::Ellipse { .. } => (),
+        }
+    }
+
+    // whether the entity is a polyline drawn with a width, which welding into lines and arcs would lose
+    pub fn has_width(&self) -> bool {
+        matches!(self, Entity::Polyline { widths, .. } if widths.iter().any(|(start, end)| *start != 0.0 || *end != 0.0))
+    }
+}
+
+// the point at `param` radians on an ellipse
+fn ellipse_point(center: &Point, major_axis: &Point, ratio: f64, param: f64) -> Point {
+    let (sin, cos) = param.sin_cos();
+    Point {
+        x: center.x + major_axis.x * cos - major_axis.y * ratio * sin,
+        y: center.y + major_axis.y * cos + major_axis.x * ratio * sin,
+    }
+}
+
+pub const DEFAULT_LAYER: &str = "0";
+
+// Hard limits for untrusted input, so a service welding uploaded drawings can reject oversized ones
+// with an error instead of running out of memory or time. None means unlimited.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Limits {
+    // bytes of DXF text
+    pub max_file_size: Option<usize>,
+    pub max_entities: Option<usize>,
+    // points in a single chain of connected entities
+    pub max_chain_points: Option<usize>,
+}
+
+impl Limits {
+    pub fn check_file_size(&self, bytes: usize) -> Result<()> {
+        match self.max_file_size {
+            Some(max) if bytes > max => Err(weld_err!("input is {} bytes, over the limit of {} bytes", bytes, max)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_entities(&self, count: usize) -> Result<()> {
+        match self.max_entities {
+            Some(max) if count > max => Err(weld_err!("input has more than {} entities", max)),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn check_chain_points(&self, count: usize) -> Result<()> {
+        match self.max_chain_points {
+            Some(max) if count > max => Err(weld_err!("chain of {} points is over the limit of {} points", count, max)),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Entities sharing a layer name, color, thickness and elevation. Welding never joins entities from
+// different layers.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Layer {
+    pub name: String,
+    // AutoCAD color index, None for BYLAYER
+    pub color: Option<i16>,
+    // the entities' thickness (code 39) and elevation (code 38), kept for extruding them
+    // downstream, or None for 0
+    #[serde(default)]
+    pub thickness: Option<f64>,
+    #[serde(default)]
+    pub elevation: Option<f64>,
     pub entities: Vec<Entity>,
+    // blocks placed on the layer, kept as they are rather than flattened into their entities
+    #[serde(default)]
+    pub inserts: Vec<Insert>,
+    // entities welding has no use for, such as MLINE and LEADER, written back as they were read
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub raw: Vec<RawEntity>,
+}
+
+impl Layer {
+    pub fn new(name: &str, color: Option<i16>) -> Layer {
+        Layer {
+            name: name.to_string(),
+            color,
+            thickness: None,
+            elevation: None,
+            entities: vec![],
+            inserts: vec![],
+            raw: vec![],
+        }
+    }
+
+    // an empty layer with the same name and attributes
+    pub fn empty_like(&self) -> Layer {
+        Layer {
+            name: self.name.clone(),
+            color: self.color,
+            thickness: self.thickness,
+            elevation: self.elevation,
+            entities: vec![],
+            inserts: vec![],
+            raw: vec![],
+        }
+    }
+}
+
+// an INSERT of the block named `block`, scaled, then rotated counter-clockwise by `rotation`
+// degrees and moved to `position`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Insert {
+    pub block: String,
+    pub position: Point,
+    pub x_scale: f64,
+    pub y_scale: f64,
+    pub rotation: f64,
+}
+
+// An entity kept as its group codes, without the handle, layer and color codes written for every
+// entity or the handles pointing at objects the output does not have. Transforms and snapping
+// reach its points through the codes listed for its kind; any others are left as they were.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RawEntity {
+    pub kind: String,
+    pub codes: Vec<(u16, String)>,
+}
+
+impl RawEntity {
+    // the entity types kept this way
+    pub const KINDS: [&'static str; 2] = ["MLINE", "LEADER"];
+
+    fn new(kind: &str, codes: &[(u16, &str)]) -> RawEntity {
+        let kept = |(code, value): &&(u16, &str)| match code {
+            5 | 8 | 62 | 102 | 330..=369 => false,
+            100 => *value != "AcDbEntity",
+            _ => true,
+        };
+        RawEntity {
+            kind: kind.to_string(),
+            codes: codes.iter().filter(kept).map(|(code, value)| (*code, value.to_string())).collect(),
+        }
+    }
+
+    // the codes of the x of each point, its y following under the code + 10
+    pub(crate) fn point_codes(&self) -> &'static [u16] {
+        match self.kind.as_str() {
+            "MLINE" => &[10, 11],
+            "LEADER" => &[10],
+            _ => &[],
+        }
+    }
+
+    // the codes of directions and offsets, which turn and scale with a transform but do not move
+    pub(crate) fn vector_codes(&self) -> &'static [u16] {
+        match self.kind.as_str() {
+            "MLINE" => &[12, 13],
+            "LEADER" => &[211, 212, 213],
+            _ => &[],
+        }
+    }
+
+    // the codes of scale factors, element offsets and text sizes, which scale with a transform
+    pub(crate) fn length_codes(&self) -> &'static [u16] {
+        match self.kind.as_str() {
+            "MLINE" => &[40, 41, 42],
+            "LEADER" => &[40, 41],
+            _ => &[],
+        }
+    }
+
+    // the entity with `map` applied to every point whose x is under one of `codes` and directly
+    // followed by its y
+    pub(crate) fn map_points(&self, codes: &[u16], map: impl Fn(&Point) -> Point) -> RawEntity {
+        let mut raw = self.clone();
+        for i in 1..raw.codes.len() {
+            let ((x_code, x), (y_code, y)) = (&raw.codes[i - 1], &raw.codes[i]);
+            if !codes.contains(x_code) || *y_code != x_code + 10 {
+                continue;
+            }
+            if let (Ok(x), Ok(y)) = (x.trim().parse::<f64>(), y.trim().parse::<f64>()) {
+                let point = map(&Point { x, y });
+                raw.codes[i - 1].1 = format_real(point.x);
+                raw.codes[i].1 = format_real(point.y);
+            }
+        }
+        raw
+    }
+
+    // the entity with `map` applied to every number under one of `codes`
+    pub(crate) fn map_values(&self, codes: &[u16], map: impl Fn(f64) -> f64) -> RawEntity {
+        let mut raw = self.clone();
+        for (_, value) in raw.codes.iter_mut().filter(|(code, _)| codes.contains(code)) {
+            if let Ok(number) = value.trim().parse::<f64>() {
+                *value = format_real(map(number));
+            }
+        }
+        raw
+    }
+}
+
+// A block definition from the BLOCKS section, its entities placed relative to `base`. Blocks whose
+// names start with `*`, such as `*Model_Space`, are left out.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Block {
+    pub name: String,
+    pub base: Point,
+    pub layers: Vec<Layer>,
+}
+
+impl Block {
+    pub fn entity_count(&self) -> usize {
+        self.layers.iter().map(|layer| layer.entities.len()).sum()
+    }
+
+    // the length of all the block's lines and curves
+    pub fn length(&self) -> f64 {
+        self.layers.iter().flat_map(|layer| layer.entities.iter()).map(Entity::length).sum()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Default)]
+pub struct Drawing {
+    pub layers: Vec<Layer>,
+    // the z of every entity, for drawings lying flat in a plane other than z = 0; welding is done in
+    // 2D and DXF output is put back at this height
+    #[serde(default)]
+    pub elevation: Option<f64>,
+    #[serde(default)]
+    pub blocks: Vec<Block>,
+    // the header's `$UCSORG` and `$LIMMIN`, when not at the origin, written back to DXF output
+    #[serde(default)]
+    pub ucs_origin: Option<Point>,
+    #[serde(default)]
+    pub limits_min: Option<Point>,
+}
+
+// a point of the header that coordinates can be taken relative to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HeaderOrigin {
+    // `$UCSORG`, the origin of the user coordinate system the drawing was authored in
+    Ucs,
+    // `$LIMMIN`, the lower left corner of the drawing limits
+    Limits,
+}
+
+// Whether a block is one of the special blocks named with a leading `*`, such as model and paper
+// space or dimension graphics, which are left out. Anonymous blocks made by users (`*U`) are kept
+// like named ones.
+fn is_space_block(name: &str) -> bool {
+    name.starts_with('*') && !name[1..].starts_with(['U', 'u'])
+}
+
+// finds or creates the layer with this name and attributes among `layers`
+fn layer_in<'l>(layers: &'l mut Vec<Layer>, name: &str, color: Option<i16>, thickness: Option<f64>, elevation: Option<f64>) -> &'l mut Layer {
+    let position = layers.iter().position(|layer| {
+        layer.name == name && layer.color == color && layer.thickness == thickness && layer.elevation == elevation
+    });
+    match position {
+        Some(i) => &mut layers[i],
+        None => {
+            let mut layer = Layer::new(name, color);
+            layer.thickness = thickness;
+            layer.elevation = elevation;
+            layers.push(layer);
+            layers.last_mut().unwrap()
+        },
+    }
 }
 
 fn eof() -> Error {
     weld_err!("unexpected eof")
 }
 
-fn unexpected_tag(expected: i32, got: i32) -> Error {
+fn unexpected_tag(expected: u16, got: u16) -> Error {
     weld_err!("unexpected tag: {}, expected: {}", got, expected)
 }
 
-fn missing_tag_for_entity(tag: i32) -> Error {
+fn missing_tag_for_entity(tag: u16) -> Error {
     weld_err!("missing tag for entity: {}", tag)
 }
 
-fn emit<T: fmt::Display>(out: &mut String, tag: i32, data: T) {
+// Exact powers of ten, the largest a double holds without rounding.
+const POWERS_OF_TEN: [f64; 23] = [
+    1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11,
+    1e12, 1e13, 1e14, 1e15, 1e16, 1e17, 1e18, 1e19, 1e20, 1e21, 1e22,
+];
+
+// Most coordinates are plain decimals of at most 15 digits, such as `-12.3456`. Their digits make
+// an exact integer and dividing that by an exact power of ten rounds once, so the result is the same
+// correctly rounded double `str::parse` gives (Clinger's fast path), without its general scanning.
+// Exponents and longer digit runs take `str::parse`.
+fn parse_f64(value: &str) -> std::result::Result<f64, std::num::ParseFloatError> {
+    let (negative, digits) = match value.as_bytes() {
+        [b'-', rest @ ..] => (true, rest),
+        bytes => (false, bytes),
+    };
+    if digits.is_empty() || digits.len() > 16 || digits == b"." {
+        return value.parse();
+    }
+    let mut mantissa = 0u64;
+    let mut decimals = None;
+    for (i, byte) in digits.iter().enumerate() {
+        match byte {
+            b'0'..=b'9' => mantissa = mantissa * 10 + (byte - b'0') as u64,
+            b'.' if decimals.is_none() => decimals = Some(digits.len() - i - 1),
+            _ => return value.parse(),
+        }
+    }
+    // 16 digits can pass 2^53 and lose the integer's exactness
+    if decimals.is_none() && digits.len() > 15 {
+        return value.parse();
+    }
+    let magnitude = mantissa as f64 / POWERS_OF_TEN[decimals.unwrap_or(0)];
+    Ok(if negative { -magnitude } else { magnitude })
+}
+
+// How reals are written in DXF text. Some exporters follow the locale and write `1,5` for 1.5.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NumberFormat {
+    #[default]
+    Point,
+    // a comma as the decimal separator, though values with a point are still read
+    Comma,
+}
+
+// Reads a real: plain decimals, exponents in either case with an optional sign (`1.0E+2`) and, in
+// the comma format, a comma as the decimal separator. Infinities and NaN are rejected, being no
+// coordinates at all.
+fn parse_number(value: &str, format: NumberFormat) -> std::result::Result<f64, String> {
+    let parsed = match format {
+        NumberFormat::Comma if value.contains(',') => value.replacen(',', ".", 1).parse::<f64>(),
+        _ => parse_f64(value),
+    };
+    match parsed {
+        Ok(number) if number.is_finite() => Ok(number),
+        Ok(_) => Err(format!("number out of range: {}", value)),
+        Err(_) if format == NumberFormat::Point && value.contains(',') => {
+            Err(format!("invalid number: {}, which looks like a comma decimal; read such input with --comma-decimals", value))
+        },
+        Err(_) => Err(format!("invalid number: {}", value)),
+    }
+}
+
+// Splits DXF text into (line, group code, value) pairs on the fly, skipping blank lines. `line` is
+// the 1-based line of the last group code read and `value_line` that of its value, for error
+// reporting.
+struct Tokens<'a> {
+    lines: std::iter::Enumerate<std::str::Split<'a, char>>,
+    // lines before the text being split
+    base: usize,
+    line: usize,
+    value_line: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn new(src: &'a str) -> Tokens<'a> {
+        Tokens::at(src, 0)
+    }
+
+    // starts reading at a byte offset, keeping line numbers relative to the whole text
+    fn at(src: &'a str, offset: usize) -> Tokens<'a> {
+        let base = src.as_bytes()[..offset].iter().filter(|b| **b == b'\n').count();
+        Tokens {
+            lines: src[offset..].split('\n').enumerate(),
+            base,
+            line: base,
+            value_line: base,
+        }
+    }
+
+    fn next_line(&mut self) -> Option<(usize, &'a str)> {
+        let base = self.base;
+        self.lines.find_map(|(i, line)| {
+            let line = line.trim();
+            if line.is_empty() {
+                None
+            } else {
+                Some((base + i + 1, line))
+            }
+        })
+    }
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Result<(u16, &'a str)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (line, tag) = self.next_line()?;
+        self.line = line;
+        let tag = match tag.parse::<u16>() {
+            Ok(tag) => tag,
+            Err(_) => return Some(Err(weld_err!("invalid group code: {}", tag))),
+        };
+        // the value is the line after its tag even when blank, as empty strings are written
+        let (value_line, value) = match self.lines.next() {
+            Some((i, value)) => (self.base + i + 1, value.trim()),
+            None => return Some(Err(eof())),
+        };
+        self.value_line = value_line;
+        Some(Ok((tag, value)))
+    }
+}
+
+// doubles and 16-bit integers, the only values entities are built from
+fn is_numeric_group_code(code: u16) -> bool {
+    matches!(code, 10..=59 | 70..=79)
+}
+
+// `Drawing::parse` splits entities sections into chunks of at least this many bytes to parse on
+// separate threads
+const PARALLEL_CHUNK_BYTES: usize = 1 << 20;
+
+// the trimmed line starting at byte `start`, and the offset of the line after it
+fn line_at(src: &str, start: usize) -> (&str, usize) {
+    let end = src[start..].find('\n').map(|i| start + i).unwrap_or(src.len());
+    (src[start..end].trim(), (end + 1).min(src.len()))
+}
+
+// The first line at or after byte `from` holding a 0 group code followed by a name, which starts an
+// entity record, and the offset just past the name. A 0 that is a value is followed by a numeric
+// group code instead. The VERTEX and SEQEND records of a POLYLINE belong to it and start nothing.
+fn next_record(src: &str, from: usize) -> Option<(usize, usize)> {
+    let bytes = src.as_bytes();
+    let mut start = if from == 0 || bytes[from - 1] == b'\n' {
+        from
+    } else {
+        from + bytes[from..].iter().position(|b| *b == b'\n')? + 1
+    };
+    while start < src.len() {
+        let (line, next) = line_at(src, start);
+        if line == "0" {
+            let mut value_start = next;
+            while value_start < src.len() {
+                let (value, after) = line_at(src, value_start);
+                if value.is_empty() {
+                    value_start = after;
+                    continue;
+                }
+                if value.starts_with(|c: char| c.is_ascii_alphabetic()) && value != "VERTEX" && value != "SEQEND" {
+                    return Some((start, after));
+                }
+                break;
+            }
+        }
+        start = next;
+    }
+    None
+}
+
+// the `0`/`ENDSEC` record closing the section that starts at byte `from`, as in `next_record`
+fn section_end(src: &str, from: usize) -> Option<(usize, usize)> {
+    let mut search = from;
+    while let Some(i) = src[search..].find("ENDSEC") {
+        let at = search + i;
+        search = at + "ENDSEC".len();
+        let line_start = src[..at].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let (line, after) = line_at(src, line_start);
+        let code_start = src[..line_start].trim_end().rfind('\n').map(|i| i + 1).unwrap_or(0);
+        if line == "ENDSEC" && code_start >= from && src[code_start..line_start].trim() == "0" {
+            return Some((code_start, after));
+        }
+    }
+    None
+}
+
+// group code ranges defined by the DXF reference
+fn is_known_group_code(code: u16) -> bool {
+    matches!(code, 0..=79 | 90..=102 | 105 | 110..=149 | 160..=179 | 210..=239 | 270..=481 | 999 | 1000..=1071)
+}
+
+// A structural problem found while parsing, at the 1-based line of the group code that caused it
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub line: usize,
+    pub kind: &'static str,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(line: usize, kind: &'static str, message: String) -> Diagnostic {
+        Diagnostic { line, kind, message }
+    }
+}
+
+// builds an entity from its numeric group codes, where codes like a SPLINE's control points may repeat
+fn parse_entity(entity_type: &str, numbers: &[(u16, f64)]) -> Result<Entity> {
+    let all = |code: u16| numbers.iter().filter(move |(c, _)| *c == code).map(|(_, value)| *value);
+    let get = |code: u16| -> Result<f64> {
+        all(code).next_back().ok_or_else(|| missing_tag_for_entity(code))
+    };
+    let point = |x: u16, y: u16| -> Result<Point> { Ok(Point { x: get(x)?, y: get(y)? }) };
+    Ok(match entity_type {
+        "LINE" => Entity::Line(point(10, 20)?, point(11, 21)?),
+        "ARC" => Entity::Arc {
+            center: point(10, 20)?,
+            radius: get(40)?,
+            start_angle: get(50)?,
+            end_angle: get(51)?,
+        },
+        "CIRCLE" => Entity::Circle {
+            center: point(10, 20)?,
+            radius: get(40)?,
+        },
+        "ELLIPSE" => Entity::Ellipse {
+            center: point(10, 20)?,
+            major_axis: point(11, 21)?,
+            ratio: get(40)?,
+            start_param: get(41).unwrap_or(0.0),
+            end_param: get(42).unwrap_or(2.0 * std::f64::consts::PI),
+        },
+        "SPLINE" => {
+            let points = |x: u16, y: u16| -> Result<Vec<Point>> {
+                Ok(all(x).zip(all(y)).map(|(x, y)| Point { x, y }).collect())
+            };
+            let control_points = points(10, 20)?;
+            let degree = get(71).unwrap_or(3.0) as usize;
+            let (knots, weights) = (all(40).collect::<Vec<_>>(), all(41).collect::<Vec<_>>());
+            let count = control_points.len();
+            if degree == 0 {
+                return Err(weld_err!("SPLINE of degree 0"));
+            }
+            if !knots.is_empty() && count > degree && knots.len() != count + degree + 1 {
+                return Err(weld_err!("SPLINE has {} knots, expected {} for {} control points of degree {}", knots.len(), count + degree + 1, count, degree));
+            }
+            if !weights.is_empty() && (weights.len() != count || weights.iter().any(|weight| *weight <= 0.0)) {
+                return Err(weld_err!("SPLINE needs a positive weight (41) for each of its {} control points", count));
+            }
+            let uniform = knots.is_empty() || count <= degree || is_clamped_uniform(&knots, count, degree);
+            let rational = weights.windows(2).any(|pair| pair[0] != pair[1]);
+            // without control points, a polyline through the fit points is the best we can do
+            if control_points.is_empty() {
+                Entity::Polyline { curve_type: 0, vertices: points(11, 21)?, widths: vec![] }
+            } else if uniform && !rational && degree <= 3 {
+                let curve_type = match degree {
+                    1 => 0,
+                    2 => 5,
+                    _ => 6,
+                };
+                Entity::Polyline { curve_type, vertices: control_points, widths: vec![] }
+            } else {
+                // spline-fit polylines cannot hold other knots, weights or degrees, so the curve is
+                // traced finely enough to weld back into arcs
+                let knots = if knots.is_empty() {
+                    (0..count + degree + 1).map(|i| i.saturating_sub(degree).min(count.saturating_sub(degree)) as f64).collect()
+                } else {
+                    knots
+                };
+                let weights = if weights.is_empty() { vec![1.0; count] } else { weights };
+                Entity::Polyline { curve_type: 0, vertices: nurbs_points(&control_points, &weights, &knots, degree, FINE_CHORD_ERROR), widths: vec![] }
+            }
+        },
+        other => return Err(weld_err!("unsupported entity type: {}", other)),
+    })
+}
+
+// The LINE or ARC a polyline vertex draws to the next one, bulging by `bulge`: the tangent of a
+// quarter of the arc's included angle, positive when it turns counter-clockwise.
+fn bulge_entity(from: &Point, to: &Point, bulge: f64) -> Entity {
+    if bulge == 0.0 || from == to {
+        return Entity::Line(from.clone(), to.clone());
+    }
+    let half_angle = 2.0 * bulge.atan();
+    let chord = from.dist(to);
+    // from the chord's middle to the center, along the chord's left normal
+    let offset = chord / 2.0 / half_angle.tan();
+    let (dx, dy) = ((to.x - from.x) / chord, (to.y - from.y) / chord);
+    let center = Point { x: (from.x + to.x) / 2.0 - dy * offset, y: (from.y + to.y) / 2.0 + dx * offset };
+    let (start, end) = if bulge > 0.0 { (from, to) } else { (to, from) };
+    Entity::Arc {
+        radius: chord / (2.0 * half_angle.sin().abs()),
+        start_angle: normalize_degrees(start.angle(&center).to_degrees()),
+        end_angle: normalize_degrees(end.angle(&center).to_degrees()),
+        center,
+    }
+}
+
+// A POLYLINE read up to its SEQEND, with each vertex's flags, bulge and start and end width
+struct PolylineRecord<'a> {
+    line: usize,
+    layer: &'a str,
+    color: Option<i16>,
+    thickness: Option<f64>,
+    elevation: Option<f64>,
+    flags: u32,
+    curve_type: u32,
+    plane: Plane,
+    // the widths of vertices that give none of their own
+    default_widths: (f64, f64),
+    vertices: Vec<(Point, u32, f64, (f64, f64))>,
+}
+
+impl PolylineRecord<'_> {
+    // The entities the polyline draws. A spline-fit polyline (flag 4) keeps its frame control points
+    // (vertex flag 16) as a B-spline, leaving out the vertices fitting added (vertex flag 8). Any
+    // other polyline runs through its vertices, including those added by arc fitting (vertex flag
+    // 1), as one straight polyline or, once any vertex bulges, as a LINE or ARC per segment. Widths
+    // are kept on polylines, and lost once a bulging polyline is split into LINEs and ARCs.
+    fn entities(&self) -> Vec<Entity> {
+        let closed = self.flags & 1 != 0;
+        let widths = |vertices: &[&(Point, u32, f64, (f64, f64))]| {
+            let mut widths = vertices.iter().map(|(_, _, _, widths)| *widths).collect::<Vec<_>>();
+            if widths.iter().all(|(start, end)| *start == 0.0 && *end == 0.0) {
+                return vec![];
+            }
+            if closed {
+                widths.extend(widths.first().cloned());
+            }
+            widths
+        };
+        if self.flags & 4 != 0 {
+            let frame = self.vertices.iter().filter(|(_, flags, _, _)| flags & 16 != 0).collect::<Vec<_>>();
+            let frame = if frame.is_empty() {
+                self.vertices.iter().filter(|(_, flags, _, _)| flags & 8 == 0).collect()
+            } else {
+                frame
+            };
+            let mut control_points = frame.iter().map(|(point, _, _, _)| point.clone()).collect::<Vec<_>>();
+            if closed {
+                control_points.extend(control_points.first().cloned());
+            }
+            let curve_type = if matches!(self.curve_type, 5 | 6 | 8) { self.curve_type } else { 6 };
+            return vec![Entity::Polyline { curve_type, vertices: control_points, widths: widths(&frame) }];
+        }
+        let vertices = self.vertices.iter().filter(|(_, flags, _, _)| flags & 16 == 0).collect::<Vec<_>>();
+        let mut points = vertices.iter().map(|(point, _, _, _)| point.clone()).collect::<Vec<_>>();
+        if closed {
+            points.extend(points.first().cloned());
+        }
+        if points.len() < 2 {
+            return vec![];
+        }
+        if vertices.iter().all(|(_, _, bulge, _)| *bulge == 0.0) {
+            return vec![Entity::Polyline { curve_type: 0, vertices: points, widths: widths(&vertices) }];
+        }
+        points.windows(2).zip(vertices.iter()).map(|(pair, (_, _, bulge, _))| bulge_entity(&pair[0], &pair[1], *bulge)).collect()
+    }
+
+    // adds the polyline's entities to the block being read or the drawing, returning how many
+    fn finish(self, block: &mut Option<Block>, drawing: &mut Drawing, plane: &mut Plane, diagnostics: &mut Vec<Diagnostic>) -> usize {
+        // polygon and polyface meshes are surfaces, with nothing to weld
+        if self.flags & (16 | 64) != 0 {
+            diagnostics.push(Diagnostic::new(self.line, "unsupported-entity", "POLYLINE mesh is left out".to_string()));
+            return 0;
+        }
+        let entities = self.entities();
+        if entities.iter().any(|entity| !matches!(entity, Entity::Polyline { .. })) && self.vertices.iter().any(|(_, _, _, (start, end))| *start != 0.0 || *end != 0.0) {
+            diagnostics.push(Diagnostic::new(self.line, "polyline-width", "POLYLINE with bulges loses its widths".to_string()));
+        }
+        let layers = match block {
+            Some(block) => &mut block.layers,
+            None => {
+                plane.add(if self.plane.mixed { None } else { Some(self.plane.z.unwrap_or(0.0)) });
+                &mut drawing.layers
+            },
+        };
+        let count = entities.len();
+        layer_in(layers, self.layer, self.color, self.thickness, self.elevation).entities.extend(entities);
+        count
+    }
+}
+
+// The z of an entity lying flat, or None when its z varies. The z of an ellipse's major axis is left
+// out, being relative to the center, as is the elevation of code 38 kept on the entity's layer.
+fn entity_z(entity_type: &str, numbers: &[(u16, f64)]) -> Option<f64> {
+    let mut zs = numbers.iter().filter(|(code, _)| match code {
+        30 => true,
+        31 => entity_type != "ELLIPSE",
+        _ => false,
+    }).map(|(_, z)| *z);
+    let first = zs.next().unwrap_or(0.0);
+    if zs.all(|z| (z - first).abs() < POINT_PRECISION) { Some(first) } else { None }
+}
+
+// whether the entities parsed so far all lie in one plane of constant z
+#[derive(Default)]
+struct Plane {
+    z: Option<f64>,
+    mixed: bool,
+}
+
+impl Plane {
+    fn add(&mut self, z: Option<f64>) {
+        match (z, self.z) {
+            (None, _) => self.mixed = true,
+            (Some(z), None) => self.z = Some(z),
+            (Some(z), Some(plane)) => self.mixed |= (z - plane).abs() >= POINT_PRECISION,
+        }
+    }
+
+    // the plane's z, or None when the entities are at z = 0 or not in one plane
+    fn elevation(&self) -> Option<f64> {
+        self.z.filter(|z| !self.mixed && z.abs() >= POINT_PRECISION)
+    }
+}
+
+fn emit<T: fmt::Display>(out: &mut String, tag: u16, data: T) {
     out.push_str(&format!("  {}\n{}\n", tag, data));
 }
 
-impl ToString for Drawing {
-    fn to_string(&self) -> String {
-        let mut out = String::new();
-        // emit(&mut out, 0, "SECTION");
-        // emit(&mut out, 2, "HEADER");
-        // emit(&mut out, 9, "$ACADVER");
-        // emit(&mut out, 1, "AC1014");
-        // emit(&mut out, 9, "$MEASUREMENT");
-        // emit(&mut out, 70, 1);
-        // emit(&mut out, 0, "ENDSEC");
+fn emit_number(out: &mut String, tag: u16, value: f64) {
+    emit(out, tag, format_real(value));
+}
 
-        emit(&mut out, 0, "SECTION");
-        emit(&mut out, 2, "BLOCKS");
-        emit(&mut out, 0, "ENDSEC");
+// The DXF version `DxfBackend` writes. R12 output is plain entities, while R2000 output adds the
+// symbol tables, model and paper space blocks, handles with owners, subclass markers and a root
+// dictionary in an OBJECTS section.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DxfVersion {
+    #[default]
+    R12,
+    R2000,
+}
 
-        emit(&mut out, 0, "SECTION");
-        emit(&mut out, 2, "ENTITIES");
-        for entity in self.entities.iter() {
-            match entity {
-                Entity::Line(left, right) => {
-                    emit(&mut out, 0, "LINE");
-                    emit(&mut out, 8, 0.0);
-                    emit(&mut out, 10, left.x);
-                    emit(&mut out, 20, left.y);
-                    emit(&mut out, 11, right.x);
-                    emit(&mut out, 21, right.y);
-                },
-                Entity::Arc { center, radius, start_angle, end_angle } => {
-                    emit(&mut out, 0, "ARC");
-                    emit(&mut out, 8, 0.0);
-                    emit(&mut out, 10, center.x);
-                    emit(&mut out, 20, center.y);
-                    emit(&mut out, 40, *radius);
-                    emit(&mut out, 50, *start_angle);
-                    emit(&mut out, 51, *end_angle);
-                },
-                Entity::Circle { center, radius } => {
-                    emit(&mut out, 0, "CIRCLE");
-                    emit(&mut out, 8, 0.0);
-                    emit(&mut out, 10, center.x);
-                    emit(&mut out, 20, center.y);
-                    emit(&mut out, 40, *radius);
-                },
-                Entity::Polyline { curve_type, vertices } => {
-                    emit(&mut out, 0, "POLYLINE");
-                    emit(&mut out, 8, 0.0);
-                    emit(&mut out, 70, 8u32);
-                    emit(&mut out, 75, curve_type);
-                    for Point { x, y } in vertices.iter() {
-                        emit(&mut out, 0, "VERTEX");
-                        emit(&mut out, 8, 0.0);
-                        emit(&mut out, 70, 32u32);
-                        emit(&mut out, 10, x);
-                        emit(&mut out, 20, y);
+// handles of the tables, dictionaries and records every R2000 drawing has; block records, layers
+// and entities are numbered after them
+const BLOCK_RECORD_TABLE_HANDLE: u64 = 0x1;
+const LAYER_TABLE_HANDLE: u64 = 0x2;
+const STYLE_TABLE_HANDLE: u64 = 0x3;
+const LTYPE_TABLE_HANDLE: u64 = 0x5;
+const VIEW_TABLE_HANDLE: u64 = 0x6;
+const UCS_TABLE_HANDLE: u64 = 0x7;
+const VPORT_TABLE_HANDLE: u64 = 0x8;
+const APPID_TABLE_HANDLE: u64 = 0x9;
+const DIMSTYLE_TABLE_HANDLE: u64 = 0xA;
+const ROOT_DICTIONARY_HANDLE: u64 = 0xC;
+const GROUP_DICTIONARY_HANDLE: u64 = 0xD;
+const PLOT_STYLE_DICTIONARY_HANDLE: u64 = 0xE;
+const NORMAL_PLOT_STYLE_HANDLE: u64 = 0xF;
+const STANDARD_STYLE_HANDLE: u64 = 0x10;
+const ACAD_APPID_HANDLE: u64 = 0x11;
+const STANDARD_DIMSTYLE_HANDLE: u64 = 0x12;
+const ACTIVE_VPORT_HANDLE: u64 = 0x13;
+const LTYPE_HANDLES: [(u64, &str, &str); 3] = [(0x14, "ByBlock", ""), (0x15, "ByLayer", ""), (0x16, "Continuous", "Solid line")];
+// the record, BLOCK and ENDBLK handles of the model and paper space blocks
+const SPACE_HANDLES: [(&str, [u64; 3]); 2] = [("*Model_Space", [0x17, 0x18, 0x19]), ("*Paper_Space", [0x1A, 0x1B, 0x1C])];
+const MODEL_SPACE_HANDLE: u64 = SPACE_HANDLES[0].1[0];
+const FIRST_ENTITY_HANDLE: u64 = 0x100;
+
+fn handle(value: u64) -> String {
+    format!("{:X}", value)
+}
+
+pub struct DxfBackend<'a> {
+    out: &'a mut String,
+    layer: String,
+    color: Option<i16>,
+    thickness: Option<f64>,
+    layer_elevation: Option<f64>,
+    angle_precision: Option<usize>,
+    version: DxfVersion,
+    fragment: bool,
+    elevation: Option<f64>,
+    ucs_origin: Option<Point>,
+    limits_min: Option<Point>,
+    next_handle: u64,
+    in_entities: bool,
+    // the handle of the block record owning the entities being written
+    owner: u64,
+    // for R2000 tables: each block's name and record handle, and the layer names in order
+    block_records: Vec<(String, u64)>,
+    layer_names: Vec<String>,
+    finished: bool,
+}
+
+impl<'a> DxfBackend<'a> {
+    pub fn new(out: &'a mut String) -> DxfBackend<'a> {
+        DxfBackend {
+            out,
+            layer: DEFAULT_LAYER.to_string(),
+            color: None,
+            thickness: None,
+            layer_elevation: None,
+            angle_precision: None,
+            version: DxfVersion::R12,
+            fragment: false,
+            elevation: None,
+            ucs_origin: None,
+            limits_min: None,
+            next_handle: FIRST_ENTITY_HANDLE,
+            in_entities: false,
+            owner: MODEL_SPACE_HANDLE,
+            block_records: vec![],
+            layer_names: vec![DEFAULT_LAYER.to_string()],
+            finished: false,
+        }
+    }
+
+    pub fn with_version(mut self, version: DxfVersion) -> DxfBackend<'a> {
+        self.version = version;
+        self
+    }
+
+    // writes every entity at this z rather than at 0
+    pub fn with_elevation(mut self, elevation: Option<f64>) -> DxfBackend<'a> {
+        self.elevation = elevation;
+        self
+    }
+
+    // writes the drawing's `$UCSORG` and `$LIMMIN` into the header
+    pub fn with_header_origins(mut self, drawing: &Drawing) -> DxfBackend<'a> {
+        self.ucs_origin = drawing.ucs_origin.clone();
+        self.limits_min = drawing.limits_min.clone();
+        self
+    }
+
+    // writes only the entities, without the sections around them or EOF, for pasting into another file
+    pub fn with_fragment(mut self, fragment: bool) -> DxfBackend<'a> {
+        self.fragment = fragment;
+        self
+    }
+
+    // writes arc angles with this many decimals instead of full precision
+    pub fn with_angle_precision(mut self, precision: Option<usize>) -> DxfBackend<'a> {
+        self.angle_precision = precision;
+        self
+    }
+
+    // the entity type, layer and color, with a handle, owner and the entity's subclass markers for R2000
+    fn emit_common(&mut self, entity_type: &str, subclasses: &[&str]) {
+        emit(self.out, 0, entity_type);
+        if self.version == DxfVersion::R2000 {
+            emit(self.out, 5, handle(self.next_handle));
+            self.next_handle += 1;
+            emit(self.out, 330, handle(self.owner));
+            emit(self.out, 100, "AcDbEntity");
+        }
+        emit(self.out, 8, &self.layer);
+        if let Some(color) = self.color {
+            emit(self.out, 62, color);
+        }
+        self.emit_subclass(subclasses);
+    }
+
+    // the layer's elevation and thickness, on the entities that take them
+    fn emit_attributes(&mut self) {
+        if let Some(elevation) = self.layer_elevation {
+            emit_number(self.out, 38, elevation);
+        }
+        if let Some(thickness) = self.thickness {
+            emit_number(self.out, 39, thickness);
+        }
+    }
+
+    // a point's x, y and, for drawings with an elevation, z under codes `code`, `code + 10` and `code + 20`
+    fn emit_point(&mut self, code: u16, point: &Point) {
+        emit_number(self.out, code, point.x);
+        emit_number(self.out, code + 10, point.y);
+        if let Some(z) = self.elevation {
+            emit_number(self.out, code + 20, z);
+        }
+    }
+
+    // ends the BLOCKS section and starts the ENTITIES section, before the first entity
+    fn open_entities(&mut self) {
+        if self.fragment || self.in_entities {
+            return;
+        }
+        self.in_entities = true;
+        emit(self.out, 0, "ENDSEC");
+        emit(self.out, 0, "SECTION");
+        emit(self.out, 2, "ENTITIES");
+    }
+
+    fn write_entity(&mut self, entity: &Entity) {
+        match entity {
+            Entity::Line(left, right) => {
+                self.emit_common("LINE", &["AcDbLine"]);
+                self.emit_attributes();
+                self.emit_point(10, left);
+                self.emit_point(11, right);
+            },
+            Entity::Arc { center, radius, start_angle, end_angle } => {
+                self.emit_common("ARC", &["AcDbCircle"]);
+                self.emit_attributes();
+                self.emit_point(10, center);
+                emit_number(self.out, 40, *radius);
+                self.emit_subclass(&["AcDbArc"]);
+                emit(self.out, 50, format_angle(*start_angle, self.angle_precision));
+                emit(self.out, 51, format_angle(*end_angle, self.angle_precision));
+            },
+            Entity::Circle { center, radius } => {
+                self.emit_common("CIRCLE", &["AcDbCircle"]);
+                self.emit_attributes();
+                self.emit_point(10, center);
+                emit_number(self.out, 40, *radius);
+            },
+            // R12 has no ELLIPSE, so it gets a polyline through points on it instead
+            Entity::Ellipse { .. } if self.version == DxfVersion::R12 => {
+                let vertices = entity.tessellate(FINE_CHORD_ERROR);
+                self.write_entity(&Entity::Polyline { curve_type: 0, vertices, widths: vec![] });
+            },
+            Entity::Ellipse { center, major_axis, ratio, start_param, end_param } => {
+                self.emit_common("ELLIPSE", &["AcDbEllipse"]);
+                self.emit_attributes();
+                self.emit_point(10, center);
+                let out = &mut *self.out;
+                emit_number(out, 11, major_axis.x);
+                emit_number(out, 21, major_axis.y);
+                emit_number(out, 40, *ratio);
+                emit_number(out, 41, *start_param);
+                emit_number(out, 42, *end_param);
+            },
+            Entity::Polyline { curve_type, vertices, widths } => {
+                // a 2D polyline, whose vertices follow (66) and whose header point holds only the
+                // elevation; B-splines are spline-fit (4) with their vertices as the frame control
+                // points (16). A constant width is written once as the default for every vertex.
+                let spline_fit = matches!(curve_type, 5 | 6 | 8);
+                let constant_width = widths.first().filter(|first| widths.iter().all(|widths| widths == *first));
+                self.emit_common("POLYLINE", &["AcDb2dPolyline"]);
+                self.emit_attributes();
+                emit(self.out, 66, 1);
+                self.emit_point(10, &Point { x: 0.0, y: 0.0 });
+                emit(self.out, 70, if spline_fit { 4 } else { 0 });
+                if let Some((start, end)) = constant_width {
+                    emit_number(self.out, 40, *start);
+                    emit_number(self.out, 41, *end);
+                }
+                if spline_fit {
+                    emit(self.out, 75, curve_type);
+                }
+                // the vertices and SEQEND belong to the POLYLINE, the handle just written
+                let owner = std::mem::replace(&mut self.owner, self.next_handle - 1);
+                for (i, vertex) in vertices.iter().enumerate() {
+                    self.emit_common("VERTEX", &["AcDbVertex", "AcDb2dVertex"]);
+                    self.emit_point(10, vertex);
+                    if let Some((start, end)) = widths.get(i).filter(|_| constant_width.is_none()) {
+                        emit_number(self.out, 40, *start);
+                        emit_number(self.out, 41, *end);
                     }
-                    emit(&mut out, 0, "SEQEND");
-                },
+                    emit(self.out, 70, if spline_fit { 16 } else { 0 });
+                }
+                self.emit_common("SEQEND", &[]);
+                self.owner = owner;
+            },
+        }
+    }
+
+    fn write_insert(&mut self, insert: &Insert) {
+        self.emit_common("INSERT", &["AcDbBlockReference"]);
+        emit(self.out, 2, &insert.block);
+        self.emit_point(10, &insert.position);
+        emit_number(self.out, 41, insert.x_scale);
+        emit_number(self.out, 42, insert.y_scale);
+        emit(self.out, 50, format_angle(insert.rotation, self.angle_precision));
+    }
+
+    // subclass markers are left out of R12 output, like the ones written for other entities
+    fn write_raw(&mut self, raw: &RawEntity) {
+        self.emit_common(&raw.kind, &[]);
+        let subclasses = self.version == DxfVersion::R2000;
+        for (code, value) in raw.codes.iter().filter(|(code, _)| *code != 100 || subclasses) {
+            emit(self.out, *code, value);
+        }
+    }
+
+    fn emit_subclass(&mut self, subclasses: &[&str]) {
+        if self.version == DxfVersion::R2000 {
+            for subclass in subclasses.iter() {
+                emit(self.out, 100, subclass);
             }
         }
-        emit(&mut out, 0, "ENDSEC");
+    }
+
+    // the BLOCK and ENDBLK of the model or paper space, which R2000 drawings always have
+    fn write_space_block(&mut self, name: &str, [record, begin, end]: [u64; 3]) {
+        let out = &mut *self.out;
+        emit(out, 0, "BLOCK");
+        emit(out, 5, handle(begin));
+        emit(out, 330, handle(record));
+        emit(out, 100, "AcDbEntity");
+        if name == "*Paper_Space" {
+            emit(out, 67, 1);
+        }
+        emit(out, 8, DEFAULT_LAYER);
+        emit(out, 100, "AcDbBlockBegin");
+        emit(out, 2, name);
+        emit(out, 70, 0);
+        for code in [10, 20, 30] {
+            emit(out, code, 0);
+        }
+        emit(out, 3, name);
+        emit(out, 1, "");
+        emit(out, 0, "ENDBLK");
+        emit(out, 5, handle(end));
+        emit(out, 330, handle(record));
+        emit(out, 100, "AcDbEntity");
+        if name == "*Paper_Space" {
+            emit(out, 67, 1);
+        }
+        emit(out, 8, DEFAULT_LAYER);
+        emit(out, 100, "AcDbBlockEnd");
+    }
+
+    // The R2000 HEADER and TABLES sections, once every block and layer has been seen: the header
+    // gives the next free handle, so they are written last and placed in front.
+    fn r2000_tables(&mut self) -> String {
+        let mut out = String::new();
+        let first_layer = self.next_handle;
+        self.next_handle += self.layer_names.len() as u64;
+        let layers = self.layer_names.iter().enumerate().map(|(i, name)| (first_layer + i as u64, name.as_str())).collect::<Vec<_>>();
         emit(&mut out, 0, "SECTION");
-        emit(&mut out, 2, "OBJECTS");
-        emit(&mut out, 0, "DICTIONARY");
+        emit(&mut out, 2, "HEADER");
+        emit(&mut out, 9, "$ACADVER");
+        emit(&mut out, 1, "AC1015");
+        emit(&mut out, 9, "$HANDSEED");
+        emit(&mut out, 5, handle(self.next_handle));
+        for (name, origin) in [("$UCSORG", &self.ucs_origin), ("$LIMMIN", &self.limits_min)] {
+            if let Some(origin) = origin {
+                emit(&mut out, 9, name);
+                emit_number(&mut out, 10, origin.x);
+                emit_number(&mut out, 20, origin.y);
+            }
+        }
         emit(&mut out, 0, "ENDSEC");
-        emit(&mut out, 0, "EOF");
 
+        emit(&mut out, 0, "SECTION");
+        emit(&mut out, 2, "TABLES");
+        let table = |out: &mut String, name: &str, table: u64, entries: usize| {
+            emit(out, 0, "TABLE");
+            emit(out, 2, name);
+            emit(out, 5, handle(table));
+            emit(out, 330, 0);
+            emit(out, 100, "AcDbSymbolTable");
+            emit(out, 70, entries);
+        };
+        let record = |out: &mut String, kind: &str, entry: u64, table: u64, subclass: &str, name: &str| {
+            emit(out, 0, kind);
+            emit(out, if kind == "DIMSTYLE" { 105 } else { 5 }, handle(entry));
+            emit(out, 330, handle(table));
+            emit(out, 100, "AcDbSymbolTableRecord");
+            emit(out, 100, subclass);
+            emit(out, 2, name);
+            emit(out, 70, 0);
+        };
+
+        table(&mut out, "VPORT", VPORT_TABLE_HANDLE, 1);
+        record(&mut out, "VPORT", ACTIVE_VPORT_HANDLE, VPORT_TABLE_HANDLE, "AcDbViewportTableRecord", "*Active");
+        for (code, value) in [(10, 0), (20, 0), (11, 1), (21, 1), (12, 0), (22, 0), (40, 1), (41, 1)] {
+            emit(&mut out, code, value);
+        }
+        emit(&mut out, 0, "ENDTAB");
+
+        table(&mut out, "LTYPE", LTYPE_TABLE_HANDLE, LTYPE_HANDLES.len());
+        for (entry, name, description) in LTYPE_HANDLES {
+            record(&mut out, "LTYPE", entry, LTYPE_TABLE_HANDLE, "AcDbLinetypeTableRecord", name);
+            emit(&mut out, 3, description);
+            emit(&mut out, 72, 65);
+            emit(&mut out, 73, 0);
+            emit(&mut out, 40, 0);
+        }
+        emit(&mut out, 0, "ENDTAB");
+
+        table(&mut out, "LAYER", LAYER_TABLE_HANDLE, layers.len());
+        for (entry, name) in layers.iter() {
+            record(&mut out, "LAYER", *entry, LAYER_TABLE_HANDLE, "AcDbLayerTableRecord", name);
+            emit(&mut out, 62, 7);
+            emit(&mut out, 6, "Continuous");
+            emit(&mut out, 370, -3);
+            emit(&mut out, 390, handle(NORMAL_PLOT_STYLE_HANDLE));
+        }
+        emit(&mut out, 0, "ENDTAB");
+
+        table(&mut out, "STYLE", STYLE_TABLE_HANDLE, 1);
+        record(&mut out, "STYLE", STANDARD_STYLE_HANDLE, STYLE_TABLE_HANDLE, "AcDbTextStyleTableRecord", "Standard");
+        for (code, value) in [(40, "0"), (41, "1"), (50, "0"), (71, "0"), (42, "2.5"), (3, "txt"), (4, "")] {
+            emit(&mut out, code, value);
+        }
+        emit(&mut out, 0, "ENDTAB");
+
+        for (name, table_handle) in [("VIEW", VIEW_TABLE_HANDLE), ("UCS", UCS_TABLE_HANDLE)] {
+            table(&mut out, name, table_handle, 0);
+            emit(&mut out, 0, "ENDTAB");
+        }
+
+        table(&mut out, "APPID", APPID_TABLE_HANDLE, 1);
+        record(&mut out, "APPID", ACAD_APPID_HANDLE, APPID_TABLE_HANDLE, "AcDbRegAppTableRecord", "ACAD");
+        emit(&mut out, 0, "ENDTAB");
+
+        table(&mut out, "DIMSTYLE", DIMSTYLE_TABLE_HANDLE, 1);
+        emit(&mut out, 100, "AcDbDimStyleTable");
+        record(&mut out, "DIMSTYLE", STANDARD_DIMSTYLE_HANDLE, DIMSTYLE_TABLE_HANDLE, "AcDbDimStyleTableRecord", "Standard");
+        emit(&mut out, 0, "ENDTAB");
+
+        let records = SPACE_HANDLES.iter().map(|(name, handles)| (name.to_string(), handles[0])).chain(self.block_records.iter().cloned()).collect::<Vec<_>>();
+        table(&mut out, "BLOCK_RECORD", BLOCK_RECORD_TABLE_HANDLE, records.len());
+        for (name, entry) in records.iter() {
+            emit(&mut out, 0, "BLOCK_RECORD");
+            emit(&mut out, 5, handle(*entry));
+            emit(&mut out, 330, handle(BLOCK_RECORD_TABLE_HANDLE));
+            emit(&mut out, 100, "AcDbSymbolTableRecord");
+            emit(&mut out, 100, "AcDbBlockTableRecord");
+            emit(&mut out, 2, name);
+        }
+        emit(&mut out, 0, "ENDTAB");
+        emit(&mut out, 0, "ENDSEC");
         out
     }
 }
 
+impl<'a> OutputBackend for DxfBackend<'a> {
+    // R2000 output is held until it is finished, when its header and tables are put in front of it
+    fn buffer(&mut self) -> Option<&mut String> {
+        if self.version == DxfVersion::R2000 && !self.fragment && !self.finished {
+            return None;
+        }
+        Some(self.out)
+    }
+
+    fn begin(&mut self) {
+        if self.fragment {
+            return;
+        }
+        let out = &mut *self.out;
+        let origins = [("$UCSORG", &self.ucs_origin), ("$LIMMIN", &self.limits_min)];
+        if self.version == DxfVersion::R12 && origins.iter().any(|(_, origin)| origin.is_some()) {
+            emit(out, 0, "SECTION");
+            emit(out, 2, "HEADER");
+            for (name, origin) in origins.iter() {
+                if let Some(origin) = origin {
+                    emit(out, 9, name);
+                    emit_number(out, 10, origin.x);
+                    emit_number(out, 20, origin.y);
+                }
+            }
+            emit(out, 0, "ENDSEC");
+        }
+
+        emit(out, 0, "SECTION");
+        emit(out, 2, "BLOCKS");
+        if self.version == DxfVersion::R2000 {
+            for (name, handles) in SPACE_HANDLES {
+                self.write_space_block(name, handles);
+            }
+        }
+    }
+
+    fn layer(&mut self, layer: &Layer) {
+        self.layer = layer.name.clone();
+        self.color = layer.color;
+        self.thickness = layer.thickness;
+        self.layer_elevation = layer.elevation;
+        if !self.layer_names.contains(&layer.name) {
+            self.layer_names.push(layer.name.clone());
+        }
+    }
+
+    fn entity(&mut self, entity: &Entity) {
+        self.open_entities();
+        self.write_entity(entity);
+    }
+
+    fn insert(&mut self, insert: &Insert) {
+        self.open_entities();
+        self.write_insert(insert);
+    }
+
+    fn raw(&mut self, raw: &RawEntity) {
+        self.open_entities();
+        self.write_raw(raw);
+    }
+
+    // Writes a block definition into the BLOCKS section, its entities without the drawing's
+    // elevation since they are placed relative to the block's base.
+    fn block(&mut self, block: &Block) {
+        if self.fragment || self.in_entities {
+            return;
+        }
+        let elevation = self.elevation.take();
+        if self.version == DxfVersion::R2000 {
+            self.owner = self.next_handle;
+            self.block_records.push((block.name.clone(), self.next_handle));
+            self.next_handle += 1;
+        }
+        self.layer(&Layer::new(DEFAULT_LAYER, None));
+        self.emit_common("BLOCK", &["AcDbBlockBegin"]);
+        emit(self.out, 2, &block.name);
+        // flag 1 marks an anonymous block
+        emit(self.out, 70, if block.name.starts_with('*') { 1 } else { 0 });
+        self.emit_point(10, &block.base);
+        emit(self.out, 3, &block.name);
+        for layer in block.layers.iter() {
+            self.layer(layer);
+            for entity in layer.entities.iter() {
+                self.write_entity(entity);
+            }
+            for insert in layer.inserts.iter() {
+                self.write_insert(insert);
+            }
+            for raw in layer.raw.iter() {
+                self.write_raw(raw);
+            }
+        }
+        self.layer(&Layer::new(DEFAULT_LAYER, None));
+        self.emit_common("ENDBLK", &["AcDbBlockEnd"]);
+        self.owner = MODEL_SPACE_HANDLE;
+        self.elevation = elevation;
+    }
+
+    fn finish(&mut self) {
+        if self.fragment {
+            return;
+        }
+        self.open_entities();
+        let out = &mut *self.out;
+        emit(out, 0, "ENDSEC");
+        if self.version == DxfVersion::R2000 {
+            emit(out, 0, "SECTION");
+            emit(out, 2, "OBJECTS");
+            emit(out, 0, "DICTIONARY");
+            emit(out, 5, handle(ROOT_DICTIONARY_HANDLE));
+            emit(out, 330, 0);
+            emit(out, 100, "AcDbDictionary");
+            emit(out, 281, 1);
+            emit(out, 3, "ACAD_GROUP");
+            emit(out, 350, handle(GROUP_DICTIONARY_HANDLE));
+            emit(out, 3, "ACAD_PLOTSTYLENAME");
+            emit(out, 350, handle(PLOT_STYLE_DICTIONARY_HANDLE));
+            emit(out, 0, "DICTIONARY");
+            emit(out, 5, handle(GROUP_DICTIONARY_HANDLE));
+            emit(out, 330, handle(ROOT_DICTIONARY_HANDLE));
+            emit(out, 100, "AcDbDictionary");
+            emit(out, 281, 1);
+            emit(out, 0, "ACDBDICTIONARYWDFLT");
+            emit(out, 5, handle(PLOT_STYLE_DICTIONARY_HANDLE));
+            emit(out, 330, handle(ROOT_DICTIONARY_HANDLE));
+            emit(out, 100, "AcDbDictionary");
+            emit(out, 281, 1);
+            emit(out, 3, "Normal");
+            emit(out, 350, handle(NORMAL_PLOT_STYLE_HANDLE));
+            emit(out, 100, "AcDbDictionaryWithDefault");
+            emit(out, 340, handle(NORMAL_PLOT_STYLE_HANDLE));
+            emit(out, 0, "ACDBPLACEHOLDER");
+            emit(out, 5, handle(NORMAL_PLOT_STYLE_HANDLE));
+            emit(out, 330, handle(PLOT_STYLE_DICTIONARY_HANDLE));
+            emit(out, 0, "ENDSEC");
+        }
+        emit(out, 0, "EOF");
+        if self.version == DxfVersion::R2000 {
+            let tables = self.r2000_tables();
+            self.out.insert_str(0, &tables);
+        }
+        self.finished = true;
+    }
+}
+
+impl fmt::Display for Drawing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        self.write_to(&mut DxfBackend::new(&mut out).with_elevation(self.elevation).with_header_origins(self));
+        f.write_str(&out)
+    }
+}
+
+// `name` numbered so that no block in `names` has it: anonymous `*U` blocks take the next free
+// number, others a `_2`, `_3`... suffix
+fn free_block_name(name: &str, names: &HashSet<String>) -> String {
+    let anonymous = name.starts_with("*U");
+    let numbered = |number: usize| if anonymous { format!("*U{}", number) } else { format!("{}_{}", name, number) };
+    (2..).map(numbered).find(|candidate| !names.contains(candidate)).unwrap()
+}
+
 impl Drawing {
+    // a drawing with all entities on the default layer
+    pub fn new(entities: Vec<Entity>) -> Drawing {
+        let mut layer = Layer::new(DEFAULT_LAYER, None);
+        layer.entities = entities;
+        Drawing {
+            layers: vec![layer],
+            ..Default::default()
+        }
+    }
 
+    pub fn entities(&self) -> impl Iterator<Item = &Entity> {
+        self.layers.iter().flat_map(|layer| layer.entities.iter())
+    }
+
+    pub fn entities_mut(&mut self) -> impl Iterator<Item = &mut Entity> {
+        self.layers.iter_mut().flat_map(|layer| layer.entities.iter_mut())
+    }
+
+    pub fn entity_count(&self) -> usize {
+        self.layers.iter().map(|layer| layer.entities.len()).sum()
+    }
+
+    pub fn header_origin(&self, origin: HeaderOrigin) -> Option<&Point> {
+        match origin {
+            HeaderOrigin::Ucs => self.ucs_origin.as_ref(),
+            HeaderOrigin::Limits => self.limits_min.as_ref(),
+        }
+    }
+
+    // the length of all the drawing's lines and curves, counting each block once however often it
+    // is inserted
+    pub fn length(&self) -> f64 {
+        self.entities().map(Entity::length).sum::<f64>() + self.blocks.iter().map(Block::length).sum::<f64>()
+    }
+
+    // finds or creates the layer with this name and color, and no thickness or elevation
+    pub fn layer_mut(&mut self, name: &str, color: Option<i16>) -> &mut Layer {
+        self.layer_mut_with(name, color, None, None)
+    }
+
+    pub fn layer_mut_with(&mut self, name: &str, color: Option<i16>, thickness: Option<f64>, elevation: Option<f64>) -> &mut Layer {
+        layer_in(&mut self.layers, name, color, thickness, elevation)
+    }
+
+    fn split_by<F: Fn(&Layer) -> String>(self, key: F) -> Vec<(String, Drawing)> {
+        let mut out: Vec<(String, Drawing)> = vec![];
+        for layer in self.layers.into_iter() {
+            let key = key(&layer);
+            match out.iter_mut().find(|(existing, _)| *existing == key) {
+                Some((_, drawing)) => drawing.layers.push(layer),
+                None => out.push((key, Drawing {
+                    layers: vec![layer],
+                    elevation: self.elevation,
+                    blocks: self.blocks.clone(),
+                    ucs_origin: self.ucs_origin.clone(),
+                    limits_min: self.limits_min.clone(),
+                })),
+            }
+        }
+        out
+    }
+
+    // Moves the layers of `other` into this drawing, joining layers with the same name and color. The
+    // elevation and header origins are kept only when both drawings share them. A block of `other`
+    // named like a different block here is renamed, its inserts following, and one equal to it is
+    // dropped.
+    pub fn merge(&mut self, mut other: Drawing) {
+        if self.layers.is_empty() {
+            self.elevation = other.elevation;
+            self.ucs_origin = other.ucs_origin;
+            self.limits_min = other.limits_min;
+        } else {
+            if self.elevation != other.elevation {
+                self.elevation = None;
+            }
+            if self.ucs_origin != other.ucs_origin {
+                self.ucs_origin = None;
+            }
+            if self.limits_min != other.limits_min {
+                self.limits_min = None;
+            }
+        }
+        let mut names = self.blocks.iter().chain(other.blocks.iter()).map(|block| block.name.clone()).collect::<HashSet<_>>();
+        let mut renamed = HashMap::new();
+        let mut blocks = vec![];
+        for mut block in std::mem::take(&mut other.blocks) {
+            match self.blocks.iter().find(|existing| existing.name == block.name) {
+                Some(existing) if *existing == block => continue,
+                Some(_) => {
+                    let name = free_block_name(&block.name, &names);
+                    names.insert(name.clone());
+                    renamed.insert(std::mem::replace(&mut block.name, name.clone()), name);
+                },
+                None => (),
+            }
+            blocks.push(block);
+        }
+        let inserts = other.layers.iter_mut().chain(blocks.iter_mut().flat_map(|block| block.layers.iter_mut())).flat_map(|layer| layer.inserts.iter_mut());
+        for insert in inserts {
+            if let Some(name) = renamed.get(&insert.block) {
+                insert.block = name.clone();
+            }
+        }
+        for layer in other.layers.into_iter() {
+            let merged = self.layer_mut_with(&layer.name, layer.color, layer.thickness, layer.elevation);
+            merged.entities.extend(layer.entities);
+            merged.inserts.extend(layer.inserts);
+            merged.raw.extend(layer.raw);
+        }
+        self.blocks.extend(blocks);
+    }
+
+    // one drawing per layer name, in first-seen order
+    pub fn split_by_layer(self) -> Vec<(String, Drawing)> {
+        self.split_by(|layer| layer.name.clone())
+    }
+
+    // one drawing per color, keyed by the color index or "bylayer"
+    pub fn split_by_color(self) -> Vec<(String, Drawing)> {
+        self.split_by(|layer| match layer.color {
+            Some(color) => color.to_string(),
+            None => "bylayer".to_string(),
+        })
+    }
+
+    // Large entities sections are parsed on all available cores. Use `parse_with_diagnostics` for a
+    // single-threaded parse.
     pub fn parse(src: &str) -> Result<Drawing> {
-        let mut lines = src.split('\n').map(|x| x.trim()).filter(|x| x.len() > 0).collect::<VecDeque<&str>>();
-        let mut entities = vec![];
-        let mut state = 0;
+        Drawing::parse_limited(src, &Limits::default())
+    }
+
+    // Parses like `parse`, failing as soon as the input breaks one of `limits`.
+    pub fn parse_limited(src: &str, limits: &Limits) -> Result<Drawing> {
+        limits.check_file_size(src.len())?;
+        let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        Drawing::parse_lines(src, 0, &mut vec![], &mut 0, Some((threads, PARALLEL_CHUNK_BYTES)), limits, NumberFormat::Point)
+    }
+
+    // Parses like `parse_limited`, reading reals written in `format`
+    pub fn parse_formatted(src: &str, limits: &Limits, format: NumberFormat) -> Result<Drawing> {
+        limits.check_file_size(src.len())?;
+        let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        Drawing::parse_lines(src, 0, &mut vec![], &mut 0, Some((threads, PARALLEL_CHUNK_BYTES)), limits, format)
+    }
+
+    // Parses raw file contents, rejecting anything that is not UTF-8. Malformed input of any kind is
+    // an error, never a panic.
+    pub fn parse_bytes(src: &[u8]) -> Result<Drawing> {
+        Drawing::parse(std::str::from_utf8(src)?)
+    }
+
+    // Parses like `parse`, also collecting structural problems that do not stop parsing. A fatal
+    // error is recorded as a `parse-error` diagnostic as well as returned.
+    pub fn parse_with_diagnostics(src: &str, diagnostics: &mut Vec<Diagnostic>) -> Result<Drawing> {
+        let mut line = 0;
+        Drawing::parse_lines(src, 0, diagnostics, &mut line, None, &Limits::default(), NumberFormat::Point).inspect_err(|e| {
+            diagnostics.push(Diagnostic::new(line, "parse-error", e.to_string()));
+        })
+    }
+
+    // Splits the entities section starting at byte `start` at entity boundaries and parses the pieces
+    // on up to `threads` threads, each ending with the record that starts the next. Returns the
+    // pieces' drawings in order and the offset just past the section's ENDSEC, or None if the section
+    // is too small to split or never ends.
+    fn parse_entities_parallel(src: &str, start: usize, threads: usize, chunk_bytes: usize, limits: &Limits, format: NumberFormat) -> Option<(Result<Vec<Drawing>>, usize)> {
+        let (end, after) = section_end(src, start)?;
+        let chunks = ((end - start) / chunk_bytes).min(threads);
+        if chunks < 2 {
+            return None;
+        }
+        let mut bounds = vec![(start, start)];
+        for i in 1..chunks {
+            let record = next_record(src, start + i * (end - start) / chunks).filter(|(at, _)| *at < end);
+            if let Some(record) = record.filter(|(at, _)| *at > bounds.last().unwrap().0) {
+                bounds.push(record);
+            }
+        }
+        bounds.push((end, after));
+        let drawings = std::thread::scope(|scope| {
+            let parsers = bounds.windows(2).map(|pair| {
+                let chunk = &src[pair[0].0..pair[1].1];
+                scope.spawn(move || Drawing::parse_lines(chunk, 3, &mut vec![], &mut 0, None, limits, format))
+            }).collect::<Vec<_>>();
+            parsers.into_iter().map(|parser| parser.join().unwrap()).collect::<Result<Vec<_>>>()
+        });
+        Some((drawings, after))
+    }
+
+    // Parses from `state` (0 between sections, 3 inside an entities section). With `parallel` set to
+    // (threads, chunk bytes), diagnostics are not collected and entities sections are parsed by
+    // `parse_entities_parallel`. Stops once more entities than `limits.max_entities` have been read.
+    fn parse_lines(src: &str, mut state: u8, diagnostics: &mut Vec<Diagnostic>, line: &mut usize, parallel: Option<(usize, usize)>, limits: &Limits, format: NumberFormat) -> Result<Drawing> {
+        let mut tokens = Tokens::new(src);
+        let mut drawing = Drawing::default();
+        // the block being read in the BLOCKS section
+        let mut block: Option<Block> = None;
+        let mut polyline: Option<PolylineRecord> = None;
+        let mut plane = Plane::default();
         let mut entity_type = "";
-        let mut entity_state: BTreeMap<i32, &str> = BTreeMap::new();
-        while lines.len() > 0 {
-            let tag = lines.pop_front().unwrap().parse::<i32>()?;
-            let value = lines.pop_front().ok_or_else(eof)?;
+        let mut entity_line = 0;
+        let mut entity_state: Vec<(u16, &str)> = vec![];
+        // numeric values are converted as they are read, into a buffer reused for every entity
+        let mut entity_numbers: Vec<(u16, f64)> = vec![];
+        // the line of the entity's color, checked once the entity is complete
+        let mut color_line = 0;
+        let mut section = ("", 0);
+        // the header variable whose values are being read
+        let mut variable = "";
+        let mut handles: BTreeMap<&str, usize> = BTreeMap::new();
+        let mut found_eof = false;
+        let mut entity_count = 0;
+        while let Some(token) = tokens.next() {
+            let tag_line = tokens.line;
+            *line = tag_line;
+            let (tag, value) = token?;
+            let number = |value: &str| parse_number(value, format).map_err(|e| weld_err!("{} on line {}", e, tokens.value_line));
+            if !is_known_group_code(tag) {
+                diagnostics.push(Diagnostic::new(tag_line, "unknown-group-code", format!("unknown group code {}", tag)));
+            }
+            if tag == 5 || tag == 105 {
+                if let Some(first) = handles.insert(value, tag_line) {
+                    diagnostics.push(Diagnostic::new(tag_line, "duplicate-handle", format!("handle {} already used on line {}", value, first)));
+                }
+            }
             if state == 0 {
                 if value == "EOF" {
+                    found_eof = true;
                     break;
                 } else if value != "SECTION" {
                     return Err(weld_err!("expected SECTION, got {}", value));
@@ -191,8 +1786,31 @@ impl Drawing {
                 continue;
             } else if state == 1 {
                 if tag == 2 {
-                    if value == "ENTITIES" {
+                    section = (value, tag_line);
+                    if value == "BLOCKS" {
                         state = 3;
+                    } else if value == "ENTITIES" {
+                        state = 3;
+                        let start = value.as_ptr() as usize - src.as_ptr() as usize + value.len();
+                        let pieces = parallel.and_then(|(threads, chunk_bytes)| Drawing::parse_entities_parallel(src, start, threads, chunk_bytes, limits, format));
+                        if let Some((pieces, after)) = pieces {
+                            // a broken piece is parsed again in order, to fail exactly where a plain parse would
+                            let pieces = match pieces {
+                                Ok(pieces) => pieces,
+                                Err(_) => return Drawing::parse_lines(src, 0, diagnostics, line, None, limits, format),
+                            };
+                            // the pieces have no header of their own to merge
+                            let header = (drawing.ucs_origin.take(), drawing.limits_min.take());
+                            for piece in pieces.into_iter() {
+                                plane.add(piece.elevation);
+                                entity_count += piece.entity_count();
+                                drawing.merge(piece);
+                            }
+                            (drawing.ucs_origin, drawing.limits_min) = header;
+                            limits.check_entities(entity_count)?;
+                            tokens = Tokens::at(src, after);
+                            state = 0;
+                        }
                     } else {
                         state = 2;
                     }
@@ -201,55 +1819,184 @@ impl Drawing {
                 }
                 continue;
             } else if state == 2 {
-                if tag == 0 {
-                    if value == "ENDSEC" {
-                        state = 0;
+                if tag == 0 && value == "ENDSEC" {
+                    state = 0;
+                } else if section.0 == "HEADER" {
+                    let origin = match variable {
+                        "$UCSORG" => Some(&mut drawing.ucs_origin),
+                        "$LIMMIN" => Some(&mut drawing.limits_min),
+                        _ => None,
+                    };
+                    match (tag, origin) {
+                        (9, _) => variable = value,
+                        (10, Some(origin)) => origin.get_or_insert(Point { x: 0.0, y: 0.0 }).x = number(value)?,
+                        (20, Some(origin)) => origin.get_or_insert(Point { x: 0.0, y: 0.0 }).y = number(value)?,
+                        _ => (),
                     }
                 }
                 continue;
             } else if state == 4 {
                 if tag == 0 {
+                    let text = |code: u16| entity_state.iter().rev().find(|(c, _)| *c == code).map(|(_, value)| *value);
+                    let number = |code: u16| entity_numbers.iter().rev().find(|(c, _)| *c == code).map(|(_, value)| *value);
+                    let layer = text(8).unwrap_or(DEFAULT_LAYER);
+                    let color = match text(62) {
+                        Some(color) => Some(color.trim().parse::<i16>().map_err(|_| weld_err!("invalid color {} on line {}", color, color_line))?),
+                        None => None,
+                    };
+                    // a thickness or elevation of 0 is the same as none
+                    let (thickness, elevation) = (number(39).filter(|t| *t != 0.0), number(38).filter(|e| *e != 0.0));
+                    let skipped = block.as_ref().is_some_and(|block| is_space_block(&block.name));
+                    if entity_type != "VERTEX" {
+                        if let Some(record) = polyline.take() {
+                            if entity_type != "SEQEND" {
+                                diagnostics.push(Diagnostic::new(record.line, "missing-seqend", "POLYLINE has no SEQEND".to_string()));
+                            }
+                            entity_count += record.finish(&mut block, &mut drawing, &mut plane, diagnostics);
+                            limits.check_entities(entity_count)?;
+                        }
+                    }
                     match entity_type {
-                        "LINE" => {
-                            entities.push(Entity::Line(
-                                Point {
-                                    x: entity_state.get(&10).ok_or_else(|| missing_tag_for_entity(10))?.parse()?,
-                                    y: entity_state.get(&20).ok_or_else(|| missing_tag_for_entity(20))?.parse()?,
+                        "BLOCK" => {
+                            let base = Point { x: number(10).unwrap_or(0.0), y: number(20).unwrap_or(0.0) };
+                            block = Some(Block { name: text(2).unwrap_or("").to_string(), base, layers: vec![] });
+                        },
+                        "ENDBLK" => {
+                            if let Some(block) = block.take().filter(|_| !skipped) {
+                                drawing.blocks.push(block);
+                            }
+                        },
+                        "LINE" | "ARC" | "CIRCLE" | "ELLIPSE" | "SPLINE" | "INSERT" if !skipped => {
+                            let layers = match &mut block {
+                                Some(block) => &mut block.layers,
+                                None => {
+                                    plane.add(entity_z(entity_type, &entity_numbers));
+                                    &mut drawing.layers
+                                },
+                            };
+                            let layer = layer_in(layers, layer, color, thickness, elevation);
+                            if entity_type == "INSERT" {
+                                layer.inserts.push(Insert {
+                                    block: text(2).ok_or_else(|| missing_tag_for_entity(2))?.to_string(),
+                                    position: Point { x: number(10).ok_or_else(|| missing_tag_for_entity(10))?, y: number(20).ok_or_else(|| missing_tag_for_entity(20))? },
+                                    x_scale: number(41).unwrap_or(1.0),
+                                    y_scale: number(42).unwrap_or(1.0),
+                                    rotation: number(50).unwrap_or(0.0),
+                                });
+                            } else {
+                                let entity = parse_entity(entity_type, &entity_numbers)?;
+                                let zero_length = match &entity {
+                                    Entity::Line(from, to) => from == to,
+                                    Entity::Arc { radius, .. } | Entity::Circle { radius, .. } => *radius == 0.0,
+                                    _ => false,
+                                };
+                                if zero_length {
+                                    diagnostics.push(Diagnostic::new(entity_line, "zero-length-entity", format!("{} has zero length", entity_type)));
+                                }
+                                layer.entities.push(entity);
+                                entity_count += 1;
+                                limits.check_entities(entity_count)?;
+                            }
+                        },
+                        kind if RawEntity::KINDS.contains(&kind) && !skipped => {
+                            let layers = match &mut block {
+                                Some(block) => &mut block.layers,
+                                None => &mut drawing.layers,
+                            };
+                            layer_in(layers, layer, color, thickness, elevation).raw.push(RawEntity::new(kind, &entity_state));
+                        },
+                        "POLYLINE" if !skipped => {
+                            let mut record = PolylineRecord {
+                                line: entity_line,
+                                layer,
+                                color,
+                                thickness,
+                                elevation,
+                                flags: number(70).unwrap_or(0.0) as u32,
+                                curve_type: number(75).unwrap_or(0.0) as u32,
+                                plane: Plane::default(),
+                                // a constant width (43) is the same start and end width throughout
+                                default_widths: match number(43) {
+                                    Some(width) => (width, width),
+                                    None => (number(40).unwrap_or(0.0), number(41).unwrap_or(0.0)),
                                 },
-                                Point {
-                                    x: entity_state.get(&11).ok_or_else(|| missing_tag_for_entity(11))?.parse()?,
-                                    y: entity_state.get(&21).ok_or_else(|| missing_tag_for_entity(21))?.parse()?,
+                                vertices: vec![],
+                            };
+                            // a 3D polyline's vertices carry their own z, a 2D one's is the header's
+                            if record.flags & 8 == 0 {
+                                record.plane.add(entity_z(entity_type, &entity_numbers));
+                            }
+                            polyline = Some(record);
+                        },
+                        "VERTEX" => match &mut polyline {
+                            Some(record) => {
+                                let point = Point { x: number(10).ok_or_else(|| missing_tag_for_entity(10))?, y: number(20).ok_or_else(|| missing_tag_for_entity(20))? };
+                                if record.flags & 8 != 0 {
+                                    record.plane.add(entity_z(entity_type, &entity_numbers));
                                 }
-                            ))
+                                let widths = (number(40).unwrap_or(record.default_widths.0), number(41).unwrap_or(record.default_widths.1));
+                                record.vertices.push((point, number(70).unwrap_or(0.0) as u32, number(42).unwrap_or(0.0), widths));
+                            },
+                            None if skipped => (),
+                            None => diagnostics.push(Diagnostic::new(entity_line, "unexpected-vertex", "VERTEX outside a POLYLINE".to_string())),
+                        },
+                        "SEQEND" => (),
+                        _ if skipped => (),
+                        other => {
+                            diagnostics.push(Diagnostic::new(entity_line, "unsupported-entity", format!("{} in a block is left out", other)));
                         },
-                        _ => unimplemented!(),
                     }
                     entity_state.clear();
+                    entity_numbers.clear();
                     state = 3;
+                } else if is_numeric_group_code(tag) && !RawEntity::KINDS.contains(&entity_type) {
+                    entity_numbers.push((tag, number(value)?));
                 } else {
-                    entity_state.insert(tag, value);
+                    if tag == 62 {
+                        color_line = tokens.value_line;
+                    }
+                    entity_state.push((tag, value));
                 }
             }
-            if state == 3 {
-                if tag == 0 {
-                    match value {
-                        "LINE" => {
-                            entity_type = "LINE";
-                            state = 4;
-                        },
-                        "ENDSEC" => {
-                            state = 0;
-                        },
-                        x => {
-                            return Err(weld_err!("unsupported entity type: {}", x));
-                        },
-                    }
+            if state == 3 && tag == 0 {
+                match value {
+                    "ENDSEC" => {
+                        if let Some(record) = polyline.take() {
+                            diagnostics.push(Diagnostic::new(record.line, "missing-seqend", "POLYLINE has no SEQEND".to_string()));
+                            entity_count += record.finish(&mut block, &mut drawing, &mut plane, diagnostics);
+                            limits.check_entities(entity_count)?;
+                        }
+                        state = 0;
+                    },
+                    // anything in the BLOCKS section is read, to be kept or left out with a diagnostic
+                    "LINE" | "ARC" | "CIRCLE" | "ELLIPSE" | "SPLINE" | "INSERT" | "POLYLINE" | "VERTEX" | "SEQEND" | "MLINE" | "LEADER" => {
+                        entity_type = value;
+                        entity_line = tag_line;
+                        state = 4;
+                    },
+                    _ if section.0 == "BLOCKS" => {
+                        entity_type = value;
+                        entity_line = tag_line;
+                        state = 4;
+                    },
+                    x => {
+                        return Err(weld_err!("unsupported entity type: {}", x));
+                    },
                 }
             }
         }
-        Ok(Drawing {
-            entities,
-        })
+        if state != 0 {
+            diagnostics.push(Diagnostic::new(section.1, "unclosed-section", format!("section {} has no ENDSEC", section.0)));
+        }
+        if !found_eof {
+            diagnostics.push(Diagnostic::new(*line, "missing-eof", "file ends without EOF".to_string()));
+        }
+        drawing.elevation = plane.elevation();
+        // an origin at 0, 0 is the same as none
+        let at_origin = |point: &Point| point.x == 0.0 && point.y == 0.0;
+        drawing.ucs_origin = drawing.ucs_origin.filter(|point| !at_origin(point));
+        drawing.limits_min = drawing.limits_min.filter(|point| !at_origin(point));
+        Ok(drawing)
     }
 }
 
@@ -257,6 +2004,24 @@ impl Drawing {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_entity_endpoints() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let mut line = Entity::Line(p(0.0, 0.0), p(3.0, 4.0));
+        assert_eq!(line.length(), 5.0);
+        line.reverse();
+        assert_eq!((line.start_point(), line.end_point()), (Some(p(3.0, 4.0)), Some(p(0.0, 0.0))));
+
+        let arc = Entity::Arc { center: p(0.0, 0.0), radius: 2.0, start_angle: 315.0, end_angle: 45.0 };
+        assert_eq!((arc.start_point(), arc.end_point()), (Some(p(2f64.sqrt(), -(2f64.sqrt()))), Some(p(2f64.sqrt(), 2f64.sqrt()))));
+        assert!((arc.length() - std::f64::consts::PI).abs() < 1e-9);
+        assert_eq!(arc.bounding_box(), Some((p(2f64.sqrt(), -(2f64.sqrt())), p(2.0, 2f64.sqrt()))));
+
+        let ellipse = Entity::Ellipse { center: p(1.0, 1.0), major_axis: p(0.0, 2.0), ratio: 0.5, start_param: 0.0, end_param: std::f64::consts::FRAC_PI_2 };
+        assert_eq!((ellipse.start_point(), ellipse.end_point()), (Some(p(1.0, 3.0)), Some(p(0.0, 1.0))));
+        assert_eq!(Entity::Polyline { curve_type: 0, vertices: vec![], widths: vec![] }.bounding_box(), None);
+    }
+
     #[test]
     fn test_angle() {
         let theta = Point {
@@ -268,4 +2033,432 @@ mod tests {
         });
         assert!((theta - std::f64::consts::PI / 4.0) < 0.0001);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parse_f64() {
+        assert_eq!(parse_f64("42").unwrap(), 42.0);
+        assert_eq!(parse_f64("-7").unwrap(), -7.0);
+        assert!(parse_f64("-0").unwrap().is_sign_negative());
+        assert_eq!(parse_f64("1.5e3").unwrap(), 1500.0);
+        assert_eq!(parse_f64("12345678901234567890").unwrap(), 12345678901234567890.0);
+        assert!(parse_f64("-").is_err());
+        assert!(parse_f64("1.2.3").is_err());
+        assert!(parse_f64(".").is_err());
+        for value in ["0.1", "-123.456789012345678", "+2.5", "1e-300", "9007199254740993", ".5", "5.", "0.30000000000000004", "123456789.012345", "-.0"] {
+            assert_eq!(parse_f64(value).unwrap(), value.parse::<f64>().unwrap(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_parse_number() {
+        assert_eq!(parse_number("1.0E+2", NumberFormat::Point).unwrap(), 100.0);
+        assert_eq!(parse_number("-2.5e-1", NumberFormat::Point).unwrap(), -0.25);
+        assert_eq!(parse_number("1,5", NumberFormat::Comma).unwrap(), 1.5);
+        assert_eq!(parse_number("-1,5E+1", NumberFormat::Comma).unwrap(), -15.0);
+        assert_eq!(parse_number("1.5", NumberFormat::Comma).unwrap(), 1.5);
+        assert!(parse_number("1,5", NumberFormat::Point).unwrap_err().contains("--comma-decimals"));
+        assert!(parse_number("1,000,5", NumberFormat::Comma).is_err());
+        assert!(parse_number("1e999", NumberFormat::Point).is_err());
+        assert!(parse_number("inf", NumberFormat::Point).is_err());
+
+        let src = "0\nSECTION\n2\nENTITIES\n0\nLINE\n8\n0\n10\n0,5\n20\n1.0E+1\n11\n2\n21\n3\n0\nENDSEC\n0\nEOF\n";
+        let error = Drawing::parse(src).unwrap_err().to_string();
+        assert!(error.contains("invalid number: 0,5") && error.contains("on line 10"), "{}", error);
+        let drawing = Drawing::parse_formatted(src, &Limits::default(), NumberFormat::Comma).unwrap();
+        assert_eq!(drawing.entities().next(), Some(&Entity::Line(Point { x: 0.5, y: 10.0 }, Point { x: 2.0, y: 3.0 })));
+
+        let error = Drawing::parse(&src.replace("0,5", "0.5").replace("8\n0\n", "8\n0\n62\nred\n")).unwrap_err().to_string();
+        assert!(error.contains("invalid color red on line 10"), "{}", error);
+    }
+
+    #[test]
+    fn test_format_angle() {
+        assert_eq!(normalize_degrees(-90.0), 270.0);
+        assert_eq!(normalize_degrees(720.0), 0.0);
+        assert_eq!(normalize_degrees(-1e-20), 0.0);
+        assert_eq!(format_angle(-45.0, None), "315");
+        assert_eq!(format_angle(12.3456, Some(2)), "12.35");
+        assert_eq!(format_angle(359.9999, Some(3)), "0.000");
+    }
+
+    #[test]
+    fn test_format_real() {
+        assert_eq!(format_real(0.1 + 0.2), "0.30000000000000004");
+        assert_eq!(format_real(12.0), "12");
+        assert_eq!(format_real(-0.0), "0");
+        assert_eq!(format_real(1e20), "100000000000000000000");
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn numbers_round_trip(x in proptest::num::f64::NORMAL | proptest::num::f64::SUBNORMAL | proptest::num::f64::ZERO, y in -1e6..1e6f64) {
+            let drawing = Drawing::new(vec![Entity::Line(Point { x, y }, Point { x: y, y: x })]);
+            let reread = Drawing::parse(&drawing.to_string()).unwrap().entities().next().cloned();
+            match reread {
+                Some(Entity::Line(from, to)) => {
+                    let bits = [from.x, from.y, to.x, to.y].map(|v| (v + 0.0).to_bits());
+                    proptest::prop_assert_eq!(bits, [x + 0.0, y, y, x + 0.0].map(f64::to_bits));
+                },
+                other => proptest::prop_assert!(false, "unexpected {:?}", other),
+            }
+        }
+
+        #[test]
+        fn short_decimals_parse_exactly(mantissa in 0u64..1_000_000_000_000_000, decimals in 0usize..16, negative: bool) {
+            let digits = format!("{:0>16}", mantissa);
+            let value = format!("{}{}.{}", if negative { "-" } else { "" }, &digits[..16 - decimals], &digits[16 - decimals..]);
+            proptest::prop_assert_eq!(parse_f64(&value).unwrap().to_bits(), value.parse::<f64>().unwrap().to_bits(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn test_format_number() {
+        assert_eq!(format_number(0.1 + 0.2), "0.3");
+        assert_eq!(format_number(12.0), "12");
+        assert_eq!(format_number(-2.5), "-2.5");
+        assert_eq!(format_number(-1e-12), "0");
+        assert_eq!(format_number(1.23456789012345), "1.2345678901");
+        assert_eq!(format_number(1e20), "100000000000000000000");
+    }
+
+    #[test]
+    fn test_parse_layers() {
+        let src = "  0\nSECTION\n  2\nENTITIES\n  0\nLINE\n  8\nCUT\n 62\n1\n 10\n0\n 20\n0\n 11\n1\n 21\n0\n  0\nLINE\n 10\n1\n 20\n0\n 11\n1\n 21\n1\n  0\nENDSEC\n  0\nEOF\n";
+        let drawing = Drawing::parse(src).unwrap();
+        assert_eq!(drawing.layers.len(), 2);
+        assert_eq!(drawing.layers[0].name, "CUT");
+        assert_eq!(drawing.layers[0].color, Some(1));
+        assert_eq!(drawing.layers[1].name, DEFAULT_LAYER);
+        assert_eq!(Drawing::parse(&drawing.to_string()).unwrap(), drawing);
+    }
+
+    #[test]
+    fn test_dxf_version() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let mut drawing = Drawing::new(vec![
+            Entity::Line(p(0.0, 0.0), p(1.0, 0.0)),
+            Entity::Arc { center: p(1.0, 1.0), radius: 1.0, start_angle: 270.0, end_angle: 90.0 },
+        ]);
+        drawing.layer_mut("CUT", Some(1)).entities.push(Entity::Circle { center: p(5.0, 5.0), radius: 2.0 });
+
+        let r12 = drawing.to_string();
+        assert!(!r12.contains("OBJECTS") && !r12.contains("AcDb"));
+
+        let mut block = Layer::new("BOLTS", None);
+        block.entities.push(Entity::Circle { center: p(0.0, 0.0), radius: 0.5 });
+        drawing.blocks.push(Block { name: "BOLT".to_string(), base: p(0.0, 0.0), layers: vec![block] });
+        drawing.layers[0].inserts.push(Insert { block: "BOLT".to_string(), position: p(3.0, 3.0), x_scale: 1.0, y_scale: 1.0, rotation: 0.0 });
+
+        let mut r2000 = String::new();
+        let mut backend = DxfBackend::new(&mut r2000).with_version(DxfVersion::R2000);
+        drawing.write_to(&mut backend);
+        assert!(backend.buffer().is_some());
+        assert!(r2000.starts_with("  0\nSECTION\n  2\nHEADER\n  9\n$ACADVER\n  1\nAC1015\n  9\n$HANDSEED\n"));
+        assert!(r2000.contains("DICTIONARY\n  5\nC\n  330\n0\n  100\nAcDbDictionary\n  281\n1\n  3\nACAD_GROUP\n  350\nD\n"));
+        let mut diagnostics = vec![];
+        assert_eq!(Drawing::parse_with_diagnostics(&r2000, &mut diagnostics).unwrap(), drawing);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+        // every handle is below the seed, and every owner is one of them
+        let codes = r2000.lines().collect::<Vec<_>>().chunks(2).map(|pair| (pair[0].trim().parse::<u16>().unwrap(), pair[1])).collect::<Vec<_>>();
+        let hex = |value: &str| u64::from_str_radix(value, 16).unwrap();
+        let seed = codes.iter().position(|code| *code == (9, "$HANDSEED")).unwrap() + 1;
+        let handles = codes.iter().enumerate().filter(|(i, (code, _))| [5, 105].contains(code) && *i != seed).map(|(_, (_, value))| hex(value)).collect::<Vec<_>>();
+        let seed = hex(codes[seed].1);
+        assert!(handles.iter().all(|handle| *handle < seed), "{:X} {:X?}", seed, handles);
+        for (code, value) in codes.iter().filter(|(code, value)| [330, 340, 350, 390].contains(code) && *value != "0") {
+            assert!(handles.contains(&hex(value)), "{} {}", code, value);
+        }
+        // the tables name every layer and block, and a block's entities belong to its record
+        let table = |kind: &str| codes.windows(6).filter(|w| w[0] == (0, kind)).map(|w| w.iter().find(|(code, _)| *code == 2).unwrap().1).collect::<Vec<_>>();
+        assert_eq!(table("LAYER"), ["0", "BOLTS", "CUT"]);
+        assert_eq!(table("BLOCK_RECORD"), ["*Model_Space", "*Paper_Space", "BOLT"]);
+        let record = r2000.split("  0\nBLOCK_RECORD\n  5\n").nth(3).unwrap().lines().next().unwrap();
+        assert!(r2000.contains(&format!("  0\nCIRCLE\n  5\n{:X}\n  330\n{}\n", hex(record) + 2, record)), "{}", r2000);
+        assert!(r2000.contains(&format!("  0\nLINE\n  5\n{:X}\n  330\n17\n", hex(record) + 4)), "{}", r2000);
+
+        let mut fragment = String::new();
+        drawing.write_to(&mut DxfBackend::new(&mut fragment).with_fragment(true));
+        assert!(fragment.starts_with("  0\nLINE\n") && !fragment.contains("SECTION") && !fragment.contains("EOF"));
+        let wrapped = format!("  0\nSECTION\n  2\nENTITIES\n{}  0\nENDSEC\n  0\nEOF\n", fragment);
+        // a fragment leaves the block definitions out
+        assert_eq!(Drawing::parse(&wrapped).unwrap(), Drawing { blocks: vec![], ..drawing });
+    }
+
+    #[test]
+    fn test_elevation() {
+        let src = "  0\nSECTION\n  2\nENTITIES\n  0\nLINE\n 10\n0\n 20\n0\n 30\n25.4\n 11\n1\n 21\n0\n 31\n25.4\n  0\nELLIPSE\n 10\n0\n 20\n0\n 30\n25.4\n 11\n2\n 21\n0\n 31\n0\n 40\n0.5\n 41\n0\n 42\n3.5\n  0\nENDSEC\n  0\nEOF\n";
+        let drawing = Drawing::parse(src).unwrap();
+        assert_eq!(drawing.elevation, Some(25.4));
+        let written = drawing.to_string();
+        assert!(written.contains("  30\n25.4\n  11\n1\n  21\n0\n  31\n25.4\n"));
+        // R12 has no ELLIPSE, so the ellipse becomes a polyline at the same height
+        assert!(!written.contains("ELLIPSE"));
+        let reread = Drawing::parse(&written).unwrap();
+        assert_eq!(reread.elevation, Some(25.4));
+        match &reread.layers[0].entities[1] {
+            Entity::Polyline { vertices, .. } => {
+                assert_eq!(vertices[0], Point { x: 2.0, y: 0.0 });
+                assert!(vertices.iter().all(|v| ((v.x / 2.0).powi(2) + (v.y / 1.0).powi(2) - 1.0).abs() < 1e-9));
+            },
+            other => panic!("unexpected {:?}", other),
+        }
+        let mut r2000 = String::new();
+        drawing.write_to(&mut DxfBackend::new(&mut r2000).with_version(DxfVersion::R2000).with_elevation(drawing.elevation));
+        assert_eq!(Drawing::parse(&r2000).unwrap(), drawing);
+
+        // entities at different heights are flattened as before
+        let tilted = src.replacen(" 31\n25.4\n", " 31\n20\n", 1);
+        assert_eq!(Drawing::parse(&tilted).unwrap().elevation, None);
+        let mut other = Drawing::parse(&src.replace("25.4", "10")).unwrap();
+        assert_eq!(other.elevation, Some(10.0));
+        other.merge(drawing);
+        assert_eq!(other.elevation, None);
+    }
+
+    #[test]
+    fn test_polyline() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let drawing = Drawing::new(vec![
+            Entity::Polyline { curve_type: 0, vertices: vec![p(0.0, 0.0), p(1.0, 0.0), p(1.0, 1.0)], widths: vec![] },
+            Entity::Polyline { curve_type: 6, vertices: vec![p(0.0, 0.0), p(1.0, 2.0), p(3.0, 2.0), p(4.0, 0.0)], widths: vec![] },
+        ]);
+        let r12 = drawing.to_string();
+        assert!(r12.contains("POLYLINE\n  8\n0\n  66\n1\n  10\n0\n  20\n0\n  70\n0\n  0\nVERTEX"));
+        assert!(r12.contains("  70\n4\n  75\n6\n") && r12.contains("  70\n16\n"));
+        assert_eq!(Drawing::parse(&r12).unwrap(), drawing);
+        let mut r2000 = String::new();
+        drawing.write_to(&mut DxfBackend::new(&mut r2000).with_version(DxfVersion::R2000));
+        let mut diagnostics = vec![];
+        assert_eq!(Drawing::parse_with_diagnostics(&r2000, &mut diagnostics).unwrap(), drawing);
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+        // a closed square whose last side bulges out into a half circle, with a vertex added by arc fitting
+        let vertex = |x: f64, y: f64, flags: u32, bulge: f64| format!("  0\nVERTEX\n 10\n{}\n 20\n{}\n 70\n{}\n 42\n{}\n", x, y, flags, bulge);
+        let polyline = |flags: u32, vertices: &[String]| format!("  0\nSECTION\n  2\nENTITIES\n  0\nPOLYLINE\n 66\n1\n 70\n{}\n{}  0\nSEQEND\n  0\nENDSEC\n  0\nEOF\n", flags, vertices.concat());
+        let bulged = Drawing::parse(&polyline(3, &[vertex(0.0, 0.0, 0, 0.0), vertex(2.0, 0.0, 1, 0.0), vertex(2.0, 2.0, 0, 0.0), vertex(0.0, 2.0, 0, 1.0)])).unwrap();
+        let entities = bulged.entities().cloned().collect::<Vec<_>>();
+        assert_eq!(entities.len(), 4);
+        assert_eq!(entities[2], Entity::Line(p(2.0, 2.0), p(0.0, 2.0)));
+        match &entities[3] {
+            Entity::Arc { center, radius, start_angle, end_angle } => {
+                assert_eq!((center, *radius), (&p(0.0, 1.0), 1.0));
+                assert!((start_angle - 90.0).abs() < 1e-9 && (end_angle - 270.0).abs() < 1e-9, "{} {}", start_angle, end_angle);
+            },
+            other => panic!("expected an arc, got {:?}", other),
+        }
+
+        // the vertices spline fitting added are left out of a spline-fit polyline
+        let fitted = Drawing::parse(&polyline(4, &[vertex(0.0, 0.0, 16, 0.0), vertex(0.5, 0.9, 8, 0.0), vertex(1.0, 2.0, 16, 0.0), vertex(3.0, 2.0, 16, 0.0)])).unwrap();
+        assert_eq!(fitted.entities().collect::<Vec<_>>(), vec![&Entity::Polyline { curve_type: 6, vertices: vec![p(0.0, 0.0), p(1.0, 2.0), p(3.0, 2.0)], widths: vec![] }]);
+
+        let mut diagnostics = vec![];
+        let mesh = Drawing::parse_with_diagnostics(&polyline(64, &[vertex(0.0, 0.0, 128, 0.0)]), &mut diagnostics).unwrap();
+        assert_eq!((mesh.entity_count(), diagnostics[0].kind), (0, "unsupported-entity"));
+    }
+
+    #[test]
+    fn test_polyline_widths() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let constant = Entity::Polyline { curve_type: 0, vertices: vec![p(0.0, 0.0), p(1.0, 0.0), p(1.0, 1.0)], widths: vec![(0.5, 0.5); 3] };
+        let tapered = Entity::Polyline { curve_type: 0, vertices: vec![p(0.0, 0.0), p(2.0, 0.0), p(2.0, 2.0)], widths: vec![(1.0, 0.0), (0.0, 2.0), (0.0, 0.0)] };
+        let drawing = Drawing::new(vec![constant, tapered.clone()]);
+        let written = drawing.to_string();
+        assert!(written.contains("  70\n0\n  40\n0.5\n  41\n0.5\n"), "{}", written);
+        assert!(written.contains("  40\n1\n  41\n0\n  70\n0\n"), "{}", written);
+        assert_eq!(Drawing::parse(&written).unwrap(), drawing);
+
+        let mut reversed = tapered;
+        reversed.reverse();
+        assert_eq!(reversed, Entity::Polyline { curve_type: 0, vertices: vec![p(2.0, 2.0), p(2.0, 0.0), p(0.0, 0.0)], widths: vec![(2.0, 0.0), (0.0, 1.0), (0.0, 0.0)] });
+
+        // a vertex without widths of its own takes the default ones, and a constant width (43) overrides both
+        let vertex = |x: f64, widths: &str| format!("  0\nVERTEX\n 10\n{}\n 20\n0\n{}", x, widths);
+        let polyline = |header: &str, vertices: &[String]| format!("  0\nSECTION\n  2\nENTITIES\n  0\nPOLYLINE\n 66\n1\n 70\n0\n{}{}  0\nSEQEND\n  0\nENDSEC\n  0\nEOF\n", header, vertices.concat());
+        let parsed = Drawing::parse(&polyline(" 40\n1\n 41\n2\n", &[vertex(0.0, ""), vertex(1.0, " 40\n3\n"), vertex(2.0, "")])).unwrap();
+        assert!(matches!(parsed.entities().next(), Some(Entity::Polyline { widths, .. }) if *widths == vec![(1.0, 2.0), (3.0, 2.0), (1.0, 2.0)]));
+        let parsed = Drawing::parse(&polyline(" 43\n0.25\n", &[vertex(0.0, ""), vertex(1.0, "")])).unwrap();
+        assert!(matches!(parsed.entities().next(), Some(Entity::Polyline { widths, .. }) if *widths == vec![(0.25, 0.25); 2]));
+
+        // widths cannot be kept once bulges split the polyline into lines and arcs
+        let mut diagnostics = vec![];
+        let bulged = Drawing::parse_with_diagnostics(&polyline(" 43\n0.25\n", &[vertex(0.0, " 42\n1\n"), vertex(1.0, "")]), &mut diagnostics).unwrap();
+        assert!(matches!(bulged.entities().next(), Some(Entity::Arc { .. })));
+        assert_eq!(diagnostics.iter().map(|diagnostic| diagnostic.kind).collect::<Vec<_>>(), vec!["polyline-width"]);
+    }
+
+    #[test]
+    fn test_spline() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let spline = |degree: usize, control: &[Point], knots: &[f64], weights: &[f64]| {
+            let mut src = format!("  0\nSECTION\n  2\nENTITIES\n  0\nSPLINE\n  8\n0\n 71\n{}\n", degree);
+            src.extend(knots.iter().map(|knot| format!(" 40\n{}\n", knot)));
+            src.extend(control.iter().map(|point| format!(" 10\n{}\n 20\n{}\n", point.x, point.y)));
+            src.extend(weights.iter().map(|weight| format!(" 41\n{}\n", weight)));
+            Drawing::parse(&(src + "  0\nENDSEC\n  0\nEOF\n"))
+        };
+        let control = [p(0.0, 0.0), p(1.0, 2.0), p(3.0, 2.0), p(4.0, 0.0)];
+        // clamped uniform knots, however scaled, are a spline-fit polyline over the control points
+        let uniform = spline(2, &control, &[2.0, 2.0, 2.0, 3.0, 4.0, 4.0, 4.0], &[]).unwrap();
+        assert_eq!(uniform.entities().next(), Some(&Entity::Polyline { curve_type: 5, vertices: control.to_vec(), widths: vec![] }));
+
+        // a full circle as a rational quadratic with doubled knots, traced on the circle
+        let w = std::f64::consts::FRAC_1_SQRT_2;
+        let square = [p(1.0, 0.0), p(1.0, 1.0), p(0.0, 1.0), p(-1.0, 1.0), p(-1.0, 0.0), p(-1.0, -1.0), p(0.0, -1.0), p(1.0, -1.0), p(1.0, 0.0)];
+        let knots = [0.0, 0.0, 0.0, 1.0, 1.0, 2.0, 2.0, 3.0, 3.0, 4.0, 4.0, 4.0];
+        let circle = spline(2, &square, &knots, &[1.0, w, 1.0, w, 1.0, w, 1.0, w, 1.0]).unwrap();
+        let vertices = match circle.entities().next() {
+            Some(Entity::Polyline { curve_type: 0, vertices, .. }) => vertices.clone(),
+            other => panic!("unexpected {:?}", other),
+        };
+        assert!(vertices.len() > 100);
+        assert!(vertices.iter().all(|vertex| (vertex.dist(&p(0.0, 0.0)) - 1.0).abs() < 1e-9));
+        assert_eq!((vertices.first(), vertices.last()), (Some(&p(1.0, 0.0)), Some(&p(1.0, 0.0))));
+        let welded = crate::dxf_process::DxfConfig::default().process_drawing(circle, &mut Default::default()).unwrap();
+        assert!(matches!(welded.entities().collect::<Vec<_>>()[..], [Entity::Circle { radius, .. }] if (radius - 1.0).abs() < 1e-3));
+
+        // without weights, non-uniform knots still move the curve: at the inner knot it is a quarter of
+        // the way between the middle control points, where the uniform spline is halfway
+        let skewed = spline(2, &control, &[0.0, 0.0, 0.0, 1.0, 4.0, 4.0, 4.0], &[]).unwrap();
+        let vertices = skewed.entities().next().unwrap().tessellate(FINE_CHORD_ERROR);
+        assert!(vertices.contains(&p(1.5, 2.0)) && !vertices.contains(&p(2.0, 2.0)), "{:?}", vertices);
+        assert_eq!((vertices.first(), vertices.last()), (Some(&control[0]), Some(&control[3])));
+
+        assert!(spline(2, &control, &[0.0, 0.0, 1.0, 1.0], &[]).unwrap_err().to_string().contains("expected 7"));
+        assert!(spline(2, &control, &[], &[1.0, -1.0, 1.0, 1.0]).is_err());
+    }
+
+    #[test]
+    fn test_raw_entities() {
+        let mline = "  0\nMLINE\n  5\n2A\n330\n1F\n100\nAcDbEntity\n  8\nWALLS\n 62\n3\n100\nAcDbMline\n  2\nSTANDARD\n340\n18\n 40\n1\n 71\n1\n 72\n2\n 10\n0\n 20\n0\n 30\n0\n 11\n0\n 21\n0\n 31\n0\n 11\n4\n 21\n0\n 31\n0\n";
+        // a value no other entity would accept is kept as text
+        let leader = "  0\nLEADER\n  8\nNOTES\n  3\nDIMSTANDARD\n 76\n2\n 10\n0\n 20\n0\n 10\n1\n 20\nn/a\n";
+        let src = format!("  0\nSECTION\n  2\nBLOCKS\n  0\nBLOCK\n  2\nTAG\n 10\n0\n 20\n0\n{}  0\nENDBLK\n  0\nENDSEC\n  0\nSECTION\n  2\nENTITIES\n  0\nLINE\n  8\nWALLS\n 62\n3\n 10\n0\n 20\n0\n 11\n4\n 21\n0\n{}  0\nENDSEC\n  0\nEOF\n", leader, mline);
+        let mut diagnostics = vec![];
+        let drawing = Drawing::parse_with_diagnostics(&src, &mut diagnostics).unwrap();
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+        assert_eq!(drawing.layers.len(), 1);
+        let raw = &drawing.layers[0].raw;
+        assert_eq!(raw.len(), 1);
+        assert_eq!(raw[0].kind, "MLINE");
+        // the handles, layer and color are written anew
+        assert_eq!(&raw[0].codes[..3], &[(100, "AcDbMline".to_string()), (2, "STANDARD".to_string()), (40, "1".to_string())]);
+        assert_eq!(drawing.blocks[0].layers[0].raw[0].codes.last(), Some(&(20, "n/a".to_string())));
+
+        let written = drawing.to_string();
+        assert!(written.contains("  0\nMLINE\n  8\nWALLS\n  62\n3\n  2\nSTANDARD\n  40\n1\n"), "{}", written);
+        assert!(written.contains("  0\nLEADER\n  8\nNOTES\n  3\nDIMSTANDARD\n"), "{}", written);
+        // R12 has no subclass markers, which R2000 output keeps
+        assert_eq!(Drawing::parse(&written).unwrap().layers[0].raw[0].codes, raw[0].codes[1..]);
+        let mut r2000 = String::new();
+        drawing.write_to(&mut DxfBackend::new(&mut r2000).with_version(DxfVersion::R2000));
+        assert!(r2000.contains("  0\nMLINE\n  5\n"), "{}", r2000);
+        assert_eq!(Drawing::parse(&r2000).unwrap(), drawing, "{}", r2000.split("ENTITIES").nth(1).unwrap());
+    }
+
+    #[test]
+    fn test_merge_blocks() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let insert = |block: &str| Insert { block: block.to_string(), position: p(5.0, 5.0), x_scale: 1.0, y_scale: 1.0, rotation: 0.0 };
+        let block = |name: &str, radius: f64, inserts: Vec<Insert>| {
+            let mut layer = Layer::new("0", None);
+            layer.entities.push(Entity::Circle { center: p(0.0, 0.0), radius });
+            layer.inserts = inserts;
+            Block { name: name.to_string(), base: p(0.0, 0.0), layers: vec![layer] }
+        };
+        let drawing = |blocks: Vec<Block>, inserts: Vec<Insert>| {
+            let mut drawing = Drawing::new(vec![]);
+            drawing.layers[0].inserts = inserts;
+            drawing.blocks = blocks;
+            drawing
+        };
+        let first = drawing(vec![block("BOLT", 1.0, vec![]), block("*U1", 3.0, vec![])], vec![insert("BOLT"), insert("*U1")]);
+        let mut merged = first.clone();
+        merged.merge(drawing(
+            vec![block("BOLT", 2.0, vec![]), block("NUT", 1.5, vec![insert("BOLT")]), block("*U1", 4.0, vec![]), block("*U2", 5.0, vec![])],
+            vec![insert("BOLT"), insert("NUT"), insert("*U1"), insert("*U2")],
+        ));
+        // an input sharing its blocks with the merged drawing adds none
+        merged.merge(first);
+        let names = merged.blocks.iter().map(|block| block.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, ["BOLT", "*U1", "BOLT_2", "NUT", "*U3", "*U2"]);
+        let inserted = merged.layers[0].inserts.iter().map(|insert| insert.block.as_str()).collect::<Vec<_>>();
+        assert_eq!(inserted, ["BOLT", "*U1", "BOLT_2", "NUT", "*U3", "*U2", "BOLT", "*U1"]);
+        assert_eq!(merged.blocks[3].layers[0].inserts[0].block, "BOLT_2");
+        assert_eq!(merged.blocks[2].layers[0].entities[0], Entity::Circle { center: p(0.0, 0.0), radius: 2.0 });
+    }
+
+    #[test]
+    fn test_header_origins() {
+        let src = "  0\nSECTION\n  2\nHEADER\n  9\n$ACADVER\n  1\nAC1009\n  9\n$UCSORG\n 10\n100\n 20\n50\n 30\n0\n  9\n$LIMMIN\n 10\n0\n 20\n0\n  0\nENDSEC\n  0\nSECTION\n  2\nENTITIES\n  0\nLINE\n 10\n0\n 20\n0\n 11\n1\n 21\n0\n  0\nENDSEC\n  0\nEOF\n";
+        let drawing = Drawing::parse(src).unwrap();
+        assert_eq!(drawing.header_origin(HeaderOrigin::Ucs), Some(&Point { x: 100.0, y: 50.0 }));
+        assert_eq!(drawing.header_origin(HeaderOrigin::Limits), None);
+        let written = drawing.to_string();
+        assert!(written.starts_with("  0\nSECTION\n  2\nHEADER\n  9\n$UCSORG\n  10\n100\n  20\n50\n  0\nENDSEC\n"));
+        assert_eq!(Drawing::parse(&written).unwrap(), drawing);
+
+        let mut other = Drawing::parse(&src.replace("$UCSORG\n 10\n100", "$UCSORG\n 10\n-100")).unwrap();
+        other.merge(drawing);
+        assert_eq!(other.ucs_origin, None);
+    }
+
+    #[test]
+    fn test_parse_parallel() {
+        let mut src = "  0\nSECTION\n  2\nHEADER\n  9\n$LIMMIN\n 10\n-5\n 20\n-5\n  0\nENDSEC\n  0\nSECTION\n  2\nENTITIES\n".to_string();
+        for i in 0..300 {
+            // a 0 value ahead of each entity must not be mistaken for the start of one
+            src.push_str(&format!("  0\nLINE\n  8\nL{}\n 70\n0\n 10\n{}\n 20\n0\n 11\n{}.5\n 21\n1\n", i % 3, i, i));
+            if i % 7 == 0 {
+                src.push_str(&format!("  0\nARC\n\n  8\nL{}\n 10\n{}\n 20\n0\n 40\n2\n 50\n0\n 51\n90\n", i % 5, i));
+            }
+            // nor a polyline's vertices taken for entities of their own
+            if i % 11 == 0 {
+                src.push_str(&format!("  0\nPOLYLINE\n 66\n1\n 70\n0\n{}  0\nSEQEND\n", "  0\nVERTEX\n 10\n1\n 20\n2\n".repeat(20)));
+            }
+        }
+        src.push_str("  0\nENDSEC\n  0\nSECTION\n  2\nOBJECTS\n  0\nENDSEC\n  0\nEOF\n");
+        let sequential = Drawing::parse_with_diagnostics(&src, &mut vec![]).unwrap();
+        let parallel = Drawing::parse_lines(&src, 0, &mut vec![], &mut 0, Some((4, 256)), &Limits::default(), NumberFormat::Point).unwrap();
+        assert_eq!(sequential.entity_count(), 371);
+        assert_eq!(parallel.limits_min, Some(Point { x: -5.0, y: -5.0 }));
+        assert_eq!(parallel, sequential);
+
+        let broken = src.replacen(" 10\n250\n", " 10\nx\n", 1);
+        let sequential = Drawing::parse_with_diagnostics(&broken, &mut vec![]).unwrap_err();
+        let parallel = Drawing::parse_lines(&broken, 0, &mut vec![], &mut 0, Some((4, 256)), &Limits::default(), NumberFormat::Point).unwrap_err();
+        assert_eq!(parallel.to_string(), sequential.to_string());
+    }
+
+    #[test]
+    fn test_parse_diagnostics() {
+        let src = "  0\nSECTION\n  2\nENTITIES\n  0\nLINE\n  5\nA1\n 10\n1\n 20\n1\n 11\n1\n 21\n1\n  0\nLINE\n  5\nA1\n 85\nx\n 10\n0\n 20\n0\n 11\n1\n 21\n0\n";
+        let mut diagnostics = vec![];
+        Drawing::parse_with_diagnostics(src, &mut diagnostics).unwrap();
+        let kinds = diagnostics.iter().map(|d| (d.line, d.kind)).collect::<Vec<_>>();
+        assert_eq!(kinds, vec![
+            (5, "zero-length-entity"),
+            (19, "duplicate-handle"),
+            (21, "unknown-group-code"),
+            (3, "unclosed-section"),
+            (29, "missing-eof"),
+        ]);
+    }
+
+    #[test]
+    fn test_parse_malformed() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let src = Drawing::new(vec![
+            Entity::Line(p(0.0, 0.0), p(1.0, 0.0)),
+            Entity::Arc { center: p(1.0, 1.0), radius: 1.0, start_angle: 270.0, end_angle: 90.0 },
+            Entity::Ellipse { center: p(0.0, 0.0), major_axis: p(2.0, 0.0), ratio: 0.5, start_param: 0.0, end_param: 1.0 },
+        ]).to_string();
+        // every truncation is either a smaller drawing or an error, never a panic
+        for end in 0..src.len() {
+            let _ = Drawing::parse_bytes(&src.as_bytes()[..end]);
+        }
+        assert!(Drawing::parse_bytes(b"  0\nSECTION\n  2\nENTITIES\n  0\nLINE\n 10\n\xff\n").is_err());
+        assert!(Drawing::parse_bytes(b"  0\nSECTION\n  2\nENTITIES\n  0\nTEXT\n  0\nENDSEC\n").is_err());
+        assert!(Drawing::parse_bytes(b"  0\nSECTION\n  2\nENTITIES\n  0\nLINE\n 10\nx\n  0\nENDSEC\n").is_err());
+    }
+}
@@ -0,0 +1,162 @@
+// conversions to and from the `dxf` crate's drawing model
+use crate::dxf::*;
+use crate::result::*;
+use dxf_crate::entities as dxf_entities;
+use dxf_crate::enums::PolylineCurvedAndSmoothSurfaceType;
+use std::convert::TryFrom;
+
+impl From<&Point> for dxf_crate::Point {
+    fn from(point: &Point) -> dxf_crate::Point {
+        dxf_crate::Point::new(point.x, point.y, 0.0)
+    }
+}
+
+impl From<&dxf_crate::Point> for Point {
+    fn from(point: &dxf_crate::Point) -> Point {
+        Point {
+            x: point.x,
+            y: point.y,
+        }
+    }
+}
+
+fn surface_type(curve_type: u32) -> PolylineCurvedAndSmoothSurfaceType {
+    match curve_type {
+        5 => PolylineCurvedAndSmoothSurfaceType::QuadraticBSpline,
+        6 => PolylineCurvedAndSmoothSurfaceType::CubicBSpline,
+        8 => PolylineCurvedAndSmoothSurfaceType::Bezier,
+        _ => PolylineCurvedAndSmoothSurfaceType::None,
+    }
+}
+
+impl From<&Drawing> for dxf_crate::Drawing {
+    fn from(drawing: &Drawing) -> dxf_crate::Drawing {
+        let mut out = dxf_crate::Drawing::new();
+        for entity in drawing.entities() {
+            let specific = match entity {
+                Entity::Line(from, to) => dxf_entities::EntityType::Line(dxf_entities::Line::new(from.into(), to.into())),
+                Entity::Arc { center, radius, start_angle, end_angle } => {
+                    dxf_entities::EntityType::Arc(dxf_entities::Arc::new(center.into(), *radius, *start_angle, *end_angle))
+                },
+                Entity::Circle { center, radius } => dxf_entities::EntityType::Circle(dxf_entities::Circle::new(center.into(), *radius)),
+                Entity::Ellipse { center, major_axis, ratio, start_param, end_param } => dxf_entities::EntityType::Ellipse(dxf_entities::Ellipse {
+                    center: center.into(),
+                    major_axis: dxf_crate::Vector::new(major_axis.x, major_axis.y, 0.0),
+                    minor_axis_ratio: *ratio,
+                    start_parameter: *start_param,
+                    end_parameter: *end_param,
+                    ..Default::default()
+                }),
+                Entity::Polyline { curve_type, vertices, widths } => {
+                    let mut polyline = dxf_entities::Polyline {
+                        surface_type: surface_type(*curve_type),
+                        ..Default::default()
+                    };
+                    for (i, vertex) in vertices.iter().enumerate() {
+                        let (starting_width, ending_width) = widths.get(i).cloned().unwrap_or((0.0, 0.0));
+                        polyline.add_vertex(&mut out, dxf_entities::Vertex { starting_width, ending_width, ..dxf_entities::Vertex::new(vertex.into()) });
+                    }
+                    dxf_entities::EntityType::Polyline(polyline)
+                },
+            };
+            out.add_entity(dxf_entities::Entity::new(specific));
+        }
+        out
+    }
+}
+
+// widths of a polyline drawn with no width are left out, as the parser does
+fn kept_widths(widths: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    if widths.iter().all(|(start, end)| *start == 0.0 && *end == 0.0) { vec![] } else { widths }
+}
+
+impl From<Drawing> for dxf_crate::Drawing {
+    fn from(drawing: Drawing) -> dxf_crate::Drawing {
+        (&drawing).into()
+    }
+}
+
+impl TryFrom<&dxf_crate::Drawing> for Drawing {
+    type Error = Error;
+
+    fn try_from(drawing: &dxf_crate::Drawing) -> Result<Drawing> {
+        let mut entities = vec![];
+        for entity in drawing.entities() {
+            entities.push(match &entity.specific {
+                dxf_entities::EntityType::Line(line) => Entity::Line((&line.p1).into(), (&line.p2).into()),
+                dxf_entities::EntityType::Arc(arc) => Entity::Arc {
+                    center: (&arc.center).into(),
+                    radius: arc.radius,
+                    start_angle: arc.start_angle,
+                    end_angle: arc.end_angle,
+                },
+                dxf_entities::EntityType::Circle(circle) => Entity::Circle {
+                    center: (&circle.center).into(),
+                    radius: circle.radius,
+                },
+                dxf_entities::EntityType::Ellipse(ellipse) => Entity::Ellipse {
+                    center: (&ellipse.center).into(),
+                    major_axis: Point { x: ellipse.major_axis.x, y: ellipse.major_axis.y },
+                    ratio: ellipse.minor_axis_ratio,
+                    start_param: ellipse.start_parameter,
+                    end_param: ellipse.end_parameter,
+                },
+                dxf_entities::EntityType::Polyline(polyline) => Entity::Polyline {
+                    curve_type: polyline.surface_type as u32,
+                    vertices: polyline.vertices().map(|v| (&v.location).into()).collect(),
+                    // a vertex with no width of its own takes the polyline's default
+                    widths: kept_widths(polyline.vertices().map(|v| (
+                        if v.starting_width != 0.0 { v.starting_width } else { polyline.default_starting_width },
+                        if v.ending_width != 0.0 { v.ending_width } else { polyline.default_ending_width },
+                    )).collect()),
+                },
+                dxf_entities::EntityType::LwPolyline(polyline) => {
+                    if polyline.vertices.iter().any(|v| v.bulge != 0.0) {
+                        return Err(weld_err!("cannot convert LWPOLYLINE with bulges"));
+                    }
+                    let mut vertices = polyline.vertices.iter().map(|v| Point { x: v.x, y: v.y }).collect::<Vec<_>>();
+                    let mut widths = polyline.vertices.iter().map(|v| if polyline.constant_width != 0.0 {
+                        (polyline.constant_width, polyline.constant_width)
+                    } else {
+                        (v.starting_width, v.ending_width)
+                    }).collect::<Vec<_>>();
+                    if polyline.flags & 1 != 0 {
+                        vertices.extend(vertices.first().cloned());
+                        widths.extend(widths.first().cloned());
+                    }
+                    Entity::Polyline {
+                        curve_type: 0,
+                        vertices,
+                        widths: kept_widths(widths),
+                    }
+                },
+                other => return Err(weld_err!("unsupported entity type: {:?}", other)),
+            });
+        }
+        Ok(Drawing::new(entities))
+    }
+}
+
+impl TryFrom<dxf_crate::Drawing> for Drawing {
+    type Error = Error;
+
+    fn try_from(drawing: dxf_crate::Drawing) -> Result<Drawing> {
+        Drawing::try_from(&drawing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let drawing = Drawing::new(vec![
+                Entity::Line(Point { x: 0.0, y: 0.0 }, Point { x: 1.0, y: 0.0 }),
+                Entity::Arc { center: Point { x: 1.0, y: 1.0 }, radius: 1.0, start_angle: 270.0, end_angle: 0.0 },
+                Entity::Polyline { curve_type: 6, vertices: vec![Point { x: 2.0, y: 1.0 }, Point { x: 3.0, y: 2.0 }], widths: vec![] },
+        ]);
+        let converted: dxf_crate::Drawing = (&drawing).into();
+        assert_eq!(Drawing::try_from(&converted).unwrap(), drawing);
+    }
+}
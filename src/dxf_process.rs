@@ -1,14 +1,190 @@
+use crate::curve::CurveModel;
 use crate::dxf::*;
+use crate::output::OutputBackend;
+use crate::pass::{Pass, Passes};
+use crate::path::{along, cross, snap_key, EPSILON};
+use crate::report::*;
 use crate::result::*;
 use std::collections::BTreeMap;
 use std::f64::consts::PI;
+use std::fmt;
+use std::io::Write;
+use serde::{Serialize, Deserialize};
 
+// how runs of points are fitted with arcs
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Fitter {
+    // grows each arc one point at a time through its first, middle and last point
+    #[default]
+    Greedy,
+    // votes on circle hypotheses over each window, tolerating the occasional outlier point
+    Ransac,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
 pub struct DxfConfig {
     pub resolution: f64, // 0.00001
     pub max_radius: f64,
     pub min_segments: usize,
+    pub fitter: Fitter,
+    // replace runs of lines that stay near a least-squares line with a single line
+    pub fit_lines: bool,
+    // join consecutive arcs on the same circle that the fitter split apart
+    pub merge_arcs: bool,
+    pub zero_length: ZeroLengthPolicy,
+    // decimals written for arc angles in DXF output, or full precision
+    pub angle_precision: Option<usize>,
+    // write arcs in G-code output as three-point CIP moves rather than G2/G3 around their centers
+    pub three_point_arcs: bool,
+    // weld the entities inside each block definition in place, leaving inserts as they are
+    pub weld_blocks: bool,
+    // version of DXF output, deciding whether it carries handles and an OBJECTS section
+    pub dxf_version: DxfVersion,
+    // write only the entities of DXF output, with no sections or EOF around them
+    pub fragment: bool,
+    pub limits: Limits,
+    // how reals are written in DXF input
+    #[serde(default)]
+    pub number_format: NumberFormat,
+    // arcs and circles replacing fewer source segments than this are left as lines
+    pub min_arc_segments_replaced: usize,
+    // most points fitted as one arc, bounding the cost of each fit on long chains
+    pub max_points_per_fit: Option<usize>,
+    // chains of more points than this are welded in overlapping chunks of about this many, fitted on
+    // separate threads and stitched back together
+    pub chunk_points: Option<usize>,
+    // degrees the chain's direction through each point of a fitted run may stray from the curve's
+    // tangent there, or unchecked
+    pub angular_tolerance: Option<f64>,
+    // picks settings for each chain of a drawing in place of the ones above
+    #[serde(skip)]
+    pub chain_params: Option<ChainParamsFn>,
+    // run by `process_drawing` on the drawing before welding it, and on the welded drawing
+    #[serde(skip)]
+    pub before_weld: Passes,
+    #[serde(skip)]
+    pub after_weld: Passes,
+}
+
+impl Default for DxfConfig {
+    fn default() -> Self {
+        DxfConfig {
+            resolution: 0.05,
+            max_radius: 100000.0,
+            min_segments: 3,
+            fitter: Fitter::Greedy,
+            fit_lines: false,
+            merge_arcs: false,
+            zero_length: ZeroLengthPolicy::Drop,
+            angle_precision: None,
+            three_point_arcs: false,
+            weld_blocks: false,
+            dxf_version: DxfVersion::R12,
+            fragment: false,
+            limits: Limits::default(),
+            number_format: NumberFormat::Point,
+            min_arc_segments_replaced: 0,
+            max_points_per_fit: None,
+            chunk_points: None,
+            angular_tolerance: None,
+            chain_params: None,
+            before_weld: Passes::new(),
+            after_weld: Passes::new(),
+        }
+    }
+}
+
+// A chain of connected points from one layer, about to be welded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chain {
+    pub layer: String,
+    pub points: Vec<Point>,
+}
+
+impl Chain {
+    pub fn length(&self) -> f64 {
+        self.points.windows(2).map(|pair| pair[0].dist(&pair[1])).sum()
+    }
+
+    pub fn closed(&self) -> bool {
+        self.points.len() > 2 && self.points.first() == self.points.last()
+    }
+
+    // the lower left and upper right corners of the box around the points
+    pub fn bounds(&self) -> (Point, Point) {
+        self.points.iter().fold((self.points[0].clone(), self.points[0].clone()), |(min, max), p| (
+            Point { x: min.x.min(p.x), y: min.y.min(p.y) },
+            Point { x: max.x.max(p.x), y: max.y.max(p.y) },
+        ))
+    }
+}
+
+// Settings for one chain; those left as None keep the config's value.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChainParams {
+    pub resolution: Option<f64>,
+    pub max_radius: Option<f64>,
+    pub min_segments: Option<usize>,
+    // leave the chain's lines as they are instead of welding it
+    pub pass_through: bool,
+}
+
+// What welding does with input lines whose ends coincide, which have no direction to chain along
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ZeroLengthPolicy {
+    #[default]
+    Drop,
+    // fail, naming the layer and where the first one is
+    Error,
+    // pass them through unwelded
+    Keep,
+}
+
+fn is_zero_length(entity: &Entity) -> bool {
+    matches!(entity, Entity::Line(from, to) if from == to)
+}
+
+// a polyline of straight segments with no width, which welds like the lines it is made of
+fn is_straight_polyline(entity: &Entity) -> bool {
+    matches!(entity, Entity::Polyline { curve_type: 0, .. }) && !entity.has_width()
+}
+
+// Whether welding leaves the entity as it is rather than chaining it: circles, ellipses, spline-fit
+// or wide polylines and arcs no lines extend, including the output of an earlier weld
+fn is_passed_through(entity: &Entity) -> bool {
+    !matches!(entity, Entity::Line(..)) && !is_straight_polyline(entity)
+}
+
+// What `DxfConfig::text_outlines` does with chains that look like text exploded into outlines
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextPolicy {
+    // leave their lines as they are
+    PassThrough,
+    // weld them at this tighter resolution
+    Resolution(f64),
+}
+
+// Called with every chain of a drawing before it is welded, to weld it with other settings: a looser
+// resolution for a decorative layer, say, or a tighter one for short chains.
+#[derive(Clone)]
+pub struct ChainParamsFn(pub std::sync::Arc<dyn Fn(&Chain) -> ChainParams + Send + Sync>);
+
+impl ChainParamsFn {
+    pub fn new(f: impl Fn(&Chain) -> ChainParams + Send + Sync + 'static) -> ChainParamsFn {
+        ChainParamsFn(std::sync::Arc::new(f))
+    }
+}
+
+impl fmt::Debug for ChainParamsFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ChainParamsFn")
+    }
 }
 
+#[derive(Clone)]
 struct Circle {
     center: Point,
     radius: f64,
@@ -23,10 +199,20 @@ impl Circle {
         radians
     }
 
+    // counter-clockwise angle from `from` to `to` about the center in [0, 2π), measured between the
+    // two radii rather than from the +X axis so it is continuous across the 0/2π seam
+    fn get_relative_radians(&self, from: &Point, to: &Point) -> f64 {
+        let a = (from.x - self.center.x, from.y - self.center.y);
+        let b = (to.x - self.center.x, to.y - self.center.y);
+        let radians = cross(a, b).atan2(a.0 * b.0 + a.1 * b.1);
+        if radians < 0.0 {
+            return 2.0 * PI + radians;
+        }
+        radians
+    }
+
     fn get_radial_dist(&self, start: &Point, end: &Point) -> f64 {
-        let start_theta = self.get_polar_radians(start);
-        let end_theta = self.get_polar_radians(end);
-        let raw = (start_theta - end_theta).abs();
+        let raw = self.get_relative_radians(start, end);
         if raw > PI {
             2.0 * PI - raw
         } else {
@@ -40,6 +226,148 @@ struct Arc {
     radius: f64,
     start_angle: f64,
     end_angle: f64,
+    // largest radial deviation of the replaced points and segments, and where it occurs
+    residual: f64,
+    residual_at: Point,
+    // the chain runs clockwise around the arc, which is still stored counter-clockwise
+    clockwise: bool,
+}
+
+// Running sums over a window of points for an algebraic (Kasa) least-squares circle fit, which
+// minimizes the sum of (|p - c|² - r²)² and so can be updated in O(1) as the window grows. Points
+// are taken relative to the window's first point to keep the sums well conditioned.
+struct CircleSums {
+    origin: Point,
+    end: usize,
+    n: f64,
+    x: f64,
+    y: f64,
+    xx: f64,
+    xy: f64,
+    yy: f64,
+    // z is the squared distance from the origin
+    xz: f64,
+    yz: f64,
+    z: f64,
+    zz: f64,
+}
+
+impl CircleSums {
+    fn new(chain: &[Point], start: usize) -> CircleSums {
+        CircleSums {
+            origin: chain[start].clone(),
+            end: start,
+            n: 1.0,
+            x: 0.0,
+            y: 0.0,
+            xx: 0.0,
+            xy: 0.0,
+            yy: 0.0,
+            xz: 0.0,
+            yz: 0.0,
+            z: 0.0,
+            zz: 0.0,
+        }
+    }
+
+    fn extend_to(&mut self, chain: &[Point], end: usize) {
+        while self.end < end {
+            self.end += 1;
+            let (x, y) = (chain[self.end].x - self.origin.x, chain[self.end].y - self.origin.y);
+            let z = x * x + y * y;
+            self.n += 1.0;
+            self.x += x;
+            self.y += y;
+            self.xx += x * x;
+            self.xy += x * y;
+            self.yy += y * y;
+            self.xz += x * z;
+            self.yz += y * z;
+            self.z += z;
+            self.zz += z * z;
+        }
+    }
+
+    // The smallest sum of (|p - c|² - r²)² any circle reaches over the window, by solving the normal
+    // equations of z = a x + b y + c. Collinear windows have no unique fit and report 0.
+    fn min_algebraic_error(&self) -> f64 {
+        let det3 = |m: [[f64; 3]; 3]| {
+            m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+                - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+                + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+        };
+        let m = [[self.xx, self.xy, self.x], [self.xy, self.yy, self.y], [self.x, self.y, self.n]];
+        let v = [self.xz, self.yz, self.z];
+        let det = det3(m);
+        if det.abs() <= EPSILON * self.n * (self.xx + self.yy).powi(2) {
+            return 0.0;
+        }
+        // Cramer's rule, replacing one column at a time with v
+        let mut projection = 0.0;
+        for column in 0..3 {
+            let mut replaced = m;
+            for row in 0..3 {
+                replaced[row][column] = v[row];
+            }
+            projection += det3(replaced) / det * v[column];
+        }
+        (self.zz - projection).max(0.0)
+    }
+
+    // True if no circle of this radius can pass within `resolution` of every point. A point within
+    // the resolution of the circle has an algebraic error of at most resolution * (2 * radius +
+    // resolution), so the least-squares error bounds the best any circle can do; the factor of two
+    // leaves room for rounding in the sums.
+    fn rules_out(&self, radius: f64, resolution: f64) -> bool {
+        let worst = resolution * (2.0 * radius + resolution);
+        self.min_algebraic_error() > 2.0 * self.n * worst * worst
+    }
+}
+
+// What is known about the window of the chain an arc is being grown over. `fitted` is the circle the
+// points up to `checked` were last checked against, with an upper bound on how far they stray from
+// it: a later circle that has drifted less than `resolution - bound` from it must also fit those
+// points, so only the points added since need checking.
+struct Window {
+    start: usize,
+    checked: usize,
+    fitted: Option<(Circle, f64)>,
+    sums: CircleSums,
+}
+
+// Length along a chain up to each point, so the point halfway along any stretch of it is found by
+// binary search however unevenly the chain is sampled.
+struct ChainLengths(Vec<f64>);
+
+impl ChainLengths {
+    fn new(chain: &[Point]) -> ChainLengths {
+        let mut lengths = ChainLengths(vec![0.0]);
+        lengths.extend_to(chain);
+        lengths
+    }
+
+    // adds the lengths up to each point of `chain` past the ones already known
+    fn extend_to(&mut self, chain: &[Point]) {
+        for i in self.0.len()..chain.len() {
+            let total = self.0[i - 1] + chain[i - 1].dist(&chain[i]);
+            self.0.push(total);
+        }
+    }
+
+    fn between(&self, start: usize, end: usize) -> f64 {
+        self.0[end] - self.0[start]
+    }
+
+    // the point strictly between `start` and `end` nearest to halfway along, the earlier one on a tie
+    fn midpoint(&self, start: usize, end: usize) -> usize {
+        let target = (self.0[start] + self.0[end]) / 2.0;
+        let after = start + 1 + self.0[start + 1..end].partition_point(|length| *length < target);
+        if after == end || (after > start + 1 && target - self.0[after - 1] <= self.0[after] - target) {
+            after - 1
+        } else {
+            after
+        }
+    }
 }
 
 #[derive(PartialEq)]
@@ -49,12 +377,381 @@ enum Direction {
     Unknown,
 }
 
+impl Direction {
+    // the way a path through the three points turns
+    fn of_turn(start: &Point, mid: &Point, end: &Point) -> Direction {
+        let turn = cross((mid.x - start.x, mid.y - start.y), (end.x - mid.x, end.y - mid.y));
+        if turn > 0.0 {
+            Direction::CounterClockwise
+        } else if turn < 0.0 {
+            Direction::Clockwise
+        } else {
+            Direction::Unknown
+        }
+    }
+}
+
 const CIRCLE_ZERO_TOLERANCE: f64 = 0.00001;
 
+// share of the resolution a point may sit on the wrong side of the chord between its neighbours
+// before the chain counts as turning against an arc, leaving room for rounding in the input
+const TURN_NOISE: f64 = 0.01;
+// points in a row turning the wrong way that reject an arc
+const TURNS_AGAINST_ARC: usize = 3;
+
+// radians within which two angles of a fit are a tie left to float noise, such as the sweep of a
+// half circle against π
+const ANGLE_TIE_TOLERANCE: f64 = 1e-9;
+
+// how many resolutions a corner must bulge out from any arc rounding it to count as sharp
+const SHARP_CORNER_MARGIN: f64 = 4.0;
+
+// circle hypotheses tried per window by the RANSAC fitter
+const RANSAC_HYPOTHESES: usize = 32;
+// share of a window's points that may stray from the arc as noise
+const RANSAC_OUTLIER_RATIO: f64 = 0.2;
+// failed fits a window grows past before giving up, so one bad point does not end an arc
+const RANSAC_LOOKAHEAD: usize = 3;
+
+// degrees off a full turn that a run back at its start may sweep and still become a circle
+const CIRCLE_SWEEP_TOLERANCE: f64 = 1.0;
+
+// share of a chunk, as a divisor, that it reaches into each neighbour for the two fits to find a
+// point where they agree to be stitched at
+const CHUNK_OVERLAP_DIVISOR: usize = 4;
+// fewest points `chunk_points` may be, leaving each chunk room for several fits
+const MIN_CHUNK_POINTS: usize = 64;
+
+// largest step in degrees an input arc grows by when absorbing one line
+const ARC_EXTENSION_STEP: f64 = 90.0;
+
+// median segment length, in resolutions, that the config's min_segments is meant for under
+// `adaptive_min_segments`; denser chains need proportionally more segments
+const ADAPTIVE_REFERENCE_SEGMENT: f64 = 5.0;
+// most times the config's min_segments that `adaptive_min_segments` raises it to
+const ADAPTIVE_MAX_FACTOR: usize = 8;
+
+// Degrees a run of points sweeps around `center`, counter-clockwise positive, summed segment by
+// segment. A run going once round a circle sweeps a full turn, while one that only comes back to its
+// start, such as out along an arc and straight back, sweeps less.
+fn run_sweep(run: &[Point], center: &Point) -> f64 {
+    run.windows(2).map(|pair| {
+        let a = (pair[0].x - center.x, pair[0].y - center.y);
+        let b = (pair[1].x - center.x, pair[1].y - center.y);
+        cross(a, b).atan2(a.0 * b.0 + a.1 * b.1)
+    }).sum::<f64>().to_degrees()
+}
+
+// One window of a chunked chain, fitted on its own
+struct ChunkFit {
+    entities: Vec<Entity>,
+    arcs: Vec<ArcReport>,
+    // (index in the chain, index in `entities`) of each point where one entity ends and the next starts,
+    // including the window's first and last point
+    ends: Vec<(usize, usize)>,
+    // the window's last point in the chain
+    to: usize,
+}
+
+// Where in `chain` the entities fitted to `chain[from..=to]` meet, found by matching each one's far
+// end to the chain's next points. Entities that cannot be matched, such as circles, leave no end.
+fn entity_ends(chain: &[Point], from: usize, to: usize, entities: &[Entity]) -> Vec<(usize, usize)> {
+    let mut ends = vec![(from, 0)];
+    let mut at = from;
+    for (i, entity) in entities.iter().enumerate().take(entities.len().saturating_sub(1)) {
+        let (start, end) = match (entity.start_point(), entity.end_point()) {
+            (Some(start), Some(end)) => (start, end),
+            _ => continue,
+        };
+        // arcs are stored counter-clockwise, so either end may be the one the chain leaves through
+        let far = if start == chain[at] { end } else { start };
+        if let Some(next) = (at + 1..to).find(|j| chain[*j] == far) {
+            at = next;
+            ends.push((next, i + 1));
+        }
+    }
+    ends.push((to, entities.len()));
+    ends
+}
+
+// The chains of a layer stored back to back in one point buffer, so a layer of many small chains
+// costs two allocations instead of one per chain. Chains are handed out as slices.
+#[derive(Clone, Debug, Default)]
+pub struct Chains {
+    points: Vec<Point>,
+    // end of each chain in `points`, which is also the start of the next
+    ends: Vec<usize>,
+    // lines left out of every chain, starting where another line already starts
+    orphans: Vec<(Point, Point)>,
+}
+
+impl Chains {
+    pub fn len(&self) -> usize {
+        self.ends.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    pub fn get(&self, i: usize) -> &[Point] {
+        let start = if i == 0 { 0 } else { self.ends[i - 1] };
+        &self.points[start..self.ends[i]]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &[Point]> + '_ {
+        (0..self.len()).map(move |i| self.get(i))
+    }
+
+    // every point of every chain, in chain order
+    pub fn points(&self) -> &[Point] {
+        &self.points
+    }
+
+    pub fn orphans(&self) -> &[(Point, Point)] {
+        &self.orphans
+    }
+}
+
+// Joins the LINEs of a layer end to start into chains of points, the input to `process_chain`. A
+// line starting where another does, at a branch or as a duplicate, cannot be chained and is kept
+// in `Chains::orphans` instead. Polylines drawn with a width are left out, to be passed through.
+pub fn layer_chains<'a>(entities: &'a [Entity]) -> Result<Chains> {
+    let mut src_dest: BTreeMap<&Point, &Point> = BTreeMap::new();
+    let mut orphans = vec![];
+    let mut chain = |from: &'a Point, to: &'a Point| {
+        // chaining one would replace the line leaving its point
+        if from == to {
+            return;
+        }
+        if let Some(replaced) = src_dest.insert(from, to) {
+            orphans.push((from.clone(), replaced.clone()));
+        }
+    };
+    for entity in entities.iter() {
+        match entity {
+            Entity::Line(from, to) => chain(from, to),
+            // a plain polyline is chained segment by segment, like the lines it draws
+            Entity::Polyline { vertices, .. } if is_straight_polyline(entity) => {
+                for pair in vertices.windows(2) {
+                    chain(&pair[0], &pair[1]);
+                }
+            },
+            // anything else is passed through welding as it is
+            _ => (),
+        }
+    }
+
+    let mut chains = Chains {
+        points: Vec::with_capacity(entities.len() + 1),
+        ends: vec![],
+        orphans,
+    };
+    while let Some((first_from, mut next)) = src_dest.pop_first() {
+        chains.points.push(first_from.clone());
+        while let Some(point) = src_dest.remove(next) {
+            chains.points.push(next.clone());
+            next = point;
+        }
+        chains.points.push(next.clone());
+        chains.ends.push(chains.points.len());
+    }
+    Ok(chains)
+}
+
+// a least-squares line through a centroid, with a unit direction
+struct FitLine {
+    center: Point,
+    direction: (f64, f64),
+}
+
+impl FitLine {
+    // orthogonal regression: the direction is the principal axis of the points
+    fn fit(points: &[Point]) -> FitLine {
+        let n = points.len() as f64;
+        let center = Point {
+            x: points.iter().map(|p| p.x).sum::<f64>() / n,
+            y: points.iter().map(|p| p.y).sum::<f64>() / n,
+        };
+        let (mut sxx, mut sxy, mut syy) = (0f64, 0f64, 0f64);
+        for p in points.iter() {
+            let (dx, dy) = (p.x - center.x, p.y - center.y);
+            sxx += dx * dx;
+            sxy += dx * dy;
+            syy += dy * dy;
+        }
+        let theta = (2.0 * sxy).atan2(sxx - syy) / 2.0;
+        FitLine {
+            center,
+            direction: (theta.cos(), theta.sin()),
+        }
+    }
+
+    fn distance(&self, point: &Point) -> f64 {
+        cross(self.direction, (point.x - self.center.x, point.y - self.center.y)).abs()
+    }
+
+    fn intersect(&self, other: &FitLine) -> Option<Point> {
+        let denom = cross(self.direction, other.direction);
+        if denom.abs() < EPSILON {
+            return None;
+        }
+        let offset = (other.center.x - self.center.x, other.center.y - self.center.y);
+        Some(along(&self.center, self.direction, cross(offset, other.direction) / denom))
+    }
+}
+
+fn segment_distance(point: &Point, start: &Point, end: &Point) -> f64 {
+    let (dx, dy) = (end.x - start.x, end.y - start.y);
+    let length = dx * dx + dy * dy;
+    if length < EPSILON {
+        return point.dist(start);
+    }
+    let t = (((point.x - start.x) * dx + (point.y - start.y) * dy) / length).clamp(0.0, 1.0);
+    point.dist(&Point { x: start.x + t * dx, y: start.y + t * dy })
+}
+
+// xorshift, seeded per chain so welding stays reproducible
+struct Rng(u64);
+
+impl Rng {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % n as u64) as usize
+    }
+}
+
+// Fits arcs through each run's first, last and halfway point, the model of the greedy fitter.
+struct ArcModel<'a> {
+    config: &'a DxfConfig,
+    // address of the first point of the run being grown, to tell when a new run starts
+    run: usize,
+    lengths: ChainLengths,
+    window: Option<Window>,
+}
+
+impl<'a> ArcModel<'a> {
+    fn new(config: &'a DxfConfig) -> ArcModel<'a> {
+        ArcModel {
+            config,
+            run: 0,
+            lengths: ChainLengths::new(&[]),
+            window: None,
+        }
+    }
+}
+
+impl CurveModel for ArcModel<'_> {
+    type Model = Arc;
+
+    fn fit(&mut self, points: &[Point]) -> Option<Arc> {
+        if points.as_ptr() as usize != self.run {
+            self.run = points.as_ptr() as usize;
+            self.lengths = ChainLengths::new(&[]);
+            self.window = None;
+        }
+        self.lengths.extend_to(points);
+        if self.config.sagitta_rules_out(points) {
+            return None;
+        }
+        let end = points.len() - 1;
+        // seeded from the point halfway along rather than the middle index, for unevenly sampled runs
+        let mid = &points[self.lengths.midpoint(0, end)];
+        let circle = self.config.make_circle(&points[0], mid, &points[end])?;
+        if !self.config.check_window(points, 0, end, &circle, &mut self.window) || !self.config.check_segment_angles(points, &circle) {
+            return None;
+        }
+        let arc = self.config.make_arc(&circle, &points[0], mid, &points[end], self.lengths.between(0, end))?;
+        if !self.config.check_turns(points, arc.clockwise) {
+            return None;
+        }
+        // the last segment must also be about as long as the arc between its ends
+        let (last, point) = (&points[end - 1], &points[end]);
+        let cdist = circle.get_radial_dist(last, point) * circle.radius;
+        if (cdist - last.dist(point)).abs() >= self.config.resolution {
+            return None;
+        }
+        Some(arc)
+    }
+
+    fn max_deviation(&self, arc: &Arc, points: &[Point]) -> Option<(f64, Point)> {
+        self.config.chain_residual(points, &Circle { center: arc.center.clone(), radius: arc.radius })
+    }
+
+    fn sweep(&self, arc: &Arc, points: &[Point]) -> Option<f64> {
+        Some(run_sweep(points, &arc.center))
+    }
+
+    fn to_entities(&self, arc: Arc, deviation: (f64, Point), segments: usize, closed: bool, report: &mut ChainReport, first: usize) -> Vec<Entity> {
+        let entity = if closed {
+            Entity::Circle { center: arc.center.clone(), radius: arc.radius }
+        } else {
+            Entity::Arc { center: arc.center.clone(), radius: arc.radius, start_angle: arc.start_angle, end_angle: arc.end_angle }
+        };
+        report.arcs.push(ArcReport {
+            entity: first,
+            center: arc.center,
+            radius: arc.radius,
+            residual: deviation.0,
+            residual_at: deviation.1,
+            segments,
+            circle: closed,
+            clockwise: arc.clockwise,
+            three_point: entity.three_point(),
+            #[cfg(feature = "debug-geometry")]
+            source: None,
+        });
+        vec![entity]
+    }
+}
+
+// Fits arcs by voting on circle hypotheses, tolerating the occasional outlier point.
+struct RansacArcModel<'a> {
+    arcs: ArcModel<'a>,
+    rng: Rng,
+}
+
+impl<'a> RansacArcModel<'a> {
+    fn new(config: &'a DxfConfig, chain: &[Point]) -> RansacArcModel<'a> {
+        RansacArcModel {
+            arcs: ArcModel::new(config),
+            rng: Rng(0x2545_f491_4f6c_dd1d ^ chain.len() as u64),
+        }
+    }
+}
+
+impl CurveModel for RansacArcModel<'_> {
+    type Model = Arc;
+
+    fn fit(&mut self, points: &[Point]) -> Option<Arc> {
+        self.arcs.config.ransac_window(points, &mut self.rng)
+    }
+
+    // measured by the fit over every point, so outliers count
+    fn max_deviation(&self, arc: &Arc, _points: &[Point]) -> Option<(f64, Point)> {
+        Some((arc.residual, arc.residual_at.clone()))
+    }
+
+    fn sweep(&self, arc: &Arc, points: &[Point]) -> Option<f64> {
+        self.arcs.sweep(arc, points)
+    }
+
+    fn to_entities(&self, arc: Arc, deviation: (f64, Point), segments: usize, closed: bool, report: &mut ChainReport, first: usize) -> Vec<Entity> {
+        self.arcs.to_entities(arc, deviation, segments, closed, report, first)
+    }
+}
+
 impl DxfConfig {
 
     // https://github.com/FormerLurker/ArcWelderPlugin/blob/master/octoprint_arc_welder/data/lib/c/arc_welder/segmented_shape.cpp#L165
+    // The points are moved about their centroid first: squaring coordinates far from the origin
+    // would lose the digits that set the circle apart.
     fn make_circle(&self, p1: &Point, p2: &Point, p3: &Point) -> Option<Circle> {
+        let origin = Point { x: (p1.x + p2.x + p3.x) / 3.0, y: (p1.y + p2.y + p3.y) / 3.0 };
+        let relative = |p: &Point| Point { x: p.x - origin.x, y: p.y - origin.y };
+        let (p1, p2, p3) = (&relative(p1), &relative(p2), &relative(p3));
         let a = p1.x * (p2.y - p3.y) - p1.y * (p2.x - p3.x) + p2.x * p3.y - p3.x * p2.y;
         if a.abs() < CIRCLE_ZERO_TOLERANCE {
             return None;
@@ -81,7 +778,7 @@ impl DxfConfig {
             return None;
         }
         Some(Circle {
-            center,
+            center: Point { x: center.x + origin.x, y: center.y + origin.y },
             radius,
         })
     }
@@ -102,13 +799,19 @@ impl DxfConfig {
     }
 
     // https://github.com/FormerLurker/ArcWelderPlugin/blob/master/octoprint_arc_welder/data/lib/c/arc_welder/segmented_arc.cpp#L212
-    fn check_chain_circle(&self, chain: &[Point], circle: &Circle, expected_length: f64) -> Option<Arc> {
+    // The largest radial deviation of the chain's points and segments from the circle, and where it
+    // occurs, or None if anything strays further than the resolution.
+    fn chain_residual(&self, chain: &[Point], circle: &Circle) -> Option<(f64, Point)> {
+        let mut residual = (0f64, chain[0].clone());
         for point in chain[1..].iter() {
             let distance = circle.center.dist(point);
             let diff = (circle.radius - distance).abs();
             if diff > self.resolution {
                 return None;
             }
+            if diff > residual.0 {
+                residual = (diff, point.clone());
+            }
         }
         for (i, point) in chain[0..chain.len() - 1].iter().enumerate() {
             let next = &chain[i + 1];
@@ -118,106 +821,521 @@ impl DxfConfig {
                 if diff > self.resolution {
                     return None;
                 }
+                if diff > residual.0 {
+                    residual = (diff, closest_point);
+                }
+            }
+        }
+        Some(residual)
+    }
+
+    // Whether each segment of the chain runs along the circle: its direction must be within
+    // `angular_tolerance` of the circle's tangent at its middle, where a chord of the circle is
+    // parallel to it. This catches zig-zags the radial check lets through on large radii.
+    fn check_segment_angles(&self, chain: &[Point], circle: &Circle) -> bool {
+        let tolerance = match self.angular_tolerance {
+            Some(degrees) => degrees.to_radians(),
+            None => return true,
+        };
+        chain.windows(2).all(|pair| {
+            let direction = (pair[1].x - pair[0].x, pair[1].y - pair[0].y);
+            let radial = ((pair[0].x + pair[1].x) / 2.0 - circle.center.x, (pair[0].y + pair[1].y) / 2.0 - circle.center.y);
+            // the tangent is square to the radius, so measure how far the segment is from square
+            let from_radial = cross(direction, radial).abs().atan2((direction.0 * radial.0 + direction.1 * radial.1).abs());
+            PI / 2.0 - from_radial <= tolerance
+        })
+    }
+
+    // Whether the chain keeps turning the same way as the arc, so an S-shaped run that stays within
+    // the resolution of a circle is not welded into one arc. A point knocked off the curve turns the
+    // chain the wrong way at that point alone, while the far side of an S does so at one point after
+    // another.
+    fn check_turns(&self, chain: &[Point], clockwise: bool) -> bool {
+        let mut against = 0;
+        for joint in chain.windows(3) {
+            let turn = cross((joint[1].x - joint[0].x, joint[1].y - joint[0].y), (joint[2].x - joint[1].x, joint[2].y - joint[1].y));
+            // how far the middle point bulges the wrong way from the chord of its neighbours
+            let bulge = if clockwise { turn } else { -turn } / joint[0].dist(&joint[2]).max(EPSILON);
+            against = if bulge > self.resolution * TURN_NOISE { against + 1 } else { 0 };
+            if against >= TURNS_AGAINST_ARC {
+                return false;
             }
         }
+        true
+    }
 
-        self.make_arc(circle, &chain[0], &chain[(chain.len() - 2) / 2 + 1], &chain[chain.len() - 1], expected_length)
+    // Whether the run's height above the chord between its ends rules out every arc within the
+    // resolution of it before a circle is fitted. An arc's points and segments all lie within the
+    // resolution of it, so the run rises to within the resolution of the arc's sagitta: a run flatter
+    // than an arc of max_radius over the same chord is straight, and one rising higher than the
+    // largest circle is too tight to fit.
+    fn sagitta_rules_out(&self, run: &[Point]) -> bool {
+        let (start, end) = (&run[0], &run[run.len() - 1]);
+        let chord = start.dist(end);
+        if chord < EPSILON {
+            return false;
+        }
+        if chord > 2.0 * self.max_radius {
+            return true;
+        }
+        let along = (end.x - start.x, end.y - start.y);
+        let height = run.iter().map(|p| cross(along, (p.x - start.x, p.y - start.y)).abs()).fold(0.0, f64::max) / chord;
+        // the sagitta of an arc of max_radius over the chord, in a form that keeps its precision for
+        // chords much shorter than the radius
+        let flattest = (chord / 2.0).powi(2) / (self.max_radius + (self.max_radius.powi(2) - (chord / 2.0).powi(2)).sqrt());
+        height + self.resolution < flattest || height > 2.0 * self.max_radius + self.resolution
     }
 
-    // https://github.com/FormerLurker/ArcWelderPlugin/blob/master/octoprint_arc_welder/data/lib/c/arc_welder/segmented_shape.cpp#L228
-    fn make_arc(&self, circle: &Circle, start: &Point, mid: &Point, end: &Point, length: f64) -> Option<Arc> {
-        let mut start_theta = circle.get_polar_radians(start);
-        let mid_theta = circle.get_polar_radians(mid);
-        let mut end_theta = circle.get_polar_radians(end);
-        let mut direction = Direction::Unknown;
-        let mut angle_radians = 0f64;
-
-        if end_theta > start_theta {
-            if start_theta < mid_theta && mid_theta < end_theta {
-                direction = Direction::CounterClockwise;
-                angle_radians = end_theta - start_theta;
-            } else if (0.0 <= mid_theta && mid_theta < start_theta) || (end_theta < mid_theta && mid_theta < PI * 2.0) {
-                direction = Direction::Clockwise;
-                angle_radians = start_theta + (2f64 * PI - end_theta);
-            }
-        } else if start_theta > end_theta {
-            if (start_theta < mid_theta && mid_theta < 2.0 * PI) || (0.0 < mid_theta && mid_theta < end_theta) {
-                direction = Direction::CounterClockwise;
-                angle_radians = end_theta + (2f64 * PI - start_theta);
-            } else if end_theta < mid_theta && mid_theta < start_theta {
-                direction = Direction::Clockwise;
-                angle_radians = start_theta - end_theta;
+    // Checks chain[start..=end] against the circle, reusing the last check of the window when the
+    // circle has hardly moved so that growing an arc does not recheck all of its points each step,
+    // and skipping the full check when the window's least-squares fit already rules the circle out.
+    fn check_window(&self, chain: &[Point], start: usize, end: usize, circle: &Circle, window: &mut Option<Window>) -> bool {
+        if !matches!(window, Some(window) if window.start == start && window.checked <= end) {
+            *window = Some(Window { start, checked: start, fitted: None, sums: CircleSums::new(chain, start) });
+        }
+        let window = window.as_mut().unwrap();
+        window.sums.extend_to(chain, end);
+        if let Some((last, bound)) = window.fitted.as_mut() {
+            let drift = last.center.dist(&circle.center) + (last.radius - circle.radius).abs();
+            if *bound + drift <= self.resolution {
+                return match self.chain_residual(&chain[window.checked..end + 1], circle) {
+                    Some((added, _)) => {
+                        *bound = bound.max(added + drift);
+                        window.checked = end;
+                        true
+                    },
+                    None => false,
+                };
             }
         }
-        if direction == Direction::Unknown {
-            return None;
+        if window.sums.rules_out(circle.radius, self.resolution) {
+            return false;
+        }
+        match self.chain_residual(&chain[start..end + 1], circle) {
+            Some((bound, _)) => {
+                window.fitted = Some((circle.clone(), bound));
+                window.checked = end;
+                true
+            },
+            None => false,
         }
+    }
 
-        if direction == Direction::Clockwise {
-            angle_radians *= -1.0;
+    // https://github.com/FormerLurker/ArcWelderPlugin/blob/master/octoprint_arc_welder/data/lib/c/arc_welder/segmented_shape.cpp#L228
+    #[allow(unused_variables)]
+    fn make_arc(&self, circle: &Circle, start: &Point, mid: &Point, end: &Point, length: f64) -> Option<Arc> {
+        // the direction comes from angles relative to the start, so an arc straddling the +X axis
+        // is classified like any other
+        let mid_radians = circle.get_relative_radians(start, mid);
+        let end_radians = circle.get_relative_radians(start, end);
+        let direction = if mid_radians < ANGLE_TIE_TOLERANCE || end_radians < ANGLE_TIE_TOLERANCE {
+            Direction::Unknown
+        } else if (mid_radians - end_radians).abs() < ANGLE_TIE_TOLERANCE {
+            // too close to call from the angles, so follow the way the chain turns through them
+            Direction::of_turn(start, mid, end)
+        } else if mid_radians < end_radians {
+            Direction::CounterClockwise
+        } else {
+            Direction::Clockwise
+        };
+        if direction == Direction::Unknown {
+            return None;
         }
 
-        // let calc_length = circle.radius * angle_radians;
+        // let angle_radians = if direction == Direction::Clockwise { end_radians - 2.0 * PI } else { end_radians };
+        // let calc_length = circle.radius * angle_radians.abs();
         // if (calc_length - length).abs() > self.resolution {
         //     return None;
         // }
+        let mut start_theta = circle.get_polar_radians(start);
+        let mut end_theta = circle.get_polar_radians(end);
         if direction == Direction::Clockwise {
-            let tmp = start_theta;
-            start_theta = end_theta;
-            end_theta = tmp;
+            std::mem::swap(&mut start_theta, &mut end_theta);
         }
-        
+
         Some(Arc {
             center: circle.center.clone(),
             radius: circle.radius,
-            start_angle: start_theta * 360.0 / PI / 2.0,
-            end_angle: end_theta * 360.0 / PI / 2.0,
+            start_angle: normalize_degrees(start_theta.to_degrees()),
+            end_angle: normalize_degrees(end_theta.to_degrees()),
+            residual: 0.0,
+            residual_at: start.clone(),
+            clockwise: direction == Direction::Clockwise,
         })
     }
 
-    fn process_chain(&self, chain: Vec<Point>) -> Result<Vec<Entity>> {
-        if self.min_segments < 3 {
-            return Err(weld_err!("min_segments must be >= 3"));
+    // Pushes the entities of a curve replacing `run`, or the run's own lines if it replaces too few of
+    // them. The curve was fitted to `measured`, which is the run without its closing segment when the
+    // curve was closed afterwards.
+    fn emit_fit<M: CurveModel>(&self, model: &M, fitted: M::Model, measured: &[Point], run: &[Point], entities: &mut Vec<Entity>, report: &mut ChainReport) {
+        let segments = run.len() - 1;
+        if segments < self.min_arc_segments_replaced {
+            entities.extend(run.windows(2).map(|pair| Entity::Line(pair[0].clone(), pair[1].clone())));
+            return;
         }
-        if chain.len() < 2 {
-            return Err(weld_err!("cannot have 0 or 1 length segments"));
-        } else if chain.len() == 2 {
-            return Ok(vec![Entity::Line(chain[0].clone(), chain[1].clone())]);
+        // measuring is left to the end, once the curve is final
+        let deviation = model.max_deviation(&fitted, measured).unwrap_or_else(|| (0.0, measured[0].clone()));
+        let closed = segments > 1 && run[0] == run[segments];
+        let first = entities.len();
+        #[cfg(feature = "debug-geometry")]
+        let arcs = report.arcs.len();
+        entities.extend(model.to_entities(fitted, deviation, segments, closed, report, first));
+        #[cfg(feature = "debug-geometry")]
+        for arc in report.arcs[arcs..].iter_mut() {
+            arc.source = Some(run.to_vec());
         }
-        let mut entities: Vec<Entity> = vec![];
+    }
 
-        // let k = 4;
-        // let ncoeffs = chain.len() + 2 - k;
-        // let mut bw = rgsl::BSpLineWorkspace::new(k, chain.len()).unwrap();
-        // let knots = rgsl::VectorF64::from_slice(&chain.iter().map(|x| x.x).collect::<Vec<f64>>()[..]).unwrap();
-        // let ys = rgsl::VectorF64::from_slice(&chain.iter().map(|x| x.y).collect::<Vec<f64>>()[..]).unwrap();
-        // let ws = rgsl::VectorF64::from_slice(&chain.iter().map(|_| 1.0).collect::<Vec<f64>>()[..]).unwrap();
-        // bw.knots(&knots);
-        // let mut X = rgsl::MatrixF64::new(chain.len(), ncoeffs).unwrap();
-        // for (i, Point {x, .. }) in chain.iter().enumerate() {
-        //     let mut output = rgsl::VectorF64::new(ncoeffs).unwrap();
-        //     bw.eval(*x, &mut output);
-        //     X.set_col(i, &output);
-        // }
-        // let mut coeffs = rgsl::VectorF64::new(ncoeffs).unwrap();
-        // let mut cov = rgsl::MatrixF64::new(ncoeffs, ncoeffs).unwrap();
-        // let mut mw = rgsl::MultifitLinearWorkspace::new(chain.len(), ncoeffs).unwrap();
-        // mw.wlinear(&X, &ws, &ys, &mut coeffs, &mut cov);
-        
-        // entities.push(Entity::Polyline {
-        //     curve_type: 0,
-        //     vertices: chain,
-        // });
-        
-        let mut current_arc_start = 0;
-        let mut current_arc_length: f64 = chain[0..self.min_segments].windows(2).map(|p| p[0].dist(&p[1])).sum();
-        let mut current_arc: Option<Arc> = None;
-        let mut i = self.min_segments - 1;
-        while i < chain.len() {
-            if current_arc_length < 0.0 {
-                panic!("current_arc_length is < 0.0 {} {} {}", i, current_arc_start, current_arc_length);
+    // Splits a run of connected points into as few pieces as fit a least-squares line within the
+    // resolution. The run's ends stay put, while the corners between pieces move to where the fitted
+    // lines cross if that is within the resolution. Pieces whose new line strays too far from their
+    // points keep their original corners, or failing that their original lines.
+    fn fit_line_run(&self, points: &[Point]) -> Vec<Entity> {
+        let mut breaks = vec![0];
+        for end in 2..points.len() {
+            let start = *breaks.last().unwrap();
+            let line = FitLine::fit(&points[start..end + 1]);
+            if points[start..end + 1].iter().any(|p| line.distance(p) > self.resolution) {
+                breaks.push(end - 1);
             }
-            let last = &chain[i - 1];
+        }
+        breaks.push(points.len() - 1);
+        let lines = breaks.windows(2).map(|pair| FitLine::fit(&points[pair[0]..pair[1] + 1])).collect::<Vec<_>>();
+
+        let mut corners = breaks.iter().map(|i| points[*i].clone()).collect::<Vec<_>>();
+        for k in 1..corners.len() - 1 {
+            if let Some(corner) = lines[k - 1].intersect(&lines[k]) {
+                if corner.dist(&corners[k]) <= self.resolution {
+                    corners[k] = corner;
+                }
+            }
+        }
+        let fits = |corners: &[Point], k: usize| points[breaks[k]..breaks[k + 1] + 1].iter()
+            .all(|p| segment_distance(p, &corners[k], &corners[k + 1]) <= self.resolution);
+        for k in 0..lines.len() {
+            if !fits(&corners, k) {
+                corners[k] = points[breaks[k]].clone();
+                corners[k + 1] = points[breaks[k + 1]].clone();
+            }
+        }
+
+        let mut entities = vec![];
+        for k in 0..lines.len() {
+            if fits(&corners, k) {
+                entities.push(Entity::Line(corners[k].clone(), corners[k + 1].clone()));
+            } else {
+                // reverting a later piece's corner can undo an earlier fit, so recheck with the originals
+                let piece = &points[breaks[k]..breaks[k + 1] + 1];
+                let (first, last) = (corners[k].clone(), corners[k + 1].clone());
+                for (i, pair) in piece.windows(2).enumerate() {
+                    let from = if i == 0 { first.clone() } else { pair[0].clone() };
+                    let to = if i == piece.len() - 2 { last.clone() } else { pair[1].clone() };
+                    entities.push(Entity::Line(from, to));
+                }
+            }
+        }
+        entities
+    }
+
+    // The arc replacing two consecutive arcs of a chain that lie on the same circle, within the
+    // resolution, with its deviation from the chain `points` both replaced, or None if they can't be
+    // joined.
+    fn join_arcs(&self, first: &Entity, second: &Entity, clockwise: bool, points: &[Point]) -> Option<(Entity, (f64, Point))> {
+        let ((a, first_radius, first_sweep), (b, second_radius, second_sweep)) = match (first, second) {
+            (Entity::Arc { center: a, radius: ra, start_angle: sa, end_angle: ea }, Entity::Arc { center: b, radius: rb, start_angle: sb, end_angle: eb }) => {
+                ((a, *ra, (ea - sa).rem_euclid(360.0)), (b, *rb, (eb - sb).rem_euclid(360.0)))
+            },
+            _ => return None,
+        };
+        if a.dist(b) > self.resolution || (first_radius - second_radius).abs() > self.resolution || first_sweep + second_sweep >= 360.0 {
+            return None;
+        }
+        // arcs run counter-clockwise, so against the chain when it turns clockwise
+        let (start, end) = if clockwise { (second.start_point()?, first.end_point()?) } else { (first.start_point()?, second.end_point()?) };
+        if start == end {
+            return None;
+        }
+        let weight = first_sweep / (first_sweep + second_sweep);
+        let near = Point { x: a.x * weight + b.x * (1.0 - weight), y: a.y * weight + b.y * (1.0 - weight) };
+        let joined = Entity::arc_between(&start, &end, &near);
+        let residual = match &joined {
+            Entity::Arc { center, radius, .. } if *radius <= self.max_radius => self.chain_residual(points, &Circle { center: center.clone(), radius: *radius })?,
+            _ => return None,
+        };
+        Some((joined, residual))
+    }
+
+    // Joins each arc of a welded chain to the one before it when they lie on the same circle and the
+    // joined arc stays within the resolution of all the chain points they replaced, as the fitter
+    // sometimes breaks one arc in two at a noisy point.
+    fn merge_arcs(&self, chain: &[Point], entities: Vec<Entity>, report: &mut ChainReport) -> Vec<Entity> {
+        let mut arcs = std::mem::take(&mut report.arcs).into_iter().peekable();
+        let mut output: Vec<Entity> = vec![];
+        // where the last entity of the output starts in the chain
+        let mut last_start = 0;
+        let mut position = 0;
+        for (i, entity) in entities.into_iter().enumerate() {
+            let arc = if arcs.peek().is_some_and(|arc| arc.entity == i) { arcs.next() } else { None };
+            let segments = arc.as_ref().map(|arc| arc.segments).unwrap_or(1);
+            let previous = report.arcs.last_mut().filter(|previous| previous.entity + 1 == output.len());
+            if let (Some(previous), Some(current)) = (previous, arc.as_ref()) {
+                let points = &chain[last_start..position + segments + 1];
+                let joined = if previous.clockwise == current.clockwise {
+                    self.join_arcs(&output[output.len() - 1], &entity, current.clockwise, points)
+                } else {
+                    None
+                };
+                if let Some((joined, residual)) = joined {
+                    if let Entity::Arc { center, radius, .. } = &joined {
+                        previous.center = center.clone();
+                        previous.radius = *radius;
+                    }
+                    previous.residual = residual.0;
+                    previous.residual_at = residual.1;
+                    previous.segments += current.segments;
+                    previous.three_point = joined.three_point();
+                    #[cfg(feature = "debug-geometry")]
+                    if let (Some(source), Some(more)) = (previous.source.as_mut(), current.source.as_ref()) {
+                        source.extend(more[1..].iter().cloned());
+                    }
+                    *output.last_mut().unwrap() = joined;
+                    position += segments;
+                    continue;
+                }
+            }
+            if let Some(mut arc) = arc {
+                arc.entity = output.len();
+                report.arcs.push(arc);
+            }
+            last_start = position;
+            output.push(entity);
+            position += segments;
+        }
+        output
+    }
+
+    // Replaces each run of lines and nearly flat arcs (bulging no more than the resolution) in a
+    // welded chain with fitted lines, refitting the chain points they replaced.
+    fn fit_lines(&self, chain: &[Point], entities: Vec<Entity>, report: &mut ChainReport) -> Vec<Entity> {
+        let mut arcs = std::mem::take(&mut report.arcs).into_iter().peekable();
+        let mut output = vec![];
+        let mut run_start: Option<usize> = None;
+        let mut position = 0;
+        for (i, entity) in entities.into_iter().enumerate() {
+            let arc = if arcs.peek().map(|arc| arc.entity == i).unwrap_or(false) { arcs.next() } else { None };
+            let straight = match &entity {
+                Entity::Line(..) => true,
+                Entity::Arc { radius, start_angle, end_angle, .. } => {
+                    let sweep = (end_angle - start_angle).rem_euclid(360.0).to_radians();
+                    radius * (1.0 - (sweep / 2.0).cos()) <= self.resolution
+                },
+                _ => false,
+            };
+            let segments = arc.as_ref().map(|arc| arc.segments).unwrap_or(1);
+            if straight {
+                run_start.get_or_insert(position);
+            } else {
+                if let Some(start) = run_start.take() {
+                    output.extend(self.fit_line_run(&chain[start..position + 1]));
+                }
+                if let Some(mut arc) = arc {
+                    arc.entity = output.len();
+                    report.arcs.push(arc);
+                }
+                output.push(entity);
+            }
+            position += segments;
+        }
+        if let Some(start) = run_start {
+            output.extend(self.fit_line_run(&chain[start..position + 1]));
+        }
+        output
+    }
+
+    // The corners of a chain made only of straight runs that meet at corners too sharp for an arc to
+    // round within the resolution, such as the outline of a rectangular part, or None as soon as any
+    // vertex might lie on a curve. An arc through a corner that turns by `theta` between segments of
+    // length `l` must stray about l / 2 * tan(theta / 4) from it.
+    fn straight_corners(&self, chain: &[Point]) -> Option<Vec<usize>> {
+        let mut corners = vec![0];
+        for i in 1..chain.len() - 1 {
+            let (prev, point, next) = (&chain[i - 1], &chain[i], &chain[i + 1]);
+            let (a, b) = (prev.dist(point), point.dist(next));
+            if a < EPSILON || b < EPSILON {
+                return None;
+            }
+            if segment_distance(point, prev, next) <= CIRCLE_ZERO_TOLERANCE {
+                continue;
+            }
+            let cos = ((point.x - prev.x) * (next.x - point.x) + (point.y - prev.y) * (next.y - point.y)) / (a * b);
+            let theta = cos.clamp(-1.0, 1.0).acos();
+            if a.min(b) / 2.0 * (theta / 4.0).tan() <= SHARP_CORNER_MARGIN * self.resolution {
+                return None;
+            }
+            corners.push(i);
+        }
+        corners.push(chain.len() - 1);
+        // a run can drift off its line a little at every vertex, so check each as a whole
+        for pair in corners.windows(2) {
+            let (start, end) = (&chain[pair[0]], &chain[pair[1]]);
+            if chain[pair[0] + 1..pair[1]].iter().any(|point| segment_distance(point, start, end) > CIRCLE_ZERO_TOLERANCE) {
+                return None;
+            }
+        }
+        Some(corners)
+    }
+
+    // Welds one chain of connected points into lines, arcs and circles.
+    pub fn process_chain(&self, chain: &[Point], report: &mut ChainReport) -> Result<Vec<Entity>> {
+        if let Some(chunk) = self.chunk_points.filter(|chunk| chain.len() > *chunk) {
+            return self.process_chain_chunked(chain, chunk, report);
+        }
+        match self.fitter {
+            Fitter::Greedy => self.process_chain_with(chain, &mut ArcModel::new(self), report),
+            Fitter::Ransac => self.process_chain_with(chain, &mut RansacArcModel::new(self, chain), report),
+        }
+    }
+
+    // Welds one chain like `process_chain`, replacing runs of points with curves from `model`
+    // instead of arcs. The configured fitter picks the runs.
+    pub fn process_chain_with<M: CurveModel>(&self, chain: &[Point], model: &mut M, report: &mut ChainReport) -> Result<Vec<Entity>> {
+        let mut entities = self.fit_chain(chain, model, report)?;
+        if self.merge_arcs {
+            entities = self.merge_arcs(chain, entities, report);
+        }
+        if !self.fit_lines {
+            return Ok(entities);
+        }
+        Ok(self.fit_lines(chain, entities, report))
+    }
+
+    // Welds a long chain as even windows of at most `chunk` points, each reaching into its neighbours,
+    // fitted side by side on up to one thread per core. Neighbouring windows are stitched at a point
+    // where both end an entity, the nearest to where they were split; when they share none, the later
+    // window is fitted again from where the earlier one ends an entity nearest the split. The whole
+    // chain is never fitted at once, so a closed chain is not welded into a single circle.
+    fn process_chain_chunked(&self, chain: &[Point], chunk: usize, report: &mut ChainReport) -> Result<Vec<Entity>> {
+        if chunk < MIN_CHUNK_POINTS {
+            return Err(weld_err!("chunk_points must be at least {}", MIN_CHUNK_POINTS));
+        }
+        let config = DxfConfig { chunk_points: None, ..self.clone() };
+        let overlap = chunk / CHUNK_OVERLAP_DIVISOR;
+        let last = chain.len() - 1;
+        // windows of at least half a chunk, so every window reaches past its neighbour's overlap
+        let windows = last.div_ceil(chunk);
+        let splits = (1..windows).map(|k| k * last / windows).collect::<Vec<_>>();
+        let ranges = (0..=splits.len()).map(|k| (
+            if k == 0 { 0 } else { splits[k - 1] - overlap },
+            if k == splits.len() { last } else { splits[k] + overlap },
+        )).collect::<Vec<_>>();
+        let fit = |from: usize, to: usize| -> Result<ChunkFit> {
+            let mut chunk_report = ChainReport::default();
+            let entities = config.process_chain(&chain[from..=to], &mut chunk_report)?;
+            Ok(ChunkFit { ends: entity_ends(chain, from, to, &entities), to, entities, arcs: chunk_report.arcs })
+        };
+        let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+        let mut fits = if threads > 1 {
+            let fit = &fit;
+            std::thread::scope(|scope| {
+                let workers = ranges.chunks(ranges.len().div_ceil(threads)).map(|ranges| {
+                    scope.spawn(move || ranges.iter().map(|(from, to)| fit(*from, *to)).collect::<Vec<_>>())
+                }).collect::<Vec<_>>();
+                workers.into_iter().flat_map(|worker| worker.join().unwrap()).collect::<Result<Vec<_>>>()
+            })?
+        } else {
+            ranges.iter().map(|(from, to)| fit(*from, *to)).collect::<Result<Vec<_>>>()?
+        };
+
+        let mut entities = vec![];
+        let mut arcs = vec![];
+        // where in the chain the stitched entities have reached
+        let mut reached = 0;
+        for k in 0..fits.len() {
+            let first = fits[k].ends.iter().find(|(at, _)| *at == reached).map(|(_, i)| *i).unwrap_or(0);
+            let (at, end) = if k + 1 == fits.len() {
+                (last, fits[k].entities.len())
+            } else {
+                let past = fits[k].ends.iter().filter(|(at, _)| *at > reached);
+                let shared = past.clone().filter(|(at, _)| fits[k + 1].ends.iter().any(|(next, _)| next == at)).min_by_key(|(at, _)| at.abs_diff(splits[k]));
+                match shared {
+                    Some(shared) => *shared,
+                    None => {
+                        let cut = *past.min_by_key(|(at, _)| at.abs_diff(splits[k])).unwrap();
+                        fits[k + 1] = fit(cut.0, fits[k + 1].to)?;
+                        cut
+                    },
+                }
+            };
+            let fitted = &mut fits[k];
+            arcs.extend(fitted.arcs.drain(..).filter(|arc| (first..end).contains(&arc.entity)).map(|arc| ArcReport {
+                entity: arc.entity - first + entities.len(),
+                ..arc
+            }));
+            entities.extend(fitted.entities.drain(first..end));
+            reached = at;
+        }
+        report.points = chain.len();
+        report.closed = chain.len() > 2 && chain.first() == chain.last();
+        report.arcs = arcs;
+        Ok(entities)
+    }
+
+    fn fit_chain<M: CurveModel>(&self, chain: &[Point], model: &mut M, report: &mut ChainReport) -> Result<Vec<Entity>> {
+        report.points = chain.len();
+        report.closed = chain.len() > 2 && chain.first() == chain.last();
+        if self.min_segments < 3 {
+            return Err(weld_err!("min_segments must be >= 3"));
+        }
+        if self.max_points_per_fit.is_some_and(|max| max <= self.min_segments) {
+            return Err(weld_err!("max_points_per_fit must be more than min_segments"));
+        }
+        if chain.len() < 2 {
+            return Err(weld_err!("cannot have 0 or 1 length segments"));
+        } else if chain.len() == 2 {
+            return Ok(vec![Entity::Line(chain[0].clone(), chain[1].clone())]);
+        }
+        // nothing to fit, so each straight run becomes a single line
+        if let Some(corners) = self.straight_corners(chain) {
+            return Ok(corners.windows(2).map(|pair| Entity::Line(chain[pair[0]].clone(), chain[pair[1]].clone())).collect());
+        }
+        if self.fitter == Fitter::Ransac {
+            return Ok(self.fit_chain_lookahead(chain, model, report));
+        }
+        let mut entities: Vec<Entity> = vec![];
+
+        // let k = 4;
+        // let ncoeffs = chain.len() + 2 - k;
+        // let mut bw = rgsl::BSpLineWorkspace::new(k, chain.len()).unwrap();
+        // let knots = rgsl::VectorF64::from_slice(&chain.iter().map(|x| x.x).collect::<Vec<f64>>()[..]).unwrap();
+        // let ys = rgsl::VectorF64::from_slice(&chain.iter().map(|x| x.y).collect::<Vec<f64>>()[..]).unwrap();
+        // let ws = rgsl::VectorF64::from_slice(&chain.iter().map(|_| 1.0).collect::<Vec<f64>>()[..]).unwrap();
+        // bw.knots(&knots);
+        // let mut X = rgsl::MatrixF64::new(chain.len(), ncoeffs).unwrap();
+        // for (i, Point {x, .. }) in chain.iter().enumerate() {
+        //     let mut output = rgsl::VectorF64::new(ncoeffs).unwrap();
+        //     bw.eval(*x, &mut output);
+        //     X.set_col(i, &output);
+        // }
+        // let mut coeffs = rgsl::VectorF64::new(ncoeffs).unwrap();
+        // let mut cov = rgsl::MatrixF64::new(ncoeffs, ncoeffs).unwrap();
+        // let mut mw = rgsl::MultifitLinearWorkspace::new(chain.len(), ncoeffs).unwrap();
+        // mw.wlinear(&X, &ws, &ys, &mut coeffs, &mut cov);
+        
+        // entities.push(Entity::Polyline {
+        //     curve_type: 0,
+        //     vertices: chain,
+        // });
+        
+        let mut current_arc_start = 0;
+        let mut current_arc_length: f64 = chain[0..self.min_segments.min(chain.len())].windows(2).map(|p| p[0].dist(&p[1])).sum();
+        // the curve fitted so far, with the index of its last point
+        let mut current_arc: Option<(M::Model, usize)> = None;
+        let mut i = self.min_segments - 1;
+        while i < chain.len() {
+            if current_arc_length < 0.0 {
+                return Err(weld_err!("current_arc_length is < 0.0 {} {} {}", i, current_arc_start, current_arc_length));
+            }
+            let last = &chain[i - 1];
             let point = &chain[i];
             // println!("i = {}, cas = {}, len = {}, last: {}, {}, point: {}, {}, in_arc = {}", i, current_arc_start, current_arc_length, last.x, last.y, point.x, point.y, current_arc.is_some());
             if last == point {
@@ -227,42 +1345,45 @@ impl DxfConfig {
             let dist = last.dist(point);
             //circlefy
             if &chain[current_arc_start] == point {
-                if let Some(arc) = current_arc.take() {
-                    entities.push(Entity::Circle {
-                        center: arc.center,
-                        radius: arc.radius,
-                    });
+                if let Some((fitted, end)) = current_arc.take() {
+                    if model.sweep(&fitted, &chain[current_arc_start..i + 1]).is_some_and(|sweep| (sweep.abs() - 360.0).abs() > CIRCLE_SWEEP_TOLERANCE) {
+                        // back at the start without going round once, so the curve ends here as an arc
+                        let run = &chain[current_arc_start..end + 1];
+                        self.emit_fit(model, fitted, run, run, &mut entities, report);
+                        current_arc_start = i - 1;
+                        current_arc_length = chain[current_arc_start..(current_arc_start + self.min_segments - 1).min(chain.len())].windows(2).map(|p| p[0].dist(&p[1])).sum();
+                        i = current_arc_start + self.min_segments - 1;
+                        continue;
+                    }
+                    self.emit_fit(model, fitted, &chain[current_arc_start..end + 1], &chain[current_arc_start..i + 1], &mut entities, report);
                     current_arc_start = i + 1;
                     current_arc_length = chain[current_arc_start..(current_arc_start + self.min_segments - 1).min(chain.len())].windows(2).map(|p| p[0].dist(&p[1])).sum();
                     i = current_arc_start + self.min_segments - 1;
                     continue;
                 }
             }
-            if let Some(circle) = self.make_circle(&chain[current_arc_start], &chain[current_arc_start + (i - current_arc_start - 2) / 2 + 1], point) {
-                if let Some(arc) = self.check_chain_circle(&chain[current_arc_start..i + 1], &circle, current_arc_length + dist) {
-                    let cdist = circle.get_radial_dist(last, point) * circle.radius;
-                    if (cdist - dist).abs() < self.resolution {
-                        current_arc_length += dist;
-                        current_arc = Some(arc);
-                        i += 1;
-                        continue;
-                    }
-                }
+            // the curve ends once the window reaches max_points_per_fit
+            let fitted = if self.max_points_per_fit.is_none_or(|max| i - current_arc_start < max) {
+                model.fit(&chain[current_arc_start..i + 1])
+            } else {
+                None
+            };
+            if let Some(fitted) = fitted {
+                current_arc_length += dist;
+                current_arc = Some((fitted, i));
+                i += 1;
+                continue;
             }
 
-            if let Some(arc) = current_arc.take() {
-                entities.push(Entity::Arc {
-                    center: arc.center,
-                    radius: arc.radius,
-                    start_angle: arc.start_angle,
-                    end_angle: arc.end_angle,
-                });
+            if let Some((fitted, end)) = current_arc.take() {
+                let run = &chain[current_arc_start..end + 1];
+                self.emit_fit(model, fitted, run, run, &mut entities, report);
                 current_arc_start = i - 1;
                 current_arc_length = chain[current_arc_start..(current_arc_start + self.min_segments - 1).min(chain.len())].windows(2).map(|p| p[0].dist(&p[1])).sum();
                 i = current_arc_start + self.min_segments - 1;
                 continue;
             } else {
-                let restart_pt = i - current_arc_start - 1 >= self.min_segments;
+                let restart_pt = i - current_arc_start > self.min_segments;
                 if !restart_pt {
                     current_arc_length += dist;
                 }
@@ -275,7 +1396,7 @@ impl DxfConfig {
                     } else {
                         // println!("p1 {}, {}", chain[current_arc_start].x, chain[current_arc_start].y);
                         // println!("p2 {}, {}", chain[current_arc_start + 1].x, chain[current_arc_start + 1].y);
-                        panic!("length of removed segment is longer than current arc length: {} {}", len, current_arc_length);
+                        return Err(weld_err!("length of removed segment is longer than current arc length: {} {}", len, current_arc_length));
                     }
                 }
                 current_arc_length -= len;
@@ -287,13 +1408,9 @@ impl DxfConfig {
 
             i += 1;
         }
-        if let Some(arc) = current_arc.take() {
-            entities.push(Entity::Arc {
-                center: arc.center,
-                radius: arc.radius,
-                start_angle: arc.start_angle,
-                end_angle: arc.end_angle,
-            });
+        if let Some((fitted, end)) = current_arc.take() {
+            let run = &chain[current_arc_start..end + 1];
+            self.emit_fit(model, fitted, run, run, &mut entities, report);
         } else {
             for points in chain[current_arc_start..chain.len()].windows(2) {
                 entities.push(Entity::Line(points[0].clone(), points[1].clone()));
@@ -303,45 +1420,1427 @@ impl DxfConfig {
         Ok(entities)
     }
 
-    pub fn process_drawing(&self, drawing: Drawing) -> Result<Drawing> {
-        let mut src_dest: BTreeMap<Point, Point> = BTreeMap::new();
+    // Circle hypotheses all pass through the window's end points (or two random interior points if
+    // the window is closed) so neighbouring entities still meet. The hypothesis with the fewest
+    // outliers wins, and is accepted if the outliers are few and isolated and the rest of the
+    // window sweeps around it in one direction.
+    fn ransac_window(&self, window: &[Point], rng: &mut Rng) -> Option<Arc> {
+        let last = window.len() - 1;
+        let closed = window[0] == window[last];
+        let interior = last - 1;
+        let mut best: Option<(usize, Circle)> = None;
+        for k in 0..RANSAC_HYPOTHESES {
+            let circle = if closed {
+                self.make_circle(&window[0], &window[1 + rng.below(interior)], &window[1 + rng.below(interior)])
+            } else if interior <= RANSAC_HYPOTHESES {
+                if k >= interior {
+                    break;
+                }
+                self.make_circle(&window[0], &window[1 + k], &window[last])
+            } else {
+                self.make_circle(&window[0], &window[1 + rng.below(interior)], &window[last])
+            };
+            let circle = match circle {
+                Some(circle) => circle,
+                None => continue,
+            };
+            let outliers = window.iter().filter(|p| (circle.radius - circle.center.dist(p)).abs() > self.resolution).count();
+            if best.as_ref().map(|(fewest, _)| outliers < *fewest).unwrap_or(true) {
+                best = Some((outliers, circle));
+            }
+        }
+        let (outliers, circle) = best?;
+        if outliers as f64 > window.len() as f64 * RANSAC_OUTLIER_RATIO {
+            return None;
+        }
+        let inlier = window.iter().map(|p| (circle.radius - circle.center.dist(p)).abs() <= self.resolution).collect::<Vec<_>>();
+        if !inlier[0] || !inlier[last] || inlier.windows(2).any(|pair| !pair[0] && !pair[1]) {
+            return None;
+        }
+
+        // the residual is over every point the arc replaces, outliers included, while only segments
+        // between inliers have to stay within the resolution
+        let mut residual = (0f64, window[0].clone());
+        for (i, point) in window.iter().enumerate() {
+            let diff = (circle.radius - circle.center.dist(point)).abs();
+            if diff > residual.0 {
+                residual = (diff, point.clone());
+            }
+            if i < last {
+                if let Some(closest_point) = self.get_closest_perpendicular_point(point, &window[i + 1], &circle.center) {
+                    let diff = (circle.radius - circle.center.dist(&closest_point)).abs();
+                    if diff > self.resolution && inlier[i] && inlier[i + 1] {
+                        return None;
+                    }
+                    if diff > residual.0 {
+                        residual = (diff, closest_point);
+                    }
+                }
+            }
+        }
 
-        let mut new_entities = vec![];
-        for entity in drawing.entities.into_iter() {
-            match entity {
-                Entity::Line(from, to) => {
-                    src_dest.insert(from, to);
+        let inliers = window.iter().zip(inlier.iter()).filter(|(_, inlier)| **inlier).map(|(p, _)| p.clone()).collect::<Vec<_>>();
+        if !self.check_segment_angles(&inliers, &circle) {
+            return None;
+        }
+        let angles = inliers.iter().map(|p| circle.get_polar_radians(p)).collect::<Vec<_>>();
+        let deltas = angles.windows(2).map(|pair| (pair[1] - pair[0] + PI).rem_euclid(2.0 * PI) - PI).collect::<Vec<_>>();
+        let sweep = deltas.iter().sum::<f64>();
+        // an open window may sweep up to a half circle, with a sweep of π give or take float noise
+        // counting as one whichever side it lands on
+        let open_limit = if closed { PI } else { PI + ANGLE_TIE_TOLERANCE };
+        if deltas.iter().any(|delta| delta * sweep < 0.0) || sweep.abs() >= 2.0 * PI || closed != (sweep.abs() > open_limit) {
+            return None;
+        }
+        if !self.check_turns(&inliers, sweep < 0.0) {
+            return None;
+        }
+        let (mut start_theta, mut end_theta) = (angles[0], angles[angles.len() - 1]);
+        if sweep < 0.0 {
+            std::mem::swap(&mut start_theta, &mut end_theta);
+        }
+        Some(Arc {
+            center: circle.center.clone(),
+            radius: circle.radius,
+            start_angle: normalize_degrees(start_theta.to_degrees()),
+            end_angle: normalize_degrees(end_theta.to_degrees()),
+            residual: residual.0,
+            residual_at: residual.1,
+            clockwise: sweep < 0.0,
+        })
+    }
+
+    // Welds a chain by growing each window as far as the model can fit it, looking a few points past
+    // a failed fit, and falling back to a line when no window starting at a point fits.
+    fn fit_chain_lookahead<M: CurveModel>(&self, chain: &[Point], model: &mut M, report: &mut ChainReport) -> Vec<Entity> {
+        let mut entities = vec![];
+        let mut start = 0;
+        while start + 1 < chain.len() {
+            let mut best = None;
+            let mut misses = 0;
+            let mut end = start + self.min_segments;
+            while end < chain.len() && misses <= RANSAC_LOOKAHEAD && self.max_points_per_fit.is_none_or(|max| end - start < max) {
+                match model.fit(&chain[start..end + 1]) {
+                    Some(fitted) => {
+                        best = Some((end, fitted));
+                        misses = 0;
+                    },
+                    None => misses += 1,
+                }
+                end += 1;
+            }
+            match best {
+                Some((end, fitted)) => {
+                    let run = &chain[start..end + 1];
+                    self.emit_fit(model, fitted, run, run, &mut entities, report);
+                    start = end;
+                },
+                None => {
+                    entities.push(Entity::Line(chain[start].clone(), chain[start + 1].clone()));
+                    start += 1;
                 },
-                x => return Err(weld_err!("cannot process dxf with non-line: {:?}", &x)),
             }
         }
-        
-        let mut chains: Vec<Vec<Point>> = vec![];
-        while src_dest.len() > 0 {
-            let mut chain = vec![];
-            let (first_from, mut next) = src_dest.iter().next().map(|(p1, p2)| (p1.clone(), p2.clone())).unwrap();
-            src_dest.remove(&first_from).unwrap();
-
-            chain.push(first_from.clone());
-            while let Some(point) = src_dest.remove(&next) {
-                chain.push(next);
-                next = point;
-            }
-            chain.push(next);
-            // println!("chain len: {}", chain.len());
-            // for p in chain.iter() {
-            //     println!("p {}, {}", p.x, p.y);
-            // }
-            chains.push(chain);
+        entities
+    }
+
+    // Counts the zero-length lines of the layers about to be welded and applies `zero_length` to them.
+    // Kept ones are passed through by `weld_layer`.
+    pub(crate) fn check_zero_length(&self, drawing: &mut Drawing, report: &mut WeldReport) -> Result<()> {
+        let blocks = drawing.blocks.iter_mut().filter(|_| self.weld_blocks).flat_map(|block| block.layers.iter_mut());
+        for layer in drawing.layers.iter_mut().chain(blocks) {
+            let count = layer.entities.iter().filter(|entity| is_zero_length(entity)).count();
+            if count == 0 {
+                continue;
+            }
+            report.zero_length_lines += count;
+            match self.zero_length {
+                ZeroLengthPolicy::Drop => layer.entities.retain(|entity| !is_zero_length(entity)),
+                ZeroLengthPolicy::Error => {
+                    let at = layer.entities.iter().find(|entity| is_zero_length(entity)).and_then(Entity::start_point).unwrap();
+                    return Err(weld_err!("{} zero-length lines on layer {}, the first at ({}, {})", count, layer.name, at.x, at.y));
+                },
+                ZeroLengthPolicy::Keep => (),
+            }
         }
-        
-        for chain in chains.into_iter() {
-            let output = self.process_chain(chain)?;
+        Ok(())
+    }
+
+    pub fn process_drawing(&self, mut drawing: Drawing, report: &mut WeldReport) -> Result<Drawing> {
+        self.before_weld.run(&mut drawing, report)?;
+        self.check_zero_length(&mut drawing, report)?;
+        self.limits.check_entities(drawing.entity_count())?;
+        report.input_entities += drawing.entity_count();
+        report.input_length += drawing.length();
+        let mut layers = vec![];
+        let mut offset = self.process_blocks(&mut drawing.blocks, report)?;
+        for layer in drawing.layers.into_iter() {
+            let layer = self.process_layer(layer, offset, report)?;
+            offset += layer.entities.len();
+            layers.push(layer);
+        }
+        report.output_entities += offset;
+        let mut welded = Drawing { layers, ..drawing };
+        report.output_length += welded.length();
+        self.after_weld.run(&mut welded, report)?;
+        Ok(welded)
+    }
+
+    // Welds the entities of each block in place when `weld_blocks` is set, once however many times the
+    // block is inserted. Blocks are written ahead of the drawing's layers, so their entities are
+    // numbered first in the report. Returns how many entities the blocks hold once welded.
+    pub(crate) fn process_blocks(&self, blocks: &mut [Block], report: &mut WeldReport) -> Result<usize> {
+        if !self.weld_blocks {
+            return Ok(0);
+        }
+        let mut offset = 0;
+        for block in blocks.iter_mut() {
+            report.input_entities += block.entity_count();
+            for layer in block.layers.iter_mut() {
+                *layer = self.process_layer(std::mem::replace(layer, Layer::new(DEFAULT_LAYER, None)), offset, report)?;
+                offset += layer.entities.len();
+            }
+        }
+        Ok(offset)
+    }
+
+    // `offset` is the index in the output drawing of this layer's first entity
+    fn process_layer(&self, layer: Layer, offset: usize, report: &mut WeldReport) -> Result<Layer> {
+        Ok(Layer {
+            entities: self.welded_entities(&layer, None, offset, report)?,
+            ..layer
+        })
+    }
+
+    fn welded_entities(&self, layer: &Layer, layer_chains: Option<&Chains>, offset: usize, report: &mut WeldReport) -> Result<Vec<Entity>> {
+        let mut new_entities = vec![];
+        let WeldReport { chains, timings, .. } = report;
+        self.weld_layer(layer, offset, 0, layer_chains, timings, &mut |output, chain_report| {
             new_entities.extend(output);
+            chains.extend(chain_report);
+            Ok(())
+        })?;
+        Ok(new_entities)
+    }
+
+    // Welds the drawing like `process_drawing` once per resolution, each report starting from
+    // `report` once the `before_weld` passes have run. Chains are only built once for the layers
+    // without input arcs, whose chains do not depend on the resolution.
+    pub fn process_resolutions(&self, mut drawing: Drawing, resolutions: &[f64], report: &mut WeldReport) -> Result<Vec<(Drawing, WeldReport)>> {
+        self.before_weld.run(&mut drawing, report)?;
+        self.check_zero_length(&mut drawing, report)?;
+        self.limits.check_entities(drawing.entity_count())?;
+        report.input_entities += drawing.entity_count();
+        report.input_length += drawing.length();
+        let started = report.timings.start();
+        let mut chains = vec![];
+        for layer in drawing.layers.iter() {
+            let has_arcs = layer.entities.iter().any(|entity| matches!(entity, Entity::Arc { .. }));
+            chains.push(if has_arcs { None } else { Some(layer_chains(&layer.entities)?) });
+        }
+        report.timings.add("chain", started);
+        resolutions.iter().map(|resolution| {
+            let config = DxfConfig { resolution: *resolution, ..self.clone() };
+            let mut report = report.clone();
+            let mut blocks = drawing.blocks.clone();
+            let mut offset = config.process_blocks(&mut blocks, &mut report)?;
+            let mut layers = vec![];
+            for (layer, chains) in drawing.layers.iter().zip(chains.iter()) {
+                let entities = config.welded_entities(layer, chains.as_ref(), offset, &mut report)?;
+                offset += entities.len();
+                layers.push(Layer { entities, inserts: layer.inserts.clone(), raw: layer.raw.clone(), ..layer.empty_like() });
+            }
+            report.output_entities += offset;
+            let mut welded = Drawing { layers, blocks, elevation: drawing.elevation, ucs_origin: drawing.ucs_origin.clone(), limits_min: drawing.limits_min.clone() };
+            report.output_length += welded.length();
+            config.after_weld.run(&mut welded, &mut report)?;
+            Ok((welded, report))
+        }).collect()
+    }
+
+    // Grows each input ARC into the lines continuing its curve: a line touching either end of the arc
+    // is absorbed when its far end and midpoint both lie within the resolution of the arc's circle, so
+    // an arc with a tessellated tail comes out as one longer arc. Returns the arcs, with a report for
+    // each one that grew, and the entities left to weld.
+    fn extend_arcs(&self, entities: &[Entity], layer: &str) -> (Vec<(Entity, Option<ChainReport>)>, Vec<Entity>) {
+        let mut ends: BTreeMap<(i64, i64), Vec<usize>> = BTreeMap::new();
+        for (i, entity) in entities.iter().enumerate() {
+            if let Entity::Line(from, to) = entity {
+                ends.entry(snap_key(from)).or_default().push(i);
+                ends.entry(snap_key(to)).or_default().push(i);
+            }
         }
-        Ok(Drawing {
-            entities: new_entities,
+        let mut used = vec![false; entities.len()];
+        let mut arcs = vec![];
+        for (i, entity) in entities.iter().enumerate() {
+            let (center, radius, mut start_angle, mut end_angle) = match entity {
+                Entity::Arc { center, radius, start_angle, end_angle } => (center, *radius, *start_angle, *end_angle),
+                _ => continue,
+            };
+            used[i] = true;
+            let mut residual = (0f64, center.polar(radius, start_angle));
+            let mut segments = 0;
+            for forward in [true, false] {
+                loop {
+                    let tip = center.polar(radius, if forward { end_angle } else { start_angle });
+                    let sweep = (end_angle - start_angle).rem_euclid(360.0);
+                    let step = ends.get(&snap_key(&tip)).into_iter().flatten().filter(|j| !used[**j]).find_map(|j| {
+                        let (from, to) = match &entities[*j] {
+                            Entity::Line(from, to) => (from, to),
+                            _ => return None,
+                        };
+                        let far = if *from == tip { to } else if *to == tip { from } else { return None };
+                        let mid = Point { x: (from.x + to.x) / 2.0, y: (from.y + to.y) / 2.0 };
+                        let deviation = (center.dist(far) - radius).abs().max((center.dist(&mid) - radius).abs());
+                        let angle = far.angle(center).to_degrees();
+                        let delta = if forward { angle - end_angle } else { start_angle - angle }.rem_euclid(360.0);
+                        if deviation > self.resolution || delta <= 0.0 || delta > ARC_EXTENSION_STEP || sweep + delta >= 360.0 {
+                            return None;
+                        }
+                        let at = if (center.dist(far) - radius).abs() >= (center.dist(&mid) - radius).abs() { far.clone() } else { mid };
+                        Some((*j, angle, deviation, at))
+                    });
+                    let (j, angle, deviation, at) = match step {
+                        Some(step) => step,
+                        None => break,
+                    };
+                    used[j] = true;
+                    segments += 1;
+                    if deviation > residual.0 {
+                        residual = (deviation, at);
+                    }
+                    if forward {
+                        end_angle = normalize_degrees(angle);
+                    } else {
+                        start_angle = normalize_degrees(angle);
+                    }
+                }
+            }
+            let arc = Entity::Arc { center: center.clone(), radius, start_angle, end_angle };
+            let report = if segments > 0 {
+                Some(ChainReport {
+                    layer: layer.to_string(),
+                    points: segments + 2,
+                    closed: false,
+                    entities: 1,
+                    arcs: vec![ArcReport {
+                        entity: 0,
+                        center: center.clone(),
+                        radius,
+                        residual: residual.0,
+                        residual_at: residual.1,
+                        segments,
+                        circle: false,
+                        clockwise: false,
+                        three_point: arc.three_point(),
+                        #[cfg(feature = "debug-geometry")]
+                        source: None,
+                    }],
+                    orphan: None,
+                })
+            } else {
+                None
+            };
+            arcs.push((arc, report));
+        }
+        let rest = entities.iter().zip(used.iter()).filter(|(_, used)| !**used).map(|(entity, _)| entity.clone()).collect();
+        (arcs, rest)
+    }
+
+    // The chains of connected lines welding the drawing would fit, layer by layer, for analysing the
+    // contours without welding them. Input arcs, and the lines they would be extended into, are not
+    // part of any chain.
+    pub fn extract_chains(&self, drawing: &Drawing) -> Result<Vec<Chain>> {
+        let mut extracted = vec![];
+        for layer in drawing.layers.iter() {
+            let chains = if layer.entities.iter().any(|entity| matches!(entity, Entity::Arc { .. })) {
+                layer_chains(&self.extend_arcs(&layer.entities, &layer.name).1)?
+            } else {
+                layer_chains(&layer.entities)?
+            };
+            extracted.extend(chains.iter().map(|points| Chain { layer: layer.name.clone(), points: points.to_vec() }));
+        }
+        Ok(extracted)
+    }
+
+    // Per-chain settings welding only the chains of `drawing` at these indices in `extract_chains`,
+    // passing the others through unwelded. Chains picked keep the settings of any `chain_params`
+    // already set.
+    pub fn only_chains(&self, drawing: &Drawing, ids: &[usize]) -> Result<ChainParamsFn> {
+        let chains = self.extract_chains(drawing)?;
+        let mut picked = vec![];
+        for id in ids.iter() {
+            picked.push(chains.get(*id).cloned().ok_or_else(|| weld_err!("no chain {}, the drawing has {}", id, chains.len()))?);
+        }
+        let inner = self.chain_params.clone();
+        Ok(ChainParamsFn::new(move |chain| {
+            let is_picked = picked.iter().any(|other| other.layer == chain.layer && other.points == chain.points);
+            match &inner {
+                Some(inner) if is_picked => (inner.0)(chain),
+                _ => ChainParams { pass_through: !is_picked, ..Default::default() },
+            }
+        }))
+    }
+
+    // Per-chain settings applying `policy` to the chains of `drawing` that `text::text_chains` takes
+    // for exploded text, since welding distorts small glyphs visibly. Other chains keep the settings
+    // of any `chain_params` already set.
+    pub fn text_outlines(&self, drawing: &Drawing, policy: TextPolicy) -> Result<ChainParamsFn> {
+        let chains = self.extract_chains(drawing)?;
+        let text = crate::text::text_chains(&chains);
+        let glyphs = chains.into_iter().zip(text).filter(|(_, text)| *text).map(|(chain, _)| chain).collect::<Vec<_>>();
+        let inner = self.chain_params.clone();
+        Ok(ChainParamsFn::new(move |chain| {
+            let params = inner.as_ref().map(|inner| (inner.0)(chain)).unwrap_or_default();
+            if !glyphs.iter().any(|glyph| glyph.layer == chain.layer && glyph.points == chain.points) {
+                return params;
+            }
+            match policy {
+                TextPolicy::PassThrough => ChainParams { pass_through: true, ..params },
+                TextPolicy::Resolution(resolution) => ChainParams { resolution: Some(resolution), ..params },
+            }
+        }))
+    }
+
+    // Per-chain settings picking min_segments from the median length of each chain's segments, so
+    // the shortest arc spans about the same length however densely the curve was tessellated: a run
+    // of tiny segments fits a circle within the resolution whatever its shape, so dense chains need
+    // more of them to count as an arc, while sparse chains need fewer, down to 3. Chains are otherwise
+    // welded with the settings of any `chain_params` already set, whose own min_segments wins.
+    pub fn adaptive_min_segments(&self) -> ChainParamsFn {
+        let inner = self.chain_params.clone();
+        let (resolution, min_segments, max_points_per_fit) = (self.resolution, self.min_segments, self.max_points_per_fit);
+        ChainParamsFn::new(move |chain| {
+            let params = inner.as_ref().map(|inner| (inner.0)(chain)).unwrap_or_default();
+            if params.pass_through || params.min_segments.is_some() {
+                return params;
+            }
+            let mut lengths = chain.points.windows(2).map(|pair| pair[0].dist(&pair[1])).collect::<Vec<_>>();
+            if lengths.is_empty() {
+                return params;
+            }
+            let middle = lengths.len() / 2;
+            let median = *lengths.select_nth_unstable_by(middle, |a, b| a.total_cmp(b)).1;
+            let resolution = params.resolution.unwrap_or(resolution);
+            let wanted = (min_segments as f64 * ADAPTIVE_REFERENCE_SEGMENT * resolution / median).round();
+            let mut adapted = if wanted.is_finite() { wanted as usize } else { usize::MAX };
+            adapted = adapted.clamp(3, (min_segments * ADAPTIVE_MAX_FACTOR).max(3));
+            if let Some(max_points) = max_points_per_fit {
+                adapted = adapted.min(max_points - 1).max(3);
+            }
+            ChainParams { min_segments: Some(adapted), ..params }
         })
     }
+
+    // a copy with the chain's settings in place of the config's own
+    fn with_params(&self, params: &ChainParams) -> DxfConfig {
+        DxfConfig {
+            resolution: params.resolution.unwrap_or(self.resolution),
+            max_radius: params.max_radius.unwrap_or(self.max_radius),
+            min_segments: params.min_segments.unwrap_or(self.min_segments),
+            chain_params: None,
+            ..self.clone()
+        }
+    }
+
+    // Welds a layer chain by chain, handing each chain's entities and report to `emit`. Input arcs are
+    // extended into neighbouring lines on their circle and emitted first. The first `skip` arcs and
+    // chains are passed over without welding, for resuming a layer that was partly welded. `chains`, if
+    // given, are the layer's chains built beforehand, which only a layer without arcs can reuse.
+    // Returns the number of entities emitted.
+    pub(crate) fn weld_layer(&self, layer: &Layer, offset: usize, skip: usize, chains: Option<&Chains>, timings: &mut Timings, emit: &mut dyn FnMut(Vec<Entity>, Option<ChainReport>) -> Result<()>) -> Result<usize> {
+        let started = timings.start();
+        let mut count = 0;
+        let rest;
+        let mut lines = &layer.entities[..];
+        let mut pieces = 0;
+        if lines.iter().any(|entity| matches!(entity, Entity::Arc { .. })) {
+            let (arcs, unused) = self.extend_arcs(lines, &layer.name);
+            for (arc, mut chain_report) in arcs {
+                pieces += 1;
+                if pieces <= skip {
+                    continue;
+                }
+                if let Some(chain_report) = chain_report.as_mut() {
+                    chain_report.arcs[0].entity = offset + count;
+                }
+                count += 1;
+                emit(vec![arc], chain_report)?;
+            }
+            rest = unused;
+            lines = &rest;
+        }
+        let built;
+        let chains = match chains {
+            Some(chains) => chains,
+            None => {
+                built = layer_chains(lines)?;
+                &built
+            },
+        };
+        timings.add("chain", started);
+        let started = timings.start();
+        for chain in chains.iter() {
+            pieces += 1;
+            if pieces <= skip {
+                continue;
+            }
+            self.limits.check_chain_points(chain.len())?;
+            let mut chain_report = ChainReport {
+                layer: layer.name.clone(),
+                ..Default::default()
+            };
+            let output = match &self.chain_params {
+                Some(chain_params) => {
+                    let params = (chain_params.0)(&Chain { layer: layer.name.clone(), points: chain.to_vec() });
+                    if params.pass_through {
+                        chain_report.points = chain.len();
+                        chain_report.closed = chain.len() > 2 && chain.first() == chain.last();
+                        chain.windows(2).map(|pair| Entity::Line(pair[0].clone(), pair[1].clone())).collect()
+                    } else {
+                        self.with_params(&params).process_chain(chain, &mut chain_report)?
+                    }
+                },
+                None => self.process_chain(chain, &mut chain_report)?,
+            };
+            for arc in chain_report.arcs.iter_mut() {
+                arc.entity += offset + count;
+            }
+            chain_report.entities = output.len();
+            count += output.len();
+            emit(output, Some(chain_report))?;
+        }
+        // zero-length lines left by `ZeroLengthPolicy::Keep` are passed through, like leftovers
+        for line in lines.iter().filter(|entity| is_zero_length(entity)) {
+            pieces += 1;
+            if pieces <= skip {
+                continue;
+            }
+            let chain_report = ChainReport {
+                layer: layer.name.clone(),
+                points: 2,
+                entities: 1,
+                ..Default::default()
+            };
+            count += 1;
+            emit(vec![line.clone()], Some(chain_report))?;
+        }
+        // as is everything that is not a line, such as polylines drawn with a width, which zero-width
+        // lines and arcs would change
+        for entity in lines.iter().filter(|entity| is_passed_through(entity)) {
+            pieces += 1;
+            if pieces <= skip {
+                continue;
+            }
+            let chain_report = ChainReport {
+                layer: layer.name.clone(),
+                points: match entity {
+                    Entity::Polyline { vertices, .. } => vertices.len(),
+                    _ => 2,
+                },
+                closed: match entity {
+                    Entity::Polyline { vertices, .. } => vertices.len() > 2 && vertices.first() == vertices.last(),
+                    _ => entity.start_point() == entity.end_point(),
+                },
+                entities: 1,
+                ..Default::default()
+            };
+            count += 1;
+            emit(vec![entity.clone()], Some(chain_report))?;
+        }
+        // leftovers are passed through as they are
+        for (from, to) in chains.orphans().iter() {
+            pieces += 1;
+            if pieces <= skip {
+                continue;
+            }
+            let chain_report = ChainReport {
+                layer: layer.name.clone(),
+                points: 2,
+                entities: 1,
+                orphan: Some((from.clone(), to.clone())),
+                ..Default::default()
+            };
+            count += 1;
+            emit(vec![Entity::Line(from.clone(), to.clone())], Some(chain_report))?;
+        }
+        timings.add("fit", started);
+        Ok(count)
+    }
+
+    // Welds like `process_drawing`, but writes each chain through the backend as soon as it is welded
+    // and moves the backend's buffered text to `sink`, so the welded drawing is never held in memory.
+    // Each input layer is dropped once it is welded.
+    pub fn stream_drawing(&self, mut drawing: Drawing, backend: &mut dyn OutputBackend, sink: &mut dyn Write, report: &mut WeldReport) -> Result<()> {
+        if !self.after_weld.is_empty() {
+            return Err(weld_err!("after_weld passes need the whole welded drawing, which is never held when streaming"));
+        }
+        self.before_weld.run(&mut drawing, report)?;
+        self.check_zero_length(&mut drawing, report)?;
+        let mut drain = |backend: &mut dyn OutputBackend| -> Result<()> {
+            if let Some(buffer) = backend.buffer() {
+                sink.write_all(buffer.as_bytes())?;
+                buffer.clear();
+            }
+            Ok(())
+        };
+        self.limits.check_entities(drawing.entity_count())?;
+        report.input_entities += drawing.entity_count();
+        report.input_length += drawing.length();
+        backend.begin();
+        let mut offset = self.process_blocks(&mut drawing.blocks, report)?;
+        for block in drawing.blocks.iter() {
+            report.output_length += block.length();
+            backend.block(block);
+        }
+        drain(backend)?;
+        for layer in drawing.layers.into_iter() {
+            backend.layer(&layer.empty_like());
+            let WeldReport { chains, timings, output_length, .. } = &mut *report;
+            offset += self.weld_layer(&layer, offset, 0, None, timings, &mut |output, chain_report| {
+                for entity in output.iter() {
+                    *output_length += entity.length();
+                    backend.entity(entity);
+                }
+                chains.extend(chain_report);
+                drain(backend)
+            })?;
+            for insert in layer.inserts.iter() {
+                backend.insert(insert);
+            }
+            for raw in layer.raw.iter() {
+                backend.raw(raw);
+            }
+        }
+        backend.finish();
+        drain(backend)?;
+        sink.flush()?;
+        report.output_entities += offset;
+        Ok(())
+    }
+}
+
+// per input entity: the entity, its chain points, the welded output and its serialized text
+const BYTES_PER_ENTITY: usize = 512;
+
+// a rough estimate of the bytes welding the drawing in memory takes at its peak
+pub fn estimated_memory(drawing: &Drawing) -> usize {
+    drawing.entity_count() * BYTES_PER_ENTITY
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a quarter arc of radius 10 with one point knocked off it
+    fn noisy_arc() -> Vec<Point> {
+        let center = Point { x: 0.0, y: 0.0 };
+        let mut chain = (0..=32).map(|i| center.polar(10.0, i as f64 * 90.0 / 32.0)).collect::<Vec<_>>();
+        chain[16] = center.polar(10.5, 45.0);
+        chain
+    }
+
+    #[test]
+    fn test_layer_chains() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let chains = layer_chains(&[
+            Entity::Line(p(5.0, 0.0), p(6.0, 0.0)),
+            Entity::Line(p(0.0, 0.0), p(1.0, 0.0)),
+            Entity::Line(p(1.0, 0.0), p(1.0, 1.0)),
+        ]).unwrap();
+        assert_eq!(chains.len(), 2);
+        assert_eq!(chains.get(0), &[p(0.0, 0.0), p(1.0, 0.0), p(1.0, 1.0)]);
+        assert_eq!(chains.get(1), &[p(5.0, 0.0), p(6.0, 0.0)]);
+        assert_eq!(chains.points().len(), 5);
+    }
+
+    #[test]
+    fn test_only_chains() {
+        let center = Point { x: 0.0, y: 0.0 };
+        let mut entities = vec![];
+        for i in 0..3 {
+            let arc = (0..=8).map(|step| Point { x: i as f64 * 30.0, y: 0.0 }.polar(10.0, 80.0 - step as f64 * 10.0)).collect::<Vec<_>>();
+            entities.extend(arc.windows(2).map(|pair| Entity::Line(pair[0].clone(), pair[1].clone())));
+        }
+        let drawing = Drawing::new(entities);
+        let mut config = DxfConfig::default();
+        assert!(config.only_chains(&drawing, &[3]).is_err());
+        config.chain_params = Some(config.only_chains(&drawing, &[1]).unwrap());
+        let mut report = WeldReport::default();
+        let welded = config.process_drawing(drawing, &mut report).unwrap();
+        assert_eq!(welded.entity_count(), 17);
+        assert_eq!(report.arcs().count(), 1);
+        assert_eq!(report.arcs().next().unwrap().center, Point { x: 30.0, ..center });
+        // the welded arc is a little longer than the chords it replaces
+        assert!(report.length_change() > 0.0 && report.length_change() < 0.02, "{}", report.length_change());
+        let report = WeldReport { feed_rate: Some(600.0), ..report };
+        assert_eq!(report.estimated_cut_seconds(), Some(report.output_length / 10.0));
+    }
+
+    #[test]
+    fn test_adaptive_min_segments() {
+        let steps = |step: f64| Chain {
+            layer: "0".to_string(),
+            points: (0..=40).map(|i| Point { x: i as f64 * step, y: 0.0 }).collect(),
+        };
+        let config = DxfConfig { resolution: 0.1, ..Default::default() };
+        let adaptive = config.adaptive_min_segments();
+        // the config's 3 at segments of 5 resolutions, scaled inversely to the segment length
+        assert_eq!((adaptive.0)(&steps(0.5)).min_segments, Some(3));
+        assert_eq!((adaptive.0)(&steps(0.1)).min_segments, Some(15));
+        assert_eq!((adaptive.0)(&steps(0.001)).min_segments, Some(24));
+        assert_eq!((adaptive.0)(&steps(10.0)).min_segments, Some(3));
+
+        let capped = DxfConfig { max_points_per_fit: Some(10), ..config.clone() }.adaptive_min_segments();
+        assert_eq!((capped.0)(&steps(0.1)).min_segments, Some(9));
+        let inner = DxfConfig { chain_params: Some(ChainParamsFn::new(|_| ChainParams { resolution: Some(0.02), ..Default::default() })), ..config };
+        let params = (inner.adaptive_min_segments().0)(&steps(0.1));
+        assert_eq!((params.resolution, params.min_segments), (Some(0.02), Some(3)));
+    }
+
+    #[test]
+    fn test_orphans() {
+        let p = |x: f64, y: f64| Point { x, y };
+        // a T: two lines leave the same point
+        let lines = vec![
+            Entity::Line(p(0.0, 0.0), p(1.0, 0.0)),
+            Entity::Line(p(1.0, 0.0), p(2.0, 0.0)),
+            Entity::Line(p(1.0, 0.0), p(1.0, 1.0)),
+        ];
+        let chains = layer_chains(&lines).unwrap();
+        assert_eq!(chains.len(), 1);
+        assert_eq!(chains.orphans(), &[(p(1.0, 0.0), p(2.0, 0.0))]);
+
+        let mut report = WeldReport::default();
+        let welded = DxfConfig::default().process_drawing(Drawing::new(lines), &mut report).unwrap();
+        assert_eq!(welded.entity_count(), 3);
+        assert!(welded.entities().any(|entity| *entity == Entity::Line(p(1.0, 0.0), p(2.0, 0.0))));
+        assert_eq!(report.orphans().collect::<Vec<_>>(), vec![("0", &(p(1.0, 0.0), p(2.0, 0.0)))]);
+    }
+
+    #[test]
+    fn test_make_circle_far_from_origin() {
+        let config = DxfConfig::default();
+        let center = Point { x: 3.0, y: -1.0 };
+        let points = [center.polar(5.0, 20.0), center.polar(5.0, 23.0), center.polar(5.0, 26.0)];
+        let chain = (0..=16).map(|i| center.polar(5.0, 20.0 + i as f64 * 5.0)).collect::<Vec<_>>();
+        let welded = config.process_chain(&chain, &mut ChainReport::default()).unwrap();
+        for offset in [1e5, 2.5e6, 4e7] {
+            let shift = |p: &Point| Point { x: p.x + offset, y: p.y - offset / 2.0 };
+            let circle = config.make_circle(&shift(&points[0]), &shift(&points[1]), &shift(&points[2])).unwrap();
+            assert!(circle.center.dist(&shift(&center)) < 1e-5 && (circle.radius - 5.0).abs() < 1e-5, "{} {:?} {}", offset, circle.center, circle.radius);
+            let shifted = config.process_chain(&chain.iter().map(shift).collect::<Vec<_>>(), &mut ChainReport::default()).unwrap();
+            assert_eq!(shifted.len(), welded.len());
+            match (&shifted[..], &welded[..]) {
+                ([Entity::Arc { center: moved, radius, .. }], [Entity::Arc { center, .. }]) => {
+                    assert!(moved.dist(&shift(center)) < 1e-5 && (radius - 5.0).abs() < 1e-5);
+                },
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_circle_sums() {
+        let center = Point { x: 40.0, y: -7.0 };
+        let arc = (0..=20).map(|i| center.polar(5.0, i as f64 * 9.0)).collect::<Vec<_>>();
+        let mut sums = CircleSums::new(&arc, 0);
+        sums.extend_to(&arc, 20);
+        assert!(sums.min_algebraic_error() < 1e-9);
+        assert!(!sums.rules_out(5.0, 0.01));
+
+        let zigzag = (0..=20).map(|i| Point { x: i as f64, y: if i % 2 == 0 { 1.0 } else { -1.0 } }).collect::<Vec<_>>();
+        let mut sums = CircleSums::new(&zigzag, 0);
+        sums.extend_to(&zigzag, 20);
+        assert!(sums.rules_out(50.0, 0.05));
+    }
+
+    #[test]
+    fn test_stream_drawing() {
+        let center = Point { x: 0.0, y: 0.0 };
+        let mut drawing = Drawing::new(vec![Entity::Circle { center: center.clone(), radius: 5.0 }]);
+        drawing.layer_mut("CUT", Some(1)).entities.push(Entity::Arc { center, radius: 8.0, start_angle: 0.0, end_angle: 90.0 });
+        drawing.flatten(0.001);
+        let config = DxfConfig::default();
+
+        let (mut expected, mut expected_report) = (String::new(), WeldReport::default());
+        config.process_drawing(drawing.clone(), &mut expected_report).unwrap().write_to(&mut DxfBackend::new(&mut expected));
+        let (mut out, mut streamed, mut report) = (String::new(), vec![], WeldReport::default());
+        config.stream_drawing(drawing.clone(), &mut DxfBackend::new(&mut out), &mut streamed, &mut report).unwrap();
+        assert!(out.is_empty());
+        assert_eq!(String::from_utf8(streamed).unwrap(), expected);
+        assert_eq!(report.to_stats_json(), expected_report.to_stats_json());
+
+        // R2000 output is held back until its header and tables can go in front
+        let mut expected = String::new();
+        config.process_drawing(drawing.clone(), &mut WeldReport::default()).unwrap().write_to(&mut DxfBackend::new(&mut expected).with_version(DxfVersion::R2000));
+        let (mut out, mut streamed) = (String::new(), vec![]);
+        config.stream_drawing(drawing, &mut DxfBackend::new(&mut out).with_version(DxfVersion::R2000), &mut streamed, &mut WeldReport::default()).unwrap();
+        assert_eq!(String::from_utf8(streamed).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_process_resolutions() {
+        let center = Point { x: 0.0, y: 0.0 };
+        let mut drawing = Drawing::new(vec![Entity::Circle { center: center.clone(), radius: 5.0 }]);
+        drawing.layer_mut("CUT", Some(1)).entities.push(Entity::Arc { center: center.clone(), radius: 8.0, start_angle: 10.0, end_angle: 90.0 });
+        drawing.flatten(0.001);
+        drawing.layer_mut("CUT", Some(1)).entities.push(Entity::Arc { center, radius: 8.0, start_angle: 100.0, end_angle: 120.0 });
+        let config = DxfConfig::default();
+        let resolutions = [0.0005, 0.05];
+        let welds = config.process_resolutions(drawing.clone(), &resolutions, &mut WeldReport::default()).unwrap();
+        assert_eq!(welds.len(), 2);
+        for (resolution, (welded, report)) in resolutions.iter().zip(welds.iter()) {
+            let mut expected_report = WeldReport::default();
+            let expected = DxfConfig { resolution: *resolution, ..config.clone() }.process_drawing(drawing.clone(), &mut expected_report).unwrap();
+            assert_eq!(welded, &expected);
+            assert_eq!(report.output_entities, expected_report.output_entities);
+            assert_eq!(report.chains.len(), expected_report.chains.len());
+        }
+        assert!(welds[0].0.entity_count() > welds[1].0.entity_count());
+    }
+
+    #[test]
+    fn test_thickness_kept_apart() {
+        let center = Point { x: 0.0, y: 0.0 };
+        let arc = (0..=32).map(|i| center.polar(10.0, i as f64 * 90.0 / 32.0)).collect::<Vec<_>>();
+        // traced from the leftmost point of each half, where chaining starts
+        let mut src = "  0\nSECTION\n  2\nENTITIES\n".to_string();
+        for (i, pair) in arc.windows(2).enumerate() {
+            let thickness = if i < 16 { " 39\n2\n" } else { "" };
+            src.push_str(&format!("  0\nLINE\n  8\nCUT\n{} 10\n{}\n 20\n{}\n 11\n{}\n 21\n{}\n", thickness, pair[1].x, pair[1].y, pair[0].x, pair[0].y));
+        }
+        src.push_str("  0\nENDSEC\n  0\nEOF\n");
+        let drawing = Drawing::parse(&src).unwrap();
+        assert_eq!(drawing.layers.iter().map(|layer| layer.thickness).collect::<Vec<_>>(), vec![Some(2.0), None]);
+
+        // the two halves are welded separately and keep their thickness
+        let welded = DxfConfig::default().process_drawing(drawing, &mut WeldReport::default()).unwrap();
+        for (layer, (from, to)) in welded.layers.iter().zip([(0.0, 45.0), (45.0, 90.0)]) {
+            assert_eq!(layer.entities.len(), 1);
+            assert_eq!(layer.entities[0].start_point(), Some(center.polar(10.0, from)));
+            assert_eq!(layer.entities[0].end_point(), Some(center.polar(10.0, to)));
+        }
+        let written = welded.to_string();
+        assert_eq!(written.matches("  39\n2\n").count(), 1);
+        let reparsed = Drawing::parse(&written).unwrap();
+        assert_eq!(reparsed.layers.iter().map(|layer| layer.thickness).collect::<Vec<_>>(), vec![Some(2.0), None]);
+    }
+
+    #[test]
+    fn test_weld_blocks() {
+        let center = Point { x: 0.0, y: 0.0 };
+        // a quarter arc traced from its leftmost point, where chaining starts
+        let arc = (0..=32).rev().map(|i| center.polar(10.0, i as f64 * 90.0 / 32.0)).collect::<Vec<_>>();
+        let mut src = "  0\nSECTION\n  2\nBLOCKS\n  0\nBLOCK\n  8\n0\n  2\n*Model_Space\n 10\n0\n 20\n0\n  0\nENDBLK\n  0\nBLOCK\n  8\n0\n  2\nBOSS\n 70\n0\n 10\n0\n 20\n0\n".to_string();
+        for pair in arc.windows(2) {
+            src.push_str(&format!("  0\nLINE\n  8\nCUT\n 10\n{}\n 20\n{}\n 11\n{}\n 21\n{}\n", pair[0].x, pair[0].y, pair[1].x, pair[1].y));
+        }
+        src.push_str("  0\nTEXT\n  8\nCUT\n  1\nlabel\n  0\nENDBLK\n  0\nENDSEC\n  0\nSECTION\n  2\nENTITIES\n");
+        for i in 0..3 {
+            src.push_str(&format!("  0\nINSERT\n  8\nPARTS\n  2\nBOSS\n 10\n{}\n 20\n0\n 50\n90\n", i * 30));
+        }
+        src.push_str("  0\nENDSEC\n  0\nEOF\n");
+        let mut diagnostics = vec![];
+        let drawing = Drawing::parse_with_diagnostics(&src, &mut diagnostics).unwrap();
+        assert_eq!(drawing.blocks.len(), 1);
+        assert_eq!(drawing.blocks[0].entity_count(), 32);
+        assert_eq!(drawing.layers[0].inserts.len(), 3);
+        assert_eq!(diagnostics.iter().map(|d| d.kind).collect::<Vec<_>>(), vec!["unsupported-entity"]);
+
+        let passed = DxfConfig::default().process_drawing(drawing.clone(), &mut WeldReport::default()).unwrap();
+        assert_eq!(passed.blocks, drawing.blocks);
+
+        let config = DxfConfig { weld_blocks: true, ..Default::default() };
+        let mut report = WeldReport::default();
+        let welded = config.process_drawing(drawing.clone(), &mut report).unwrap();
+        assert!(matches!(welded.blocks[0].layers[0].entities[..], [Entity::Arc { radius, .. }] if (radius - 10.0).abs() < 1e-6));
+        assert_eq!(welded.layers, drawing.layers);
+        assert_eq!((report.input_entities, report.output_entities), (32, 1));
+
+        let written = welded.to_string();
+        assert_eq!(written.matches("INSERT").count(), 3);
+        assert_eq!(Drawing::parse(&written).unwrap().blocks.len(), 1);
+        let (mut streamed, mut sink) = (String::new(), vec![]);
+        config.stream_drawing(drawing, &mut DxfBackend::new(&mut streamed), &mut sink, &mut WeldReport::default()).unwrap();
+        assert_eq!(String::from_utf8(sink).unwrap(), written);
+    }
+
+    #[test]
+    fn test_raw_entities_passed_through() {
+        let center = Point { x: 0.0, y: 0.0 };
+        let arc = (0..=32).rev().map(|i| center.polar(10.0, i as f64 * 90.0 / 32.0)).collect::<Vec<_>>();
+        let mut src = "  0\nSECTION\n  2\nENTITIES\n".to_string();
+        for pair in arc.windows(2) {
+            src.push_str(&format!("  0\nLINE\n  8\nOUTLINE\n 10\n{}\n 20\n{}\n 11\n{}\n 21\n{}\n", pair[0].x, pair[0].y, pair[1].x, pair[1].y));
+        }
+        src.push_str("  0\nMLINE\n  8\nOUTLINE\n  2\nSTANDARD\n 72\n2\n 11\n0\n 21\n0\n 11\n4\n 21\n0\n  0\nLEADER\n  8\nNOTES\n 10\n0\n 20\n0\n 10\n1\n 20\n1\n  0\nENDSEC\n  0\nEOF\n");
+        let welded = crate::weld_dxf(&src, &DxfConfig::default()).unwrap();
+        let drawing = Drawing::parse(&welded).unwrap();
+        assert!(matches!(drawing.layers[0].entities[..], [Entity::Arc { .. }]));
+        assert_eq!(drawing.layers.iter().flat_map(|layer| layer.raw.iter().map(|raw| raw.kind.as_str())).collect::<Vec<_>>(), vec!["MLINE", "LEADER"]);
+
+        let (mut streamed, mut sink) = (String::new(), vec![]);
+        DxfConfig::default().stream_drawing(Drawing::parse(&src).unwrap(), &mut DxfBackend::new(&mut streamed), &mut sink, &mut WeldReport::default()).unwrap();
+        assert_eq!(String::from_utf8(sink).unwrap(), welded);
+    }
+
+    #[test]
+    fn test_limits() {
+        let mut drawing = Drawing::new(vec![Entity::Circle { center: Point { x: 0.0, y: 0.0 }, radius: 5.0 }]);
+        drawing.flatten(0.001);
+        let mut src = String::new();
+        drawing.write_to(&mut DxfBackend::new(&mut src));
+        let count = drawing.entity_count();
+        let limited = |limits: Limits| crate::weld_dxf(&src, &DxfConfig { limits, ..Default::default() });
+
+        assert!(limited(Limits { max_file_size: Some(src.len()), max_entities: Some(count), max_chain_points: Some(count + 1) }).is_ok());
+        let error = limited(Limits { max_file_size: Some(100), ..Default::default() }).unwrap_err();
+        assert!(error.to_string().contains(&format!("input is {} bytes, over the limit of 100 bytes", src.len())));
+        let error = limited(Limits { max_entities: Some(count - 1), ..Default::default() }).unwrap_err();
+        assert!(error.to_string().contains(&format!("input has more than {} entities", count - 1)));
+        assert!(limited(Limits { max_chain_points: Some(count), ..Default::default() }).is_err());
+    }
+
+    #[test]
+    fn test_extend_input_arc() {
+        let center = Point { x: 3.0, y: 4.0 };
+        let mut entities = vec![Entity::Arc { center: center.clone(), radius: 10.0, start_angle: 0.0, end_angle: 90.0 }];
+        // the curve continues as lines to 180 degrees, then turns off it
+        let tail = (0..=10).map(|i| center.polar(10.0, 90.0 + i as f64 * 9.0)).collect::<Vec<_>>();
+        entities.extend(tail.windows(2).map(|pair| Entity::Line(pair[0].clone(), pair[1].clone())));
+        entities.push(Entity::Line(tail[10].clone(), Point { x: tail[10].x, y: tail[10].y - 5.0 }));
+        // and one line runs backwards into the start
+        entities.push(Entity::Line(center.polar(10.0, -5.0), center.polar(10.0, 0.0)));
+
+        let mut report = WeldReport::default();
+        let welded = DxfConfig::default().process_drawing(Drawing::new(entities), &mut report).unwrap();
+        let entities = welded.entities().collect::<Vec<_>>();
+        assert_eq!(entities.len(), 2);
+        match entities[0] {
+            Entity::Arc { radius, start_angle, end_angle, .. } => {
+                assert!((radius - 10.0).abs() < 1e-9);
+                assert!((start_angle - 355.0).abs() < 1e-9);
+                assert!((end_angle - 180.0).abs() < 1e-9);
+            },
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(entities[1], Entity::Line(..)));
+        assert_eq!(report.chains[0].arcs[0].segments, 11);
+        assert!(report.max_residual() < DxfConfig::default().resolution);
+    }
+
+    #[test]
+    fn test_arc_across_seam() {
+        let config = DxfConfig::default();
+        let circle = Circle { center: Point { x: 0.0, y: 0.0 }, radius: 10.0 };
+        // the midpoint sits exactly on the seam
+        let start = circle.center.polar(10.0, 350.0);
+        let mid = Point { x: 10.0, y: 0.0 };
+        let end = circle.center.polar(10.0, 10.0);
+        for (from, to) in [(&start, &end), (&end, &start)] {
+            let arc = config.make_arc(&circle, from, &mid, to, 0.0).unwrap();
+            assert!((arc.start_angle - 350.0).abs() < 1e-9 && (arc.end_angle - 10.0).abs() < 1e-9);
+        }
+        assert!((circle.get_radial_dist(&circle.center.polar(10.0, 359.0), &circle.center.polar(10.0, 1.0)) - 2f64.to_radians()).abs() < 1e-12);
+
+        // clockwise arcs straddling the seam weld like any other
+        for offset in [-5.0, -1e-12, 0.0, 1e-12, 5.0] {
+            let chain = (0..=6).map(|i| circle.center.polar(10.0, offset + 30.0 - i as f64 * 10.0)).collect::<Vec<_>>();
+            let entities = config.process_chain(&chain, &mut ChainReport::default()).unwrap();
+            assert_eq!(entities.len(), 1, "offset {}", offset);
+            assert!(matches!(entities[0], Entity::Arc { .. }));
+        }
+    }
+
+    #[test]
+    fn test_sagitta_rules_out() {
+        let config = DxfConfig { max_radius: 100.0, ..Default::default() };
+        let center = Point { x: 0.0, y: 0.0 };
+        // a run of radius 1000 along 60 units, flatter than any arc of radius 100 over it
+        let flat = (0..=12).map(|i| Point { x: 0.0, y: -1000.0 }.polar(1000.0, 88.3 + i as f64 * 0.283)).collect::<Vec<_>>();
+        assert!(config.sagitta_rules_out(&flat));
+        assert!(!DxfConfig::default().sagitta_rules_out(&flat));
+        let arc = (0..=12).map(|i| center.polar(20.0, 30.0 + i as f64 * 10.0)).collect::<Vec<_>>();
+        assert!(!config.sagitta_rules_out(&arc));
+        assert!(config.sagitta_rules_out(&[Point { x: -150.0, y: 0.0 }, center.clone(), Point { x: 150.0, y: 0.0 }]));
+        // rising higher than the largest circle's diameter
+        let tight = DxfConfig { max_radius: 5.0, ..Default::default() };
+        assert!(tight.sagitta_rules_out(&[center.clone(), Point { x: 1.0, y: 12.0 }, Point { x: 2.0, y: 0.0 }]));
+        assert!(!tight.sagitta_rules_out(&[center.clone(), Point { x: 1.0, y: 8.0 }, Point { x: 2.0, y: 0.0 }]));
+    }
+
+    #[test]
+    fn test_circle_sweep() {
+        let center = Point { x: 1.0, y: -2.0 };
+        let circle = (0..=24).map(|i| center.polar(4.0, 10.0 + i as f64 * 15.0)).collect::<Vec<_>>();
+        assert!((run_sweep(&circle, &center) - 360.0).abs() < 1e-6);
+        assert!((run_sweep(&circle.iter().rev().cloned().collect::<Vec<_>>(), &center) + 360.0).abs() < 1e-6);
+        // out along a quarter arc and straight back to where it started
+        let mut arc_and_chord = (0..=12).map(|i| center.polar(4.0, 10.0 + i as f64 * 7.5)).collect::<Vec<_>>();
+        arc_and_chord.push(arc_and_chord[0].clone());
+        assert!(run_sweep(&arc_and_chord, &center).abs() < 1e-6);
+
+        let config = DxfConfig::default();
+        let entities = config.process_chain(&circle, &mut ChainReport::default()).unwrap();
+        assert!(matches!(&entities[..], [Entity::Circle { .. }]), "{:?}", entities);
+        let entities = config.process_chain(&arc_and_chord, &mut ChainReport::default()).unwrap();
+        match &entities[..] {
+            [Entity::Arc { start_angle, end_angle, .. }, Entity::Line(from, to)] => {
+                assert!((start_angle - 10.0).abs() < 1e-6 && (end_angle - 100.0).abs() < 1e-6);
+                assert_eq!((from, to), (&arc_and_chord[12], &arc_and_chord[0]));
+            },
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_semicircle_direction() {
+        let center = Point { x: 1.0, y: -2.0 };
+        let ccw = (0..=12).map(|i| center.polar(4.0, 90.0 + i as f64 * 15.0)).collect::<Vec<_>>();
+        let cw = ccw.iter().rev().cloned().collect::<Vec<_>>();
+        for fitter in [Fitter::Greedy, Fitter::Ransac] {
+            let config = DxfConfig { fitter, ..Default::default() };
+            for (chain, clockwise) in [(&ccw, false), (&cw, true)] {
+                let mut report = ChainReport::default();
+                let entities = config.process_chain(chain, &mut report).unwrap();
+                match &entities[..] {
+                    [Entity::Arc { start_angle, end_angle, .. }] => {
+                        assert!((start_angle - 90.0).abs() < 1e-6 && (end_angle - 270.0).abs() < 1e-6);
+                    },
+                    other => panic!("unexpected {:?}", other),
+                }
+                assert_eq!(report.arcs[0].clockwise, clockwise);
+            }
+        }
+    }
+
+    #[cfg(feature = "debug-geometry")]
+    #[test]
+    fn test_arc_source() {
+        let center = Point { x: 1.0, y: -2.0 };
+        let arc = (0..=12).map(|i| center.polar(4.0, 90.0 + i as f64 * 15.0)).collect::<Vec<_>>();
+        let mut chain = vec![Point { x: -3.0, y: 6.0 }];
+        chain.extend(arc.iter().cloned());
+        for fitter in [Fitter::Greedy, Fitter::Ransac] {
+            let config = DxfConfig { fitter, ..Default::default() };
+            let mut report = ChainReport::default();
+            let entities = config.process_chain(&chain, &mut report).unwrap();
+            let fitted = &report.arcs[0];
+            assert!(matches!(entities[fitted.entity], Entity::Arc { .. }));
+            assert_eq!(fitted.source.as_ref(), Some(&arc));
+        }
+    }
+
+    #[test]
+    fn test_zero_length() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let corners = [p(0.0, 0.0), p(10.0, 0.0), p(10.0, 10.0), p(0.0, 10.0), p(0.0, 0.0)];
+        let mut entities = corners.windows(2).map(|pair| Entity::Line(pair[0].clone(), pair[1].clone())).collect::<Vec<_>>();
+        entities.insert(1, Entity::Line(p(10.0, 0.0), p(10.0, 0.0)));
+        let drawing = Drawing::new(entities);
+        let weld = |zero_length: ZeroLengthPolicy| {
+            let mut report = WeldReport::default();
+            let welded = DxfConfig { zero_length, ..Default::default() }.process_drawing(drawing.clone(), &mut report);
+            (welded, report)
+        };
+
+        let (welded, report) = weld(ZeroLengthPolicy::Drop);
+        assert_eq!(welded.unwrap().entity_count(), 4);
+        assert_eq!(report.zero_length_lines, 1);
+        assert_eq!(report.orphans().count(), 0);
+
+        let (welded, report) = weld(ZeroLengthPolicy::Keep);
+        let welded = welded.unwrap();
+        assert_eq!(welded.entity_count(), 5);
+        assert!(welded.entities().any(is_zero_length));
+        assert_eq!(report.orphans().count(), 0);
+
+        let error = weld(ZeroLengthPolicy::Error).0.unwrap_err().to_string();
+        assert!(error.contains("1 zero-length lines on layer 0, the first at (10, 0)"), "{}", error);
+    }
+
+    #[test]
+    fn test_wide_polyline_passed_through() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let arc = (0..=12).map(|i| p(0.0, 0.0).polar(10.0, 100.0 - i as f64 * 7.5)).collect::<Vec<_>>();
+        let mut entities = arc.windows(2).map(|pair| Entity::Line(pair[0].clone(), pair[1].clone())).collect::<Vec<_>>();
+        let tapered = Entity::Polyline { curve_type: 0, vertices: arc.iter().map(|point| p(point.x, point.y + 100.0)).collect(), widths: vec![(1.0, 0.5); arc.len()] };
+        entities.push(tapered.clone());
+        let mut report = WeldReport::default();
+        let welded = DxfConfig::default().process_drawing(Drawing::new(entities), &mut report).unwrap();
+        let welded = welded.entities().collect::<Vec<_>>();
+        assert!(welded.iter().any(|entity| matches!(entity, Entity::Arc { .. })));
+        assert_eq!(welded.last(), Some(&&tapered));
+        assert_eq!(report.chains.last().map(|chain| (chain.points, chain.entities)), Some((arc.len(), 1)));
+    }
+
+    #[test]
+    fn test_weld_polyline() {
+        let arc = (0..=12).map(|i| Point { x: 0.0, y: 0.0 }.polar(10.0, 100.0 - i as f64 * 7.5)).collect::<Vec<_>>();
+        let mut src = "  0\nSECTION\n  2\nENTITIES\n  0\nPOLYLINE\n  8\n0\n 66\n1\n 70\n0\n".to_string();
+        for point in arc.iter() {
+            src.push_str(&format!("  0\nVERTEX\n  8\n0\n 10\n{}\n 20\n{}\n", point.x, point.y));
+        }
+        src.push_str("  0\nSEQEND\n  0\nENDSEC\n  0\nEOF\n");
+        let welded = Drawing::parse(&crate::weld_dxf(&src, &DxfConfig::default()).unwrap()).unwrap();
+        assert!(matches!(welded.entities().collect::<Vec<_>>()[..], [Entity::Arc { radius, .. }] if (radius - 10.0).abs() < 1e-6));
+
+        // as do its segments joined to lines
+        let mut entities = vec![Entity::Polyline { curve_type: 0, vertices: arc[..7].to_vec(), widths: vec![] }];
+        entities.extend(arc[6..].windows(2).map(|pair| Entity::Line(pair[0].clone(), pair[1].clone())));
+        let chains = layer_chains(&entities).unwrap();
+        assert_eq!(chains.iter().map(|chain| chain.len()).collect::<Vec<_>>(), vec![arc.len()]);
+    }
+
+    #[test]
+    fn test_circles_passed_through() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let arc = (0..=12).map(|i| p(0.0, 0.0).polar(10.0, 100.0 - i as f64 * 7.5)).collect::<Vec<_>>();
+        let mut entities = arc.windows(2).map(|pair| Entity::Line(pair[0].clone(), pair[1].clone())).collect::<Vec<_>>();
+        let circle = Entity::Circle { center: p(50.0, 0.0), radius: 5.0 };
+        entities.push(circle.clone());
+        let mut report = WeldReport::default();
+        let welded = DxfConfig::default().process_drawing(Drawing::new(entities), &mut report).unwrap();
+        assert!(matches!(welded.entities().collect::<Vec<_>>()[..], [Entity::Arc { .. }, c] if *c == circle));
+        assert!(report.chains.last().unwrap().closed);
+
+        // the welded output welds again, as does its DXF text
+        let rewelded = DxfConfig::default().process_drawing(welded.clone(), &mut WeldReport::default()).unwrap();
+        assert_eq!(rewelded, welded);
+        assert!(crate::weld_dxf(&welded.to_string(), &DxfConfig::default()).is_ok());
+    }
+
+    #[test]
+    fn test_timings() {
+        let drawing = Drawing::new(noisy_arc().windows(2).map(|pair| Entity::Line(pair[0].clone(), pair[1].clone())).collect());
+        let mut report = WeldReport::default();
+        DxfConfig::default().process_drawing(drawing.clone(), &mut report).unwrap();
+        assert!(report.timings.phases.is_empty());
+
+        let mut report = WeldReport { timings: Timings { enabled: true, ..Default::default() }, ..Default::default() };
+        report.timings.record("parse", 0.5);
+        DxfConfig::default().process_drawing(drawing, &mut report).unwrap();
+        let phases = report.timings.phases.iter().map(|timing| timing.phase).collect::<Vec<_>>();
+        assert_eq!(phases, vec!["parse", "chain", "fit"]);
+        assert!(report.timings.total() >= 0.5);
+        assert_eq!(report.timings.to_string().lines().count(), 4);
+    }
+
+    #[test]
+    fn test_merge_arcs() {
+        let config = DxfConfig { merge_arcs: true, ..Default::default() };
+        let center = Point { x: 3.0, y: 1.0 };
+        let ccw = (0..=24).map(|i| center.polar(10.0, 10.0 + i as f64 * 5.0)).collect::<Vec<_>>();
+        let cw = ccw.iter().rev().cloned().collect::<Vec<_>>();
+        // each half welded on its own, as if the fitter had broken the arc in the middle
+        let split = |chain: &[Point]| {
+            let mut report = ChainReport::default();
+            let mut entities = vec![];
+            for half in [&chain[..13], &chain[12..]] {
+                let mut half_report = ChainReport::default();
+                entities.extend(config.process_chain(half, &mut half_report).unwrap());
+                report.arcs.extend(half_report.arcs.into_iter().map(|arc| ArcReport { entity: entities.len() - 1, ..arc }));
+            }
+            (entities, report)
+        };
+        for chain in [&ccw, &cw] {
+            let (entities, mut report) = split(chain);
+            assert_eq!(entities.len(), 2);
+            let merged = config.merge_arcs(chain, entities, &mut report);
+            match &merged[..] {
+                [Entity::Arc { center: merged_center, radius, start_angle, end_angle }] => {
+                    assert!(merged_center.dist(&center) < 1e-6 && (radius - 10.0).abs() < 1e-6);
+                    assert!((start_angle - 10.0).abs() < 1e-6 && (end_angle - 130.0).abs() < 1e-6);
+                },
+                other => panic!("unexpected {:?}", other),
+            }
+            assert_eq!(report.arcs.len(), 1);
+            assert_eq!(report.arcs[0].segments, 24);
+        }
+
+        // arcs on different circles stay apart
+        let mut bent = ccw[..13].to_vec();
+        bent.extend((1..=12).map(|i| center.polar(5.0, 70.0).polar(5.0, 70.0 + i as f64 * 5.0)));
+        let (entities, mut report) = split(&bent);
+        assert_eq!(config.merge_arcs(&bent, entities, &mut report).len(), 2);
+    }
+
+    #[test]
+    fn test_arc_segment_bounds() {
+        let center = Point { x: 0.0, y: 0.0 };
+        let p = |x: f64, y: f64| Point { x, y };
+        // a short arc between two straight runs
+        let mut chain = vec![p(20.0, 10.0)];
+        chain.extend((0..4).map(|i| center.polar(10.0, 90.0 + i as f64 * 10.0)));
+        chain.push(p(-30.0, -20.0));
+        for fitter in [Fitter::Greedy, Fitter::Ransac] {
+            let config = DxfConfig { fitter, ..Default::default() };
+            let welded = config.process_chain(&chain, &mut ChainReport::default()).unwrap();
+            assert!(welded.iter().any(|entity| matches!(entity, Entity::Arc { .. })));
+            let config = DxfConfig { fitter, min_arc_segments_replaced: 4, ..Default::default() };
+            let mut report = ChainReport::default();
+            let welded = config.process_chain(&chain, &mut report).unwrap();
+            assert_eq!(welded.len(), chain.len() - 1);
+            assert!(report.arcs.is_empty());
+        }
+
+        let circle = (0..=64).map(|i| center.polar(10.0, i as f64 * 360.0 / 64.0)).collect::<Vec<_>>();
+        for fitter in [Fitter::Greedy, Fitter::Ransac] {
+            let config = DxfConfig { fitter, max_points_per_fit: Some(17), ..Default::default() };
+            let mut report = ChainReport::default();
+            let welded = config.process_chain(&circle, &mut report).unwrap();
+            assert_eq!(welded.len(), 4);
+            assert!(report.arcs.iter().all(|arc| arc.segments <= 16));
+        }
+        assert!(DxfConfig { max_points_per_fit: Some(3), ..Default::default() }.process_chain(&circle, &mut ChainReport::default()).is_err());
+    }
+
+    #[test]
+    fn test_angular_tolerance() {
+        // densely sampled and zig-zagging across a large radius, within the resolution throughout
+        let center = Point { x: 0.0, y: -1000.0 };
+        let chain = (0..=100).map(|i| center.polar(1000.0 + if i % 2 == 0 { 0.01 } else { -0.01 }, 90.5 - i as f64 * 0.01)).collect::<Vec<_>>();
+        for fitter in [Fitter::Greedy, Fitter::Ransac] {
+            let config = DxfConfig { fitter, ..Default::default() };
+            assert_eq!(config.process_chain(&chain, &mut ChainReport::default()).unwrap().len(), 1);
+            let config = DxfConfig { fitter, angular_tolerance: Some(2.0), ..Default::default() };
+            let welded = config.process_chain(&chain, &mut ChainReport::default()).unwrap();
+            // only the tight arcs through single zig-zags remain
+            assert!(welded.len() > 1 && welded.iter().all(|entity| !matches!(entity, Entity::Arc { radius, .. } if *radius > 10.0)));
+        }
+    }
+
+    #[test]
+    fn test_s_shape() {
+        // a gentle curve with an S across it, within the resolution of a single arc
+        let chain = (0..=20).map(|i| {
+            let t = i as f64 / 20.0;
+            Point { x: 20.0 * t, y: 0.04 * (2.0 * PI * t).sin() + 0.2 * t * t }
+        }).collect::<Vec<_>>();
+        for fitter in [Fitter::Greedy, Fitter::Ransac] {
+            let welded = DxfConfig { fitter, ..Default::default() }.process_chain(&chain, &mut ChainReport::default()).unwrap();
+            assert!(welded.len() > 1);
+        }
+    }
+
+    #[test]
+    fn test_chunked_chain() {
+        // a wave of half circles, each of 24 segments, bending alternately left and right
+        let mut chain = vec![];
+        for k in 0..40 {
+            let center = Point { x: 10.0 * k as f64 + 5.0, y: 0.0 };
+            let half = (0..24).map(|i| i as f64 * 7.5).map(|angle| if k % 2 == 0 { center.polar(5.0, 180.0 - angle) } else { center.polar(5.0, 180.0 + angle) });
+            chain.extend(half);
+        }
+        chain.push(Point { x: 400.0, y: 0.0 });
+        let config = DxfConfig::default();
+        let mut whole_report = ChainReport::default();
+        let whole = config.process_chain(&chain, &mut whole_report).unwrap();
+        for chunk_points in [64, 100, 333] {
+            let mut report = ChainReport::default();
+            let chunked = DxfConfig { chunk_points: Some(chunk_points), ..config.clone() }.process_chain(&chain, &mut report).unwrap();
+            assert_eq!(chunked, whole, "{}", chunk_points);
+            assert_eq!(report.points, chain.len());
+            assert_eq!(report.arcs.iter().map(|arc| arc.entity).collect::<Vec<_>>(), whole_report.arcs.iter().map(|arc| arc.entity).collect::<Vec<_>>());
+        }
+        assert!(DxfConfig { chunk_points: Some(10), ..config.clone() }.process_chain(&chain, &mut ChainReport::default()).is_err());
+
+        // a closed chain just over a chunk is still split, so it is not welded into one circle
+        let circle = (0..=70).map(|i| Point { x: 0.0, y: 0.0 }.polar(10.0, i as f64 * 360.0 / 70.0)).collect::<Vec<_>>();
+        assert_eq!(config.process_chain(&circle, &mut ChainReport::default()).unwrap().len(), 1);
+        let chunked = DxfConfig { chunk_points: Some(64), ..config.clone() }.process_chain(&circle, &mut ChainReport::default()).unwrap();
+        assert!(chunked.len() > 1 && chunked.iter().all(|entity| matches!(entity, Entity::Arc { .. })), "{:?}", chunked);
+
+        // one long arc leaves the windows no end in common, so each is refitted from where the last one ends
+        let center = Point { x: 0.0, y: 0.0 };
+        let arc = (0..=3000).map(|i| center.polar(1000.0, 10.0 + i as f64 * 0.05)).collect::<Vec<_>>();
+        let chunked = DxfConfig { chunk_points: Some(500), ..config }.process_chain(&arc, &mut ChainReport::default()).unwrap();
+        assert!(chunked.len() > 1 && chunked.iter().all(|entity| matches!(entity, Entity::Arc { .. })));
+        assert!(chunked.windows(2).all(|pair| pair[0].end_point() == pair[1].start_point()));
+        assert_eq!((chunked[0].start_point(), chunked.last().unwrap().end_point()), (Some(arc[0].clone()), Some(arc[3000].clone())));
+    }
+
+    // replaces runs of collinear points with one line
+    struct LineModel;
+
+    impl CurveModel for LineModel {
+        type Model = (Point, Point);
+
+        fn fit(&mut self, points: &[Point]) -> Option<(Point, Point)> {
+            let (start, end) = (&points[0], &points[points.len() - 1]);
+            points.iter().all(|p| segment_distance(p, start, end) < 0.01).then(|| (start.clone(), end.clone()))
+        }
+
+        fn max_deviation(&self, line: &(Point, Point), points: &[Point]) -> Option<(f64, Point)> {
+            points.iter().map(|p| (segment_distance(p, &line.0, &line.1), p.clone())).max_by(|a, b| a.0.partial_cmp(&b.0).unwrap())
+        }
+
+        fn to_entities(&self, line: (Point, Point), _deviation: (f64, Point), _segments: usize, _closed: bool, _report: &mut ChainReport, _first: usize) -> Vec<Entity> {
+            vec![Entity::Line(line.0, line.1)]
+        }
+    }
+
+    #[test]
+    fn test_custom_curve_model() {
+        // two straight runs meeting at a bend too gentle to be taken as a corner
+        let p = |x: f64, y: f64| Point { x, y };
+        let chain = (0..=20).map(|i| p(i as f64, (i.max(10) - 10) as f64 * 0.1)).collect::<Vec<_>>();
+        let mut report = ChainReport::default();
+        let welded = DxfConfig::default().process_chain_with(&chain, &mut LineModel, &mut report).unwrap();
+        assert_eq!(welded, vec![Entity::Line(p(0.0, 0.0), p(10.0, 0.0)), Entity::Line(p(10.0, 0.0), p(20.0, 1.0))]);
+    }
+
+    #[test]
+    fn test_chain_params() {
+        // traced from its leftmost point, where chaining starts
+        let lines = noisy_arc().windows(2).rev().map(|pair| Entity::Line(pair[1].clone(), pair[0].clone())).collect::<Vec<_>>();
+        let drawing = Drawing {
+            layers: ["decoration", "mating"].iter().map(|name| Layer { entities: lines.clone(), ..Layer::new(name, None) }).collect(),
+            ..Default::default()
+        };
+        let config = DxfConfig {
+            chain_params: Some(ChainParamsFn::new(|chain| {
+                assert_eq!(chain.points.len(), 33);
+                assert!((chain.length() - 10.0 * PI / 2.0).abs() < 1.0 && !chain.closed());
+                match chain.layer.as_str() {
+                    "decoration" => ChainParams { resolution: Some(1.0), ..Default::default() },
+                    _ => ChainParams::default(),
+                }
+            })),
+            ..Default::default()
+        };
+        let welded = config.process_drawing(drawing, &mut WeldReport::default()).unwrap();
+        // the knocked off point only fits within the looser resolution
+        assert_eq!(welded.layers[0].entities.len(), 1);
+        assert!(welded.layers[1].entities.len() > 1);
+    }
+
+    #[test]
+    fn test_extract_chains() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let mut drawing = Drawing::new(vec![
+            Entity::Line(p(0.0, 0.0), p(1.0, 0.0)),
+            Entity::Line(p(1.0, 0.0), p(1.0, 1.0)),
+            // continues the arc, so it is absorbed rather than chained
+            Entity::Arc { center: p(10.0, 0.0), radius: 1.0, start_angle: 0.0, end_angle: 90.0 },
+            Entity::Line(p(10.0, 1.0), p(10.0 + (100f64).to_radians().cos(), (100f64).to_radians().sin())),
+        ]);
+        drawing.layer_mut("holes", None).entities.push(Entity::Line(p(5.0, 5.0), p(6.0, 5.0)));
+        let chains = DxfConfig::default().extract_chains(&drawing).unwrap();
+        assert_eq!(chains.len(), 2);
+        assert_eq!(chains[0].points, vec![p(0.0, 0.0), p(1.0, 0.0), p(1.0, 1.0)]);
+        assert_eq!(chains[0].length(), 2.0);
+        assert_eq!(chains[1].layer, "holes");
+        assert_eq!(chains[1].bounds(), (p(5.0, 5.0), p(6.0, 5.0)));
+    }
+
+    #[test]
+    fn test_chain_midpoint() {
+        let p = |x: f64| Point { x, y: 0.0 };
+        let even = ChainLengths::new(&(0..=6).map(|i| p(i as f64)).collect::<Vec<_>>());
+        assert_eq!(even.midpoint(0, 6), 3);
+        assert_eq!(even.midpoint(1, 4), 2);
+        assert_eq!(even.midpoint(0, 2), 1);
+        // densely sampled at the start, so the middle index is nowhere near halfway
+        let uneven = ChainLengths::new(&[0.0, 0.1, 0.2, 0.3, 0.4, 5.0, 10.0].map(p));
+        assert_eq!(uneven.midpoint(0, 6), 5);
+        assert_eq!(uneven.between(0, 6), 10.0);
+        assert_eq!(uneven.midpoint(4, 6), 5);
+    }
+
+    #[test]
+    fn test_straight_chain() {
+        // a 20 x 10 rectangle with every side cut into unit segments
+        let p = |x: f64, y: f64| Point { x, y };
+        let mut chain = (0..20).map(|i| p(i as f64, 0.0)).collect::<Vec<_>>();
+        chain.extend((0..10).map(|i| p(20.0, i as f64)));
+        chain.extend((0..20).map(|i| p(20.0 - i as f64, 10.0)));
+        chain.extend((0..=10).map(|i| p(0.0, 10.0 - i as f64)));
+        let entities = DxfConfig::default().process_chain(&chain, &mut ChainReport::default()).unwrap();
+        assert_eq!(entities, vec![
+            Entity::Line(p(0.0, 0.0), p(20.0, 0.0)),
+            Entity::Line(p(20.0, 0.0), p(20.0, 10.0)),
+            Entity::Line(p(20.0, 10.0), p(0.0, 10.0)),
+            Entity::Line(p(0.0, 10.0), p(0.0, 0.0)),
+        ]);
+
+        // shallow corners may still be part of an arc
+        let center = p(0.0, 0.0);
+        let polygon = (0..=16).map(|i| center.polar(10.0, i as f64 * 5.0)).collect::<Vec<_>>();
+        assert!(DxfConfig::default().straight_corners(&polygon).is_none());
+    }
+
+    #[test]
+    fn test_ransac_outlier() {
+        let greedy = DxfConfig::default().process_chain(&noisy_arc(), &mut ChainReport::default()).unwrap();
+        assert!(greedy.len() > 1);
+
+        let config = DxfConfig { fitter: Fitter::Ransac, ..Default::default() };
+        let mut report = ChainReport::default();
+        let entities = config.process_chain(&noisy_arc(), &mut report).unwrap();
+        assert_eq!(entities.len(), 1);
+        match &entities[0] {
+            Entity::Arc { radius, start_angle, end_angle, .. } => {
+                assert!((radius - 10.0).abs() < 1e-9);
+                // the start may come out as 0 or 360
+                assert!(((start_angle + 180.0).rem_euclid(360.0) - 180.0).abs() < 1e-6);
+                assert!((end_angle - 90.0).abs() < 1e-6);
+            },
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(report.arcs[0].segments, 32);
+        // the outlier half a unit off the arc is its residual, over the resolution
+        assert!((report.arcs[0].residual - 0.5).abs() < 1e-9);
+        assert_eq!(report.arcs[0].residual_at, Point { x: 0.0, y: 0.0 }.polar(10.5, 45.0));
+        assert!(report.arcs[0].residual > config.resolution);
+    }
+
+    #[test]
+    fn test_fit_lines() {
+        // an L of two jittery edges with the corner knocked off
+        let jitter = |i: usize| if i.is_multiple_of(2) { 0.02 } else { -0.02 };
+        let mut chain = (0..10).map(|i| Point { x: i as f64, y: jitter(i) }).collect::<Vec<_>>();
+        chain[0].y = 0.0;
+        chain.push(Point { x: 10.01, y: -0.01 });
+        chain.extend((1..=10).map(|i| Point { x: 10.0 + jitter(i), y: i as f64 }));
+        chain[20].x = 10.0;
+
+        let config = DxfConfig { fit_lines: true, ..Default::default() };
+        let entities = config.process_chain(&chain, &mut ChainReport::default()).unwrap();
+        assert_eq!(entities.len(), 2, "{:?}", entities);
+        match (&entities[0], &entities[1]) {
+            (Entity::Line(start, corner), Entity::Line(_, end)) => {
+                assert_eq!(start, &Point { x: 0.0, y: 0.0 });
+                assert!(corner.dist(&Point { x: 10.0, y: 0.0 }) < 0.01);
+                assert_eq!(end, &Point { x: 10.0, y: 10.0 });
+            },
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_arc_endpoints() {
+        // arcs across the 0° axis in both directions must reconstruct to the chain they replaced
+        let center = Point { x: 3.0, y: -2.0 };
+        let ccw = (0..=24).map(|i| center.polar(5.0, -60.0 + i as f64 * 5.0)).collect::<Vec<_>>();
+        let cw = ccw.iter().rev().cloned().collect::<Vec<_>>();
+        for (chain, ccw) in [(ccw, true), (cw, false)] {
+            let entities = DxfConfig::default().process_chain(&chain, &mut ChainReport::default()).unwrap();
+            let mut cursor = chain.first().unwrap().clone();
+            for entity in entities.iter() {
+                match entity {
+                    Entity::Arc { center, radius, start_angle, end_angle } => {
+                        assert!((0.0..360.0).contains(start_angle) && (0.0..360.0).contains(end_angle));
+                        let (start, end) = (center.polar(*radius, *start_angle), center.polar(*radius, *end_angle));
+                        let (from, to) = if ccw { (start, end) } else { (end, start) };
+                        assert_eq!(from, cursor);
+                        cursor = to;
+                    },
+                    other => panic!("unexpected {:?}", other),
+                }
+            }
+            assert_eq!(&cursor, chain.last().unwrap());
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use std::fmt::Display;
+
+// a quality check failed: intersections, thresholds or lint findings
+pub const CHECK_FAILED: i32 = 1;
+pub const USAGE_ERROR: i32 = 2;
+pub const IO_ERROR: i32 = 3;
+pub const PARSE_ERROR: i32 = 4;
+pub const FIT_ERROR: i32 = 5;
+
+// prints `message` to stderr and exits with `code`
+pub fn fail(code: i32, message: impl Display) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(code)
+}
+
+pub trait OrExit<T> {
+    // unwraps, or exits with `code` after printing the context and error
+    fn or_exit(self, code: i32, context: &str) -> T;
+}
+
+impl<T, E: Display> OrExit<T> for Result<T, E> {
+    fn or_exit(self, code: i32, context: &str) -> T {
+        self.unwrap_or_else(|e| fail(code, format!("{}: {}", context, e)))
+    }
+}
@@ -0,0 +1,94 @@
+use crate::dxf_process::DxfConfig;
+use crate::result::*;
+use std::ffi::{CStr, CString};
+use std::os::raw::{c_char, c_int};
+use std::panic;
+use std::ptr;
+
+unsafe fn read_str<'a>(ptr: *const c_char, name: &str) -> Result<&'a str> {
+    if ptr.is_null() {
+        return Err(weld_err!("{} is null", name));
+    }
+    Ok(CStr::from_ptr(ptr).to_str()?)
+}
+
+unsafe fn weld(input: *const c_char, opts_json: *const c_char) -> Result<String> {
+    let input = read_str(input, "input")?;
+    let config: DxfConfig = if opts_json.is_null() {
+        DxfConfig::default()
+    } else {
+        match read_str(opts_json, "opts_json")?.trim() {
+            "" => DxfConfig::default(),
+            opts => serde_json::from_str(opts)?,
+        }
+    };
+    crate::weld_dxf(input, &config)
+}
+
+fn into_c_string(text: String) -> *mut c_char {
+    // interior nuls can't cross the boundary, drop them rather than fail
+    CString::new(text.replace('\0', "")).unwrap_or_default().into_raw()
+}
+
+/// Welds the DXF text `input` using the JSON options `opts_json` (may be null for defaults).
+/// Returns 0 and sets `*out` on success, or returns -1 and sets `*err` to an error message.
+/// Strings returned through `out` and `err` must be released with `dxf_weld_free`.
+///
+/// # Safety
+///
+/// `input` and `opts_json` must be null or valid nul-terminated strings; `out` and `err` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn dxf_weld(input: *const c_char, opts_json: *const c_char, out: *mut *mut c_char, err: *mut *mut c_char) -> c_int {
+    if !out.is_null() {
+        *out = ptr::null_mut();
+    }
+    if !err.is_null() {
+        *err = ptr::null_mut();
+    }
+    let result = panic::catch_unwind(|| weld(input, opts_json))
+        .unwrap_or_else(|_| Err(weld_err!("panic while welding")));
+    match result {
+        Ok(text) => {
+            if !out.is_null() {
+                *out = into_c_string(text);
+            }
+            0
+        },
+        Err(e) => {
+            if !err.is_null() {
+                *err = into_c_string(e.to_string());
+            }
+            -1
+        },
+    }
+}
+
+/// Releases a string returned by `dxf_weld`.
+///
+/// # Safety
+///
+/// `text` must be null or a pointer returned by `dxf_weld` that has not been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn dxf_weld_free(text: *mut c_char) {
+    if !text.is_null() {
+        drop(CString::from_raw(text));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffi_error() {
+        let input = CString::new("  0\nSECTION\n  2\nENTITIES\n  0\nBOGUS\n").unwrap();
+        let mut out = ptr::null_mut();
+        let mut err = ptr::null_mut();
+        unsafe {
+            assert_eq!(dxf_weld(input.as_ptr(), ptr::null(), &mut out, &mut err), -1);
+            assert!(out.is_null());
+            assert!(CStr::from_ptr(err).to_str().unwrap().contains("BOGUS"));
+            dxf_weld_free(err);
+        }
+    }
+}
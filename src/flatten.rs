@@ -0,0 +1,47 @@
+use crate::dxf::*;
+
+impl Drawing {
+    // Replaces every arc, circle, ellipse and polyline with a chain of LINEs that strays no more than
+    // `chord_error` from the curve, for controllers that only accept lines. The inverse of welding.
+    pub fn flatten(&mut self, chord_error: f64) {
+        for layer in self.layers.iter_mut() {
+            layer.entities = layer.entities.drain(..).flat_map(|entity| match entity {
+                Entity::Line(..) => vec![entity],
+                other => other.tessellate(chord_error).windows(2)
+                    .map(|pair| Entity::Line(pair[0].clone(), pair[1].clone()))
+                    .collect(),
+            }).collect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flatten() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let mut drawing = Drawing::new(vec![
+            Entity::Ellipse { center: p(0.0, 0.0), major_axis: p(0.0, 10.0), ratio: 0.5, start_param: 0.0, end_param: std::f64::consts::PI },
+        ]);
+        drawing.flatten(0.01);
+        let entities = drawing.entities().collect::<Vec<_>>();
+        assert!(entities.len() > 10);
+        for entity in entities.iter() {
+            match entity {
+                // the half ellipse has x in [-5, 0] and y in [-10, 10]
+                Entity::Line(from, _) => assert!(((from.x / 5.0).powi(2) + (from.y / 10.0).powi(2) - 1.0).abs() < 1e-9 && from.x <= 1e-9),
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+
+        let mut drawing = Drawing::new(vec![
+            Entity::Polyline { curve_type: 6, vertices: vec![p(0.0, 0.0), p(1.0, 2.0), p(3.0, 2.0), p(4.0, 0.0), p(6.0, 1.0)], widths: vec![] },
+        ]);
+        drawing.flatten(0.01);
+        let entities = drawing.entities().collect::<Vec<_>>();
+        assert!(matches!(entities.first().unwrap(), Entity::Line(from, _) if *from == p(0.0, 0.0)));
+        assert!(matches!(entities.last().unwrap(), Entity::Line(_, to) if *to == p(6.0, 1.0)));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::dxf::format_number;
+
+// gaps up to this many times the resolution are reported as keeping chains from closing
+pub const MAX_GAP_RESOLUTIONS: f64 = 10.0;
+// characters in the longest bar of the histogram
+const HISTOGRAM_WIDTH: usize = 40;
+
+// the number of gaps in each decade of size, as (lower bound, count), from the smallest gap's decade
+// up to the largest's
+pub fn histogram(gaps: &[f64]) -> Vec<(f64, usize)> {
+    let decade = |gap: f64| gap.log10().floor() as i32;
+    let (low, high) = match (gaps.iter().cloned().reduce(f64::min), gaps.iter().cloned().reduce(f64::max)) {
+        (Some(low), Some(high)) => (decade(low), decade(high)),
+        _ => return vec![],
+    };
+    (low..=high).map(|power| (10f64.powi(power), gaps.iter().filter(|gap| decade(**gap) == power).count())).collect()
+}
+
+// the smallest of 1, 2 or 5 times a power of ten that joins every gap
+pub fn suggested_join_tolerance(gaps: &[f64]) -> Option<f64> {
+    let largest = gaps.iter().cloned().reduce(f64::max)?;
+    let power = 10f64.powi(largest.log10().floor() as i32);
+    [1.0, 2.0, 5.0, 10.0].iter().map(|step| step * power).find(|tolerance| *tolerance >= largest)
+}
+
+// A warning listing the gaps between dangling ends with a histogram of their sizes and the
+// `--join-tolerance` that would close them, or None without gaps.
+pub fn format_report(gaps: &[f64]) -> Option<String> {
+    Some(format!("warning: {}\n{}", describe(gaps)?, format_histogram(gaps)))
+}
+
+// how many gaps there are, how large and the `--join-tolerance` closing them, or None without gaps
+pub fn describe(gaps: &[f64]) -> Option<String> {
+    let tolerance = suggested_join_tolerance(gaps)?;
+    Some(format!(
+        "{} {} up to {} keep chains from joining or closing; --join-tolerance {} would join them",
+        gaps.len(), if gaps.len() == 1 { "gap" } else { "gaps" }, format_number(gaps[gaps.len() - 1]), format_number(tolerance),
+    ))
+}
+
+// a line per decade of gap size with a bar as long as its share of the most gaps in a decade
+pub fn format_histogram(gaps: &[f64]) -> String {
+    let mut report = String::new();
+    let bins = histogram(gaps);
+    let most = bins.iter().map(|(_, count)| *count).max().unwrap_or(1);
+    let labels = bins.iter().map(|(low, _)| format!("{} - {}", format_number(*low), format_number(low * 10.0))).collect::<Vec<_>>();
+    let width = labels.iter().map(String::len).max().unwrap_or(0);
+    for (label, (_, count)) in labels.iter().zip(bins.iter()) {
+        let bar = (count * HISTOGRAM_WIDTH).div_ceil(most);
+        report += &format!("  {:<width$}  {:>5} {}\n", label, count, "#".repeat(bar), width = width);
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cleanup;
+    use crate::dxf::*;
+
+    fn line(x1: f64, y1: f64, x2: f64, y2: f64) -> Entity {
+        Entity::Line(Point { x: x1, y: y1 }, Point { x: x2, y: y2 })
+    }
+
+    #[test]
+    fn test_gaps() {
+        let drawing = Drawing::new(vec![
+            // a square failing to close by 0.003, and a line 0.02 short of the next
+            line(0.0, 0.0, 10.0, 0.0),
+            line(10.0, 0.0, 10.0, 10.0),
+            line(10.0, 10.0, 0.0, 10.0),
+            line(0.0, 10.0, 0.0, 0.003),
+            line(20.0, 0.0, 30.0, 0.0),
+            line(30.02, 0.0, 40.0, 0.0),
+            // a short lone line, which is no gap
+            line(50.0, 0.0, 50.1, 0.0),
+        ]);
+        let gaps = cleanup::gaps(&drawing, 0.5);
+        assert_eq!(gaps.len(), 2);
+        assert!((gaps[0] - 0.003).abs() < 1e-9 && (gaps[1] - 0.02).abs() < 1e-9);
+        assert_eq!(cleanup::gaps(&drawing, 0.01).len(), 1);
+
+        assert_eq!(histogram(&gaps), vec![(0.001, 1), (0.01, 1)]);
+        assert_eq!(suggested_join_tolerance(&gaps), Some(0.02));
+        assert_eq!(suggested_join_tolerance(&[0.0042]), Some(0.005));
+        assert_eq!(suggested_join_tolerance(&[]), None);
+        assert!(format_report(&gaps).unwrap().contains("--join-tolerance 0.02"));
+
+        let mut joined = drawing.clone();
+        cleanup::snap_endpoints(&mut joined, suggested_join_tolerance(&gaps).unwrap());
+        assert!(cleanup::gaps(&joined, 0.5).is_empty());
+    }
+}
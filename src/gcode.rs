@@ -0,0 +1,464 @@
+use crate::dxf::*;
+use crate::tessellate::FINE_CHORD_ERROR;
+use crate::dxf_process::DxfConfig;
+use crate::output::OutputBackend;
+use crate::report::ChainReport;
+use crate::result::*;
+use std::fmt;
+
+// a run of consecutive G1 XY moves at a constant feed, starting from the position before the first move
+#[derive(Clone, Debug, PartialEq)]
+pub struct MoveRun {
+    pub feed: Option<f64>,
+    pub points: Vec<Point>,
+    pub numbering: Numbering,
+}
+
+// The N words and checksums of a run's moves, so the moves written in their place are numbered the
+// same way. Welding writes fewer moves than it replaces, which take the first of the run's numbers.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Numbering {
+    pub numbers: Vec<u64>,
+    // each move ends with `*` and the XOR of the line's bytes before it
+    pub checksums: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Block {
+    Raw(String),
+    Moves(MoveRun),
+    Welded {
+        feed: Option<f64>,
+        start: Point,
+        entities: Vec<Entity>,
+        numbering: Numbering,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Program {
+    pub blocks: Vec<Block>,
+}
+
+pub fn is_gcode_path(path: &str) -> bool {
+    let lower = path.to_ascii_lowercase();
+    [".gcode", ".gco", ".nc", ".ngc", ".tap"].iter().any(|ext| lower.ends_with(ext))
+}
+
+fn strip_comments(line: &str) -> String {
+    let line = line.split(';').next().unwrap_or("");
+    let mut out = String::new();
+    let mut depth = 0;
+    for c in line.chars() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            c if depth == 0 => out.push(c),
+            _ => (),
+        }
+    }
+    out
+}
+
+// the words of a line, and whether it ends with a checksum, which isn't checked
+fn parse_words(line: &str) -> Result<(Vec<(char, f64)>, bool)> {
+    let line = strip_comments(line);
+    let (line, checksum) = match line.split_once('*') {
+        Some((words, checksum)) if checksum.trim().chars().all(|c| c.is_ascii_digit()) => (words, true),
+        Some(_) => return Err(weld_err!("invalid gcode checksum: {}", line)),
+        None => (line.as_str(), false),
+    };
+    let mut words = vec![];
+    let mut chars = line.chars().filter(|c| !c.is_whitespace()).peekable();
+    while let Some(letter) = chars.next() {
+        if !letter.is_ascii_alphabetic() {
+            return Err(weld_err!("unexpected character in gcode: {}", letter));
+        }
+        let mut number = String::new();
+        while let Some(c) = chars.peek() {
+            if c.is_ascii_digit() || *c == '.' || *c == '-' || *c == '+' {
+                number.push(*c);
+                chars.next();
+            } else {
+                break;
+            }
+        }
+        let value = number.parse::<f64>().map_err(|_| weld_err!("invalid number for gcode word {}: '{}'", letter, number))?;
+        words.push((letter.to_ascii_uppercase(), value));
+    }
+    Ok((words, checksum))
+}
+
+// the XOR of a line's bytes, as RepRap hosts check it
+fn checksum(line: &str) -> u8 {
+    line.bytes().fold(0, |sum, byte| sum ^ byte)
+}
+
+// `text` with each line numbered and checksummed after `numbering`. Lines past the last number are
+// left plain, as hosts only check the checksum of a numbered line.
+fn number_lines(text: &str, numbering: &Numbering) -> String {
+    let mut out = String::new();
+    for (i, line) in text.lines().enumerate() {
+        let number = numbering.numbers.get(i);
+        let line = match number {
+            Some(number) => format!("N{} {}", number, line),
+            None => line.to_string(),
+        };
+        out.push_str(&line);
+        if numbering.checksums && number.is_some() {
+            out.push_str(&format!("*{}", checksum(&line)));
+        }
+        out.push('\n');
+    }
+    out
+}
+
+struct ParseState {
+    position: Point,
+    absolute: bool,
+    motion: Option<u32>,
+    feed: Option<f64>,
+}
+
+impl Program {
+    pub fn parse(src: &str) -> Result<Program> {
+        let mut blocks = vec![];
+        let mut state = ParseState {
+            position: Point { x: 0.0, y: 0.0 },
+            absolute: true,
+            motion: None,
+            feed: None,
+        };
+        let mut run: Option<MoveRun> = None;
+
+        for line in src.lines() {
+            // the % around the program, as some controllers want
+            if line.trim() == "%" {
+                if let Some(done) = run.take() {
+                    blocks.push(Block::Moves(done));
+                }
+                blocks.push(Block::Raw(line.to_string()));
+                continue;
+            }
+            let (words, checksummed) = parse_words(line)?;
+            let mut motion = state.motion;
+            let mut feed = state.feed;
+            let mut target = state.position.clone();
+            let mut weldable = true;
+            let mut has_xy = false;
+            let mut number = None;
+            for (letter, value) in words.iter() {
+                match letter {
+                    'G' if *value == 0.0 || *value == 1.0 || *value == 2.0 || *value == 3.0 => motion = Some(*value as u32),
+                    'G' if *value == 90.0 => {
+                        state.absolute = true;
+                        weldable = false;
+                    },
+                    'G' if *value == 91.0 => {
+                        state.absolute = false;
+                        weldable = false;
+                    },
+                    'X' | 'Y' => {
+                        let axis = if *letter == 'X' { &mut target.x } else { &mut target.y };
+                        if state.absolute {
+                            *axis = *value;
+                        } else {
+                            *axis += value;
+                        }
+                        has_xy = true;
+                    },
+                    'F' => feed = Some(*value),
+                    'N' => number = Some(*value as u64),
+                    _ => weldable = false,
+                }
+            }
+            // relative moves are passed through, as welded moves are written in absolute coordinates,
+            // and so are moves with comments, which have nowhere to go once welded
+            let commented = strip_comments(line).len() != line.len();
+            weldable = weldable && has_xy && motion == Some(1) && state.absolute && !commented;
+
+            if weldable {
+                let continues = matches!(&run, Some(current) if current.feed == feed && current.numbering.checksums == checksummed);
+                if !continues {
+                    if let Some(done) = run.take() {
+                        blocks.push(Block::Moves(done));
+                    }
+                    run = Some(MoveRun {
+                        feed,
+                        points: vec![state.position.clone()],
+                        numbering: Numbering { numbers: vec![], checksums: checksummed },
+                    });
+                }
+                let current = run.as_mut().unwrap();
+                current.points.push(target.clone());
+                current.numbering.numbers.extend(number);
+            } else {
+                if let Some(done) = run.take() {
+                    blocks.push(Block::Moves(done));
+                }
+                blocks.push(Block::Raw(line.to_string()));
+            }
+
+            // arcs already in the file still move the tool to their endpoint
+            state.position = target;
+            state.motion = motion;
+            state.feed = feed;
+        }
+        if let Some(done) = run.take() {
+            blocks.push(Block::Moves(done));
+        }
+
+        Ok(Program {
+            blocks,
+        })
+    }
+}
+
+fn emit_move(out: &mut String, code: &str, feed: &mut Option<f64>, words: &[(&str, f64)]) {
+    out.push_str(code);
+    for (letter, value) in words.iter() {
+        // round first so tiny negatives don't print as -0.0000
+        let value = (value * 10000.0).round() / 10000.0 + 0.0;
+        out.push_str(&format!(" {}{:.4}", letter, value));
+    }
+    if let Some(feed) = feed.take() {
+        out.push_str(&format!(" F{}", feed));
+    }
+    out.push('\n');
+}
+
+pub struct GcodeBackend<'a> {
+    out: &'a mut String,
+    // where the tool is, if known; entities not starting here get a rapid move first
+    pub position: Option<Point>,
+    // emitted on the next cutting move
+    pub feed: Option<f64>,
+    // write arcs as CIP moves through a point halfway along rather than G2/G3 around the center
+    pub three_point_arcs: bool,
+}
+
+impl<'a> GcodeBackend<'a> {
+    pub fn new(out: &'a mut String) -> GcodeBackend<'a> {
+        GcodeBackend {
+            out,
+            position: None,
+            feed: None,
+            three_point_arcs: false,
+        }
+    }
+
+    fn rapid_to(&mut self, point: &Point) -> Point {
+        if self.position.as_ref() != Some(point) {
+            emit_move(self.out, "G0", &mut None, &[("X", point.x), ("Y", point.y)]);
+        }
+        point.clone()
+    }
+}
+
+impl<'a> OutputBackend for GcodeBackend<'a> {
+    fn buffer(&mut self) -> Option<&mut String> {
+        Some(self.out)
+    }
+
+    fn entity(&mut self, entity: &Entity) {
+        match entity {
+            Entity::Line(from, to) => {
+                self.rapid_to(from);
+                emit_move(self.out, "G1", &mut self.feed, &[("X", to.x), ("Y", to.y)]);
+                self.position = Some(to.clone());
+            },
+            Entity::Arc { center, radius, start_angle, end_angle } => {
+                let arc_start = center.polar(*radius, *start_angle);
+                let arc_end = center.polar(*radius, *end_angle);
+                let near_start = match &self.position {
+                    Some(position) => position.dist(&arc_start) <= position.dist(&arc_end),
+                    None => true,
+                };
+                // arcs are stored counter-clockwise, so a chain entering at the end angle runs clockwise
+                let (code, from, to) = if near_start {
+                    ("G3", arc_start, arc_end)
+                } else {
+                    ("G2", arc_end, arc_start)
+                };
+                let from = self.rapid_to(&from);
+                if self.three_point_arcs {
+                    let mid = entity.three_point().map(|arc| arc.mid).unwrap_or_else(|| to.clone());
+                    emit_move(self.out, "CIP", &mut self.feed, &[("X", to.x), ("Y", to.y), ("I1=", mid.x), ("J1=", mid.y)]);
+                } else {
+                    emit_move(self.out, code, &mut self.feed, &[("X", to.x), ("Y", to.y), ("I", center.x - from.x), ("J", center.y - from.y)]);
+                }
+                self.position = Some(to);
+            },
+            Entity::Circle { center, radius } => {
+                let from = match &self.position {
+                    Some(position) if &center.polar(*radius, position.angle(center).to_degrees()) == position => position.clone(),
+                    _ => self.rapid_to(&center.polar(*radius, 0.0)),
+                };
+                emit_move(self.out, "G3", &mut self.feed, &[("X", from.x), ("Y", from.y), ("I", center.x - from.x), ("J", center.y - from.y)]);
+                self.position = Some(from);
+            },
+            Entity::Ellipse { .. } | Entity::Polyline { .. } => {
+                let vertices = entity.tessellate(FINE_CHORD_ERROR);
+                if let Some(first) = vertices.first() {
+                    self.rapid_to(first);
+                }
+                for vertex in vertices.iter().skip(1) {
+                    emit_move(self.out, "G1", &mut self.feed, &[("X", vertex.x), ("Y", vertex.y)]);
+                }
+                if let Some(last) = vertices.last() {
+                    self.position = Some(last.clone());
+                }
+            },
+        }
+    }
+}
+
+impl Drawing {
+    pub fn to_gcode(&self) -> String {
+        let mut out = String::new();
+        self.write_to(&mut GcodeBackend::new(&mut out));
+        out
+    }
+}
+
+impl Program {
+    // the program's text, with welded arcs written as CIP moves when `three_point_arcs` is set
+    pub fn to_gcode(&self, three_point_arcs: bool) -> String {
+        let mut out = String::new();
+        for block in self.blocks.iter() {
+            match block {
+                Block::Raw(line) => {
+                    out.push_str(line);
+                    out.push('\n');
+                },
+                Block::Moves(run) => {
+                    let mut moves = String::new();
+                    let mut feed = run.feed;
+                    for point in run.points[1..].iter() {
+                        emit_move(&mut moves, "G1", &mut feed, &[("X", point.x), ("Y", point.y)]);
+                    }
+                    out.push_str(&number_lines(&moves, &run.numbering));
+                },
+                Block::Welded { feed, start, entities, numbering } => {
+                    let mut moves = String::new();
+                    let mut backend = GcodeBackend::new(&mut moves);
+                    backend.position = Some(start.clone());
+                    backend.feed = *feed;
+                    backend.three_point_arcs = three_point_arcs;
+                    for entity in entities.iter() {
+                        backend.entity(entity);
+                    }
+                    out.push_str(&number_lines(&moves, numbering));
+                },
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_gcode(false))
+    }
+}
+
+impl DxfConfig {
+    pub fn process_program(&self, program: Program) -> Result<Program> {
+        let mut blocks = vec![];
+        for block in program.blocks.into_iter() {
+            match block {
+                Block::Moves(run) if run.points.len() > self.min_segments => {
+                    let start = run.points[0].clone();
+                    blocks.push(Block::Welded {
+                        feed: run.feed,
+                        start,
+                        entities: self.process_chain(&run.points, &mut ChainReport::default())?,
+                        numbering: run.numbering,
+                    });
+                },
+                block => blocks.push(block),
+            }
+        }
+        Ok(Program {
+            blocks,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_weld_gcode_arc() {
+        let mut src = String::from("G21 ; mm\nG90\nG0 X10 Y0\n");
+        for i in 1..=16 {
+            let theta = std::f64::consts::PI / 2.0 * i as f64 / 16.0;
+            src.push_str(&format!("G1 X{:.6} Y{:.6} F600\n", 10.0 * theta.cos(), 10.0 * theta.sin()));
+        }
+        src.push_str("M5\n");
+        let program = Program::parse(&src).unwrap();
+        assert_eq!(program.blocks.len(), 5);
+        let config = DxfConfig {
+            resolution: 0.05,
+            max_radius: 100000.0,
+            min_segments: 3,
+            ..Default::default()
+        };
+        let out = config.process_program(program).unwrap().to_string();
+        let moves = out.lines().filter(|l| l.starts_with("G1") || l.starts_with("G3")).collect::<Vec<_>>();
+        assert_eq!(moves.len(), 1);
+        assert!(moves[0].starts_with("G3 X0.0000 Y10.0000 I-10.0000 J0.0000 F600"));
+    }
+
+    #[test]
+    fn test_numbered_program() {
+        let mut src = String::from("%\nN10 G90 (absolute)\nN20 G0 X10 Y0\n");
+        for i in 1..=16 {
+            let theta = std::f64::consts::PI / 2.0 * i as f64 / 16.0;
+            let line = format!("N{} G1 X{:.6} Y{:.6} F600", 20 + i * 10, 10.0 * theta.cos(), 10.0 * theta.sin());
+            src.push_str(&format!("{}*{}\n", line, checksum(&line)));
+        }
+        src.push_str("N190 G1 X0 Y20 ; up the side\nN200 G91\nN210 G1 X1 Y1\nN220 G1 X1 Y0\nN230 G1 X1 Y-1\nN240 G1 X1 Y0\n%\n");
+        let program = Program::parse(&src).unwrap();
+        let config = DxfConfig { resolution: 0.05, max_radius: 100000.0, min_segments: 3, ..Default::default() };
+        let out = config.process_program(program).unwrap().to_string();
+        let lines = out.lines().collect::<Vec<_>>();
+        assert_eq!(lines[..3], ["%", "N10 G90 (absolute)", "N20 G0 X10 Y0"]);
+        let arc = "N30 G3 X0.0000 Y10.0000 I-10.0000 J0.0000 F600";
+        assert_eq!(lines[3], format!("{}*{}", arc, checksum(arc)));
+        // the commented move and the relative ones are kept as they were
+        assert_eq!(lines[4..], src.lines().skip(19).collect::<Vec<_>>()[..]);
+
+        assert!(Program::parse("G1 X1 Y1*x\n").is_err());
+    }
+
+    #[test]
+    fn test_number_lines() {
+        let numbering = Numbering { numbers: vec![10, 20], checksums: true };
+        let numbered = number_lines("G1 X1 Y0\nG1 X1 Y1\nG1 X0 Y1\n", &numbering);
+        let (first, second) = ("N10 G1 X1 Y0", "N20 G1 X1 Y1");
+        assert_eq!(numbered, format!("{}*{}\n{}*{}\nG1 X0 Y1\n", first, checksum(first), second, checksum(second)));
+    }
+
+    #[test]
+    fn test_three_point_arcs() {
+        let arc = Entity::Arc { center: Point { x: 0.0, y: 0.0 }, radius: 10.0, start_angle: 0.0, end_angle: 90.0 };
+        let three_point = arc.three_point().unwrap();
+        assert_eq!(three_point.mid, Point { x: 50f64.sqrt(), y: 50f64.sqrt() });
+        match three_point.to_entity() {
+            Some(Entity::Arc { center, radius, start_angle, end_angle }) => {
+                assert_eq!(center, Point { x: 0.0, y: 0.0 });
+                assert!((radius - 10.0).abs() < 1e-9 && start_angle.abs() < 1e-9 && (end_angle - 90.0).abs() < 1e-9);
+            },
+            other => panic!("expected an arc, got {:?}", other),
+        }
+
+        let mut out = String::new();
+        let mut backend = GcodeBackend::new(&mut out);
+        backend.three_point_arcs = true;
+        backend.position = Some(Point { x: 0.0, y: 10.0 });
+        backend.entity(&arc);
+        assert_eq!(out, "CIP X10.0000 Y0.0000 I1=7.0711 J1=7.0711\n");
+    }
+}
@@ -0,0 +1,61 @@
+// conversions to and from `geo-types` coordinates and line strings
+use crate::dxf::*;
+use geo_types::{Coord, LineString};
+use std::iter::FromIterator;
+
+impl From<&Point> for Coord<f64> {
+    fn from(point: &Point) -> Coord<f64> {
+        Coord { x: point.x, y: point.y }
+    }
+}
+
+impl From<Coord<f64>> for Point {
+    fn from(coord: Coord<f64>) -> Point {
+        Point {
+            x: coord.x,
+            y: coord.y,
+        }
+    }
+}
+
+impl Entity {
+    // the entity's path, with curves split into segments within `chord_error`
+    pub fn to_line_string(&self, chord_error: f64) -> LineString<f64> {
+        self.tessellate(chord_error).iter().map(Coord::from).collect()
+    }
+}
+
+impl Drawing {
+    pub fn to_line_strings(&self, chord_error: f64) -> Vec<LineString<f64>> {
+        self.entities().map(|entity| entity.to_line_string(chord_error)).collect()
+    }
+}
+
+// one LINE per segment, on layer 0, ready to weld
+impl FromIterator<LineString<f64>> for Drawing {
+    fn from_iter<I: IntoIterator<Item = LineString<f64>>>(line_strings: I) -> Drawing {
+        let entities = line_strings.into_iter().flat_map(|line_string| {
+            line_string.lines().map(|line| Entity::Line(line.start.into(), line.end.into())).collect::<Vec<_>>()
+        }).collect();
+        Drawing::new(entities)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let square = LineString::from(vec![(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0), (0.0, 0.0)]);
+        let drawing: Drawing = vec![square.clone()].into_iter().collect();
+        assert_eq!(drawing.entity_count(), 4);
+        let line_strings = drawing.to_line_strings(0.01);
+        assert_eq!(line_strings[1], LineString::from(vec![(1.0, 0.0), (1.0, 1.0)]));
+
+        let circle = Entity::Circle { center: Point { x: 0.0, y: 0.0 }, radius: 1.0 }.to_line_string(0.01);
+        let (first, last) = (circle.0.first().unwrap(), circle.0.last().unwrap());
+        assert_eq!(Point::from(*first), Point::from(*last));
+        assert!(circle.coords().all(|coord| (Point::from(*coord).dist(&Point { x: 0.0, y: 0.0 }) - 1.0).abs() < 1e-9));
+    }
+}
@@ -0,0 +1,112 @@
+use crate::dxf::*;
+use crate::tessellate::{ccw_sweep, FINE_CHORD_ERROR};
+use serde_json::json;
+
+pub fn is_geojson_path(path: &str) -> bool {
+    let lower = path.to_ascii_lowercase();
+    lower.ends_with(".geojson") || lower.ends_with(".json")
+}
+
+pub fn is_wkt_path(path: &str) -> bool {
+    path.to_ascii_lowercase().ends_with(".wkt")
+}
+
+// joins consecutive connected entities into point paths
+fn paths(drawing: &Drawing, chord_error: f64) -> Vec<Vec<Point>> {
+    let mut paths: Vec<Vec<Point>> = vec![];
+    for entity in drawing.entities() {
+        let mut points = entity.tessellate(chord_error);
+        if points.len() < 2 {
+            continue;
+        }
+        if let Some(path) = paths.last_mut() {
+            let last = path.last().unwrap();
+            if last == points.last().unwrap() {
+                points.reverse();
+            }
+            if last == &points[0] {
+                path.extend(points.into_iter().skip(1));
+                continue;
+            }
+        }
+        paths.push(points);
+    }
+    paths
+}
+
+fn coordinates(points: &[Point]) -> serde_json::Value {
+    points.iter().map(|p| json!([p.x, p.y])).collect()
+}
+
+impl Drawing {
+    // arcs and circles are tessellated so that no chord strays more than `chord_error` from the true curve
+    pub fn to_geojson(&self, chord_error: f64) -> String {
+        let features = paths(self, chord_error).into_iter().map(|path| {
+            let geometry = if path.len() > 3 && path.first() == path.last() {
+                json!({ "type": "Polygon", "coordinates": [coordinates(&path)] })
+            } else {
+                json!({ "type": "LineString", "coordinates": coordinates(&path) })
+            };
+            json!({ "type": "Feature", "properties": {}, "geometry": geometry })
+        }).collect::<Vec<_>>();
+        json!({ "type": "FeatureCollection", "features": features }).to_string()
+    }
+
+    // arcs are kept exact as CIRCULARSTRINGs through their start, mid and end points
+    pub fn to_wkt(&self) -> String {
+        let wkt_points = |points: &[Point]| points.iter().map(|p| format!("{} {}", p.x, p.y)).collect::<Vec<_>>().join(", ");
+        let geometries = self.entities().map(|entity| match entity {
+            Entity::Line(from, to) => format!("LINESTRING ({})", wkt_points(&[from.clone(), to.clone()])),
+            Entity::Arc { center, radius, start_angle, end_angle } => {
+                let sweep = ccw_sweep(*start_angle, *end_angle);
+                let points = [0.0, 0.5, 1.0].iter().map(|t| center.polar(*radius, start_angle + sweep * t)).collect::<Vec<_>>();
+                format!("CIRCULARSTRING ({})", wkt_points(&points))
+            },
+            Entity::Circle { center, radius } => {
+                let points = [0.0, 90.0, 180.0, 270.0, 0.0].iter().map(|angle| center.polar(*radius, *angle)).collect::<Vec<_>>();
+                format!("CIRCULARSTRING ({})", wkt_points(&points))
+            },
+            // spline-fit polylines follow their curve rather than their frame
+            Entity::Ellipse { .. } | Entity::Polyline { .. } => format!("LINESTRING ({})", wkt_points(&entity.tessellate(FINE_CHORD_ERROR))),
+        }).collect::<Vec<_>>();
+        format!("GEOMETRYCOLLECTION ({})\n", geometries.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_circle_is_polygon() {
+        let drawing = Drawing::new(vec![Entity::Circle {
+            center: Point { x: 0.0, y: 0.0 },
+            radius: 10.0,
+        }]);
+        let parsed: serde_json::Value = serde_json::from_str(&drawing.to_geojson(0.01)).unwrap();
+        let geometry = &parsed["features"][0]["geometry"];
+        assert_eq!(geometry["type"], "Polygon");
+        for point in geometry["coordinates"][0].as_array().unwrap() {
+            let (x, y) = (point[0].as_f64().unwrap(), point[1].as_f64().unwrap());
+            assert!(((x * x + y * y).sqrt() - 10.0).abs() < 0.0001);
+        }
+    }
+
+    #[test]
+    fn test_wkt_spline() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let frame = vec![p(0.0, 0.0), p(5.0, 10.0), p(10.0, 0.0)];
+        let plain = Drawing::new(vec![Entity::Polyline { curve_type: 0, vertices: frame.clone(), widths: vec![] }]);
+        assert_eq!(plain.to_wkt(), "GEOMETRYCOLLECTION (LINESTRING (0 0, 5 10, 10 0))\n");
+        // a quadratic spline through the same frame peaks halfway up it
+        let spline = Drawing::new(vec![Entity::Polyline { curve_type: 5, vertices: frame, widths: vec![] }]);
+        let wkt = spline.to_wkt();
+        let points = wkt.trim_start_matches("GEOMETRYCOLLECTION (LINESTRING (").trim_end_matches("))\n").split(", ").map(|point| {
+            let (x, y) = point.split_once(' ').unwrap();
+            p(x.parse().unwrap(), y.parse().unwrap())
+        }).collect::<Vec<_>>();
+        assert!(points.len() > 3);
+        assert_eq!((points[0].clone(), points[points.len() - 1].clone()), (p(0.0, 0.0), p(10.0, 0.0)));
+        assert!(points.iter().all(|point| point.y <= 5.0 + 1e-9));
+    }
+}
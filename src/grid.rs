@@ -0,0 +1,141 @@
+use crate::dxf::*;
+
+// `value` rounded to a multiple of `grid`, dividing by the grid's inverse so that steps like 0.01 give
+// 0.3 rather than 0.30000000000000004
+fn snap(value: f64, grid: f64) -> f64 {
+    (value / grid).round() / (1.0 / grid)
+}
+
+fn snap_point(point: &Point, grid: f64) -> Point {
+    Point { x: snap(point.x, grid), y: snap(point.y, grid) }
+}
+
+// The entity with its coordinates on the grid, or None if it shrinks away. Arcs keep running through
+// their ends: they are snapped and the center moved along the ends' bisector to where it is closest to
+// where it was, so lines and arcs sharing an end still do.
+fn snap_entity(entity: Entity, grid: f64) -> Option<Entity> {
+    Some(match entity {
+        Entity::Line(from, to) => {
+            let (from, to) = (snap_point(&from, grid), snap_point(&to, grid));
+            if from == to {
+                return None;
+            }
+            Entity::Line(from, to)
+        },
+        Entity::Arc { ref center, radius, start_angle, end_angle } => {
+            let (start, end) = (snap_point(&entity.start_point()?, grid), snap_point(&entity.end_point()?, grid));
+            if start == end {
+                // an arc nearly all the way round closes into a circle, a short one vanishes
+                let circle = Entity::Circle { center: center.clone(), radius };
+                return if (end_angle - start_angle).rem_euclid(360.0) > 180.0 { snap_entity(circle, grid) } else { None };
+            }
+            Entity::arc_between(&start, &end, center)
+        },
+        Entity::Circle { center, radius } => {
+            let radius = snap(radius, grid);
+            if radius <= 0.0 {
+                return None;
+            }
+            Entity::Circle { center: snap_point(&center, grid), radius }
+        },
+        Entity::Ellipse { center, major_axis, ratio, start_param, end_param } => {
+            Entity::Ellipse { center: snap_point(&center, grid), major_axis, ratio, start_param, end_param }
+        },
+        Entity::Polyline { curve_type, vertices, widths } => {
+            Entity::Polyline { curve_type, vertices: vertices.iter().map(|vertex| snap_point(vertex, grid)).collect(), widths }
+        },
+    })
+}
+
+fn snap_layers(layers: &mut [Layer], grid: f64) -> usize {
+    let mut dropped = 0;
+    for layer in layers.iter_mut() {
+        let before = layer.entities.len();
+        layer.entities = layer.entities.drain(..).filter_map(|entity| snap_entity(entity, grid)).collect();
+        dropped += before - layer.entities.len();
+        for insert in layer.inserts.iter_mut() {
+            insert.position = snap_point(&insert.position, grid);
+        }
+        for raw in layer.raw.iter_mut() {
+            *raw = raw.map_points(raw.point_codes(), |point| snap_point(point, grid));
+        }
+    }
+    dropped
+}
+
+impl Drawing {
+    // Moves every coordinate onto a grid of `grid` units, for CAM systems with limited precision:
+    // line ends, circle centers and radii, polyline vertices, ellipse centers, insert positions, the
+    // points of MLINE and LEADER entities and block bases. Arcs have their ends snapped and their centers and radii follow, so connected
+    // entities stay connected. Returns the number of entities dropped for shrinking to nothing.
+    pub fn snap_to_grid(&mut self, grid: f64) -> usize {
+        let mut dropped = snap_layers(&mut self.layers, grid);
+        for block in self.blocks.iter_mut() {
+            block.base = snap_point(&block.base, grid);
+            dropped += snap_layers(&mut block.layers, grid);
+        }
+        dropped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn p(x: f64, y: f64) -> Point {
+        Point { x, y }
+    }
+
+    #[test]
+    fn test_snap_to_grid() {
+        let center = p(10.003, 0.004);
+        let arc = Entity::Arc { center: center.clone(), radius: 5.0, start_angle: 0.0, end_angle: 90.0 };
+        let (start, end) = (arc.start_point().unwrap(), arc.end_point().unwrap());
+        let mut drawing = Drawing::new(vec![
+            Entity::Line(p(0.0, 0.0), start.clone()),
+            arc,
+            Entity::Line(end, p(0.0, 5.0041)),
+            Entity::Line(p(20.0, 0.0), p(20.002, 0.001)),
+            Entity::Circle { center: p(30.0049, 30.0), radius: 2.3456 },
+        ]);
+        assert_eq!(drawing.snap_to_grid(0.01), 1);
+        let entities = &drawing.layers[0].entities;
+        assert_eq!(entities.len(), 4);
+        assert_eq!(entities[0], Entity::Line(p(0.0, 0.0), p(15.0, 0.0)));
+        assert_eq!(entities[2], Entity::Line(p(10.0, 5.0), p(0.0, 5.0)));
+        assert_eq!(entities[1].start_point().unwrap(), p(15.0, 0.0));
+        assert_eq!(entities[1].end_point().unwrap(), p(10.0, 5.0));
+        assert_eq!(entities[3], Entity::Circle { center: p(30.0, 30.0), radius: 2.35 });
+        match &entities[1] {
+            Entity::Arc { center: snapped, radius, .. } => assert!(snapped.dist(&center) < 0.01 && (radius - 5.0).abs() < 0.01),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_snap_closing_arc() {
+        // an arc whose ends snap together becomes a circle of its own radius, not of its length
+        let mut drawing = Drawing::new(vec![
+            Entity::Arc { center: p(0.0, 0.0), radius: 2.451, start_angle: 0.0, end_angle: 359.4 },
+            Entity::Arc { center: p(10.0, 0.0), radius: 2.451, start_angle: 0.0, end_angle: 0.6 },
+        ]);
+        assert_eq!(drawing.snap_to_grid(0.1), 1);
+        assert_eq!(drawing.layers[0].entities, vec![Entity::Circle { center: p(0.0, 0.0), radius: 2.5 }]);
+    }
+
+    #[test]
+    fn test_snap_raw() {
+        let codes = |codes: &[(u16, &str)]| codes.iter().map(|(code, value)| (*code, value.to_string())).collect::<Vec<_>>();
+        let mut drawing = Drawing::default();
+        drawing.layers.push(Layer::new("OUTLINE", None));
+        drawing.layers[0].raw.push(RawEntity {
+            kind: "MLINE".to_string(),
+            codes: codes(&[(40, "1.003"), (10, "0.004"), (20, "1.996"), (11, "4.0071"), (21, "0.3"), (12, "0.7071"), (22, "0.7071")]),
+        });
+        assert_eq!(drawing.snap_to_grid(0.01), 0);
+        assert_eq!(
+            drawing.layers[0].raw[0].codes,
+            codes(&[(40, "1.003"), (10, "0"), (20, "2"), (11, "4.01"), (21, "0.3"), (12, "0.7071"), (22, "0.7071")])
+        );
+    }
+}
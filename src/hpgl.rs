@@ -0,0 +1,177 @@
+use crate::dxf::*;
+use crate::tessellate::FINE_CHORD_ERROR;
+use crate::output::OutputBackend;
+
+// HPGL plotter units are 0.025mm
+pub const HPGL_UNITS_PER_MM: f64 = 40.0;
+
+pub fn is_hpgl_path(path: &str) -> bool {
+    let lower = path.to_ascii_lowercase();
+    lower.ends_with(".plt") || lower.ends_with(".hpgl") || lower.ends_with(".hpg")
+}
+
+pub struct HpglBackend<'a> {
+    out: &'a mut String,
+    scale: f64,
+    position: Option<Point>,
+}
+
+impl<'a> HpglBackend<'a> {
+    pub fn new(out: &'a mut String, scale: f64) -> HpglBackend<'a> {
+        HpglBackend {
+            out,
+            scale,
+            position: None,
+        }
+    }
+
+    fn coord(&self, value: f64) -> i64 {
+        (value * self.scale).round() as i64
+    }
+
+    fn move_to(&mut self, point: &Point) {
+        if self.position.as_ref() != Some(point) {
+            let command = format!("PU{},{};", self.coord(point.x), self.coord(point.y));
+            self.out.push_str(&command);
+            self.position = Some(point.clone());
+        }
+    }
+
+    fn line_to(&mut self, point: &Point) {
+        let command = format!("PD{},{};", self.coord(point.x), self.coord(point.y));
+        self.out.push_str(&command);
+        self.position = Some(point.clone());
+    }
+
+    // positive sweep is counter-clockwise
+    fn arc(&mut self, center: &Point, radius: f64, from_degrees: f64, sweep: f64) {
+        self.move_to(&center.polar(radius, from_degrees));
+        let command = format!("PD;AA{},{},{:.3};", self.coord(center.x), self.coord(center.y), sweep);
+        self.out.push_str(&command);
+        self.position = Some(center.polar(radius, from_degrees + sweep));
+    }
+}
+
+impl<'a> OutputBackend for HpglBackend<'a> {
+    fn buffer(&mut self) -> Option<&mut String> {
+        Some(self.out)
+    }
+
+    fn begin(&mut self) {
+        self.out.push_str("IN;SP1;");
+    }
+
+    fn entity(&mut self, entity: &Entity) {
+        match entity {
+            Entity::Line(from, to) => {
+                self.move_to(from);
+                self.line_to(to);
+            },
+            Entity::Arc { center, radius, start_angle, end_angle } => {
+                let mut sweep = end_angle - start_angle;
+                if sweep <= 0.0 {
+                    sweep += 360.0;
+                }
+                let arc_end = center.polar(*radius, *end_angle);
+                // draw from whichever end the pen is already sitting on to avoid a pen lift
+                if self.position.as_ref() == Some(&arc_end) {
+                    self.arc(center, *radius, *end_angle, -sweep);
+                } else {
+                    self.arc(center, *radius, *start_angle, sweep);
+                }
+            },
+            Entity::Circle { center, radius } => {
+                self.arc(center, *radius, 0.0, 360.0);
+            },
+            Entity::Ellipse { .. } | Entity::Polyline { .. } => {
+                let vertices = entity.tessellate(FINE_CHORD_ERROR);
+                if let Some(first) = vertices.first() {
+                    self.move_to(first);
+                }
+                for vertex in vertices.iter().skip(1) {
+                    self.line_to(vertex);
+                }
+            },
+        }
+    }
+
+    fn finish(&mut self) {
+        self.out.push_str("PU;SP0;\n");
+    }
+}
+
+impl Drawing {
+    pub fn to_hpgl(&self, scale: f64) -> String {
+        let mut out = String::new();
+        self.write_to(&mut HpglBackend::new(&mut out, scale));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn p(x: f64, y: f64) -> Point {
+        Point { x, y }
+    }
+
+    // splits plotter output back into its commands and their numbers
+    fn commands(hpgl: &str) -> Vec<(&str, Vec<f64>)> {
+        hpgl.trim_end().split_terminator(';').map(|command| {
+            let (name, args) = command.split_at(2);
+            (name, args.split_terminator(',').map(|arg| arg.parse().unwrap()).collect())
+        }).collect()
+    }
+
+    #[test]
+    fn test_is_hpgl_path() {
+        assert!(is_hpgl_path("out.PLT"));
+        assert!(is_hpgl_path("out.hpgl"));
+        assert!(is_hpgl_path("out.hpg"));
+        assert!(!is_hpgl_path("out.dxf"));
+    }
+
+    #[test]
+    fn test_pen_moves() {
+        let drawing = Drawing::new(vec![
+            Entity::Line(p(0.0, 0.0), p(1.0, 0.0)),
+            Entity::Line(p(1.0, 0.0), p(1.0, 1.0)),
+            Entity::Line(p(2.0, 0.0), p(3.0, -0.5)),
+        ]);
+        // the pen stays down along connected lines and lifts between separate ones
+        assert_eq!(drawing.to_hpgl(HPGL_UNITS_PER_MM), "IN;SP1;PU0,0;PD40,0;PD40,40;PU80,0;PD120,-20;PU;SP0;\n");
+        assert_eq!(drawing.to_hpgl(1.0), "IN;SP1;PU0,0;PD1,0;PD1,1;PU2,0;PD3,-1;PU;SP0;\n");
+    }
+
+    #[test]
+    fn test_arcs() {
+        let drawing = Drawing::new(vec![
+            Entity::Arc { center: p(0.0, 0.0), radius: 1.0, start_angle: 0.0, end_angle: 90.0 },
+            // entered at its end, where the arc before left the pen, so drawn clockwise
+            Entity::Arc { center: p(0.0, 2.0), radius: 1.0, start_angle: 90.0, end_angle: 270.0 },
+            Entity::Circle { center: p(5.0, 5.0), radius: 2.0 },
+        ]);
+        let hpgl = drawing.to_hpgl(HPGL_UNITS_PER_MM);
+        assert_eq!(hpgl, "IN;SP1;PU40,0;PD;AA0,0,90.000;PD;AA0,80,-180.000;PU280,200;PD;AA200,200,360.000;PU;SP0;\n");
+        let arcs = commands(&hpgl).into_iter().filter(|(name, _)| *name == "AA").map(|(_, args)| args).collect::<Vec<_>>();
+        assert_eq!(arcs, [vec![0.0, 0.0, 90.0], vec![0.0, 80.0, -180.0], vec![200.0, 200.0, 360.0]]);
+    }
+
+    #[test]
+    fn test_tessellated() {
+        let drawing = Drawing::new(vec![
+            Entity::Polyline { curve_type: 0, vertices: vec![p(0.0, 0.0), p(1.0, 0.0), p(1.0, 1.0)], widths: vec![] },
+            Entity::Ellipse { center: p(10.0, 0.0), major_axis: p(2.0, 0.0), ratio: 0.5, start_param: 0.0, end_param: std::f64::consts::TAU },
+        ]);
+        let hpgl = drawing.to_hpgl(HPGL_UNITS_PER_MM);
+        let commands = commands(&hpgl);
+        assert_eq!(commands[..5], [("IN", vec![]), ("SP", vec![1.0]), ("PU", vec![0.0, 0.0]), ("PD", vec![40.0, 0.0]), ("PD", vec![40.0, 40.0])]);
+        // the ellipse is one pen-down run from its start on the major axis back to it
+        assert_eq!(commands[5], ("PU", vec![480.0, 0.0]));
+        let ellipse = &commands[6..commands.len() - 2];
+        assert!(ellipse.len() > 8 && ellipse.iter().all(|(name, _)| *name == "PD"));
+        assert_eq!(ellipse.last().unwrap().1, [480.0, 0.0]);
+        assert_eq!(commands[commands.len() - 2..], [("PU", vec![]), ("SP", vec![0.0])]);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::dxf::*;
+use crate::report::*;
+use crate::svg::*;
+
+const STYLE: &str = "body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+td, th { border: 1px solid #ccc; padding: 4px 12px; text-align: left; }
+svg { width: 100%; max-height: 80vh; border: 1px solid #ccc; }
+path { fill: none; vector-effect: non-scaling-stroke; }
+.input path { stroke: #bbb; stroke-width: 3; }
+.output path { stroke: #2060c0; stroke-width: 1; }
+.output path.arc { stroke: #d02828; stroke-width: 2; }
+.output path.arc:hover { stroke: #ff9900; stroke-width: 4; }
+";
+
+// self-contained html page overlaying the welded output on the input, with a tooltip per arc
+pub fn render_report(input: &Drawing, output: &Drawing, report: &WeldReport, chord_error: f64) -> String {
+    let mut out = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>DXF weld report</title>\n<style>\n");
+    out.push_str(STYLE);
+    out.push_str("</style>\n</head>\n<body>\n<h1>DXF weld report</h1>\n<table>\n");
+    for (label, value) in report.summary() {
+        out.push_str(&format!("<tr><th>{}</th><td>{}</td></tr>\n", label, escape(&value)));
+    }
+    out.push_str("</table>\n");
+
+    out.push_str(&svg_header(input, chord_error));
+    out.push_str("\n<g transform=\"scale(1,-1)\">\n<g class=\"input\">\n");
+    for entity in input.entities() {
+        out.push_str(&format!("<path d=\"{}\"/>\n", path_data(entity)));
+    }
+    out.push_str("</g>\n<g class=\"output\">\n");
+    let mut arcs = report.arcs().collect::<Vec<_>>();
+    arcs.sort_by_key(|arc| arc.entity);
+    let mut arcs = arcs.into_iter().peekable();
+    for (i, entity) in output.entities().enumerate() {
+        match arcs.peek() {
+            Some(arc) if arc.entity == i => {
+                out.push_str(&format!(
+                    "<path class=\"arc\" d=\"{}\"><title>{}\nradius: {:.6}\nresidual: {:.6}\nsegments replaced: {}</title></path>\n",
+                    path_data(entity), if arc.circle { "circle" } else { "arc" }, arc.radius, arc.residual, arc.segments,
+                ));
+                arcs.next();
+            },
+            _ => out.push_str(&format!("<path d=\"{}\"/>\n", path_data(entity))),
+        }
+    }
+    out.push_str("</g>\n</g>\n</svg>\n</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dxf_process::DxfConfig;
+
+    #[test]
+    fn test_render_report() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let points = (0..=16).map(|i| p(0.0, 0.0).polar(10.0, 90.0 - i as f64 * 90.0 / 16.0)).collect::<Vec<_>>();
+        let mut entities = points.windows(2).map(|pair| Entity::Line(pair[0].clone(), pair[1].clone())).collect::<Vec<_>>();
+        entities.push(Entity::Line(p(20.0, 0.0), p(30.0, 0.0)));
+        let input = Drawing::new(entities);
+        let config = DxfConfig::default();
+        let mut report = WeldReport::default();
+        let output = config.process_drawing(input.clone(), &mut report).unwrap();
+        let html = render_report(&input, &output, &report, config.resolution);
+
+        assert!(html.starts_with("<!DOCTYPE html>\n") && html.ends_with("</html>\n"));
+        assert!(html.contains("<tr><th>Input entities</th><td>17</td></tr>\n<tr><th>Output entities</th><td>2</td></tr>\n"), "{}", html);
+        assert!(html.contains("<tr><th>Arcs</th><td>1</td></tr>\n"), "{}", html);
+        assert!(html.contains("<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox="));
+
+        let (_, overlay) = html.split_once("<g class=\"input\">\n").unwrap();
+        let (input_paths, output_paths) = overlay.split_once("</g>\n<g class=\"output\">\n").unwrap();
+        assert_eq!(input_paths.lines().count(), 17);
+        let output_paths = output_paths.split("</path>\n").collect::<Vec<_>>();
+        // the welded arc carries its tooltip and the line is drawn plainly
+        let (arc, tooltip) = output_paths[0].split_once("><title>").unwrap();
+        assert!(arc.starts_with("<path class=\"arc\" d=\"M 10 0") && arc.contains(" A 10 10 0 0 1 "), "{}", arc);
+        // the residual is the sagitta of the replaced segments, about 0.012
+        assert!(tooltip.starts_with("arc\nradius: 10.000000\nresidual: 0.01"), "{}", tooltip);
+        assert!(tooltip.ends_with("\nsegments replaced: 16</title>"), "{}", tooltip);
+        assert!(output_paths[1].starts_with("<path d=\"M 20 0 L 30 0\"/>\n</g>\n</g>\n</svg>"), "{}", output_paths[1]);
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("<b title=\"a & b\">"), "&lt;b title=&quot;a &amp; b&quot;&gt;");
+    }
+}
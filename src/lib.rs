@@ -0,0 +1,95 @@
+#[macro_use]
+pub mod result;
+pub use result::*;
+
+pub mod checkpoint;
+pub mod cleanup;
+pub mod curve;
+pub mod diff;
+pub mod dxf;
+#[cfg(feature = "dxf-crate")]
+pub mod dxf_interop;
+pub mod dxf_process;
+pub mod ffi;
+pub mod flatten;
+pub mod gaps;
+pub mod gcode;
+#[cfg(feature = "geo")]
+pub mod geo_interop;
+pub mod gis;
+pub mod grid;
+pub mod hpgl;
+pub mod html;
+pub mod lint;
+#[cfg(feature = "nalgebra")]
+pub mod nalgebra_interop;
+pub mod nesting;
+pub mod offset;
+pub mod order;
+pub mod output;
+pub mod pass;
+mod path;
+pub mod points;
+pub mod preset;
+pub mod preview;
+pub mod quadrants;
+pub mod report;
+pub mod summary;
+pub mod svg;
+pub mod tessellate;
+#[cfg(any(test, feature = "testgen"))]
+pub mod testgen;
+pub mod text;
+pub mod transform;
+pub mod tune;
+pub mod validate;
+pub mod warnings;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+use dxf_process::DxfConfig;
+
+// Welds one chain of connected points into lines, arcs and circles, for geometry that never was a
+// DXF. Unlike `DxfConfig::process_chain` it checks the points first, so bad input is an error rather
+// than a panic.
+pub fn weld_polyline(points: &[dxf::Point], config: &DxfConfig) -> Result<Vec<dxf::Entity>> {
+    if let Some(point) = points.iter().find(|point| !point.x.is_finite() || !point.y.is_finite()) {
+        return Err(weld_err!("non-finite point ({}, {})", point.x, point.y));
+    }
+    if config.resolution.is_nan() || config.resolution <= 0.0 {
+        return Err(weld_err!("resolution must be positive"));
+    }
+    config.process_chain(points, &mut report::ChainReport::default())
+}
+
+// parses, welds and re-serializes a DXF document entirely in memory
+pub fn weld_dxf(input: &str, config: &DxfConfig) -> Result<String> {
+    let drawing = dxf::Drawing::parse_formatted(input, &config.limits, config.number_format)?;
+    let welded = config.process_drawing(drawing, &mut report::WeldReport::default())?;
+    let mut out = String::new();
+    welded.write_to(&mut dxf::DxfBackend::new(&mut out).with_angle_precision(config.angle_precision));
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dxf::{Entity, Point};
+
+    #[test]
+    fn test_weld_polyline() {
+        let center = Point { x: 3.0, y: 4.0 };
+        let arc = (0..=12).map(|i| center.polar(5.0, 20.0 + i as f64 * 10.0)).collect::<Vec<_>>();
+        let welded = weld_polyline(&arc, &DxfConfig::default()).unwrap();
+        assert!(matches!(&welded[..], [Entity::Arc { .. }]), "{:?}", welded);
+
+        // fewer points than min_segments stay as they are
+        let config = DxfConfig { min_segments: 6, ..Default::default() };
+        assert_eq!(weld_polyline(&arc[..4], &config).unwrap().len(), 3);
+        assert!(weld_polyline(&arc[..1], &config).is_err());
+        let mut bad = arc.clone();
+        bad[5].y = f64::NAN;
+        assert!(weld_polyline(&bad, &config).is_err());
+        assert!(weld_polyline(&arc, &DxfConfig { resolution: 0.0, ..Default::default() }).is_err());
+    }
+}
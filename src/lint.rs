@@ -0,0 +1,10 @@
+use crate::dxf::*;
+
+// Structural findings for a DXF file, ordered by line: everything the parser notices plus the fatal
+// error that stopped it, if any.
+pub fn lint(src: &str) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let _ = Drawing::parse_with_diagnostics(src, &mut diagnostics);
+    diagnostics.sort_by_key(|diagnostic| diagnostic.line);
+    diagnostics
+}
@@ -1,25 +1,547 @@
 use std::env;
+use std::io::IsTerminal;
+use std::time::{Duration, Instant};
 
-#[macro_use]
-mod result;
-pub use result::*;
+use dxf_welder::*;
+use dxf_welder::dxf_process::*;
+use dxf_welder::pass::Pass;
 
-mod dxf_process;
-use dxf_process::*;
+mod cli;
+mod exit;
 
-mod dxf;
+use exit::OrExit;
 
 fn main() {
-    let args = env::args().into_iter().skip(1).collect::<Vec<String>>();
-    let infile = args.get(0).expect("no input file");
-    let outfile = args.get(1).expect("no output file");
-    let config = DxfConfig {
-        resolution: 0.05,
-        max_radius: 100000.0,
-        min_segments: 3,
-    };
-    let input = std::fs::read_to_string(infile).expect("failed to read dxf");
-    let parsed = dxf::Drawing::parse(&input).expect("failed to parse dxf");
-    let out_drawing = config.process_drawing(parsed).expect("failed to process dxf file");
-    std::fs::write(&outfile, out_drawing.to_string()).expect("failed to write dxf file");
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    match args.first().map(String::as_str) {
+        Some("lint") => return lint(&args[1..]),
+        Some("tune") => return tune_session(&args[1..]),
+        Some("info") => return info(&args[1..]),
+        Some("diff") => return diff(cli::DiffOptions::parse(args.into_iter().skip(1)).or_exit(exit::USAGE_ERROR, "invalid arguments")),
+        Some("flatten") | Some("unweld") => return flatten(cli::FlattenOptions::parse(args.into_iter().skip(1)).or_exit(exit::USAGE_ERROR, "invalid arguments")),
+        _ => (),
+    }
+    let options = cli::Options::parse(args).or_exit(exit::USAGE_ERROR, "invalid arguments");
+    let outfile = &options.outfile;
+    let mut config = DxfConfig {
+        fitter: options.fitter,
+        fit_lines: options.fit_lines,
+        merge_arcs: options.merge_arcs,
+        zero_length: options.zero_length,
+        angle_precision: options.angle_precision,
+        three_point_arcs: options.three_point_arcs,
+        dxf_version: options.dxf_version,
+        fragment: options.fragment,
+        weld_blocks: options.weld_blocks,
+        min_arc_segments_replaced: options.min_arc_segments,
+        max_points_per_fit: options.max_points_per_fit,
+        chunk_points: options.chunk_points,
+        angular_tolerance: options.angular_tolerance,
+        ..options.preset.map(DxfConfig::from_preset).unwrap_or_default()
+    };
+    if let [infile] = &options.infiles[..] {
+        if gcode::is_gcode_path(infile) {
+            let input = std::fs::read_to_string(infile).or_exit(exit::IO_ERROR, "failed to read input file");
+            let parsed = gcode::Program::parse(&input).or_exit(exit::PARSE_ERROR, "failed to parse gcode");
+            let out_program = config.process_program(parsed).or_exit(exit::FIT_ERROR, "failed to process gcode file");
+            write_file(outfile, out_program.to_gcode(config.three_point_arcs), options.backup).or_exit(exit::IO_ERROR, "failed to write gcode file");
+            return;
+        }
+    }
+    let mut weld_report = report::WeldReport {
+        feed_rate: options.feed_rate,
+        timings: report::Timings { enabled: true, ..Default::default() },
+        ..Default::default()
+    };
+    let mut clock = Instant::now();
+    let transform = options.transform();
+    let number_format = if options.comma_decimals { dxf::NumberFormat::Comma } else { dxf::NumberFormat::Point };
+    let mut inputs = vec![];
+    for infile in options.infiles.iter() {
+        let mut drawing = read_drawing(infile, options.mmap, number_format);
+        if let Some(origin) = options.origin.and_then(|origin| drawing.header_origin(origin).cloned()) {
+            drawing.transform(&transform::Transform::translate(origin.x, origin.y));
+        }
+        if !transform.is_identity() {
+            drawing.transform(&transform);
+        }
+        inputs.push(drawing);
+    }
+    let mut parsed = match options.tile {
+        Some((columns, spacing)) => transform::tile(inputs, columns, spacing, config.resolution),
+        None => inputs.into_iter().fold(dxf::Drawing::default(), |mut merged, drawing| {
+            merged.merge(drawing);
+            merged
+        }),
+    };
+    let debug_input = options.debug_layer.as_ref().map(|_| parsed.clone());
+    weld_report.time_phase("parse", &mut clock);
+    let mut cleanup = pass::Passes::new();
+    if let Some(tolerance) = options.join_tolerance {
+        cleanup = cleanup.then(pass::JoinEndpoints { tolerance });
+    }
+    if let Some(max_degrees) = options.snap_axes {
+        cleanup = cleanup.then(pass::SnapToAxes { max_degrees });
+    }
+    if let Some(tolerance) = options.remove_overlaps {
+        cleanup = cleanup.then(pass::RemoveOverlaps { tolerance });
+    }
+    if let Some(min_size) = options.min_feature_size {
+        cleanup = cleanup.then(pass::RemoveSmallFeatures { min_size, chord_error: config.resolution });
+    }
+    cleanup.run(&mut parsed, &mut weld_report).or_exit(exit::FIT_ERROR, "failed to clean up drawing");
+    weld_report.gaps = cleanup::gaps(&parsed, config.resolution * gaps::MAX_GAP_RESOLUTIONS);
+    weld_report.time_phase("cleanup", &mut clock);
+    let resolution = if options.auto_resolution || options.heal_only { None } else { Some(config.resolution) };
+    weld_report.warnings = warnings::check(&parsed, resolution);
+    if let Some(ids) = &options.chains {
+        config.chain_params = Some(config.only_chains(&parsed, ids).or_exit(exit::USAGE_ERROR, "invalid --chains"));
+    }
+    if let Some(policy) = options.text_outlines {
+        config.chain_params = Some(config.text_outlines(&parsed, policy).or_exit(exit::FIT_ERROR, "failed to look for text"));
+    }
+    if options.adaptive_min_segments {
+        config.chain_params = Some(config.adaptive_min_segments());
+    }
+    if let Some(max_memory) = options.max_memory {
+        let estimate = dxf_process::estimated_memory(&parsed);
+        if estimate > max_memory {
+            match options.needs_whole_output() {
+                Some(option) => eprintln!("welding needs about {:.1} MB, over --max-memory, but {} needs the whole output in memory", estimate as f64 / 1048576.0, option),
+                None => return stream_weld(&options, &config, parsed, weld_report, clock),
+            }
+        }
+    }
+    let original = if options.preview.is_some() || options.report.is_some() {
+        Some(parsed.clone())
+    } else {
+        None
+    };
+    if let Some(resolutions) = &options.resolutions {
+        let welds = config.process_resolutions(parsed, resolutions, &mut weld_report).or_exit(exit::FIT_ERROR, "failed to process dxf file");
+        for (resolution, (welded, weld_report)) in resolutions.iter().zip(welds) {
+            let config = DxfConfig { resolution: *resolution, ..config.clone() };
+            finish_weld(&options, &config, original.as_ref(), debug_input.clone(), welded, weld_report, Some(&resolution.to_string()));
+        }
+        return;
+    }
+    let out_drawing = if options.heal_only {
+        weld_report.input_entities = parsed.entity_count();
+        weld_report.input_length = parsed.length();
+        weld_report.heal = Some(cleanup::heal(&mut parsed, &options.heal_config()));
+        weld_report.output_entities = parsed.entity_count();
+        weld_report.output_length = parsed.length();
+        parsed
+    } else if options.auto_resolution {
+        let mut trials = tune::sweep(&config, &parsed, &tune::SWEEP_RESOLUTIONS, &weld_report).or_exit(exit::FIT_ERROR, "failed to process dxf file");
+        let table = tune::format_table(&trials);
+        let max_deviation = match options.max_deviation {
+            Some(max_deviation) => max_deviation,
+            None => {
+                // without a cap the user picks a resolution from the table
+                print!("{}", table);
+                return;
+            },
+        };
+        eprint!("{}", table);
+        let best = match tune::best_under(&trials, max_deviation) {
+            Some(best) => trials.swap_remove(best),
+            None => exit::fail(exit::CHECK_FAILED, format!("no resolution keeps the deviation under {}", max_deviation)),
+        };
+        eprintln!("using resolution {}", best.resolution);
+        config.resolution = best.resolution;
+        weld_report = best.report;
+        best.drawing
+    } else if let Some(path) = &options.checkpoint {
+        resume_weld(&config, parsed, &mut weld_report, std::path::Path::new(path))
+    } else {
+        config.process_drawing(parsed, &mut weld_report).or_exit(exit::FIT_ERROR, "failed to process dxf file")
+    };
+    if options.heal_only {
+        weld_report.time_phase("heal", &mut clock);
+    }
+    finish_weld(&options, &config, original.as_ref(), debug_input, out_drawing, weld_report, None);
+}
+
+// Analyses, checks and writes a welded drawing, timing each phase from now on as welding timed its
+// own. With `suffix`, as for one of several --resolutions, the output, report, preview and stats
+// paths are suffixed with it.
+fn finish_weld(options: &cli::Options, config: &DxfConfig, original: Option<&dxf::Drawing>, debug_input: Option<dxf::Drawing>, mut out_drawing: dxf::Drawing, mut weld_report: report::WeldReport, suffix: Option<&str>) {
+    let mut clock = Instant::now();
+    let path = |path: &String| suffix.map_or_else(|| path.clone(), |suffix| cli::suffixed_path(path, suffix));
+    let outfile = &path(&options.outfile);
+    // the report's arcs point at entities of the drawing as welded, which offsetting and reordering
+    // replace and move
+    let welded = (options.report.is_some() && (options.offset.is_some() || options.holes_first)).then(|| out_drawing.clone());
+    if let Some(distance) = options.offset {
+        offset::offset_loops(&mut out_drawing, distance);
+    }
+    let mut analysis = pass::Passes::new();
+    if options.holes_first {
+        analysis = analysis.then(pass::HolesFirst);
+    }
+    let verify = pass::Verify {
+        chord_error: config.resolution,
+        // only reports and stats show the loops and parts
+        loops: options.report.is_some() || options.stats_json.is_some(),
+        parts: options.report.is_some() || options.stats_json.is_some(),
+        // and the summary warns about crossings unless warnings are off
+        intersections: options.report.is_some() || options.stats_json.is_some() || options.fail_on_intersection || !(options.quiet || options.no_warn),
+        part_proximity: options.part_proximity,
+    };
+    analysis.then(verify).run(&mut out_drawing, &mut weld_report).or_exit(exit::FIT_ERROR, "failed to analyse welded drawing");
+    if let (Some(report_path), Some(original)) = (&options.report, original) {
+        let html = html::render_report(original, welded.as_ref().unwrap_or(&out_drawing), &weld_report, config.resolution);
+        write_file(&path(report_path), html, false).or_exit(exit::IO_ERROR, "failed to write report");
+    }
+    if let (Some(preview), Some(original)) = (&options.preview, original) {
+        let image = preview::render_preview(original, &out_drawing, config.resolution).or_exit(exit::IO_ERROR, "failed to render preview");
+        write_file(&path(preview), image, false).or_exit(exit::IO_ERROR, "failed to write preview");
+    }
+    weld_report.time_phase("analysis", &mut clock);
+    let mut failures = vec![];
+    if options.fail_on_intersection {
+        for intersection in weld_report.intersections.iter() {
+            let kind = match (intersection.same_chain, intersection.overlap) {
+                (true, false) => "self-intersection",
+                (false, false) => "chain intersection",
+                (true, true) => "self-overlap",
+                (false, true) => "chain overlap",
+            };
+            failures.push(format!("{} on layer {} at ({}, {})", kind, intersection.layer, intersection.point.x, intersection.point.y));
+        }
+    }
+    if options.fail_on_orphans {
+        for (layer, (from, to)) in weld_report.orphans() {
+            failures.push(format!("orphan segment on layer {} from ({}, {}) to ({}, {})", layer, from.x, from.y, to.x, to.y));
+        }
+    }
+    if let Some(minimum) = options.fail_if_compression_below {
+        if weld_report.compression_ratio() < minimum {
+            failures.push(format!("compression ratio {:.2} is below {}", weld_report.compression_ratio(), minimum));
+        }
+    }
+    if let Some(maximum) = options.fail_if_deviation_above {
+        if weld_report.max_residual() > maximum {
+            failures.push(format!("maximum deviation {} is above {}", weld_report.max_residual(), maximum));
+        }
+    }
+    if !failures.is_empty() {
+        print_summary(options, &weld_report, outfile);
+        for failure in failures.iter() {
+            eprintln!("{}", failure);
+        }
+        if let Some(stats_path) = &options.stats_json {
+            write_file(&path(stats_path), weld_report.to_stats_json(), false).or_exit(exit::IO_ERROR, "failed to write stats");
+        }
+        std::process::exit(exit::CHECK_FAILED);
+    }
+    if options.optimize_start {
+        order::optimize_start(&mut out_drawing);
+    }
+    if options.no_circles {
+        out_drawing.split_circles();
+    }
+    if options.split_quadrants {
+        out_drawing.split_quadrants();
+    }
+    if let Some(fraction) = options.deviation_layer {
+        let ticks = validate::deviation_ticks(&out_drawing, &weld_report, fraction * config.resolution, config.resolution);
+        if !ticks.is_empty() {
+            out_drawing.layer_mut(validate::DEVIATION_LAYER, None).entities.extend(ticks);
+        }
+    }
+    if options.block_per_loop {
+        nesting::loops_to_blocks(&mut out_drawing);
+    }
+    match options.split_parts {
+        Some(cli::PartOutput::Layers) => {
+            nesting::parts_to_layers(&mut out_drawing, options.part_proximity);
+        },
+        Some(cli::PartOutput::Blocks) => {
+            nesting::parts_to_blocks(&mut out_drawing, options.part_proximity);
+        },
+        None => (),
+    }
+    if let (Some(name), Some(input)) = (&options.debug_layer, debug_input) {
+        if out_drawing.layers.iter().any(|layer| &layer.name == name) {
+            exit::fail(exit::USAGE_ERROR, format!("debug layer {} is already used by the drawing", name));
+        }
+        out_drawing.layer_mut(name, None).entities.extend(input.layers.into_iter().flat_map(|layer| layer.entities));
+    }
+    // back to coordinates relative to the origin all inputs shared, if they did
+    if let Some(origin) = options.origin.and_then(|origin| out_drawing.header_origin(origin).cloned()) {
+        out_drawing.transform(&transform::Transform::translate(-origin.x, -origin.y));
+    }
+    if let Some(grid) = options.snap_grid {
+        let dropped = out_drawing.snap_to_grid(grid);
+        if dropped > 0 {
+            weld_report.warnings.push(warnings::Warning { kind: "snap-grid", message: format!("snapping to the grid dropped {} entities smaller than it", dropped) });
+        }
+    }
+    if options.split_layers || options.split_colors {
+        let parts = if options.split_layers {
+            out_drawing.split_by_layer()
+        } else {
+            out_drawing.split_by_color()
+        };
+        let paths = cli::suffixed_paths(outfile, &parts.iter().map(|(key, _)| key.as_str()).collect::<Vec<_>>());
+        for ((_, part), path) in parts.iter().zip(paths.iter()) {
+            write_drawing(path, part, config, options.backup);
+        }
+    } else {
+        write_drawing(outfile, &out_drawing, config, options.backup);
+    }
+    weld_report.time_phase("write", &mut clock);
+    if let Some(path) = &options.checkpoint {
+        std::fs::remove_file(path).or_exit(exit::IO_ERROR, "failed to remove checkpoint");
+    }
+    if options.stats {
+        eprint!("{}", weld_report.timings);
+    }
+    if let Some(stats_path) = &options.stats_json {
+        write_file(&path(stats_path), weld_report.to_stats_json(), false).or_exit(exit::IO_ERROR, "failed to write stats");
+    }
+    print_summary(options, &weld_report, outfile);
+}
+
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
+
+// Welds under --checkpoint, picking up the progress saved at `path` by an earlier run of the same
+// drawing and settings and saving it again every few seconds and once welding is done.
+fn resume_weld(config: &DxfConfig, drawing: dxf::Drawing, weld_report: &mut report::WeldReport, path: &std::path::Path) -> dxf::Drawing {
+    let key = checkpoint::Checkpoint::key(config, &drawing).or_exit(exit::FIT_ERROR, "failed to fingerprint drawing");
+    let mut progress = match checkpoint::Checkpoint::load(path) {
+        Ok(Some(saved)) if saved.key == key => {
+            eprintln!("resuming from {}", path.display());
+            saved
+        },
+        Ok(Some(_)) | Err(_) => {
+            eprintln!("ignoring checkpoint {}, which is for another drawing or settings", path.display());
+            checkpoint::Checkpoint::new(key)
+        },
+        Ok(None) => checkpoint::Checkpoint::new(key),
+    };
+    let mut saved_at = Instant::now();
+    let welded = config.resume_drawing(drawing, weld_report, &mut progress, &mut |progress| {
+        if progress.finished || saved_at.elapsed() >= CHECKPOINT_INTERVAL {
+            progress.save(path)?;
+            saved_at = Instant::now();
+        }
+        Ok(())
+    });
+    match welded {
+        Ok(welded) => welded,
+        Err(e) => {
+            // keep whatever was welded before the failure
+            progress.save(path).or_exit(exit::IO_ERROR, "failed to write checkpoint");
+            exit::fail(exit::FIT_ERROR, format!("failed to process dxf file: {}", e))
+        },
+    }
+}
+
+// welds chain by chain under --max-memory, writing each chain's entities as soon as they are welded
+fn stream_weld(options: &cli::Options, config: &DxfConfig, drawing: dxf::Drawing, mut weld_report: report::WeldReport, clock: Instant) {
+    let outfile = std::path::Path::new(&options.outfile);
+    let temporary = output::temporary_path(outfile);
+    let file = std::fs::File::create(&temporary).or_exit(exit::IO_ERROR, "failed to write output file");
+    let mut sink = std::io::BufWriter::new(file);
+    let mut out = String::new();
+    let mut backend = output::backend_for_path(&options.outfile, &drawing, config, &mut out);
+    let streamed = config.stream_drawing(drawing, backend.as_mut(), &mut sink, &mut weld_report)
+        .and_then(|_| Ok(sink.into_inner().map_err(|e| e.into_error())?.sync_all()?));
+    if let Err(e) = streamed {
+        let _ = std::fs::remove_file(&temporary);
+        let code = if e.is::<std::io::Error>() { exit::IO_ERROR } else { exit::FIT_ERROR };
+        exit::fail(code, format!("failed to process dxf file: {}", e));
+    }
+    output::replace_with(&temporary, outfile, options.backup).or_exit(exit::IO_ERROR, "failed to write output file");
+    // what chain building and fitting didn't take went to writing
+    let welding = weld_report.timings.seconds("chain") + weld_report.timings.seconds("fit");
+    weld_report.timings.record("write", clock.elapsed().as_secs_f64() - welding);
+    if options.stats {
+        eprint!("{}", weld_report.timings);
+    }
+    if let Some(stats_path) = &options.stats_json {
+        write_file(stats_path, weld_report.to_stats_json(), false).or_exit(exit::IO_ERROR, "failed to write stats");
+    }
+    print_summary(options, &weld_report, &options.outfile);
+}
+
+// The summary of the run on stderr, in color on a terminal unless NO_COLOR is set, or as JSON on
+// stdout under --json. Nothing under --quiet, and no warnings under --no-warn.
+fn print_summary(options: &cli::Options, weld_report: &report::WeldReport, outfile: &str) {
+    if options.quiet {
+        return;
+    }
+    let mut summary = summary::Summary::new(weld_report, outfile);
+    if options.no_warn {
+        summary.warnings.clear();
+    }
+    if options.json {
+        println!("{}", summary.to_json());
+    } else {
+        eprint!("{}", summary.render(std::io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none()));
+    }
+}
+
+fn write_file(path: &str, contents: impl AsRef<[u8]>, backup: bool) -> dxf_welder::Result<()> {
+    output::write_atomic(std::path::Path::new(path), contents.as_ref(), backup)
+}
+
+fn write_drawing(path: &str, drawing: &dxf::Drawing, config: &DxfConfig, backup: bool) {
+    let output = if gis::is_geojson_path(path) {
+        drawing.to_geojson(config.resolution)
+    } else if gis::is_wkt_path(path) {
+        drawing.to_wkt()
+    } else {
+        let mut out = String::new();
+        drawing.write_to(output::backend_for_path(path, drawing, config, &mut out).as_mut());
+        out
+    };
+    write_file(path, output, backup).or_exit(exit::IO_ERROR, "failed to write output file");
+}
+
+// reads a DXF or point list, optionally parsing straight from a memory map of the file
+fn read_drawing(path: &str, mmap: bool, format: dxf::NumberFormat) -> dxf::Drawing {
+    if !mmap {
+        let input = std::fs::read_to_string(path).or_exit(exit::IO_ERROR, "failed to read input file");
+        return parse_drawing(path, &input, format);
+    }
+    let file = std::fs::File::open(path).or_exit(exit::IO_ERROR, "failed to read input file");
+    // safety: the map only lives while parsing, and the input must not be truncated meanwhile
+    let map = unsafe { memmap2::Mmap::map(&file) }.or_exit(exit::IO_ERROR, "failed to map input file");
+    parse_drawing(path, std::str::from_utf8(&map).or_exit(exit::PARSE_ERROR, "failed to parse input file"), format)
+}
+
+fn parse_drawing(path: &str, input: &str, format: dxf::NumberFormat) -> dxf::Drawing {
+    if points::is_points_path(path) {
+        points::parse_points(input).or_exit(exit::PARSE_ERROR, "failed to parse point list")
+    } else {
+        dxf::Drawing::parse_formatted(input, &dxf::Limits::default(), format).or_exit(exit::PARSE_ERROR, "failed to parse dxf")
+    }
+}
+
+// tessellates every curve into lines at the given resolution
+fn flatten(options: cli::FlattenOptions) {
+    let mut config = DxfConfig::default();
+    if let Some(resolution) = options.resolution {
+        config.resolution = resolution;
+    }
+    let mut drawing = read_drawing(&options.infile, false, dxf::NumberFormat::Point);
+    drawing.flatten(config.resolution);
+    write_drawing(&options.outfile, &drawing, &config, false);
+}
+
+// lists the chains welding would fit, numbered as `--chains` takes them
+fn info(args: &[String]) {
+    let infile = match args {
+        [infile] => infile,
+        _ => exit::fail(exit::USAGE_ERROR, "usage: info <infile>"),
+    };
+    let drawing = read_drawing(infile, false, dxf::NumberFormat::Point);
+    let chains = DxfConfig::default().extract_chains(&drawing).or_exit(exit::FIT_ERROR, "failed to chain drawing");
+    for (id, chain) in chains.iter().enumerate() {
+        let (min, max) = chain.bounds();
+        let shape = if chain.closed() { "closed" } else { "open" };
+        println!("{} [{}] {} points, {}, length {:.3}, ({}, {}) to ({}, {})", id, chain.layer, chain.points.len(), shape, chain.length(), min.x, min.y, max.x, max.y);
+    }
+    println!("{} chains", chains.len());
+}
+
+const DIFF_TOLERANCE: f64 = 0.001;
+
+// prints the entities only found in one of two drawings and the distance between their outlines,
+// failing if they differ
+fn diff(options: cli::DiffOptions) {
+    let tolerance = options.tolerance.unwrap_or(DIFF_TOLERANCE);
+    let (first, second) = (read_drawing(&options.first, false, dxf::NumberFormat::Point), read_drawing(&options.second, false, dxf::NumberFormat::Point));
+    let diff = diff::diff(&first, &second, tolerance);
+    for (sign, unmatched) in diff.removed.iter().map(|u| ('-', u)).chain(diff.added.iter().map(|u| ('+', u))) {
+        println!("{} [{}] {}", sign, unmatched.layer, serde_json::to_string(&unmatched.entity).unwrap());
+    }
+    println!("{} removed, {} added", diff.removed.len(), diff.added.len());
+    match &diff.hausdorff_at {
+        Some(at) => println!("hausdorff distance {} at ({}, {})", diff.hausdorff, at.x, at.y),
+        None => println!("hausdorff distance {}", diff.hausdorff),
+    }
+    if !diff.is_same(tolerance) {
+        std::process::exit(exit::CHECK_FAILED);
+    }
+}
+
+const TUNE_HELP: &str = "commands: + / - double or halve the resolution, ] / [ double or halve the join tolerance, \
+r VALUE sets the resolution, j VALUE the join tolerance, w FILE writes the output, q quits";
+
+// Loads one input and re-welds it as the settings are changed from stdin, printing statistics and a
+// braille preview of the output after every change.
+fn tune_session(args: &[String]) {
+    let infile = match args {
+        [infile] => infile,
+        _ => exit::fail(exit::USAGE_ERROR, "usage: tune <infile>"),
+    };
+    let mut session = tune::Session::new(read_drawing(infile, false, dxf::NumberFormat::Point), DxfConfig::default());
+    eprintln!("{}", TUNE_HELP);
+    let mut line = String::new();
+    loop {
+        let (resolution, join_tolerance) = (session.config.resolution, session.join_tolerance);
+        match session.weld() {
+            Ok((welded, report)) => {
+                print!("{}", preview::render_braille(welded, resolution, 60, 20));
+                println!("{}", tune::format_stats(resolution, join_tolerance, report));
+            },
+            Err(e) => eprintln!("failed to weld: {}", e),
+        }
+        print!("> ");
+        std::io::Write::flush(&mut std::io::stdout()).or_exit(exit::IO_ERROR, "failed to write output");
+        line.clear();
+        if std::io::stdin().read_line(&mut line).or_exit(exit::IO_ERROR, "failed to read input") == 0 {
+            break;
+        }
+        let mut words = line.split_whitespace();
+        match (words.next(), words.next()) {
+            (None, _) => (),
+            (Some("+"), None) => session.config.resolution *= 2.0,
+            (Some("-"), None) => session.config.resolution /= 2.0,
+            (Some("]"), None) => session.join_tolerance = if join_tolerance > 0.0 { join_tolerance * 2.0 } else { 0.001 },
+            // halving below 0.001 turns joining off
+            (Some("["), None) => session.join_tolerance = if join_tolerance > 0.001 { join_tolerance / 2.0 } else { 0.0 },
+            (Some("r"), Some(value)) | (Some("j"), Some(value)) => match value.parse::<f64>() {
+                Ok(value) if value > 0.0 && line.starts_with('r') => session.config.resolution = value,
+                Ok(value) if value >= 0.0 && line.starts_with('j') => session.join_tolerance = value,
+                _ => eprintln!("invalid value: {}", value),
+            },
+            (Some("w"), Some(path)) => {
+                let config = session.config.clone();
+                if let Ok((welded, _)) = session.weld() {
+                    write_drawing(path, welded, &config, false);
+                }
+            },
+            (Some("q"), None) => break,
+            _ => eprintln!("{}", TUNE_HELP),
+        }
+    }
+}
+
+// prints the findings for every file as a JSON array and fails if there are any
+fn lint(paths: &[String]) {
+    if paths.is_empty() {
+        exit::fail(exit::USAGE_ERROR, "usage: lint <file.dxf>...");
+    }
+    let mut findings = vec![];
+    for path in paths.iter() {
+        let input = std::fs::read_to_string(path).or_exit(exit::IO_ERROR, "failed to read input file");
+        for diagnostic in lint::lint(&input) {
+            findings.push(serde_json::json!({
+                "file": path,
+                "line": diagnostic.line,
+                "kind": diagnostic.kind,
+                "message": diagnostic.message,
+            }));
+        }
+    }
+    println!("{}", serde_json::to_string_pretty(&findings).unwrap());
+    if !findings.is_empty() {
+        std::process::exit(exit::CHECK_FAILED);
+    }
 }
@@ -0,0 +1,31 @@
+// conversions to and from `nalgebra` points
+use crate::dxf::Point;
+use nalgebra::Point2;
+
+impl From<&Point> for Point2<f64> {
+    fn from(point: &Point) -> Point2<f64> {
+        Point2::new(point.x, point.y)
+    }
+}
+
+impl From<Point2<f64>> for Point {
+    fn from(point: Point2<f64>) -> Point {
+        Point {
+            x: point.x,
+            y: point.y,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra::{Rotation2, Vector2};
+
+    #[test]
+    fn test_round_trip() {
+        let point = Point { x: 1.0, y: 2.0 };
+        let moved = Rotation2::new(std::f64::consts::FRAC_PI_2) * Point2::from(&point) + Vector2::new(1.0, 0.0);
+        assert_eq!(Point::from(moved), Point { x: -1.0, y: 1.0 });
+    }
+}
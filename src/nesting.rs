@@ -0,0 +1,497 @@
+use crate::dxf::*;
+use crate::path::*;
+use crate::report::{LoopReport, PartReport};
+use std::collections::HashMap;
+
+fn outline(piece: &Piece) -> Option<Vec<Point>> {
+    match piece {
+        Piece::Loop(segments, _) => Some(segments.iter().flat_map(|s| s.samples()).collect()),
+        Piece::Entity(circle @ Entity::Circle { radius, .. }) => Some(circle.tessellate(radius * 0.001)),
+        Piece::Entity(_) => None,
+    }
+}
+
+fn area(polygon: &[Point]) -> f64 {
+    polygon.iter().zip(polygon.iter().cycle().skip(1)).map(|(a, b)| a.x * b.y - b.x * a.y).sum::<f64>().abs() / 2.0
+}
+
+// even-odd ray casting
+fn contains(polygon: &[Point], point: &Point) -> bool {
+    let mut inside = false;
+    for (a, b) in polygon.iter().zip(polygon.iter().cycle().skip(1)) {
+        if (a.y > point.y) != (b.y > point.y) && point.x < a.x + (point.y - a.y) * (b.x - a.x) / (b.y - a.y) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+// the smallest enclosing polygon of each polygon
+fn parents(polygons: &[Vec<Point>], areas: &[f64], index: &BoundsIndex) -> Vec<Option<usize>> {
+    (0..polygons.len()).map(|i| {
+        index.around(&polygons[i][0])
+            .filter(|j| *j != i && areas[*j] > areas[i] && contains(&polygons[*j], &polygons[i][0]))
+            .min_by(|a, b| areas[*a].partial_cmp(&areas[*b]).unwrap())
+    }).collect()
+}
+
+// The polygons' bounding boxes bucketed into a grid of cells about the size of a typical polygon, so
+// finding the polygons around a point only tests those whose box covers it rather than every one.
+struct BoundsIndex {
+    bounds: Vec<(Point, Point)>,
+    cell: f64,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+}
+
+impl BoundsIndex {
+    fn new(polygons: &[Vec<Point>]) -> BoundsIndex {
+        let bounds = polygons.iter().map(|polygon| polygon_bounds(polygon)).collect::<Vec<_>>();
+        let mut sizes = bounds.iter().map(|(min, max)| (max.x - min.x).max(max.y - min.y)).collect::<Vec<_>>();
+        sizes.sort_by(f64::total_cmp);
+        let (min, max) = bounds.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), (min, max)| {
+            (low.min(min.x).min(min.y), high.max(max.x).max(max.y))
+        });
+        let extent = (max - min).max(0.0);
+        // no coarser than the median polygon, and no finer than 1/256 of the drawing so a sheet-sized
+        // outline doesn't fill millions of cells
+        let cell = sizes.get(sizes.len() / 2).copied().unwrap_or(0.0).max(extent / 256.0);
+        let cell = if cell > 0.0 && cell.is_finite() { cell } else { 1.0 };
+        let mut index = BoundsIndex { bounds: vec![], cell, cells: HashMap::new() };
+        for (i, (min, max)) in bounds.iter().enumerate() {
+            let ((x0, y0), (x1, y1)) = (index.key(min), index.key(max));
+            for x in x0..=x1 {
+                for y in y0..=y1 {
+                    index.cells.entry((x, y)).or_default().push(i);
+                }
+            }
+        }
+        index.bounds = bounds;
+        index
+    }
+
+    fn key(&self, point: &Point) -> (i64, i64) {
+        ((point.x / self.cell).floor() as i64, (point.y / self.cell).floor() as i64)
+    }
+
+    // the polygons whose box covers `point`, in ascending order
+    fn around<'a>(&'a self, point: &'a Point) -> impl Iterator<Item = usize> + 'a {
+        self.cells.get(&self.key(point)).into_iter().flatten().copied().filter(move |i| {
+            let (min, max) = &self.bounds[*i];
+            min.x <= point.x && point.x <= max.x && min.y <= point.y && point.y <= max.y
+        })
+    }
+}
+
+pub(crate) fn depth(parents: &[Option<usize>], mut i: usize) -> usize {
+    let mut depth = 0;
+    while let Some(parent) = parents[i] {
+        depth += 1;
+        i = parent;
+    }
+    depth
+}
+
+fn polygon_bounds(polygon: &[Point]) -> (Point, Point) {
+    polygon.iter().skip(1).fold((polygon[0].clone(), polygon[0].clone()), |(min, max), p| (
+        Point { x: min.x.min(p.x), y: min.y.min(p.y) },
+        Point { x: max.x.max(p.x), y: max.y.max(p.y) },
+    ))
+}
+
+// the distance between two boxes, 0 if they overlap
+fn box_gap(a: &(Point, Point), b: &(Point, Point)) -> f64 {
+    let dx = (b.0.x - a.1.x).max(a.0.x - b.1.x).max(0.0);
+    let dy = (b.0.y - a.1.y).max(a.0.y - b.1.y).max(0.0);
+    dx.hypot(dy)
+}
+
+// The grouping of one layer's pieces into parts, each an outer contour with the holes directly
+// inside it and the open geometry belonging to it.
+struct LayerParts {
+    polygons: Vec<Vec<Point>>,
+    // see `parents`
+    parents: Vec<Option<usize>>,
+    // index in `polygons` of each loop piece
+    loops: Vec<Option<usize>>,
+    // part of each piece, numbered in drawing order of the outer contours
+    parts: Vec<Option<usize>>,
+    // index in `polygons` of each part's outer contour
+    outers: Vec<usize>,
+}
+
+// Loops at even depth are outer contours starting a part, and loops at odd depth are holes joining
+// the part around them; islands inside holes are parts of their own. Open pieces join the part of the
+// innermost loop around their start, or else the part whose outer contour's box comes within
+// `proximity` of theirs, the nearest if several do.
+fn layer_parts(pieces: &[Piece], proximity: f64) -> LayerParts {
+    let mut polygons = vec![];
+    let loops = pieces.iter().map(|piece| outline(piece).map(|polygon| {
+        polygons.push(polygon);
+        polygons.len() - 1
+    })).collect::<Vec<_>>();
+    let areas = polygons.iter().map(|polygon| area(polygon)).collect::<Vec<_>>();
+    let index = BoundsIndex::new(&polygons);
+    let parents = parents(&polygons, &areas, &index);
+    let is_outer = (0..polygons.len()).map(|k| depth(&parents, k).is_multiple_of(2)).collect::<Vec<_>>();
+    let mut outers = vec![];
+    let mut loop_parts = vec![0; polygons.len()];
+    for k in 0..polygons.len() {
+        if is_outer[k] {
+            loop_parts[k] = outers.len();
+            outers.push(k);
+        }
+    }
+    for k in 0..polygons.len() {
+        if !is_outer[k] {
+            loop_parts[k] = loop_parts[parents[k].unwrap()];
+        }
+    }
+    let bounds = outers.iter().map(|k| polygon_bounds(&polygons[*k])).collect::<Vec<_>>();
+    let parts = pieces.iter().zip(loops.iter()).map(|(piece, polygon)| {
+        if let Some(k) = polygon {
+            return Some(loop_parts[*k]);
+        }
+        let entity = match piece {
+            Piece::Entity(entity) => entity,
+            Piece::Loop(..) => return None,
+        };
+        let (start, entity_bounds) = (entity.start_point()?, entity.bounding_box()?);
+        let around = index.around(&start)
+            .filter(|k| contains(&polygons[*k], &start))
+            .min_by(|a, b| areas[*a].total_cmp(&areas[*b]));
+        if let Some(k) = around {
+            return Some(loop_parts[k]);
+        }
+        (0..outers.len())
+            .map(|part| (part, box_gap(&bounds[part], &entity_bounds)))
+            .filter(|(_, gap)| *gap <= proximity)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(part, _)| part)
+    }).collect();
+    LayerParts { polygons, parents, loops, parts, outers }
+}
+
+// the loop reports of one layer's polygons, with `parent` offset by the loops of earlier layers
+fn layer_loops(layer: &Layer, polygons: &[Vec<Point>], parents: &[Option<usize>], offset: usize) -> Vec<LoopReport> {
+    polygons.iter().enumerate().map(|(i, polygon)| {
+        let (min, max) = polygon_bounds(polygon);
+        LoopReport {
+            layer: layer.name.clone(),
+            min,
+            max,
+            area: area(polygon),
+            depth: depth(parents, i),
+            parent: parents[i].map(|parent| parent + offset),
+        }
+    }).collect()
+}
+
+// the piece index of the smallest loop around each closed piece, None for outer contours and open pieces
+pub(crate) fn piece_parents(pieces: &[Piece]) -> Vec<Option<usize>> {
+    let (loop_indices, polygons): (Vec<usize>, Vec<Vec<Point>>) = pieces.iter().enumerate()
+        .filter_map(|(i, piece)| outline(piece).map(|polygon| (i, polygon)))
+        .unzip();
+    let areas = polygons.iter().map(|polygon| area(polygon)).collect::<Vec<_>>();
+    let mut parents = vec![None; pieces.len()];
+    for (k, parent) in self::parents(&polygons, &areas, &BoundsIndex::new(&polygons)).into_iter().enumerate() {
+        parents[loop_indices[k]] = parent.map(|parent| loop_indices[parent]);
+    }
+    parents
+}
+
+// the nesting depth of each closed piece, 0 for outer contours, 1 for the holes in them and so on, and
+// None for open pieces
+pub(crate) fn piece_depths(pieces: &[Piece]) -> Vec<Option<usize>> {
+    let parents = piece_parents(pieces);
+    pieces.iter().enumerate().map(|(i, piece)| outline(piece).map(|_| depth(&parents, i))).collect()
+}
+
+// Builds the containment tree of the closed loops (rings of connected entities and circles) on each
+// layer. Loops are listed in drawing order and `parent` refers to an index in the returned list.
+pub fn loops(drawing: &Drawing) -> Vec<LoopReport> {
+    let mut loops = vec![];
+    for layer in drawing.layers.iter() {
+        let polygons = split_loops(layer.entities.clone()).iter().filter_map(outline).collect::<Vec<_>>();
+        let areas = polygons.iter().map(|polygon| area(polygon)).collect::<Vec<_>>();
+        let parents = parents(&polygons, &areas, &BoundsIndex::new(&polygons));
+        let layer_loops = layer_loops(layer, &polygons, &parents, loops.len());
+        loops.extend(layer_loops);
+    }
+    loops
+}
+
+// Groups each layer's loops and open geometry into parts (see `layer_parts`), for nesting tools that
+// place whole parts. Loops are referred to by their index in `loops`.
+pub fn parts(drawing: &Drawing, proximity: f64) -> Vec<PartReport> {
+    loops_and_parts(drawing, proximity).1
+}
+
+// `loops` and `parts` together, nesting each layer's loops once for both
+pub fn loops_and_parts(drawing: &Drawing, proximity: f64) -> (Vec<LoopReport>, Vec<PartReport>) {
+    let mut loops = vec![];
+    let mut parts = vec![];
+    for layer in drawing.layers.iter() {
+        let pieces = split_loops(layer.entities.clone());
+        let grouped = layer_parts(&pieces, proximity);
+        let loop_offset = loops.len();
+        loops.extend(layer_loops(layer, &grouped.polygons, &grouped.parents, loop_offset));
+        let first = parts.len();
+        for outer in grouped.outers.iter() {
+            let (min, max) = polygon_bounds(&grouped.polygons[*outer]);
+            parts.push(PartReport { layer: layer.name.clone(), min, max, loops: vec![loop_offset + outer], open_entities: 0 });
+        }
+        for ((piece, polygon), part) in pieces.iter().zip(grouped.loops.iter()).zip(grouped.parts.iter()) {
+            let part = match part {
+                Some(part) => &mut parts[first + part],
+                None => continue,
+            };
+            match (polygon, piece) {
+                (Some(k), _) if part.loops[0] != loop_offset + k => part.loops.push(loop_offset + k),
+                (None, Piece::Entity(_)) => part.open_entities += 1,
+                _ => (),
+            }
+        }
+    }
+    (loops, parts)
+}
+
+fn piece_entities(piece: Piece) -> Vec<Entity> {
+    match piece {
+        Piece::Loop(_, entities) => entities,
+        Piece::Entity(entity) => vec![entity],
+    }
+}
+
+// Moves the entities of each part onto a layer of its own, named after its layer with `-part1`,
+// `-part2` and so on, added after the drawing's layers. Entities belonging to no part stay where they
+// are. Returns the number of parts.
+pub fn parts_to_layers(drawing: &mut Drawing, proximity: f64) -> usize {
+    let mut part_layers = vec![];
+    for layer in drawing.layers.iter_mut() {
+        let pieces = split_loops(std::mem::take(&mut layer.entities));
+        let grouped = layer_parts(&pieces, proximity);
+        let first = part_layers.len();
+        for n in 0..grouped.outers.len() {
+            let mut part = layer.empty_like();
+            part.name = format!("{}-part{}", layer.name, n + 1);
+            part_layers.push(part);
+        }
+        for (piece, part) in pieces.into_iter().zip(grouped.parts) {
+            match part {
+                Some(part) => part_layers[first + part].entities.extend(piece_entities(piece)),
+                None => layer.entities.extend(piece_entities(piece)),
+            }
+        }
+    }
+    let count = part_layers.len();
+    drawing.layers.extend(part_layers);
+    count
+}
+
+// the first anonymous block name after `number` not already taken
+fn anonymous_block_name(blocks: &[Block], number: &mut usize) -> String {
+    loop {
+        *number += 1;
+        let name = format!("*U{}", number);
+        if !blocks.iter().any(|block| block.name == name) {
+            return name;
+        }
+    }
+}
+
+// Moves the entities of each part into an anonymous block of its own, like `loops_to_blocks` does
+// with loops, inserted at the origin on the part's layer. Returns the number of parts.
+pub fn parts_to_blocks(drawing: &mut Drawing, proximity: f64) -> usize {
+    let mut number = 0;
+    let mut count = 0;
+    for layer in drawing.layers.iter_mut() {
+        let pieces = split_loops(std::mem::take(&mut layer.entities));
+        let grouped = layer_parts(&pieces, proximity);
+        let mut parts = vec![vec![]; grouped.outers.len()];
+        for (piece, part) in pieces.into_iter().zip(grouped.parts) {
+            match part {
+                Some(part) => parts[part].extend(piece_entities(piece)),
+                None => layer.entities.extend(piece_entities(piece)),
+            }
+        }
+        for entities in parts {
+            let name = anonymous_block_name(&drawing.blocks, &mut number);
+            let mut part = layer.empty_like();
+            part.entities = entities;
+            drawing.blocks.push(Block { name: name.clone(), base: Point { x: 0.0, y: 0.0 }, layers: vec![part] });
+            layer.inserts.push(Insert { block: name, position: Point { x: 0.0, y: 0.0 }, x_scale: 1.0, y_scale: 1.0, rotation: 0.0 });
+            count += 1;
+        }
+    }
+    count
+}
+
+fn emit_subtree(i: usize, children: &[Vec<usize>], pieces: &mut [Option<Piece>], out: &mut Vec<Entity>) {
+    for child in children[i].iter() {
+        emit_subtree(*child, children, pieces, out);
+    }
+    match pieces[i].take() {
+        Some(Piece::Loop(_, entities)) => out.extend(entities),
+        Some(Piece::Entity(entity)) => out.push(entity),
+        None => (),
+    }
+}
+
+// Reorders each layer so every loop comes after the loops nested inside it, keeping the parts
+// themselves in drawing order. Cutting holes before their outer contour keeps the part attached to
+// the sheet until it is finished.
+pub fn holes_first(drawing: &mut Drawing) {
+    for layer in drawing.layers.iter_mut() {
+        let pieces = split_loops(std::mem::take(&mut layer.entities));
+        let parents = piece_parents(&pieces);
+        let mut children = vec![vec![]; pieces.len()];
+        for (i, parent) in parents.iter().enumerate() {
+            if let Some(parent) = parent {
+                children[*parent].push(i);
+            }
+        }
+        let mut pieces = pieces.into_iter().map(Some).collect::<Vec<_>>();
+        for (i, parent) in parents.iter().enumerate() {
+            if parent.is_none() {
+                emit_subtree(i, &children, &mut pieces, &mut layer.entities);
+            }
+        }
+    }
+}
+
+// Moves every closed loop (rings of connected entities and circles) into an anonymous block of its
+// own, named `*U1`, `*U2` and so on, inserted at the origin on the loop's layer, so tools working on
+// blocks can place each part separately. Open paths stay where they are. Returns the number of loops
+// moved.
+pub fn loops_to_blocks(drawing: &mut Drawing) -> usize {
+    let mut number = 0;
+    let mut moved = 0;
+    for layer in drawing.layers.iter_mut() {
+        for piece in split_loops(std::mem::take(&mut layer.entities)) {
+            let entities = match piece {
+                Piece::Loop(_, entities) => entities,
+                Piece::Entity(circle @ Entity::Circle { .. }) => vec![circle],
+                Piece::Entity(entity) => {
+                    layer.entities.push(entity);
+                    continue;
+                },
+            };
+            let name = anonymous_block_name(&drawing.blocks, &mut number);
+            let mut part = layer.empty_like();
+            part.entities = entities;
+            drawing.blocks.push(Block { name: name.clone(), base: Point { x: 0.0, y: 0.0 }, layers: vec![part] });
+            layer.inserts.push(Insert { block: name, position: Point { x: 0.0, y: 0.0 }, x_scale: 1.0, y_scale: 1.0, rotation: 0.0 });
+            moved += 1;
+        }
+    }
+    moved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hole_in_part() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let mut drawing = Drawing::new(vec![
+            Entity::Polyline { curve_type: 0, vertices: vec![p(0.0, 0.0), p(10.0, 0.0), p(10.0, 10.0), p(0.0, 10.0), p(0.0, 0.0)], widths: vec![] },
+            Entity::Circle { center: p(5.0, 5.0), radius: 2.0 },
+            Entity::Circle { center: p(20.0, 5.0), radius: 2.0 },
+        ]);
+        let loops = loops(&drawing);
+        assert_eq!(loops.len(), 3);
+        assert_eq!(loops[0].depth, 0);
+        assert_eq!(loops[1].parent, Some(0));
+        assert_eq!(loops[1].depth, 1);
+        assert_eq!(loops[2].parent, None);
+
+        holes_first(&mut drawing);
+        let entities = drawing.entities().collect::<Vec<_>>();
+        assert!(matches!(entities[0], Entity::Circle { center, .. } if center.x == 5.0));
+        assert!(matches!(entities[1], Entity::Polyline { .. }));
+    }
+
+    #[test]
+    fn test_sheet_of_parts() {
+        // a sheet outline around a grid of plates, each with a hole, so polygons span many index cells
+        let p = |x: f64, y: f64| Point { x, y };
+        let square = |x: f64, y: f64, size: f64| Entity::Polyline { curve_type: 0, vertices: vec![p(x, y), p(x + size, y), p(x + size, y + size), p(x, y + size), p(x, y)], widths: vec![] };
+        let mut entities = vec![square(-1.0, -1.0, 102.0)];
+        for i in 0..10 {
+            for j in 0..10 {
+                entities.push(square(i as f64 * 10.0, j as f64 * 10.0, 8.0));
+                entities.push(Entity::Circle { center: p(i as f64 * 10.0 + 4.0, j as f64 * 10.0 + 4.0), radius: 2.0 });
+            }
+        }
+        let loops = loops(&Drawing::new(entities));
+        assert_eq!(loops.len(), 201);
+        assert_eq!(loops[0].parent, None);
+        for k in 0..100 {
+            assert_eq!((loops[1 + 2 * k].parent, loops[1 + 2 * k].depth), (Some(0), 1));
+            assert_eq!((loops[2 + 2 * k].parent, loops[2 + 2 * k].depth), (Some(1 + 2 * k), 2));
+        }
+    }
+
+    #[test]
+    fn test_parts() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let square = |x: f64, size: f64| Entity::Polyline { curve_type: 0, vertices: vec![p(x, 0.0), p(x + size, 0.0), p(x + size, size), p(x, size), p(x, 0.0)], widths: vec![] };
+        let drawing = Drawing::new(vec![
+            // a hole listed before its part, with an island inside it
+            Entity::Circle { center: p(5.0, 5.0), radius: 3.0 },
+            square(0.0, 10.0),
+            Entity::Circle { center: p(5.0, 5.0), radius: 1.0 },
+            // an engraving inside the part and a tab just outside it
+            Entity::Line(p(1.0, 9.0), p(2.0, 9.0)),
+            Entity::Line(p(10.5, 5.0), p(11.0, 5.0)),
+            square(20.0, 5.0),
+            Entity::Line(p(50.0, 0.0), p(60.0, 0.0)),
+        ]);
+        let found = parts(&drawing, 1.0);
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0].loops, vec![1, 0]);
+        assert_eq!(found[0].open_entities, 2);
+        assert_eq!((found[0].min.clone(), found[0].max.clone()), (p(0.0, 0.0), p(10.0, 10.0)));
+        assert_eq!(found[1].loops, vec![2]);
+        assert_eq!(found[2].loops, vec![3]);
+        assert_eq!(found[2].open_entities, 0);
+        assert_eq!(parts(&drawing, 0.1)[0].open_entities, 1);
+        let (nested, _) = loops_and_parts(&drawing, 1.0);
+        assert_eq!(nested.iter().map(|l| (l.parent, l.depth)).collect::<Vec<_>>(), loops(&drawing).iter().map(|l| (l.parent, l.depth)).collect::<Vec<_>>());
+
+        let mut layered = drawing.clone();
+        assert_eq!(parts_to_layers(&mut layered, 1.0), 3);
+        assert_eq!(layered.layers.iter().map(|layer| (layer.name.as_str(), layer.entities.len())).collect::<Vec<_>>(),
+            vec![("0", 1), ("0-part1", 4), ("0-part2", 1), ("0-part3", 1)]);
+
+        let mut blocked = drawing;
+        assert_eq!(parts_to_blocks(&mut blocked, 1.0), 3);
+        assert_eq!(blocked.layers[0].entities, vec![Entity::Line(p(50.0, 0.0), p(60.0, 0.0))]);
+        assert_eq!(blocked.blocks.iter().map(|block| block.entity_count()).collect::<Vec<_>>(), vec![4, 1, 1]);
+    }
+
+    #[test]
+    fn test_loops_to_blocks() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let square = vec![
+            Entity::Line(p(0.0, 0.0), p(10.0, 0.0)),
+            Entity::Line(p(10.0, 0.0), p(10.0, 10.0)),
+            Entity::Line(p(10.0, 10.0), p(0.0, 10.0)),
+            Entity::Line(p(0.0, 10.0), p(0.0, 0.0)),
+        ];
+        let open = Entity::Line(p(20.0, 0.0), p(30.0, 0.0));
+        let circle = Entity::Circle { center: p(5.0, 5.0), radius: 2.0 };
+        let mut drawing = Drawing::new(square.iter().cloned().chain(vec![open.clone(), circle.clone()]).collect());
+        drawing.blocks.push(Block { name: "*U1".to_string(), base: p(0.0, 0.0), layers: vec![] });
+        assert_eq!(loops_to_blocks(&mut drawing), 2);
+        assert_eq!(drawing.layers[0].entities, vec![open]);
+        let names = drawing.layers[0].inserts.iter().map(|insert| insert.block.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["*U2", "*U3"]);
+        assert_eq!(drawing.blocks[1].layers[0].entities, square);
+        assert_eq!(drawing.blocks[2].layers[0].entities, vec![circle]);
+
+        let reread = Drawing::parse(&drawing.to_string()).unwrap();
+        assert_eq!(reread.blocks.iter().map(|block| block.entity_count()).collect::<Vec<_>>(), vec![0, 4, 1]);
+    }
+}
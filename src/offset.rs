@@ -0,0 +1,212 @@
+use crate::dxf::*;
+use crate::nesting::piece_depths;
+use crate::path::*;
+
+impl Segment {
+    // the segment moved `t` to the right of its direction of travel, or None if an arc collapses
+    fn offset(&self, t: f64) -> Option<Segment> {
+        match self {
+            Segment::Line(start, end) => {
+                let (ux, uy) = self.tangent(start);
+                Some(Segment::Line(along(start, (uy, -ux), t), along(end, (uy, -ux), t)))
+            },
+            Segment::Arc { center, radius, start, end, ccw } => {
+                let new_radius = if *ccw { radius + t } else { radius - t };
+                if new_radius <= EPSILON {
+                    return None;
+                }
+                let scale = |p: &Point| Point {
+                    x: center.x + (p.x - center.x) * new_radius / radius,
+                    y: center.y + (p.y - center.y) * new_radius / radius,
+                };
+                Some(Segment::Arc { center: center.clone(), radius: new_radius, start: scale(start), end: scale(end), ccw: *ccw })
+            },
+        }
+    }
+}
+
+fn line_circle(p: &Point, u: (f64, f64), center: &Point, radius: f64) -> Vec<Point> {
+    let (fx, fy) = (p.x - center.x, p.y - center.y);
+    let b = fx * u.0 + fy * u.1;
+    let disc = b * b - (fx * fx + fy * fy - radius * radius);
+    if disc < 0.0 {
+        return vec![];
+    }
+    vec![along(p, u, -b + disc.sqrt()), along(p, u, -b - disc.sqrt())]
+}
+
+// intersections of the segments extended to full lines and circles
+fn intersect(a: &Segment, b: &Segment) -> Vec<Point> {
+    match (a, b) {
+        (Segment::Line(p, _), Segment::Line(q, _)) => {
+            let (u, v) = (a.tangent(p), b.tangent(q));
+            let denom = cross(u, v);
+            if denom.abs() < EPSILON {
+                return vec![];
+            }
+            vec![along(p, u, cross((q.x - p.x, q.y - p.y), v) / denom)]
+        },
+        (Segment::Line(p, _), Segment::Arc { center, radius, .. }) => line_circle(p, a.tangent(p), center, *radius),
+        (Segment::Arc { center, radius, .. }, Segment::Line(q, _)) => line_circle(q, b.tangent(q), center, *radius),
+        (Segment::Arc { center: c1, radius: r1, .. }, Segment::Arc { center: c2, radius: r2, .. }) => {
+            let d = c1.dist(c2);
+            if d < EPSILON || d > r1 + r2 || d < (r1 - r2).abs() {
+                return vec![];
+            }
+            let a = (r1 * r1 - r2 * r2 + d * d) / (2.0 * d);
+            let h = (r1 * r1 - a * a).max(0.0).sqrt();
+            let u = ((c2.x - c1.x) / d, (c2.y - c1.y) / d);
+            let mid = along(c1, u, a);
+            vec![along(&mid, (-u.1, u.0), h), along(&mid, (u.1, -u.0), h)]
+        },
+    }
+}
+
+fn offset_loop(segments: &[Segment], distance: f64) -> Vec<Entity> {
+    // positive distances grow the loop, so offset to the right of a counter-clockwise loop
+    let t = if signed_area(segments) > 0.0 { distance } else { -distance };
+    let (mut originals, mut offsets) = (vec![], vec![]);
+    for segment in segments.iter() {
+        if let Some(offset) = segment.offset(t) {
+            originals.push(segment);
+            offsets.push(offset);
+        }
+    }
+    let count = offsets.len();
+    let mut joins = vec![None; count];
+    for i in 0..count {
+        let j = (i + 1) % count;
+        if offsets[i].end() == offsets[j].start() {
+            continue;
+        }
+        let corner = originals[j].start();
+        let turn = cross(originals[i].tangent(originals[i].end()), originals[j].tangent(corner));
+        if turn * t > 0.0 {
+            // the offset pieces have pulled apart, so round the corner
+            joins[i] = Some(Segment::Arc {
+                center: corner.clone(),
+                radius: t.abs(),
+                start: offsets[i].end().clone(),
+                end: offsets[j].start().clone(),
+                ccw: turn > 0.0,
+            });
+            continue;
+        }
+        let nearest = intersect(&offsets[i], &offsets[j]).into_iter()
+            .min_by(|a, b| a.dist(corner).partial_cmp(&b.dist(corner)).unwrap());
+        match nearest {
+            Some(point) => {
+                offsets[i].set_end(point.clone());
+                offsets[j].set_start(point);
+            },
+            None => joins[i] = Some(Segment::Line(offsets[i].end().clone(), offsets[j].start().clone())),
+        }
+    }
+    offsets.iter().zip(joins.iter()).flat_map(|(offset, join)| {
+        std::iter::once(offset.to_entity()).chain(join.iter().map(|join| join.to_entity()))
+    }).collect()
+}
+
+fn offset_layer(layer: &mut Layer, distance: f64) -> usize {
+    let pieces = split_loops(std::mem::take(&mut layer.entities));
+    let depths = piece_depths(&pieces);
+    let mut loops = 0;
+    for (piece, depth) in pieces.into_iter().zip(depths) {
+        // holes move the other way, so a part grows by shrinking its holes
+        let distance = if depth.is_some_and(|depth| depth % 2 == 1) { -distance } else { distance };
+        match piece {
+            Piece::Loop(segments, _) => {
+                layer.entities.extend(offset_loop(&segments, distance));
+                loops += 1;
+            },
+            Piece::Entity(Entity::Circle { center, radius }) => {
+                if radius + distance > EPSILON {
+                    layer.entities.push(Entity::Circle { center, radius: radius + distance });
+                }
+                loops += 1;
+            },
+            Piece::Entity(entity) => layer.entities.push(entity),
+        }
+    }
+    loops
+}
+
+// Offsets every closed loop (circles and rings of connected lines, arcs and polylines) by `distance`:
+// positive values grow each part outward, its outer contour growing and the holes in it shrinking, and
+// negative values shrink it, with convex corners rounded and concave corners trimmed. Islands inside
+// holes are parts of their own. Open paths are left untouched. Returns the number of loops offset.
+pub fn offset_loops(drawing: &mut Drawing, distance: f64) -> usize {
+    drawing.layers.iter_mut().map(|layer| offset_layer(layer, distance)).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(size: f64) -> Drawing {
+        let p = |x: f64, y: f64| Point { x, y };
+        Drawing::new(vec![
+            Entity::Line(p(0.0, 0.0), p(size, 0.0)),
+            Entity::Line(p(size, 0.0), p(size, size)),
+            Entity::Line(p(0.0, size), p(size, size)),
+            Entity::Line(p(0.0, size), p(0.0, 0.0)),
+        ])
+    }
+
+    #[test]
+    fn test_grow_square() {
+        let mut drawing = square(10.0);
+        assert_eq!(offset_loops(&mut drawing, 1.0), 1);
+        let entities = drawing.entities().collect::<Vec<_>>();
+        assert_eq!(entities.len(), 8);
+        for entity in entities {
+            match entity {
+                Entity::Line(from, to) => assert!((from.dist(to) - 10.0).abs() < 1e-9),
+                Entity::Arc { radius, .. } => assert!((radius - 1.0).abs() < 1e-9),
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_shrink_square() {
+        let mut drawing = square(10.0);
+        offset_loops(&mut drawing, -1.0);
+        let entities = drawing.entities().collect::<Vec<_>>();
+        assert_eq!(entities.len(), 4);
+        for entity in entities {
+            match entity {
+                Entity::Line(from, to) => {
+                    assert!((from.dist(to) - 8.0).abs() < 1e-9);
+                    assert!(from.x >= 1.0 - 1e-9 && from.x <= 9.0 + 1e-9);
+                },
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_hole_shrinks() {
+        // a square plate with a round hole and a square hole, which both close up as the plate grows
+        let mut drawing = square(30.0);
+        let p = |x: f64, y: f64| Point { x, y };
+        drawing.layers[0].entities.extend(vec![
+            Entity::Circle { center: p(8.0, 15.0), radius: 3.0 },
+            Entity::Line(p(18.0, 12.0), p(24.0, 12.0)),
+            Entity::Line(p(24.0, 12.0), p(24.0, 18.0)),
+            Entity::Line(p(24.0, 18.0), p(18.0, 18.0)),
+            Entity::Line(p(18.0, 18.0), p(18.0, 12.0)),
+        ]);
+        assert_eq!(offset_loops(&mut drawing, 1.0), 3);
+        let entities = drawing.entities().collect::<Vec<_>>();
+        assert!(entities.iter().any(|e| matches!(e, Entity::Circle { radius, .. } if (radius - 2.0).abs() < 1e-9)));
+        let lines = entities.iter().filter_map(|e| match e {
+            Entity::Line(from, to) => Some(from.dist(to)),
+            _ => None,
+        }).collect::<Vec<_>>();
+        // the plate's sides keep their length between rounded corners and the hole's shrink to 4
+        assert_eq!(lines.iter().filter(|length| (**length - 30.0).abs() < 1e-9).count(), 4);
+        assert_eq!(lines.iter().filter(|length| (**length - 4.0).abs() < 1e-9).count(), 4);
+        assert!(!entities.iter().any(|e| matches!(e, Entity::Arc { center, .. } if center.x > 17.0 && center.x < 25.0 && center.y > 11.0 && center.y < 19.0)));
+    }
+}
@@ -0,0 +1,217 @@
+use crate::dxf::*;
+use crate::nesting::{depth, piece_parents};
+use crate::path::*;
+use std::collections::HashMap;
+
+// Where each piece can be entered, bucketed into a grid for finding the entry nearest the tool.
+struct Entries {
+    cell: f64,
+    cells: HashMap<(i64, i64), Vec<(usize, Point)>>,
+    // the lowest and highest occupied cell keys
+    low: (i64, i64),
+    high: (i64, i64),
+}
+
+impl Entries {
+    fn new(entries: Vec<(usize, Point)>) -> Entries {
+        let (min, max) = entries.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(low, high), (_, p)| {
+            (low.min(p.x).min(p.y), high.max(p.x).max(p.y))
+        });
+        // about one entry per cell, were they spread evenly
+        let cell = (max - min) / (entries.len() as f64).sqrt();
+        let cell = if cell > 0.0 && cell.is_finite() { cell } else { 1.0 };
+        let mut index = Entries { cell, cells: HashMap::new(), low: (i64::MAX, i64::MAX), high: (i64::MIN, i64::MIN) };
+        for (piece, point) in entries {
+            let key = index.key(&point);
+            index.low = (index.low.0.min(key.0), index.low.1.min(key.1));
+            index.high = (index.high.0.max(key.0), index.high.1.max(key.1));
+            index.cells.entry(key).or_default().push((piece, point));
+        }
+        index
+    }
+
+    fn key(&self, point: &Point) -> (i64, i64) {
+        ((point.x / self.cell).floor() as i64, (point.y / self.cell).floor() as i64)
+    }
+
+    // The occupied cells on the border of the square `ring` cells out from `center`.
+    fn ring(&self, (cx, cy): (i64, i64), ring: i64) -> Vec<(i64, i64)> {
+        let (low, high) = (self.low, self.high);
+        let columns = (cx - ring).max(low.0)..=(cx + ring).min(high.0);
+        let mut cells = vec![];
+        let rows = if ring == 0 { vec![cy] } else { vec![cy - ring, cy + ring] };
+        for y in rows {
+            if (low.1..=high.1).contains(&y) {
+                cells.extend(columns.clone().map(|x| (x, y)));
+            }
+        }
+        for y in (cy - ring + 1).max(low.1)..=(cy + ring - 1).min(high.1) {
+            for x in [cx - ring, cx + ring] {
+                if (low.0..=high.0).contains(&x) {
+                    cells.push((x, y));
+                }
+            }
+        }
+        cells
+    }
+
+    // the nearest entry to `from` of a piece that is `ready`, searching rings of cells outward from the
+    // first that reaches an occupied cell until no closer one can be left
+    fn nearest(&self, from: &Point, ready: impl Fn(usize) -> bool) -> Option<(usize, Point)> {
+        if self.cells.is_empty() {
+            return None;
+        }
+        let (cx, cy) = self.key(from);
+        let (low, high) = (self.low, self.high);
+        let first = (low.0 - cx).max(cx - high.0).max(low.1 - cy).max(cy - high.1).max(0);
+        let last = (cx - low.0).max(high.0 - cx).max(cy - low.1).max(high.1 - cy);
+        let mut best: Option<(f64, usize, &Point)> = None;
+        for ring in first..=last {
+            for key in self.ring((cx, cy), ring) {
+                for (piece, point) in self.cells.get(&key).into_iter().flatten() {
+                    let distance = point.dist(from);
+                    if best.is_none_or(|(nearest, _, _)| distance < nearest) && ready(*piece) {
+                        best = Some((distance, *piece, point));
+                    }
+                }
+            }
+            // anything further out is at least this many cells away
+            if matches!(best, Some((nearest, _, _)) if nearest <= ring as f64 * self.cell) {
+                break;
+            }
+        }
+        best.map(|(_, piece, point)| (piece, point.clone()))
+    }
+}
+
+// Loops can be entered at any vertex, lines and polylines at either end and anything else at its start.
+fn entries(piece: &Piece) -> Vec<Point> {
+    match piece {
+        Piece::Loop(segments, _) => segments.iter().map(|segment| segment.start().clone()).collect(),
+        Piece::Entity(entity @ (Entity::Line(..) | Entity::Polyline { .. })) => entity.start_point().into_iter().chain(entity.end_point()).collect(),
+        Piece::Entity(entity) => entity.start_point().into_iter().collect(),
+    }
+}
+
+// Orders each layer's pieces to cut rapid travel, starting from the origin and going each time to the
+// nearest piece not yet cut, entered where it is nearest: loops at their nearest vertex and lines and
+// polylines from their nearer end. A loop is only cut after the loops inside it, so no part is freed
+// before its holes, and outer contours run clockwise and holes counter-clockwise, keeping the part on
+// the right of the cut. Loops are rewritten as lines and arcs in travel order. Arcs, circles and
+// ellipses stay counter-clockwise.
+pub fn optimize_start(drawing: &mut Drawing) {
+    let mut from = Point { x: 0.0, y: 0.0 };
+    for layer in drawing.layers.iter_mut() {
+        let pieces = split_loops(std::mem::take(&mut layer.entities));
+        let parents = piece_parents(&pieces);
+        let mut inside = vec![0; pieces.len()];
+        for parent in parents.iter().flatten() {
+            inside[*parent] += 1;
+        }
+        let index = Entries::new(pieces.iter().enumerate().flat_map(|(i, piece)| entries(piece).into_iter().map(move |entry| (i, entry))).collect());
+        let mut pieces = pieces.into_iter().map(Some).collect::<Vec<_>>();
+        while let Some((i, entry)) = index.nearest(&from, |i| pieces[i].is_some() && inside[i] == 0) {
+            if let Some(parent) = parents[i] {
+                inside[parent] -= 1;
+            }
+            match pieces[i].take() {
+                Some(Piece::Loop(mut segments, _)) => {
+                    let hole = depth(&parents, i) % 2 == 1;
+                    if (signed_area(&segments) > 0.0) != hole {
+                        segments = segments.into_iter().rev().map(Segment::reversed).collect();
+                    }
+                    let start = segments.iter().position(|segment| segment.start() == &entry).unwrap_or(0);
+                    segments.rotate_left(start);
+                    from = segments[0].start().clone();
+                    layer.entities.extend(segments.iter().map(Segment::to_entity));
+                },
+                Some(Piece::Entity(mut entity)) => {
+                    if entity.start_point().as_ref() != Some(&entry) {
+                        entity.reverse();
+                    }
+                    if let Some(end) = entity.end_point() {
+                        from = end;
+                    }
+                    layer.entities.push(entity);
+                },
+                None => (),
+            }
+        }
+        // pieces with nowhere to enter, such as empty polylines, and the loops around them
+        for piece in pieces.into_iter().flatten() {
+            match piece {
+                Piece::Loop(_, entities) => layer.entities.extend(entities),
+                Piece::Entity(entity) => layer.entities.push(entity),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(x: f64, y: f64, size: f64) -> Vec<Entity> {
+        let p = |x: f64, y: f64| Point { x, y };
+        vec![
+            Entity::Line(p(x, y), p(x + size, y)),
+            Entity::Line(p(x + size, y), p(x + size, y + size)),
+            Entity::Line(p(x + size, y + size), p(x, y + size)),
+            Entity::Line(p(x, y + size), p(x, y)),
+        ]
+    }
+
+    #[test]
+    fn test_start_near_previous() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let mut entities = vec![Entity::Line(p(-5.0, 0.0), p(9.0, 0.0))];
+        entities.extend(square(10.0, 0.0, 10.0));
+        let mut drawing = Drawing::new(entities);
+        optimize_start(&mut drawing);
+        let entities = drawing.entities().collect::<Vec<_>>();
+        assert_eq!(entities.len(), 5);
+        // clockwise from the corner nearest the line's end
+        assert_eq!(entities[1], &Entity::Line(p(10.0, 0.0), p(10.0, 10.0)));
+        assert_eq!(entities[4], &Entity::Line(p(20.0, 0.0), p(10.0, 0.0)));
+    }
+
+    #[test]
+    fn test_order_and_direction() {
+        let p = |x: f64, y: f64| Point { x, y };
+        // a far plate listed first, a line to be cut backwards and a near plate with a hole listed last
+        let mut entities = square(100.0, 0.0, 10.0);
+        entities.push(Entity::Line(p(60.0, 0.0), p(30.0, 0.0)));
+        entities.extend(square(0.0, 0.0, 20.0));
+        entities.extend(square(5.0, 5.0, 5.0).into_iter().rev().map(|mut line| {
+            line.reverse();
+            line
+        }));
+        let mut drawing = Drawing::new(entities);
+        optimize_start(&mut drawing);
+        let entities = drawing.entities().cloned().collect::<Vec<_>>();
+        assert_eq!(entities.len(), 13);
+        // the hole first, counter-clockwise from its corner nearest the origin
+        assert_eq!(entities[0], Entity::Line(p(5.0, 5.0), p(10.0, 5.0)));
+        // then the plate around it clockwise, from its corner nearest the hole
+        assert_eq!(entities[4], Entity::Line(p(0.0, 0.0), p(0.0, 20.0)));
+        assert_eq!(entities[8], Entity::Line(p(30.0, 0.0), p(60.0, 0.0)));
+        assert_eq!(entities[9], Entity::Line(p(100.0, 0.0), p(100.0, 10.0)));
+    }
+
+    #[test]
+    fn test_far_from_origin() {
+        let p = |x: f64, y: f64| Point { x, y };
+        // listed furthest first, each a little further out than the one before it in travel order
+        let entities = (0..100).rev().map(|i| {
+            let (x, y) = (1e6 + (i % 10) as f64 * 3.0, 1e6 + (i / 10) as f64 * 3.0);
+            Entity::Line(p(x, y), p(x + 1.0, y))
+        }).collect::<Vec<_>>();
+        let mut drawing = Drawing::new(entities);
+        optimize_start(&mut drawing);
+        let entities = drawing.entities().collect::<Vec<_>>();
+        assert_eq!(entities.len(), 100);
+        assert_eq!(entities[0], &Entity::Line(p(1e6, 1e6), p(1e6 + 1.0, 1e6)));
+        // then on to the next line over, which starts 2 past the end of the first
+        assert_eq!(entities[1], &Entity::Line(p(1e6 + 3.0, 1e6), p(1e6 + 4.0, 1e6)));
+    }
+}
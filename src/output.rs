@@ -0,0 +1,220 @@
+use crate::dxf::*;
+use crate::dxf_process::DxfConfig;
+use crate::gcode::{self, GcodeBackend};
+use crate::hpgl::{self, HpglBackend, HPGL_UNITS_PER_MM};
+use crate::svg::{self, SvgBackend};
+use crate::result::*;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Streams a drawing into some output format one entity at a time. Implement this to add a custom
+// format; backends write into a caller-owned buffer.
+pub trait OutputBackend {
+    fn begin(&mut self) {}
+
+    // called with each block definition after `begin`, before any layer
+    fn block(&mut self, _block: &Block) {}
+
+    // called before the entities of each layer
+    fn layer(&mut self, _layer: &Layer) {}
+
+    fn entity(&mut self, entity: &Entity);
+
+    // called after the entities of a layer with each block it inserts
+    fn insert(&mut self, _insert: &Insert) {}
+
+    // called after a layer's inserts with each entity kept as it was read
+    fn raw(&mut self, _raw: &RawEntity) {}
+
+    fn finish(&mut self) {}
+
+    // The text written so far, for callers streaming it out as they go. Whatever is taken from it is
+    // not written again.
+    fn buffer(&mut self) -> Option<&mut String> {
+        None
+    }
+}
+
+impl Drawing {
+    pub fn write_to(&self, backend: &mut dyn OutputBackend) {
+        backend.begin();
+        for block in self.blocks.iter() {
+            backend.block(block);
+        }
+        for layer in self.layers.iter() {
+            backend.layer(layer);
+            for entity in layer.entities.iter() {
+                backend.entity(entity);
+            }
+            for insert in layer.inserts.iter() {
+                backend.insert(insert);
+            }
+            for raw in layer.raw.iter() {
+                backend.raw(raw);
+            }
+        }
+        backend.finish();
+    }
+}
+
+// picks a backend from the output file extension, falling back to DXF, set up from `config`
+pub fn backend_for_path<'a>(path: &str, drawing: &Drawing, config: &DxfConfig, out: &'a mut String) -> Box<dyn OutputBackend + 'a> {
+    if hpgl::is_hpgl_path(path) {
+        Box::new(HpglBackend::new(out, HPGL_UNITS_PER_MM))
+    } else if svg::is_svg_path(path) {
+        Box::new(SvgBackend::new(out, drawing, config.resolution))
+    } else if gcode::is_gcode_path(path) {
+        let mut backend = GcodeBackend::new(out);
+        backend.three_point_arcs = config.three_point_arcs;
+        Box::new(backend)
+    } else {
+        Box::new(DxfBackend::new(out).with_angle_precision(config.angle_precision).with_version(config.dxf_version).with_fragment(config.fragment).with_elevation(drawing.elevation).with_header_origins(drawing))
+    }
+}
+
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// where output for `path` is written until it is complete: `out.dxf.tmp` for `out.dxf`
+pub fn temporary_path(path: &Path) -> PathBuf {
+    suffixed(path, ".tmp")
+}
+
+// Moves a complete temporary file over `path`. With `backup`, a file already at `path` is kept as
+// `path.bak` (`out.dxf.bak`), replacing any older backup.
+pub fn replace_with(temporary: &Path, path: &Path, backup: bool) -> Result<()> {
+    if backup && path.exists() {
+        std::fs::rename(path, suffixed(path, ".bak"))?;
+    }
+    std::fs::rename(temporary, path)?;
+    Ok(())
+}
+
+// Writes to a temporary file next to `path` and renames it into place, so a failure part way through
+// never leaves a truncated file at `path`: it holds either its old contents or all of the new ones.
+pub fn write_atomic(path: &Path, contents: &[u8], backup: bool) -> Result<()> {
+    let temporary = temporary_path(path);
+    let written = std::fs::File::create(&temporary).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&temporary);
+        return Err(e.into());
+    }
+    replace_with(&temporary, path, backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a line running into a quarter arc, and a circle apart from them
+    fn known_drawing() -> Drawing {
+        let p = |x: f64, y: f64| Point { x, y };
+        Drawing::new(vec![
+            Entity::Line(p(0.0, 0.0), p(10.0, 0.0)),
+            Entity::Arc { center: p(10.0, 5.0), radius: 5.0, start_angle: 270.0, end_angle: 0.0 },
+            Entity::Circle { center: p(30.0, 5.0), radius: 2.5 },
+        ])
+    }
+
+    fn written(path: &str) -> String {
+        let drawing = known_drawing();
+        let mut out = String::new();
+        drawing.write_to(backend_for_path(path, &drawing, &DxfConfig::default(), &mut out).as_mut());
+        out
+    }
+
+    #[test]
+    fn test_dxf_backend() {
+        let out = written("out.dxf");
+        assert!(out.contains("\nLINE\n") && out.contains("\nARC\n") && out.contains("\nCIRCLE\n"));
+        assert_eq!(Drawing::parse(&out).unwrap().entities().cloned().collect::<Vec<_>>(), known_drawing().entities().cloned().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_svg_backend() {
+        assert_eq!(written("out.svg"), concat!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"-0.65 -8.15 33.8 8.8\">",
+            "<g transform=\"scale(1,-1)\" fill=\"none\" stroke=\"black\" stroke-width=\"1\">\n",
+            "<path vector-effect=\"non-scaling-stroke\" d=\"M 0 0 L 10 0\"/>\n",
+            "<path vector-effect=\"non-scaling-stroke\" d=\"M 9.999999999999998 0 A 5 5 0 0 1 15 5\"/>\n",
+            "<path vector-effect=\"non-scaling-stroke\" d=\"M 32.5 5 A 2.5 2.5 0 1 1 27.5 5 A 2.5 2.5 0 1 1 32.5 5\"/>\n",
+            "</g>\n</svg>\n",
+        ));
+    }
+
+    #[test]
+    fn test_hpgl_backend() {
+        // the arc starts where the line left the pen, so it is drawn without lifting it
+        assert_eq!(written("out.plt"), "IN;SP1;PU0,0;PD400,0;PD;AA400,200,90.000;PU1300,200;PD;AA1200,200,360.000;PU;SP0;\n");
+    }
+
+    #[test]
+    fn test_gcode_backend() {
+        assert_eq!(written("out.nc"), concat!(
+            "G0 X0.0000 Y0.0000\n",
+            "G1 X10.0000 Y0.0000\n",
+            "G3 X15.0000 Y5.0000 I0.0000 J5.0000\n",
+            "G0 X32.5000 Y5.0000\n",
+            "G3 X32.5000 Y5.0000 I-2.5000 J0.0000\n",
+        ));
+    }
+
+    // what a third-party format sees: every call, in order
+    #[derive(Default)]
+    struct Recorder(Vec<String>);
+
+    impl OutputBackend for Recorder {
+        fn begin(&mut self) {
+            self.0.push("begin".to_string());
+        }
+
+        fn layer(&mut self, layer: &Layer) {
+            self.0.push(format!("layer {}", layer.name));
+        }
+
+        fn entity(&mut self, entity: &Entity) {
+            let kind = match entity {
+                Entity::Line(..) => "line",
+                Entity::Arc { .. } => "arc",
+                Entity::Circle { .. } => "circle",
+                _ => "other",
+            };
+            self.0.push(kind.to_string());
+        }
+
+        fn finish(&mut self) {
+            self.0.push("finish".to_string());
+        }
+    }
+
+    #[test]
+    fn test_custom_backend() {
+        let mut recorder = Recorder::default();
+        known_drawing().write_to(&mut recorder);
+        assert_eq!(recorder.0, ["begin", "layer 0", "line", "arc", "circle", "finish"]);
+    }
+
+    #[test]
+    fn test_write_atomic() {
+        let dir = std::env::temp_dir().join(format!("dxf_welder_output_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("out.dxf");
+        write_atomic(&path, b"first", true).unwrap();
+        assert!(!dir.join("out.dxf.bak").exists());
+        write_atomic(&path, b"second", true).unwrap();
+        write_atomic(&path, b"third", false).unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"third");
+        assert_eq!(std::fs::read(dir.join("out.dxf.bak")).unwrap(), b"first");
+        assert!(!temporary_path(&path).exists());
+
+        // a directory that does not exist
+        assert!(write_atomic(&dir.join("missing").join("out.dxf"), b"lost", false).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,205 @@
+use crate::cleanup::{self, HealConfig};
+use crate::dxf::*;
+use crate::dxf_process::DxfConfig;
+use crate::nesting;
+use crate::report::WeldReport;
+use crate::result::*;
+use crate::transform::Transform;
+use crate::validate;
+use std::fmt;
+use std::sync::Arc;
+
+// One stage of processing a drawing: cleanup, transforms, welding, checks. Stages record what they
+// did in the report. Implement this to add stages of your own to `Passes`.
+pub trait Pass {
+    fn run(&self, drawing: &mut Drawing, report: &mut WeldReport) -> Result<()>;
+}
+
+// Passes run one after the other, stopping at the first that fails. `DxfConfig::before_weld` and
+// `after_weld` are run around welding by `process_drawing`.
+#[derive(Clone, Default)]
+pub struct Passes(Vec<Arc<dyn Pass + Send + Sync>>);
+
+impl Passes {
+    pub fn new() -> Passes {
+        Passes::default()
+    }
+
+    pub fn then(mut self, pass: impl Pass + Send + Sync + 'static) -> Passes {
+        self.0.push(Arc::new(pass));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Pass for Passes {
+    fn run(&self, drawing: &mut Drawing, report: &mut WeldReport) -> Result<()> {
+        for pass in self.0.iter() {
+            pass.run(drawing, report)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Passes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Passes({})", self.0.len())
+    }
+}
+
+pub struct ApplyTransform(pub Transform);
+
+impl Pass for ApplyTransform {
+    fn run(&self, drawing: &mut Drawing, _report: &mut WeldReport) -> Result<()> {
+        drawing.transform(&self.0);
+        Ok(())
+    }
+}
+
+// see `cleanup::snap_endpoints`
+pub struct JoinEndpoints {
+    pub tolerance: f64,
+}
+
+impl Pass for JoinEndpoints {
+    fn run(&self, drawing: &mut Drawing, report: &mut WeldReport) -> Result<()> {
+        report.endpoints_joined += cleanup::snap_endpoints(drawing, self.tolerance);
+        Ok(())
+    }
+}
+
+// see `cleanup::snap_to_axes`
+pub struct SnapToAxes {
+    pub max_degrees: f64,
+}
+
+impl Pass for SnapToAxes {
+    fn run(&self, drawing: &mut Drawing, report: &mut WeldReport) -> Result<()> {
+        report.lines_straightened += cleanup::snap_to_axes(drawing, self.max_degrees);
+        Ok(())
+    }
+}
+
+// see `cleanup::remove_overlaps`
+pub struct RemoveOverlaps {
+    pub tolerance: f64,
+}
+
+impl Pass for RemoveOverlaps {
+    fn run(&self, drawing: &mut Drawing, report: &mut WeldReport) -> Result<()> {
+        report.overlaps_removed += cleanup::remove_overlaps(drawing, self.tolerance);
+        Ok(())
+    }
+}
+
+// see `cleanup::remove_small_features`
+pub struct RemoveSmallFeatures {
+    pub min_size: f64,
+    pub chord_error: f64,
+}
+
+impl Pass for RemoveSmallFeatures {
+    fn run(&self, drawing: &mut Drawing, report: &mut WeldReport) -> Result<()> {
+        report.small_features_removed += cleanup::remove_small_features(drawing, self.min_size, self.chord_error);
+        Ok(())
+    }
+}
+
+pub struct Heal(pub HealConfig);
+
+impl Pass for Heal {
+    fn run(&self, drawing: &mut Drawing, report: &mut WeldReport) -> Result<()> {
+        report.heal = Some(cleanup::heal(drawing, &self.0));
+        Ok(())
+    }
+}
+
+// welds the drawing in place with `DxfConfig::process_drawing`
+pub struct Weld(pub DxfConfig);
+
+impl Pass for Weld {
+    fn run(&self, drawing: &mut Drawing, report: &mut WeldReport) -> Result<()> {
+        *drawing = self.0.process_drawing(std::mem::take(drawing), report)?;
+        Ok(())
+    }
+}
+
+// see `nesting::holes_first`
+pub struct HolesFirst;
+
+impl Pass for HolesFirst {
+    fn run(&self, drawing: &mut Drawing, _report: &mut WeldReport) -> Result<()> {
+        nesting::holes_first(drawing);
+        Ok(())
+    }
+}
+
+// Lists the drawing's loops, parts and intersections in the report without changing it.
+pub struct Verify {
+    pub chord_error: f64,
+    // list the loops, which a large drawing takes a while to nest
+    pub loops: bool,
+    // group the loops into parts, nesting them once for both
+    pub parts: bool,
+    // look for crossings, which the summary and --fail-on-intersection need
+    pub intersections: bool,
+    // see `nesting::parts`
+    pub part_proximity: f64,
+}
+
+impl Pass for Verify {
+    fn run(&self, drawing: &mut Drawing, report: &mut WeldReport) -> Result<()> {
+        if self.parts {
+            let (loops, parts) = nesting::loops_and_parts(drawing, self.part_proximity);
+            if self.loops {
+                report.loops = loops;
+            }
+            report.parts = parts;
+        } else if self.loops {
+            report.loops = nesting::loops(drawing);
+        }
+        if self.intersections {
+            report.intersections = validate::intersections(drawing, self.chord_error);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a downstream pass, dropping a layer
+    struct DropLayer(&'static str);
+
+    impl Pass for DropLayer {
+        fn run(&self, drawing: &mut Drawing, _report: &mut WeldReport) -> Result<()> {
+            drawing.layers.retain(|layer| layer.name != self.0);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_passes() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let center = p(0.0, 0.0);
+        let mut circle = (0..32).map(|i| center.polar(10.0, i as f64 * 360.0 / 32.0)).collect::<Vec<_>>();
+        circle.push(circle[0].clone());
+        let mut drawing = Drawing::new(circle.windows(2).map(|pair| Entity::Line(pair[0].clone(), pair[1].clone())).collect());
+        drawing.layer_mut("notes", None).entities.push(Entity::Line(p(0.0, 0.0), p(1.0, 0.0)));
+        let config = DxfConfig {
+            before_weld: Passes::new().then(DropLayer("notes")).then(ApplyTransform(Transform::scale(0.5))),
+            after_weld: Passes::new().then(Verify { chord_error: 0.05, loops: true, parts: true, intersections: true, part_proximity: 0.0 }),
+            ..Default::default()
+        };
+        let mut report = WeldReport::default();
+        let welded = config.process_drawing(drawing, &mut report).unwrap();
+        assert_eq!(welded.layers.len(), 1);
+        assert!(matches!(welded.entities().next(), Some(Entity::Circle { radius, .. }) if (radius - 5.0).abs() < 1e-6));
+        assert_eq!(report.loops.len(), 1);
+        assert_eq!(report.parts.len(), 1);
+    }
+}
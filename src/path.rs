@@ -0,0 +1,220 @@
+use crate::dxf::*;
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+
+pub(crate) const EPSILON: f64 = 1e-9;
+
+// one directed piece of a loop; arcs remember which way they are traversed
+#[derive(Clone, Debug)]
+pub(crate) enum Segment {
+    Line(Point, Point),
+    Arc { center: Point, radius: f64, start: Point, end: Point, ccw: bool },
+}
+
+pub(crate) fn cross(a: (f64, f64), b: (f64, f64)) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+pub(crate) fn along(p: &Point, direction: (f64, f64), t: f64) -> Point {
+    Point { x: p.x + direction.0 * t, y: p.y + direction.1 * t }
+}
+
+impl Segment {
+    pub(crate) fn start(&self) -> &Point {
+        match self {
+            Segment::Line(start, _) | Segment::Arc { start, .. } => start,
+        }
+    }
+
+    pub(crate) fn end(&self) -> &Point {
+        match self {
+            Segment::Line(_, end) | Segment::Arc { end, .. } => end,
+        }
+    }
+
+    pub(crate) fn set_start(&mut self, point: Point) {
+        match self {
+            Segment::Line(start, _) | Segment::Arc { start, .. } => *start = point,
+        }
+    }
+
+    pub(crate) fn set_end(&mut self, point: Point) {
+        match self {
+            Segment::Line(_, end) | Segment::Arc { end, .. } => *end = point,
+        }
+    }
+
+    pub(crate) fn reversed(self) -> Segment {
+        match self {
+            Segment::Line(start, end) => Segment::Line(end, start),
+            Segment::Arc { center, radius, start, end, ccw } => Segment::Arc { center, radius, start: end, end: start, ccw: !ccw },
+        }
+    }
+
+    // unit direction of travel at `point`
+    pub(crate) fn tangent(&self, point: &Point) -> (f64, f64) {
+        match self {
+            Segment::Line(start, end) => {
+                let length = start.dist(end).max(EPSILON);
+                ((end.x - start.x) / length, (end.y - start.y) / length)
+            },
+            Segment::Arc { center, radius, ccw, .. } => {
+                let (rx, ry) = ((point.x - center.x) / radius, (point.y - center.y) / radius);
+                if *ccw {
+                    (-ry, rx)
+                } else {
+                    (ry, -rx)
+                }
+            },
+        }
+    }
+
+    pub(crate) fn sweep(&self) -> f64 {
+        match self {
+            Segment::Line(..) => 0.0,
+            Segment::Arc { center, start, end, ccw, .. } => {
+                let (a, b) = (start.angle(center), end.angle(center));
+                if *ccw {
+                    (b - a).rem_euclid(2.0 * PI)
+                } else {
+                    -(a - b).rem_euclid(2.0 * PI)
+                }
+            },
+        }
+    }
+
+    // points along the segment in travel order, excluding the end point
+    pub(crate) fn samples(&self) -> Vec<Point> {
+        match self {
+            Segment::Line(start, _) => vec![start.clone()],
+            Segment::Arc { center, radius, start, .. } => {
+                let (a, sweep) = (start.angle(center), self.sweep());
+                (0..16).map(|i| center.polar(*radius, (a + sweep * i as f64 / 16.0).to_degrees())).collect()
+            },
+        }
+    }
+
+    pub(crate) fn to_entity(&self) -> Entity {
+        match self {
+            Segment::Line(start, end) => Entity::Line(start.clone(), end.clone()),
+            Segment::Arc { center, radius, start, end, ccw } => {
+                let (from, to) = if *ccw { (start, end) } else { (end, start) };
+                Entity::Arc {
+                    center: center.clone(),
+                    radius: *radius,
+                    start_angle: normalize_degrees(from.angle(center).to_degrees()),
+                    end_angle: normalize_degrees(to.angle(center).to_degrees()),
+                }
+            },
+        }
+    }
+}
+
+// positive for counter-clockwise loops
+pub(crate) fn signed_area(segments: &[Segment]) -> f64 {
+    let points = segments.iter().flat_map(|s| s.samples()).collect::<Vec<_>>();
+    points.iter().zip(points.iter().cycle().skip(1)).map(|(a, b)| a.x * b.y - b.x * a.y).sum::<f64>() / 2.0
+}
+
+// union-find root of `i`, compressing the path as it goes
+pub(crate) fn find(parents: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[i] = root;
+    root
+}
+
+pub(crate) enum Piece {
+    // a closed ring of lines, arcs and polylines in travel order, with the entities it was built from
+    Loop(Vec<Segment>, Vec<Entity>),
+    Entity(Entity),
+}
+
+// snaps points onto a grid so recomputed arc endpoints still meet their neighbours
+pub(crate) fn snap_key(point: &Point) -> (i64, i64) {
+    ((point.x / 1e-5).round() as i64, (point.y / 1e-5).round() as i64)
+}
+
+// Splits entities into closed loops and everything else (circles, open paths and branching
+// geometry), kept in the order their first entity appeared.
+pub(crate) fn split_loops(entities: Vec<Entity>) -> Vec<Piece> {
+    let mut segments = vec![];
+    let mut loops = vec![];
+    let mut segment_counts = vec![0; entities.len()];
+    for (i, entity) in entities.iter().enumerate() {
+        match entity {
+            Entity::Line(from, to) => segments.push((Segment::Line(from.clone(), to.clone()), i)),
+            Entity::Arc { center, radius, start_angle, end_angle } => {
+                let start = center.polar(*radius, *start_angle);
+                let end = center.polar(*radius, *end_angle);
+                segments.push((Segment::Arc { center: center.clone(), radius: *radius, start, end, ccw: true }, i));
+            },
+            Entity::Polyline { vertices, .. } => {
+                for pair in vertices.windows(2) {
+                    segments.push((Segment::Line(pair[0].clone(), pair[1].clone()), i));
+                }
+            },
+            Entity::Circle { .. } | Entity::Ellipse { .. } => (),
+        }
+        segment_counts[i] = segments.iter().rev().take_while(|(_, owner)| *owner == i).count();
+    }
+
+    let mut endpoints: BTreeMap<(i64, i64), Vec<usize>> = BTreeMap::new();
+    for (i, (segment, _)) in segments.iter().enumerate() {
+        endpoints.entry(snap_key(segment.start())).or_default().push(i);
+        endpoints.entry(snap_key(segment.end())).or_default().push(i);
+    }
+    let mut used = vec![false; segments.len()];
+    for first in 0..segments.len() {
+        if used[first] {
+            continue;
+        }
+        // walk until we are back at the start, giving up at any branch or dead end
+        let mut members = vec![first];
+        let mut path = vec![segments[first].0.clone()];
+        used[first] = true;
+        let target = snap_key(segments[first].0.start());
+        let closed = loop {
+            let cursor = snap_key(path.last().unwrap().end());
+            if cursor == target {
+                break true;
+            }
+            let next = match endpoints.get(&cursor) {
+                Some(candidates) if candidates.len() == 2 => candidates.iter().find(|i| !used[**i]).cloned(),
+                _ => None,
+            };
+            let next = match next {
+                Some(next) => next,
+                None => break false,
+            };
+            used[next] = true;
+            members.push(next);
+            let segment = segments[next].0.clone();
+            path.push(if snap_key(segment.start()) == cursor { segment } else { segment.reversed() });
+        };
+        // a polyline only half in the loop stays whole
+        let mut owners: BTreeMap<usize, usize> = BTreeMap::new();
+        for member in members.iter() {
+            *owners.entry(segments[*member].1).or_default() += 1;
+        }
+        if closed && owners.iter().all(|(owner, count)| *count == segment_counts[*owner]) {
+            loops.push((path, owners.into_keys().collect::<Vec<_>>()));
+        }
+    }
+
+    let mut entities = entities.into_iter().map(Some).collect::<Vec<_>>();
+    let mut pieces = vec![];
+    for (path, owners) in loops {
+        let members = owners.iter().filter_map(|owner| entities[*owner].take()).collect();
+        pieces.push((owners[0], Piece::Loop(path, members)));
+    }
+    for (i, entity) in entities.into_iter().enumerate() {
+        if let Some(entity) = entity {
+            pieces.push((i, Piece::Entity(entity)));
+        }
+    }
+    pieces.sort_by_key(|(order, _)| *order);
+    pieces.into_iter().map(|(_, piece)| piece).collect()
+}
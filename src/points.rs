@@ -0,0 +1,59 @@
+use crate::dxf::*;
+use crate::result::*;
+
+pub fn is_points_path(path: &str) -> bool {
+    let lower = path.to_ascii_lowercase();
+    lower.ends_with(".csv") || lower.ends_with(".xy") || lower.ends_with(".txt")
+}
+
+fn parse_point(line: &str) -> Option<Result<Point>> {
+    let mut values = line.split(|c: char| c == ',' || c == ';' || c.is_whitespace()).filter(|x| !x.is_empty());
+    let x = values.next()?;
+    let y = match values.next() {
+        Some(y) => y,
+        None => return Some(Err(weld_err!("expected x and y values, got: {}", line))),
+    };
+    Some(x.parse::<f64>().and_then(|x| Ok(Point { x, y: y.parse()? })).map_err(|e| e.into()))
+}
+
+// Parses x,y point lists separated by commas, semicolons or whitespace. Blank lines separate chains,
+// `#` starts a comment and a non-numeric first line is treated as a header.
+pub fn parse_points(src: &str) -> Result<Drawing> {
+    let mut chains: Vec<Vec<Point>> = vec![vec![]];
+    for (i, line) in src.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            if !chains.last().unwrap().is_empty() {
+                chains.push(vec![]);
+            }
+            continue;
+        }
+        match parse_point(line) {
+            Some(Ok(point)) => chains.last_mut().unwrap().push(point),
+            Some(Err(_)) if i == 0 => continue,
+            Some(Err(e)) => return Err(weld_err!("line {}: {}", i + 1, e)),
+            None => (),
+        }
+    }
+
+    let mut entities = vec![];
+    for chain in chains.iter() {
+        for pair in chain.windows(2) {
+            entities.push(Entity::Line(pair[0].clone(), pair[1].clone()));
+        }
+    }
+    Ok(Drawing::new(entities))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_points() {
+        let drawing = parse_points("x,y\n0,0\n1,0\n1,1\n\n5 5 # probe 2\n6\t5\n").unwrap();
+        assert_eq!(drawing.entity_count(), 3);
+        assert_eq!(drawing.entities().nth(2).unwrap(), &Entity::Line(Point { x: 5.0, y: 5.0 }, Point { x: 6.0, y: 5.0 }));
+        assert!(parse_points("0,0\n1,a\n").is_err());
+    }
+}
@@ -0,0 +1,87 @@
+use crate::cleanup::HealConfig;
+use crate::dxf_process::DxfConfig;
+use serde::{Serialize, Deserialize};
+
+// Settings bundled for common machines, in millimeters, so users can name their machine instead of
+// tuning each value.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Preset {
+    // fine detail on a fiber or CO2 laser
+    LaserFine,
+    LaserCoarse,
+    // a wide kerf, where small wiggles are lost anyway
+    Plasma,
+    Waterjet,
+}
+
+struct PresetValues {
+    resolution: f64,
+    max_radius: f64,
+    min_segments: usize,
+    // dangling ends up to this far apart are bridged when healing
+    gap_tolerance: f64,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [Preset::LaserFine, Preset::LaserCoarse, Preset::Plasma, Preset::Waterjet];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Preset::LaserFine => "laser-fine",
+            Preset::LaserCoarse => "laser-coarse",
+            Preset::Plasma => "plasma",
+            Preset::Waterjet => "waterjet",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Preset> {
+        Preset::ALL.iter().find(|preset| preset.name() == name).copied()
+    }
+
+    fn values(self) -> PresetValues {
+        match self {
+            Preset::LaserFine => PresetValues { resolution: 0.01, max_radius: 10000.0, min_segments: 3, gap_tolerance: 0.01 },
+            Preset::LaserCoarse => PresetValues { resolution: 0.05, max_radius: 50000.0, min_segments: 3, gap_tolerance: 0.05 },
+            Preset::Plasma => PresetValues { resolution: 0.2, max_radius: 100000.0, min_segments: 4, gap_tolerance: 0.25 },
+            Preset::Waterjet => PresetValues { resolution: 0.1, max_radius: 100000.0, min_segments: 4, gap_tolerance: 0.1 },
+        }
+    }
+
+    pub fn heal_config(self) -> HealConfig {
+        HealConfig {
+            max_gap: self.values().gap_tolerance,
+            ..Default::default()
+        }
+    }
+}
+
+impl DxfConfig {
+    // the defaults with the preset's resolution, maximum radius and minimum segments
+    pub fn from_preset(preset: Preset) -> DxfConfig {
+        let values = preset.values();
+        DxfConfig {
+            resolution: values.resolution,
+            max_radius: values.max_radius,
+            min_segments: values.min_segments,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets() {
+        for preset in Preset::ALL {
+            assert_eq!(Preset::from_name(preset.name()), Some(preset));
+            assert_eq!(serde_json::to_string(&preset).unwrap(), format!("\"{}\"", preset.name()));
+            let config = DxfConfig::from_preset(preset);
+            assert!(config.resolution > 0.0 && config.min_segments >= 3);
+            assert!(preset.heal_config().max_gap >= preset.heal_config().snap_tolerance);
+        }
+        assert_eq!(Preset::from_name("router"), None);
+    }
+}
@@ -0,0 +1,167 @@
+use crate::dxf::*;
+use crate::tessellate::bounds;
+use crate::result::*;
+
+const PANEL_SIZE: u32 = 800;
+const MARGIN: f64 = 20.0;
+const BACKGROUND: [u8; 3] = [255, 255, 255];
+const LINE_COLOR: [u8; 3] = [140, 140, 140];
+const ARC_COLOR: [u8; 3] = [220, 40, 40];
+
+struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: u32, height: u32) -> Canvas {
+        Canvas {
+            width,
+            height,
+            pixels: BACKGROUND.iter().cycle().take((width * height * 3) as usize).cloned().collect(),
+        }
+    }
+
+    fn plot(&mut self, x: i64, y: i64, color: [u8; 3]) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 {
+            return;
+        }
+        let offset = ((y as u32 * self.width + x as u32) * 3) as usize;
+        self.pixels[offset..offset + 3].copy_from_slice(&color);
+    }
+
+    // bresenham
+    fn line(&mut self, from: (i64, i64), to: (i64, i64), color: [u8; 3]) {
+        let (mut x, mut y) = from;
+        let dx = (to.0 - x).abs();
+        let dy = -(to.1 - y).abs();
+        let sx = if x < to.0 { 1 } else { -1 };
+        let sy = if y < to.1 { 1 } else { -1 };
+        let mut err = dx + dy;
+        loop {
+            self.plot(x, y, color);
+            if x == to.0 && y == to.1 {
+                break;
+            }
+            let e2 = 2 * err;
+            if e2 >= dy {
+                err += dy;
+                x += sx;
+            }
+            if e2 <= dx {
+                err += dx;
+                y += sy;
+            }
+        }
+    }
+}
+
+struct View {
+    min: Point,
+    scale: f64,
+    // pixel that `min` lands on
+    left: f64,
+    bottom: f64,
+}
+
+impl View {
+    fn project(&self, point: &Point) -> (i64, i64) {
+        let x = self.left + (point.x - self.min.x) * self.scale;
+        let y = self.bottom - (point.y - self.min.y) * self.scale;
+        (x.round() as i64, y.round() as i64)
+    }
+}
+
+fn draw(canvas: &mut Canvas, view: &View, drawing: &Drawing, chord_error: f64) {
+    for entity in drawing.entities() {
+        let color = match entity {
+            Entity::Arc { .. } | Entity::Circle { .. } => ARC_COLOR,
+            _ => LINE_COLOR,
+        };
+        let points = entity.tessellate(chord_error);
+        for pair in points.windows(2) {
+            canvas.line(view.project(&pair[0]), view.project(&pair[1]), color);
+        }
+    }
+}
+
+// renders `before` and `after` side by side at a shared scale, input on the left
+pub fn render_preview(before: &Drawing, after: &Drawing, chord_error: f64) -> Result<Vec<u8>> {
+    let (min, max) = bounds(before, chord_error)
+        .or_else(|| bounds(after, chord_error))
+        .ok_or_else(|| weld_err!("cannot preview an empty drawing"))?;
+    let extent = (max.x - min.x).max(max.y - min.y).max(f64::EPSILON);
+    let scale = (PANEL_SIZE as f64 - 2.0 * MARGIN) / extent;
+
+    let mut canvas = Canvas::new(PANEL_SIZE * 2, PANEL_SIZE);
+    for y in 0..PANEL_SIZE as i64 {
+        canvas.plot(PANEL_SIZE as i64, y, [0, 0, 0]);
+    }
+    // curves are drawn to half a pixel regardless of the weld resolution
+    let pixel_error = 0.5 / scale;
+    let bottom = PANEL_SIZE as f64 - MARGIN;
+    draw(&mut canvas, &View { min: min.clone(), scale, left: MARGIN, bottom }, before, pixel_error);
+    draw(&mut canvas, &View { min, scale, left: PANEL_SIZE as f64 + MARGIN, bottom }, after, pixel_error);
+
+    let mut out = vec![];
+    {
+        let mut encoder = png::Encoder::new(&mut out, canvas.width, canvas.height);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&canvas.pixels)?;
+    }
+    Ok(out)
+}
+
+// Renders the drawing as `rows` lines of `columns` Unicode braille characters, two by four dots
+// each, for previews in a terminal. Curves are drawn to half a dot; `chord_error` is only used to
+// find the drawing's bounds. Empty drawings render as nothing.
+pub fn render_braille(drawing: &Drawing, chord_error: f64, columns: usize, rows: usize) -> String {
+    let (width, height) = (columns * 2, rows * 4);
+    let (min, max) = match bounds(drawing, chord_error) {
+        Some(bounds) => bounds,
+        None => return String::new(),
+    };
+    let scale = ((width - 1) as f64 / (max.x - min.x).max(f64::EPSILON)).min((height - 1) as f64 / (max.y - min.y).max(f64::EPSILON));
+    let mut canvas = Canvas::new(width as u32, height as u32);
+    draw(&mut canvas, &View { min, scale, left: 0.0, bottom: (height - 1) as f64 }, drawing, 0.5 / scale);
+
+    // dot bits of a braille cell, by row then column
+    const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+    let mut out = String::new();
+    for row in 0..rows {
+        for column in 0..columns {
+            let mut bits = 0;
+            for (dy, dots) in DOTS.iter().enumerate() {
+                for (dx, dot) in dots.iter().enumerate() {
+                    let offset = ((row * 4 + dy) * width + column * 2 + dx) * 3;
+                    if canvas.pixels[offset..offset + 3] != BACKGROUND {
+                        bits |= dot;
+                    }
+                }
+            }
+            out.push(char::from_u32(0x2800 + bits).unwrap());
+        }
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_braille() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let drawing = Drawing::new(vec![
+            Entity::Line(p(0.0, 0.0), p(10.0, 0.0)),
+            Entity::Line(p(10.0, 0.0), p(10.0, 10.0)),
+        ]);
+        let text = render_braille(&drawing, 0.05, 4, 2);
+        assert_eq!(text, "⠀⠀⠀⢸\n⣀⣀⣀⣸\n");
+        assert_eq!(render_braille(&Drawing::new(vec![]), 0.05, 4, 2), "");
+    }
+}
@@ -0,0 +1,106 @@
+use crate::dxf::*;
+
+// angles closer than this to a quadrant boundary are not split again
+const ANGLE_EPSILON: f64 = 1e-9;
+
+// the arc cut at every multiple of 90° strictly inside it, as counter-clockwise (start, end) pairs
+fn quadrant_spans(start_angle: f64, sweep: f64) -> Vec<(f64, f64)> {
+    let end = start_angle + sweep;
+    let mut spans = vec![];
+    let mut from = start_angle;
+    let mut boundary = ((start_angle + ANGLE_EPSILON) / 90.0).ceil() * 90.0;
+    while boundary < end - ANGLE_EPSILON {
+        spans.push((from, boundary));
+        from = boundary;
+        boundary += 90.0;
+    }
+    spans.push((from, end));
+    spans.iter().map(|(from, to)| (normalize_degrees(*from), normalize_degrees(*to))).collect()
+}
+
+impl Drawing {
+    // Splits every arc where it crosses the 0°, 90°, 180° or 270° axis, and every circle into four
+    // quarter arcs, for postprocessors that reject arcs spanning quadrants. The geometry is unchanged.
+    pub fn split_quadrants(&mut self) {
+        for layer in self.layers.iter_mut() {
+            layer.entities = layer.entities.drain(..).flat_map(|entity| {
+                let (center, radius, start_angle, sweep) = match &entity {
+                    Entity::Arc { center, radius, start_angle, end_angle } => {
+                        (center.clone(), *radius, *start_angle, (end_angle - start_angle).rem_euclid(360.0))
+                    },
+                    Entity::Circle { center, radius } => (center.clone(), *radius, 0.0, 360.0),
+                    _ => return vec![entity],
+                };
+                quadrant_spans(start_angle, sweep).into_iter().map(|(start_angle, end_angle)| Entity::Arc {
+                    center: center.clone(),
+                    radius,
+                    start_angle,
+                    end_angle,
+                }).collect()
+            }).collect();
+        }
+    }
+
+    // Replaces every circle with two half arcs meeting at 0° and 180°, for posts and controllers that
+    // cannot cut a full circle. The geometry is unchanged.
+    pub fn split_circles(&mut self) {
+        for layer in self.layers.iter_mut() {
+            layer.entities = layer.entities.drain(..).flat_map(|entity| match entity {
+                Entity::Circle { center, radius } => vec![
+                    Entity::Arc { center: center.clone(), radius, start_angle: 0.0, end_angle: 180.0 },
+                    Entity::Arc { center, radius, start_angle: 180.0, end_angle: 0.0 },
+                ],
+                other => vec![other],
+            }).collect();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_quadrants() {
+        let center = Point { x: 1.0, y: 2.0 };
+        let mut drawing = Drawing::new(vec![
+            Entity::Arc { center: center.clone(), radius: 3.0, start_angle: 45.0, end_angle: 135.0 },
+            Entity::Arc { center: center.clone(), radius: 3.0, start_angle: 350.0, end_angle: 90.0 },
+            Entity::Arc { center: center.clone(), radius: 3.0, start_angle: 90.0, end_angle: 180.0 },
+            Entity::Circle { center: center.clone(), radius: 3.0 },
+        ]);
+        drawing.split_quadrants();
+        let angles = drawing.entities().map(|entity| match entity {
+            Entity::Arc { start_angle, end_angle, .. } => (*start_angle, *end_angle),
+            other => panic!("unexpected {:?}", other),
+        }).collect::<Vec<_>>();
+        assert_eq!(angles, vec![
+            (45.0, 90.0), (90.0, 135.0),
+            (350.0, 0.0), (0.0, 90.0),
+            (90.0, 180.0),
+            (0.0, 90.0), (90.0, 180.0), (180.0, 270.0), (270.0, 0.0),
+        ]);
+    }
+
+    #[test]
+    fn test_split_circles() {
+        let center = Point { x: 1.0, y: 2.0 };
+        let arc = Entity::Arc { center: center.clone(), radius: 3.0, start_angle: 45.0, end_angle: 135.0 };
+        let mut drawing = Drawing::new(vec![arc.clone(), Entity::Circle { center: center.clone(), radius: 3.0 }]);
+        drawing.split_circles();
+        let halves = drawing.entities().skip(1).cloned().collect::<Vec<_>>();
+        assert_eq!(drawing.entities().next(), Some(&arc));
+        assert_eq!(halves, vec![
+            Entity::Arc { center: center.clone(), radius: 3.0, start_angle: 0.0, end_angle: 180.0 },
+            Entity::Arc { center: center.clone(), radius: 3.0, start_angle: 180.0, end_angle: 0.0 },
+        ]);
+        assert_eq!(halves[0].end_point(), halves[1].start_point());
+        assert_eq!(halves[1].end_point(), halves[0].start_point());
+
+        // halves then quadrants give the same four quarters as a circle split into quadrants directly
+        drawing.split_quadrants();
+        let mut quarters = Drawing::new(vec![Entity::Circle { center, radius: 3.0 }]);
+        quarters.split_quadrants();
+        assert_eq!(drawing.entities().skip(2).collect::<Vec<_>>(), quarters.entities().collect::<Vec<_>>());
+    }
+}
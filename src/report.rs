@@ -0,0 +1,317 @@
+use crate::dxf::*;
+use crate::warnings::Warning;
+use serde::{Serialize, Deserialize};
+use std::fmt;
+use std::time::Instant;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ArcReport {
+    // index of the arc or circle in the output drawing
+    pub entity: usize,
+    pub center: Point,
+    pub radius: f64,
+    pub residual: f64,
+    // where the largest deviation occurs
+    pub residual_at: Point,
+    // number of source segments replaced
+    pub segments: usize,
+    pub circle: bool,
+    // the source chain runs clockwise around it; output arcs are always counter-clockwise
+    pub clockwise: bool,
+    // the arc's start, middle and end at full precision, for formats taking arcs that way; None for
+    // circles
+    #[serde(default)]
+    pub three_point: Option<ThreePointArc>,
+    // the source points the arc or circle replaced, in chain order, for drawing fits over their
+    // input; None for input arcs grown over neighbouring lines
+    #[cfg(feature = "debug-geometry")]
+    #[serde(default)]
+    pub source: Option<Vec<Point>>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct ChainReport {
+    pub layer: String,
+    pub points: usize,
+    pub closed: bool,
+    pub entities: usize,
+    pub arcs: Vec<ArcReport>,
+    // the line's ends, for a leftover line that could not be chained because another line starts
+    // where it does; it is passed through unwelded
+    #[serde(default)]
+    pub orphan: Option<(Point, Point)>,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LoopReport {
+    pub layer: String,
+    pub min: Point,
+    pub max: Point,
+    pub area: f64,
+    // 0 for outer contours, 1 for holes in them, 2 for islands inside holes and so on
+    pub depth: usize,
+    // index of the smallest loop containing this one
+    pub parent: Option<usize>,
+}
+
+// An outer contour with the holes inside it and the open geometry belonging to it
+#[derive(Serialize, Clone, Debug)]
+pub struct PartReport {
+    pub layer: String,
+    // box around the outer contour
+    pub min: Point,
+    pub max: Point,
+    // indices in `WeldReport::loops` of the outer contour, first, and its holes
+    pub loops: Vec<usize>,
+    pub open_entities: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct IntersectionReport {
+    pub layer: String,
+    pub point: Point,
+    // true when a chain crosses itself, false when two chains cross
+    pub same_chain: bool,
+    // true when the segments run along each other from `point` rather than cross there
+    pub overlap: bool,
+}
+
+// cleanup done by `--heal-only`
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct HealReport {
+    pub endpoints_snapped: usize,
+    pub zero_length_removed: usize,
+    pub duplicates_removed: usize,
+    pub gaps_bridged: usize,
+    pub lines_reversed: usize,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct PhaseTiming {
+    pub phase: &'static str,
+    pub seconds: f64,
+}
+
+// Wall time spent in each phase of a run (`parse`, `cleanup`, `chain`, `fit`, `analysis`, `write`),
+// in the order they first ran. Welding only times chain building and fitting when `enabled`, as the
+// library may run where there is no clock, such as in WebAssembly.
+#[derive(Serialize, Clone, Debug, Default)]
+#[serde(transparent)]
+pub struct Timings {
+    #[serde(skip)]
+    pub enabled: bool,
+    pub phases: Vec<PhaseTiming>,
+}
+
+impl Timings {
+    // the time now, if timings are kept
+    pub fn start(&self) -> Option<Instant> {
+        if self.enabled {
+            Some(Instant::now())
+        } else {
+            None
+        }
+    }
+
+    // adds the time since `started`, if it was taken, to `phase`
+    pub fn add(&mut self, phase: &'static str, started: Option<Instant>) {
+        if let Some(started) = started {
+            self.record(phase, started.elapsed().as_secs_f64());
+        }
+    }
+
+    pub fn record(&mut self, phase: &'static str, seconds: f64) {
+        match self.phases.iter_mut().find(|timing| timing.phase == phase) {
+            Some(timing) => timing.seconds += seconds,
+            None => self.phases.push(PhaseTiming { phase, seconds }),
+        }
+    }
+
+    pub fn seconds(&self, phase: &str) -> f64 {
+        self.phases.iter().filter(|timing| timing.phase == phase).map(|timing| timing.seconds).sum()
+    }
+
+    pub fn total(&self) -> f64 {
+        // folded from 0 rather than summed, which gives -0 for no phases
+        self.phases.iter().fold(0.0, |total, timing| total + timing.seconds)
+    }
+}
+
+// a table of the phases with their share of the total
+impl fmt::Display for Timings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.total();
+        for timing in self.phases.iter() {
+            let share = if total > 0.0 { timing.seconds / total * 100.0 } else { 0.0 };
+            writeln!(f, "{:<10} {:>10.4}s {:>5.1}%", timing.phase, timing.seconds, share)?;
+        }
+        writeln!(f, "{:<10} {:>10.4}s", "total", total)
+    }
+}
+
+// totals over the whole run, written next to the full report by `--stats-json`
+#[derive(Serialize)]
+struct Aggregate {
+    compression_ratio: f64,
+    max_residual: f64,
+    arcs: usize,
+    circles: usize,
+    segments_replaced: usize,
+    dropped_entities: usize,
+    length_change: f64,
+    estimated_cut_seconds: Option<f64>,
+    total_seconds: f64,
+}
+
+#[derive(Serialize)]
+struct Stats<'a> {
+    aggregate: Aggregate,
+    #[serde(flatten)]
+    report: &'a WeldReport,
+}
+
+// `1:02:03` or `2:03`
+pub fn format_duration(seconds: f64) -> String {
+    let seconds = seconds.round() as u64;
+    match seconds / 3600 {
+        0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+        hours => format!("{}:{:02}:{:02}", hours, seconds / 60 % 60, seconds % 60),
+    }
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct WeldReport {
+    pub input_entities: usize,
+    pub output_entities: usize,
+    // total length of the lines and curves welded and written, which welding should barely change
+    pub input_length: f64,
+    pub output_length: f64,
+    // in drawing units per minute, for estimating the cut time
+    pub feed_rate: Option<f64>,
+    // line endpoints moved onto a neighbour within the join tolerance before welding
+    pub endpoints_joined: usize,
+    // input lines whose ends coincide, dropped, refused or kept as `DxfConfig::zero_length` says
+    pub zero_length_lines: usize,
+    // nearly horizontal or vertical lines made exactly so before welding
+    pub lines_straightened: usize,
+    // sizes of the small gaps between dangling line ends left before welding, smallest first
+    pub gaps: Vec<f64>,
+    // duplicate line pieces removed before welding
+    pub overlaps_removed: usize,
+    // closed loops dropped for being smaller than the minimum feature size
+    pub small_features_removed: usize,
+    pub chains: Vec<ChainReport>,
+    // containment tree of the closed loops in the output
+    pub loops: Vec<LoopReport>,
+    // the loops and open geometry grouped into parts
+    pub parts: Vec<PartReport>,
+    pub intersections: Vec<IntersectionReport>,
+    pub heal: Option<HealReport>,
+    // likely mistakes in the input or settings, and anything else welding had to give up on
+    pub warnings: Vec<Warning>,
+    pub timings: Timings,
+}
+
+impl WeldReport {
+    pub fn arcs(&self) -> impl Iterator<Item = &ArcReport> {
+        self.chains.iter().flat_map(|chain| chain.arcs.iter())
+    }
+
+    // the layer and ends of each line left out of chaining
+    pub fn orphans(&self) -> impl Iterator<Item = (&str, &(Point, Point))> {
+        self.chains.iter().filter_map(|chain| Some((chain.layer.as_str(), chain.orphan.as_ref()?)))
+    }
+
+    pub fn compression_ratio(&self) -> f64 {
+        if self.output_entities == 0 {
+            return 1.0;
+        }
+        self.input_entities as f64 / self.output_entities as f64
+    }
+
+    pub fn max_residual(&self) -> f64 {
+        self.arcs().map(|arc| arc.residual).fold(0.0, f64::max)
+    }
+
+    // input entities removed by cleanup rather than welded
+    pub fn dropped_entities(&self) -> usize {
+        let healed = self.heal.as_ref().map(|heal| heal.zero_length_removed + heal.duplicates_removed).unwrap_or(0);
+        self.overlaps_removed + self.small_features_removed + healed
+    }
+
+    // how much longer the output path is than the input, negative when shorter
+    pub fn length_change(&self) -> f64 {
+        self.output_length - self.input_length
+    }
+
+    // seconds to cut the output path at `feed_rate`, leaving out rapid moves and acceleration
+    pub fn estimated_cut_seconds(&self) -> Option<f64> {
+        self.feed_rate.filter(|rate| *rate > 0.0).map(|rate| self.output_length / rate * 60.0)
+    }
+
+    // records the time since `clock` as `phase` and restarts the clock
+    pub fn time_phase(&mut self, phase: &'static str, clock: &mut Instant) {
+        self.timings.record(phase, clock.elapsed().as_secs_f64());
+        *clock = Instant::now();
+    }
+
+    // the full report with aggregate metrics alongside, for pipelines that gate on weld quality
+    pub fn to_stats_json(&self) -> String {
+        let stats = Stats {
+            aggregate: Aggregate {
+                compression_ratio: self.compression_ratio(),
+                max_residual: self.max_residual(),
+                arcs: self.arcs().filter(|arc| !arc.circle).count(),
+                circles: self.arcs().filter(|arc| arc.circle).count(),
+                segments_replaced: self.arcs().map(|arc| arc.segments).sum(),
+                dropped_entities: self.dropped_entities(),
+                length_change: self.length_change(),
+                estimated_cut_seconds: self.estimated_cut_seconds(),
+                total_seconds: self.timings.total(),
+            },
+            report: self,
+        };
+        serde_json::to_string_pretty(&stats).unwrap()
+    }
+
+    // label/value pairs for summary tables
+    pub fn summary(&self) -> Vec<(&'static str, String)> {
+        let mut summary = vec![
+            ("Input entities", self.input_entities.to_string()),
+            ("Output entities", self.output_entities.to_string()),
+            ("Endpoints joined", self.endpoints_joined.to_string()),
+            ("Gaps", self.gaps.len().to_string()),
+            ("Lines straightened", self.lines_straightened.to_string()),
+            ("Zero-length lines", self.zero_length_lines.to_string()),
+            ("Overlaps removed", self.overlaps_removed.to_string()),
+            ("Small features removed", self.small_features_removed.to_string()),
+            ("Chains", self.chains.iter().filter(|chain| chain.orphan.is_none()).count().to_string()),
+            ("Orphan segments", self.orphans().count().to_string()),
+            ("Closed loops", self.loops.len().to_string()),
+            ("Holes", self.loops.iter().filter(|l| l.depth % 2 == 1).count().to_string()),
+            ("Parts", self.parts.len().to_string()),
+            ("Intersections", self.intersections.len().to_string()),
+            ("Arcs", self.arcs().filter(|arc| !arc.circle).count().to_string()),
+            ("Circles", self.arcs().filter(|arc| arc.circle).count().to_string()),
+            ("Segments replaced", self.arcs().map(|arc| arc.segments).sum::<usize>().to_string()),
+            ("Compression ratio", format!("{:.2}", self.compression_ratio())),
+            ("Max residual", format!("{:.6}", self.max_residual())),
+            ("Input length", format!("{:.3}", self.input_length)),
+            ("Output length", format!("{:.3}", self.output_length)),
+            ("Length change", format!("{:+.6}", self.length_change())),
+        ];
+        if let Some(seconds) = self.estimated_cut_seconds() {
+            summary.push(("Estimated cut time", format_duration(seconds)));
+        }
+        if let Some(heal) = &self.heal {
+            summary.extend(vec![
+                ("Endpoints snapped", heal.endpoints_snapped.to_string()),
+                ("Zero-length lines removed", heal.zero_length_removed.to_string()),
+                ("Duplicate lines removed", heal.duplicates_removed.to_string()),
+                ("Gaps bridged", heal.gaps_bridged.to_string()),
+                ("Lines reversed", heal.lines_reversed.to_string()),
+            ]);
+        }
+        summary
+    }
+}
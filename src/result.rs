@@ -7,7 +7,7 @@ pub type Error = Box<dyn std::error::Error + Send + Sync + 'static>;
 pub type Result<T> = std::result::Result<T, Error>;
 
 pub fn to_io_error(e: Error) -> IoError {
-    return IoError::new(ErrorKind::Other, e);
+    IoError::other(e)
 }
 
 #[derive(Debug)]
@@ -0,0 +1,196 @@
+use crate::dxf::format_number;
+use crate::gaps;
+use crate::report::{format_duration, WeldReport};
+use serde::Serialize;
+
+const BOLD: &str = "1";
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+
+// The stage of welding a warning comes from, which the summary groups warnings by
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    // the input drawing and settings, before anything is welded
+    Parse,
+    // joining lines into chains
+    Chaining,
+    // fitting arcs and checking the welded output
+    Fitting,
+}
+
+impl Category {
+    fn name(&self) -> &'static str {
+        match self {
+            Category::Parse => "parse",
+            Category::Chaining => "chaining",
+            Category::Fitting => "fitting",
+        }
+    }
+
+    fn of(kind: &str) -> Category {
+        match kind {
+            "units" | "origin" | "resolution" => Category::Parse,
+            "gaps" | "orphans" | "zero-length" => Category::Chaining,
+            _ => Category::Fitting,
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct SummaryWarning {
+    pub category: Category,
+    pub kind: &'static str,
+    pub message: String,
+}
+
+// What a run of the command line tool did, printed when it ends: the counts that matter most and
+// every warning, grouped by category, from the report's own warnings and what it found along the way.
+#[derive(Serialize, Clone, Debug)]
+pub struct Summary {
+    pub output: String,
+    pub healed: bool,
+    pub input_entities: usize,
+    pub output_entities: usize,
+    pub chains: usize,
+    pub arcs: usize,
+    pub circles: usize,
+    pub compression_ratio: f64,
+    pub max_residual: f64,
+    pub input_length: f64,
+    pub output_length: f64,
+    pub estimated_cut_seconds: Option<f64>,
+    pub total_seconds: f64,
+    // in category order, parse first
+    pub warnings: Vec<SummaryWarning>,
+}
+
+impl Summary {
+    pub fn new(report: &WeldReport, output: &str) -> Summary {
+        let mut warnings = report.warnings.iter().map(|warning| SummaryWarning {
+            category: Category::of(warning.kind),
+            kind: warning.kind,
+            message: warning.message.clone(),
+        }).collect::<Vec<_>>();
+        let mut warn = |kind: &'static str, message: String| warnings.push(SummaryWarning { category: Category::of(kind), kind, message });
+        if let Some(gaps) = gaps::describe(&report.gaps) {
+            warn("gaps", format!("{}\n{}", gaps, gaps::format_histogram(&report.gaps).trim_end()));
+        }
+        let orphans = report.orphans().count();
+        if orphans > 0 {
+            warn("orphans", format!("{} {} starting where another line starts could not be chained and {} left unwelded", orphans, plural(orphans, "line"), if orphans == 1 { "was" } else { "were" }));
+        }
+        if report.zero_length_lines > 0 {
+            warn("zero-length", format!("{} zero-length {} in the input", report.zero_length_lines, plural(report.zero_length_lines, "line")));
+        }
+        let crossings = report.intersections.iter().filter(|intersection| !intersection.same_chain).count();
+        let self_intersections = report.intersections.len() - crossings;
+        if self_intersections > 0 {
+            warn("self-intersections", format!("{} {} in the welded output", self_intersections, plural(self_intersections, "self-intersection")));
+        }
+        if crossings > 0 {
+            warn("crossings", format!("{} {} between chains in the welded output", crossings, plural(crossings, "crossing")));
+        }
+        warnings.sort_by_key(|warning| warning.category as u8);
+        Summary {
+            output: output.to_string(),
+            healed: report.heal.is_some(),
+            input_entities: report.input_entities,
+            output_entities: report.output_entities,
+            chains: report.chains.iter().filter(|chain| chain.orphan.is_none()).count(),
+            arcs: report.arcs().filter(|arc| !arc.circle).count(),
+            circles: report.arcs().filter(|arc| arc.circle).count(),
+            compression_ratio: report.compression_ratio(),
+            max_residual: report.max_residual(),
+            input_length: report.input_length,
+            output_length: report.output_length,
+            estimated_cut_seconds: report.estimated_cut_seconds(),
+            total_seconds: report.timings.total(),
+            warnings,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).unwrap()
+    }
+
+    // The summary as lines for a terminal, headings in bold and warnings in yellow when `color` is
+    // set. Each category of warnings is headed by how many there are.
+    pub fn render(&self, color: bool) -> String {
+        let paint = |code: &str, text: &str| if color { format!("\x1b[{}m{}\x1b[0m", code, text) } else { text.to_string() };
+        let mut out = format!(
+            "{} {} entities into {} ({:.2}x) in {:.2}s: {}\n",
+            paint(&format!("{};{}", BOLD, GREEN), if self.healed { "healed" } else { "welded" }),
+            self.input_entities, self.output_entities, self.compression_ratio, self.total_seconds, self.output,
+        );
+        out += &format!("  {} chains, {} arcs, {} circles, max residual {}\n", self.chains, self.arcs, self.circles, format_number(self.max_residual));
+        if let Some(seconds) = self.estimated_cut_seconds {
+            out += &format!(
+                "  path length {:.3} -> {:.3} ({:+.6}), estimated cut time {}\n",
+                self.input_length, self.output_length, self.output_length - self.input_length, format_duration(seconds),
+            );
+        }
+        for category in [Category::Parse, Category::Chaining, Category::Fitting] {
+            let group = self.warnings.iter().filter(|warning| warning.category == category).collect::<Vec<_>>();
+            if group.is_empty() {
+                continue;
+            }
+            out += &format!("{} ({})\n", paint(BOLD, category.name()), group.len());
+            for warning in group {
+                let mut lines = warning.message.lines();
+                out += &format!("  {} {}\n", paint(YELLOW, "warning:"), lines.next().unwrap_or(""));
+                for line in lines {
+                    out += &format!("  {}\n", line);
+                }
+            }
+        }
+        out
+    }
+}
+
+fn plural(count: usize, word: &str) -> String {
+    if count == 1 { word.to_string() } else { format!("{}s", word) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dxf::Point;
+    use crate::report::{ChainReport, IntersectionReport};
+    use crate::warnings::Warning;
+
+    #[test]
+    fn test_summary() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let report = WeldReport {
+            input_entities: 40,
+            output_entities: 10,
+            gaps: vec![0.02, 0.03],
+            chains: vec![
+                ChainReport { layer: "0".to_string(), points: 30, ..Default::default() },
+                ChainReport { layer: "0".to_string(), points: 2, orphan: Some((p(0.0, 0.0), p(1.0, 0.0))), ..Default::default() },
+            ],
+            intersections: vec![IntersectionReport { layer: "0".to_string(), point: p(1.0, 1.0), same_chain: true, overlap: false }],
+            warnings: vec![Warning { kind: "units", message: "the drawing spans 2e6".to_string() }],
+            ..Default::default()
+        };
+        let summary = Summary::new(&report, "out.dxf");
+        assert_eq!((summary.chains, summary.compression_ratio), (1, 4.0));
+        let kinds = summary.warnings.iter().map(|warning| (warning.category, warning.kind)).collect::<Vec<_>>();
+        assert_eq!(kinds, vec![
+            (Category::Parse, "units"),
+            (Category::Chaining, "gaps"),
+            (Category::Chaining, "orphans"),
+            (Category::Fitting, "self-intersections"),
+        ]);
+
+        let plain = summary.render(false);
+        assert!(plain.starts_with("welded 40 entities into 10 (4.00x) in 0.00s: out.dxf\n"), "{}", plain);
+        assert!(plain.contains("chaining (2)\n  warning: 2 gaps up to 0.03 keep chains"), "{}", plain);
+        // the gap histogram stays under its warning
+        assert!(plain.contains("\n    0.01 - 0.1      2 "), "{}", plain);
+        assert!(!plain.contains('\x1b'));
+        assert!(summary.render(true).contains("\x1b[1mfitting\x1b[0m (1)\n  \x1b[33mwarning:\x1b[0m 1 self-intersection"));
+        assert!(summary.to_json().contains("\"category\": \"chaining\""));
+    }
+}
@@ -0,0 +1,109 @@
+use crate::dxf::*;
+use crate::tessellate::{bounds, FINE_CHORD_ERROR};
+use crate::output::OutputBackend;
+
+pub fn is_svg_path(path: &str) -> bool {
+    path.to_ascii_lowercase().ends_with(".svg")
+}
+
+// path data in drawing coordinates, meant to be placed in a y-flipped group
+pub fn path_data(entity: &Entity) -> String {
+    match entity {
+        Entity::Line(from, to) => format!("M {} {} L {} {}", from.x, from.y, to.x, to.y),
+        Entity::Arc { center, radius, start_angle, end_angle } => {
+            let start = center.polar(*radius, *start_angle);
+            let end = center.polar(*radius, *end_angle);
+            let mut sweep = end_angle - start_angle;
+            if sweep <= 0.0 {
+                sweep += 360.0;
+            }
+            let large_arc = if sweep > 180.0 { 1 } else { 0 };
+            format!("M {} {} A {} {} 0 {} 1 {} {}", start.x, start.y, radius, radius, large_arc, end.x, end.y)
+        },
+        Entity::Circle { center, radius } => format!(
+            "M {} {} A {r} {r} 0 1 1 {} {} A {r} {r} 0 1 1 {} {}",
+            center.x + radius, center.y, center.x - radius, center.y, center.x + radius, center.y, r = radius,
+        ),
+        Entity::Ellipse { center, major_axis, ratio, start_param, end_param } => {
+            let point = |t: f64| Point {
+                x: center.x + major_axis.x * t.cos() - major_axis.y * ratio * t.sin(),
+                y: center.y + major_axis.y * t.cos() + major_axis.x * ratio * t.sin(),
+            };
+            let major = (major_axis.x * major_axis.x + major_axis.y * major_axis.y).sqrt();
+            let rotation = major_axis.y.atan2(major_axis.x).to_degrees();
+            let mut sweep = (end_param - start_param).rem_euclid(2.0 * std::f64::consts::PI);
+            if sweep == 0.0 {
+                sweep = 2.0 * std::f64::consts::PI;
+            }
+            // two halves, each under 180 degrees, so a full ellipse does not start and end on the same point
+            let (start, mid, end) = (point(*start_param), point(start_param + sweep / 2.0), point(start_param + sweep));
+            format!(
+                "M {} {} A {rx} {ry} {rot} 0 1 {} {} A {rx} {ry} {rot} 0 1 {} {}",
+                start.x, start.y, mid.x, mid.y, end.x, end.y, rx = major, ry = major * ratio, rot = rotation,
+            )
+        },
+        Entity::Polyline { curve_type: 5, .. } | Entity::Polyline { curve_type: 6, .. } => {
+            path_data(&Entity::Polyline { curve_type: 0, vertices: entity.tessellate(FINE_CHORD_ERROR), widths: vec![] })
+        },
+        Entity::Polyline { vertices, .. } => vertices.iter().enumerate()
+            .map(|(i, p)| format!("{} {} {}", if i == 0 { "M" } else { "L" }, p.x, p.y))
+            .collect::<Vec<_>>()
+            .join(" "),
+    }
+}
+
+// opening <svg> tag sized to fit `drawing` with a small margin
+pub fn svg_header(drawing: &Drawing, chord_error: f64) -> String {
+    let (min, max) = bounds(drawing, chord_error).unwrap_or((Point { x: 0.0, y: 0.0 }, Point { x: 1.0, y: 1.0 }));
+    let margin = (max.x - min.x).max(max.y - min.y) * 0.02;
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"{} {} {} {}\">",
+        min.x - margin, -max.y - margin, max.x - min.x + 2.0 * margin, max.y - min.y + 2.0 * margin,
+    )
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+pub struct SvgBackend<'a> {
+    out: &'a mut String,
+    header: String,
+}
+
+impl<'a> SvgBackend<'a> {
+    // the drawing is only used to size the view box
+    pub fn new(out: &'a mut String, drawing: &Drawing, chord_error: f64) -> SvgBackend<'a> {
+        SvgBackend {
+            out,
+            header: svg_header(drawing, chord_error),
+        }
+    }
+}
+
+impl<'a> OutputBackend for SvgBackend<'a> {
+    fn buffer(&mut self) -> Option<&mut String> {
+        Some(self.out)
+    }
+
+    fn begin(&mut self) {
+        self.out.push_str(&self.header);
+        self.out.push_str("<g transform=\"scale(1,-1)\" fill=\"none\" stroke=\"black\" stroke-width=\"1\">\n");
+    }
+
+    fn entity(&mut self, entity: &Entity) {
+        self.out.push_str(&format!("<path vector-effect=\"non-scaling-stroke\" d=\"{}\"/>\n", path_data(entity)));
+    }
+
+    fn finish(&mut self) {
+        self.out.push_str("</g>\n</svg>\n");
+    }
+}
+
+impl Drawing {
+    pub fn to_svg(&self, chord_error: f64) -> String {
+        let mut out = String::new();
+        self.write_to(&mut SvgBackend::new(&mut out, self, chord_error));
+        out
+    }
+}
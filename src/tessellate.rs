@@ -0,0 +1,202 @@
+use crate::dxf::*;
+use std::f64::consts::PI;
+
+// chord error for writers that have no resolution of their own
+pub(crate) const FINE_CHORD_ERROR: f64 = 0.001;
+// most segments a curve is split into, however small the chord error
+const MAX_CURVE_SEGMENTS: f64 = 65536.0;
+
+fn arc_points(center: &Point, radius: f64, start_angle: f64, sweep: f64, chord_error: f64) -> Vec<Point> {
+    let step = if chord_error >= radius {
+        180.0
+    } else {
+        (2.0 * (1.0 - chord_error / radius).acos()).to_degrees()
+    };
+    // a NaN count (from a NaN chord error) casts to 0
+    let count = ((sweep.abs() / step).ceil().clamp(1.0, MAX_CURVE_SEGMENTS) as usize).max(1);
+    (0..=count).map(|i| center.polar(radius, start_angle + sweep * i as f64 / count as f64)).collect()
+}
+
+pub(crate) fn ccw_sweep(start_angle: f64, end_angle: f64) -> f64 {
+    let sweep = end_angle - start_angle;
+    if sweep <= 0.0 {
+        sweep + 360.0
+    } else {
+        sweep
+    }
+}
+
+// An ellipse is a circle of the major radius squashed along the minor axis, which only shrinks chord
+// deviations, so the circle's step is safe.
+fn ellipse_points(center: &Point, major_axis: &Point, ratio: f64, start_param: f64, end_param: f64, chord_error: f64) -> Vec<Point> {
+    let major = (major_axis.x * major_axis.x + major_axis.y * major_axis.y).sqrt();
+    let minor_axis = Point { x: -major_axis.y * ratio, y: major_axis.x * ratio };
+    let mut sweep = (end_param - start_param).rem_euclid(2.0 * PI);
+    if sweep == 0.0 {
+        sweep = 2.0 * PI;
+    }
+    let step = if chord_error >= major {
+        PI
+    } else {
+        2.0 * (1.0 - chord_error / major).acos()
+    };
+    let count = ((sweep / step).ceil().clamp(1.0, MAX_CURVE_SEGMENTS) as usize).max(1);
+    (0..=count).map(|i| {
+        let t = start_param + sweep * i as f64 / count as f64;
+        Point {
+            x: center.x + major_axis.x * t.cos() + minor_axis.x * t.sin(),
+            y: center.y + major_axis.y * t.cos() + minor_axis.y * t.sin(),
+        }
+    }).collect()
+}
+
+// de Boor evaluation of a clamped uniform B-spline at `t` in [0, 1]
+fn b_spline_point(control: &[Point], degree: usize, t: f64) -> Point {
+    let spans = control.len() - degree;
+    let knot = |i: usize| (i.saturating_sub(degree) as f64).min(spans as f64) / spans as f64;
+    let span = ((t * spans as f64).floor() as usize).min(spans - 1) + degree;
+    let mut d = control[span - degree..=span].to_vec();
+    for r in 1..=degree {
+        for j in (r..=degree).rev() {
+            let i = span - degree + j;
+            let alpha = (t - knot(i)) / (knot(i + degree + 1 - r) - knot(i));
+            d[j] = Point {
+                x: (1.0 - alpha) * d[j - 1].x + alpha * d[j].x,
+                y: (1.0 - alpha) * d[j - 1].y + alpha * d[j].y,
+            };
+        }
+    }
+    d[degree].clone()
+}
+
+// de Boor evaluation at `t` of a B-spline with its own knots and weights, in homogeneous coordinates
+// so the weights carry through
+fn nurbs_point(control: &[Point], weights: &[f64], knots: &[f64], degree: usize, t: f64) -> Point {
+    // the last non-empty knot span starting at or before t
+    let span = (degree..control.len()).rev().find(|i| knots[*i] <= t && knots[*i] < knots[i + 1]).unwrap_or(degree);
+    let mut d = (span - degree..=span).map(|i| (control[i].x * weights[i], control[i].y * weights[i], weights[i])).collect::<Vec<_>>();
+    for r in 1..=degree {
+        for j in (r..=degree).rev() {
+            let i = span - degree + j;
+            let alpha = (t - knots[i]) / (knots[i + degree + 1 - r] - knots[i]);
+            d[j] = (
+                (1.0 - alpha) * d[j - 1].0 + alpha * d[j].0,
+                (1.0 - alpha) * d[j - 1].1 + alpha * d[j].1,
+                (1.0 - alpha) * d[j - 1].2 + alpha * d[j].2,
+            );
+        }
+    }
+    Point { x: d[degree].0 / d[degree].2, y: d[degree].1 / d[degree].2 }
+}
+
+// halves parameter intervals until every chord midpoint is within `chord_error` of the curve
+fn subdivide<F: Fn(f64) -> Point>(curve: &F, t0: f64, t1: f64, depth: usize, chord_error: f64, out: &mut Vec<Point>) {
+    let (p0, p1, mid) = (curve(t0), curve(t1), curve((t0 + t1) / 2.0));
+    let chord_mid = Point { x: (p0.x + p1.x) / 2.0, y: (p0.y + p1.y) / 2.0 };
+    if depth < 16 && (depth < 2 || chord_mid.dist(&mid) > chord_error) {
+        subdivide(curve, t0, (t0 + t1) / 2.0, depth + 1, chord_error, out);
+        subdivide(curve, (t0 + t1) / 2.0, t1, depth + 1, chord_error, out);
+    } else {
+        out.push(p1);
+    }
+}
+
+fn spline_points(control: &[Point], degree: usize, chord_error: f64) -> Vec<Point> {
+    if control.len() <= degree {
+        return control.to_vec();
+    }
+    let curve = |t: f64| b_spline_point(control, degree, t);
+    let mut points = vec![control[0].clone()];
+    let spans = control.len() - degree;
+    for span in 0..spans {
+        subdivide(&curve, span as f64 / spans as f64, (span + 1) as f64 / spans as f64, 0, chord_error, &mut points);
+    }
+    points
+}
+
+// Points along a B-spline with `control.len() + degree + 1` knots and a positive weight per control
+// point, each non-empty knot span subdivided like a uniform spline's
+pub(crate) fn nurbs_points(control: &[Point], weights: &[f64], knots: &[f64], degree: usize, chord_error: f64) -> Vec<Point> {
+    if control.len() <= degree {
+        return control.to_vec();
+    }
+    let curve = |t: f64| nurbs_point(control, weights, knots, degree, t);
+    let mut points = vec![curve(knots[degree])];
+    for span in degree..control.len() {
+        if knots[span] < knots[span + 1] {
+            subdivide(&curve, knots[span], knots[span + 1], 0, chord_error, &mut points);
+        }
+    }
+    points
+}
+
+// whether `knots` are those of a clamped uniform B-spline, which a spline-fit polyline stands for,
+// up to where they start and how far apart they are
+pub(crate) fn is_clamped_uniform(knots: &[f64], control_points: usize, degree: usize) -> bool {
+    let (first, last) = (knots[0], knots[knots.len() - 1]);
+    let spans = control_points - degree;
+    last > first && knots.iter().enumerate().all(|(i, knot)| {
+        let uniform = (i.saturating_sub(degree) as f64).min(spans as f64) / spans as f64;
+        ((knot - first) / (last - first) - uniform).abs() < 1e-9
+    })
+}
+
+impl Entity {
+    // Points along the entity from its start to its end, with no chord between them straying more
+    // than `max_chord_error` from the true curve. Lines and plain polylines are their own vertices.
+    pub fn tessellate(&self, max_chord_error: f64) -> Vec<Point> {
+        let mut points = match self {
+            Entity::Line(from, to) => vec![from.clone(), to.clone()],
+            Entity::Arc { center, radius, start_angle, end_angle } => {
+                arc_points(center, *radius, *start_angle, ccw_sweep(*start_angle, *end_angle), max_chord_error)
+            },
+            Entity::Circle { center, radius } => arc_points(center, *radius, 0.0, 360.0, max_chord_error),
+            Entity::Ellipse { center, major_axis, ratio, start_param, end_param } => {
+                ellipse_points(center, major_axis, *ratio, *start_param, *end_param, max_chord_error)
+            },
+            // quadratic and cubic spline-fit polylines are clamped B-splines over their vertices
+            Entity::Polyline { curve_type: 5, vertices, .. } => spline_points(vertices, 2, max_chord_error),
+            Entity::Polyline { curve_type: 6, vertices, .. } => spline_points(vertices, 3, max_chord_error),
+            Entity::Polyline { vertices, .. } => vertices.clone(),
+        };
+        // a closed outline ends exactly where it starts, rather than a rounding error away
+        if points.len() > 2 && points.first() == points.last() {
+            let first = points[0].clone();
+            *points.last_mut().unwrap() = first;
+        }
+        points
+    }
+}
+
+pub(crate) fn bounds(drawing: &Drawing, chord_error: f64) -> Option<(Point, Point)> {
+    let mut points = drawing.entities().flat_map(|e| e.tessellate(chord_error));
+    let first = points.next()?;
+    Some(points.fold((first.clone(), first), |(min, max), p| (
+        Point { x: min.x.min(p.x), y: min.y.min(p.y) },
+        Point { x: max.x.max(p.x), y: max.y.max(p.y) },
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tessellate() {
+        let center = Point { x: 1.0, y: 2.0 };
+        let arc = Entity::Arc { center: center.clone(), radius: 10.0, start_angle: 300.0, end_angle: 60.0 };
+        let points = arc.tessellate(0.01);
+        assert_eq!((points.first().cloned(), points.last().cloned()), (arc.start_point(), arc.end_point()));
+        for pair in points.windows(2) {
+            let mid = Point { x: (pair[0].x + pair[1].x) / 2.0, y: (pair[0].y + pair[1].y) / 2.0 };
+            assert!(10.0 - mid.dist(&center) <= 0.01);
+        }
+        // a chord error of zero is capped rather than splitting forever
+        assert!(arc.tessellate(0.0).len() <= MAX_CURVE_SEGMENTS as usize + 1);
+        assert_eq!(arc.tessellate(f64::NAN).len(), 2);
+        // closed outlines end on their first point exactly, so they chain closed
+        let circle = Entity::Circle { center: Point { x: 0.0, y: 0.0 }, radius: 5.0 };
+        let points = circle.tessellate(0.001);
+        assert_eq!((points[0].x.to_bits(), points[0].y.to_bits()), (points[points.len() - 1].x.to_bits(), points[points.len() - 1].y.to_bits()));
+    }
+}
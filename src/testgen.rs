@@ -0,0 +1,80 @@
+use crate::dxf::*;
+use proptest::prelude::*;
+
+// A random drawing of lines and arcs, tessellated into LINEs the way CAD exports do. `curves` keeps
+// the exact geometry and `chord_error` the tessellation resolution used.
+#[derive(Clone, Debug)]
+pub struct GeneratedDrawing {
+    pub curves: Vec<Entity>,
+    pub chord_error: f64,
+    pub drawing: Drawing,
+}
+
+fn point() -> impl Strategy<Value = Point> {
+    (-100.0..100.0f64, -100.0..100.0f64).prop_map(|(x, y)| Point { x, y })
+}
+
+pub fn line() -> impl Strategy<Value = Entity> {
+    (point(), point()).prop_filter("zero-length line", |(from, to)| from.dist(to) > 0.1)
+        .prop_map(|(from, to)| Entity::Line(from, to))
+}
+
+// arcs sweeping 10° to 350°, and full circles
+pub fn arc() -> impl Strategy<Value = Entity> {
+    prop_oneof![
+        (point(), 1.0..50.0f64, 0.0..360.0f64, 10.0..350.0f64).prop_map(|(center, radius, start_angle, sweep)| Entity::Arc {
+            center,
+            radius,
+            start_angle,
+            end_angle: normalize_degrees(start_angle + sweep),
+        }),
+        (point(), 1.0..50.0f64).prop_map(|(center, radius)| Entity::Circle { center, radius }),
+    ]
+}
+
+// up to `max_curves` lines and arcs, tessellated with a chord error between 0.0005 and 0.01
+pub fn drawing(max_curves: usize) -> impl Strategy<Value = GeneratedDrawing> {
+    (prop::collection::vec(prop_oneof![line(), arc()], 1..=max_curves), 0.0005..0.01f64).prop_map(|(curves, chord_error)| {
+        let lines = curves.iter().flat_map(|curve| {
+            curve.tessellate(chord_error).windows(2).map(|pair| Entity::Line(pair[0].clone(), pair[1].clone())).collect::<Vec<_>>()
+        }).collect();
+        GeneratedDrawing {
+            curves,
+            chord_error,
+            drawing: Drawing::new(lines),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dxf_process::DxfConfig;
+    use crate::report::WeldReport;
+
+    fn segment_distance(point: &Point, from: &Point, to: &Point) -> f64 {
+        let (dx, dy) = (to.x - from.x, to.y - from.y);
+        let length = dx * dx + dy * dy;
+        let t = if length == 0.0 { 0.0 } else { (((point.x - from.x) * dx + (point.y - from.y) * dy) / length).clamp(0.0, 1.0) };
+        point.dist(&Point { x: from.x + t * dx, y: from.y + t * dy })
+    }
+
+    proptest! {
+        #[test]
+        fn welded_output_stays_within_resolution(generated in drawing(4)) {
+            let config = DxfConfig::default();
+            let input = generated.drawing.entities().map(|entity| match entity {
+                Entity::Line(from, to) => (from.clone(), to.clone()),
+                other => panic!("unexpected {:?}", other),
+            }).collect::<Vec<_>>();
+            let welded = config.process_drawing(generated.drawing.clone(), &mut WeldReport::default()).unwrap();
+            for entity in welded.entities() {
+                for point in entity.tessellate(config.resolution / 10.0) {
+                    let distance = input.iter().map(|(from, to)| segment_distance(&point, from, to)).fold(f64::MAX, f64::min);
+                    // sampling the output arcs adds its own chord error
+                    prop_assert!(distance <= config.resolution * 1.1, "{:?} is {} from the input", point, distance);
+                }
+            }
+        }
+    }
+}
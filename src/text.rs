@@ -0,0 +1,104 @@
+use crate::dxf::*;
+use crate::dxf_process::Chain;
+
+// share of the drawing's extent a loop may span and still be a glyph
+pub const MAX_GLYPH_SHARE: f64 = 0.05;
+// standard deviation, in radians, of the turns along a loop above which it is shaped like a glyph,
+// mixing strokes, corners both ways and tight curves; circles, slots and convex polygons turn evenly
+pub const MIN_TURN_DEVIATION: f64 = 0.5;
+// glyph-shaped loops a row of small loops needs to count as a line of text
+pub const MIN_GLYPHS: usize = 4;
+
+// standard deviation of the signed turn at each point of a closed chain
+fn turn_deviation(points: &[Point]) -> f64 {
+    let directions = points.windows(2)
+        .filter(|pair| pair[0] != pair[1])
+        .map(|pair| (pair[1].y - pair[0].y).atan2(pair[1].x - pair[0].x))
+        .collect::<Vec<_>>();
+    if directions.len() < 3 {
+        return 0.0;
+    }
+    let turns = directions.iter().zip(directions.iter().cycle().skip(1)).map(|(a, b)| {
+        let turn = (b - a).rem_euclid(2.0 * std::f64::consts::PI);
+        if turn > std::f64::consts::PI { turn - 2.0 * std::f64::consts::PI } else { turn }
+    }).collect::<Vec<_>>();
+    let mean = turns.iter().sum::<f64>() / turns.len() as f64;
+    (turns.iter().map(|turn| (turn - mean).powi(2)).sum::<f64>() / turns.len() as f64).sqrt()
+}
+
+// Which chains look like text exploded into outlines: small closed loops lined up in rows (bands of
+// loops whose middles fall within the height of the band's first loop) where at least `MIN_GLYPHS`
+// of them turn as unevenly as glyphs do. Every small loop of such a row counts, so the plain
+// counters of letters like `o` go along with the rest of their word.
+pub fn text_chains(chains: &[Chain]) -> Vec<bool> {
+    let mut text = vec![false; chains.len()];
+    let bounds = chains.iter().map(Chain::bounds).collect::<Vec<_>>();
+    let extent = bounds.iter().cloned().reduce(|(min, max), (low, high)| (
+        Point { x: min.x.min(low.x), y: min.y.min(low.y) },
+        Point { x: max.x.max(high.x), y: max.y.max(high.y) },
+    ));
+    let max_size = match extent {
+        Some((min, max)) => (max.x - min.x).max(max.y - min.y) * MAX_GLYPH_SHARE,
+        None => return text,
+    };
+    let mut small = (0..chains.len()).filter(|i| {
+        let (min, max) = &bounds[*i];
+        chains[*i].closed() && (max.x - min.x).max(max.y - min.y) <= max_size
+    }).collect::<Vec<_>>();
+    let middle = |i: usize| (bounds[i].0.y + bounds[i].1.y) / 2.0;
+    small.sort_by(|a, b| chains[*a].layer.cmp(&chains[*b].layer).then(middle(*a).total_cmp(&middle(*b))));
+    let mut start = 0;
+    while start < small.len() {
+        let first = small[start];
+        let half_height = (bounds[first].1.y - bounds[first].0.y) / 2.0;
+        let end = start + small[start..].iter()
+            .take_while(|i| chains[**i].layer == chains[first].layer && middle(**i) - middle(first) <= half_height)
+            .count();
+        let band = &small[start..end];
+        if band.iter().filter(|i| turn_deviation(&chains[**i].points) >= MIN_TURN_DEVIATION).count() >= MIN_GLYPHS {
+            for i in band.iter() {
+                text[*i] = true;
+            }
+        }
+        start = end;
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chain(points: Vec<(f64, f64)>) -> Chain {
+        let mut points = points.into_iter().map(|(x, y)| Point { x, y }).collect::<Vec<_>>();
+        points.push(points[0].clone());
+        Chain { layer: "0".to_string(), points }
+    }
+
+    // an L-shaped glyph outline 2 wide and 3 high
+    fn glyph(x: f64, y: f64) -> Chain {
+        chain(vec![(x, y), (x + 2.0, y), (x + 2.0, y + 0.5), (x + 0.5, y + 0.5), (x + 0.5, y + 3.0), (x, y + 3.0)])
+    }
+
+    fn circle(x: f64, y: f64, radius: f64) -> Chain {
+        chain((0..36).map(|i| {
+            let point = Point { x, y }.polar(radius, i as f64 * 10.0);
+            (point.x, point.y)
+        }).collect())
+    }
+
+    #[test]
+    fn test_text_chains() {
+        let mut chains = vec![chain(vec![(0.0, 0.0), (200.0, 0.0), (200.0, 100.0), (0.0, 100.0)])];
+        // a word of four glyphs and a round counter, then a row of holes
+        chains.extend((0..4).map(|i| glyph(10.0 + i as f64 * 3.0, 50.0)));
+        chains.push(circle(23.0, 51.5, 1.0));
+        chains.extend((0..6).map(|i| circle(10.0 + i as f64 * 10.0, 10.0, 1.5)));
+        let text = text_chains(&chains);
+        assert_eq!(text.iter().filter(|t| **t).count(), 5);
+        assert!(text[1..6].iter().all(|t| *t));
+
+        // too few glyphs for a line of text
+        assert!(text_chains(&chains[..4]).iter().all(|t| !t));
+    }
+}
@@ -0,0 +1,297 @@
+use crate::dxf::*;
+
+// A similarity transform (uniform scale, rotation, translation and reflection) stored as a 2x3
+// affine matrix, so arcs stay arcs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transform {
+    a: f64,
+    b: f64,
+    c: f64,
+    d: f64,
+    e: f64,
+    f: f64,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform { a: 1.0, b: 0.0, c: 0.0, d: 0.0, e: 1.0, f: 0.0 }
+    }
+
+    pub fn scale(factor: f64) -> Transform {
+        Transform { a: factor, b: 0.0, c: 0.0, d: 0.0, e: factor, f: 0.0 }
+    }
+
+    // counter-clockwise about the origin
+    pub fn rotate_degrees(degrees: f64) -> Transform {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Transform { a: cos, b: -sin, c: 0.0, d: sin, e: cos, f: 0.0 }
+    }
+
+    // negates x, mirroring across the y axis
+    pub fn mirror_x() -> Transform {
+        Transform { a: -1.0, b: 0.0, c: 0.0, d: 0.0, e: 1.0, f: 0.0 }
+    }
+
+    // negates y, mirroring across the x axis
+    pub fn mirror_y() -> Transform {
+        Transform { a: 1.0, b: 0.0, c: 0.0, d: 0.0, e: -1.0, f: 0.0 }
+    }
+
+    pub fn translate(x: f64, y: f64) -> Transform {
+        Transform { a: 1.0, b: 0.0, c: x, d: 0.0, e: 1.0, f: y }
+    }
+
+    // applies `self` first, then `next`
+    pub fn then(&self, next: &Transform) -> Transform {
+        Transform {
+            a: next.a * self.a + next.b * self.d,
+            b: next.a * self.b + next.b * self.e,
+            c: next.a * self.c + next.b * self.f + next.c,
+            d: next.d * self.a + next.e * self.d,
+            e: next.d * self.b + next.e * self.e,
+            f: next.d * self.c + next.e * self.f + next.f,
+        }
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Transform::identity()
+    }
+
+    fn determinant(&self) -> f64 {
+        self.a * self.e - self.b * self.d
+    }
+
+    // true if the transform flips orientation, turning counter-clockwise arcs clockwise
+    pub fn is_reflection(&self) -> bool {
+        self.determinant() < 0.0
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        self.determinant().abs().sqrt()
+    }
+
+    pub fn apply(&self, point: &Point) -> Point {
+        Point {
+            x: self.a * point.x + self.b * point.y + self.c,
+            y: self.d * point.x + self.e * point.y + self.f,
+        }
+    }
+}
+
+impl Entity {
+    pub fn transform(&self, transform: &Transform) -> Entity {
+        match self {
+            Entity::Line(from, to) => Entity::Line(transform.apply(from), transform.apply(to)),
+            Entity::Arc { center, radius, start_angle, end_angle } => {
+                let new_center = transform.apply(center);
+                // map the endpoints and measure the new angles from them, which covers rotation and reflection alike
+                let angle_of = |degrees: f64| normalize_degrees(transform.apply(&center.polar(*radius, degrees)).angle(&new_center).to_degrees());
+                let (start_angle, end_angle) = if transform.is_reflection() {
+                    (angle_of(*end_angle), angle_of(*start_angle))
+                } else {
+                    (angle_of(*start_angle), angle_of(*end_angle))
+                };
+                Entity::Arc {
+                    center: new_center,
+                    radius: radius * transform.scale_factor(),
+                    start_angle,
+                    end_angle,
+                }
+            },
+            Entity::Circle { center, radius } => Entity::Circle {
+                center: transform.apply(center),
+                radius: radius * transform.scale_factor(),
+            },
+            Entity::Ellipse { center, major_axis, ratio, start_param, end_param } => {
+                let new_center = transform.apply(center);
+                let end = transform.apply(&Point { x: center.x + major_axis.x, y: center.y + major_axis.y });
+                // a reflection runs the parameter backwards
+                let (start_param, end_param) = if transform.is_reflection() {
+                    (-end_param, -start_param)
+                } else {
+                    (*start_param, *end_param)
+                };
+                Entity::Ellipse {
+                    major_axis: Point { x: end.x - new_center.x, y: end.y - new_center.y },
+                    center: new_center,
+                    ratio: *ratio,
+                    start_param,
+                    end_param,
+                }
+            },
+            Entity::Polyline { curve_type, vertices, widths } => Entity::Polyline {
+                curve_type: *curve_type,
+                vertices: vertices.iter().map(|v| transform.apply(v)).collect(),
+                widths: widths.iter().map(|(start, end)| (start * transform.scale_factor(), end * transform.scale_factor())).collect(),
+            },
+        }
+    }
+}
+
+impl Entity {
+    // Mirrors across the y axis. Arcs are stored counter-clockwise, so reflecting one reverses its
+    // sweep: the new start is the mirror of the old end.
+    pub fn mirror_x(&self) -> Entity {
+        match self {
+            Entity::Arc { center, radius, start_angle, end_angle } => Entity::Arc {
+                center: Point { x: -center.x, y: center.y },
+                radius: *radius,
+                start_angle: normalize_degrees(180.0 - end_angle),
+                end_angle: normalize_degrees(180.0 - start_angle),
+            },
+            other => other.transform(&Transform::mirror_x()),
+        }
+    }
+
+    // mirrors across the x axis
+    pub fn mirror_y(&self) -> Entity {
+        match self {
+            Entity::Arc { center, radius, start_angle, end_angle } => Entity::Arc {
+                center: Point { x: center.x, y: -center.y },
+                radius: *radius,
+                start_angle: normalize_degrees(-end_angle),
+                end_angle: normalize_degrees(-start_angle),
+            },
+            other => other.transform(&Transform::mirror_y()),
+        }
+    }
+}
+
+impl Insert {
+    // Moves the insert with the transform. Block definitions are left as they are: the insert's
+    // rotation follows the block's x axis, and a reflection flips its y scale.
+    pub fn transform(&self, transform: &Transform) -> Insert {
+        let origin = transform.apply(&Point { x: 0.0, y: 0.0 });
+        let x_axis = transform.apply(&Point { x: 0.0, y: 0.0 }.polar(1.0, self.rotation));
+        let mirror = if transform.is_reflection() { -1.0 } else { 1.0 };
+        Insert {
+            block: self.block.clone(),
+            position: transform.apply(&self.position),
+            x_scale: self.x_scale * transform.scale_factor(),
+            y_scale: self.y_scale * transform.scale_factor() * mirror,
+            rotation: normalize_degrees(x_axis.angle(&origin).to_degrees()),
+        }
+    }
+}
+
+impl RawEntity {
+    // Moves the entity's points with the transform. Its directions turn and scale without moving,
+    // and its lengths scale.
+    pub fn transform(&self, transform: &Transform) -> RawEntity {
+        let origin = transform.apply(&Point { x: 0.0, y: 0.0 });
+        self.map_points(self.point_codes(), |point| transform.apply(point))
+            .map_points(self.vector_codes(), |vector| {
+                let moved = transform.apply(vector);
+                Point { x: moved.x - origin.x, y: moved.y - origin.y }
+            })
+            .map_values(self.length_codes(), |length| length * transform.scale_factor())
+    }
+}
+
+impl Drawing {
+    pub fn transform(&mut self, transform: &Transform) {
+        for entity in self.entities_mut() {
+            *entity = entity.transform(transform);
+        }
+        for raw in self.layers.iter_mut().flat_map(|layer| layer.raw.iter_mut()) {
+            *raw = raw.transform(transform);
+        }
+        for insert in self.layers.iter_mut().flat_map(|layer| layer.inserts.iter_mut()) {
+            *insert = insert.transform(transform);
+        }
+    }
+}
+
+// Merges `drawings` into one, laid out left to right, bottom to top in a grid of `columns`
+// cells sized to the largest part plus `spacing`.
+pub fn tile(drawings: Vec<Drawing>, columns: usize, spacing: f64, chord_error: f64) -> Drawing {
+    let bounds = drawings.iter().map(|d| crate::tessellate::bounds(d, chord_error)).collect::<Vec<_>>();
+    let (mut cell_width, mut cell_height) = (0f64, 0f64);
+    for (min, max) in bounds.iter().flatten() {
+        cell_width = cell_width.max(max.x - min.x);
+        cell_height = cell_height.max(max.y - min.y);
+    }
+    let mut merged = Drawing::default();
+    for (i, (mut drawing, bounds)) in drawings.into_iter().zip(bounds).enumerate() {
+        if let Some((min, _)) = bounds {
+            let x = (i % columns) as f64 * (cell_width + spacing);
+            let y = (i / columns) as f64 * (cell_height + spacing);
+            drawing.transform(&Transform::translate(x - min.x, y - min.y));
+        }
+        merged.merge(drawing);
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mirror_arc() {
+        let arc = Entity::Arc { center: Point { x: 2.0, y: 1.0 }, radius: 1.0, start_angle: 0.0, end_angle: 90.0 };
+        assert_eq!(arc.mirror_x(), Entity::Arc { center: Point { x: -2.0, y: 1.0 }, radius: 1.0, start_angle: 90.0, end_angle: 180.0 });
+        assert_eq!(arc.mirror_y(), Entity::Arc { center: Point { x: 2.0, y: -1.0 }, radius: 1.0, start_angle: 270.0, end_angle: 0.0 });
+        match arc.transform(&Transform::mirror_x()) {
+            Entity::Arc { start_angle, end_angle, .. } => {
+                assert!((start_angle - 90.0).abs() < 1e-9);
+                assert!((end_angle - 180.0).abs() < 1e-9);
+            },
+            other => panic!("unexpected entity {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rotate_arc() {
+        let arc = Entity::Arc { center: Point { x: 1.0, y: 0.0 }, radius: 1.0, start_angle: 0.0, end_angle: 90.0 };
+        let transform = Transform::scale(2.0).then(&Transform::rotate_degrees(90.0)).then(&Transform::translate(1.0, 1.0));
+        match arc.transform(&transform) {
+            Entity::Arc { center, radius, start_angle, end_angle } => {
+                assert_eq!(center, Point { x: 1.0, y: 3.0 });
+                assert!((radius - 2.0).abs() < 1e-9);
+                assert!((start_angle - 90.0).abs() < 1e-9);
+                assert!((end_angle - 180.0).abs() < 1e-9);
+            },
+            other => panic!("unexpected entity {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_transform_insert() {
+        let insert = Insert { block: "BOSS".to_string(), position: Point { x: 3.0, y: 1.0 }, x_scale: 1.0, y_scale: 2.0, rotation: 30.0 };
+        // where a point of the block lands once inserted
+        let place = |insert: &Insert, x: f64, y: f64| {
+            let origin = Point { x: 0.0, y: 0.0 };
+            let scaled = Point { x: x * insert.x_scale, y: y * insert.y_scale };
+            let turned = origin.polar(scaled.dist(&origin), scaled.angle(&origin).to_degrees() + insert.rotation);
+            Point { x: insert.position.x + turned.x, y: insert.position.y + turned.y }
+        };
+        let transform = Transform::mirror_x().then(&Transform::scale(0.5)).then(&Transform::rotate_degrees(45.0));
+        let moved = insert.transform(&transform);
+        for (x, y) in [(1.0, 0.0), (0.0, 1.0), (2.0, -3.0)] {
+            assert_eq!(place(&moved, x, y), transform.apply(&place(&insert, x, y)));
+        }
+    }
+
+    #[test]
+    fn test_transform_raw() {
+        let codes = |codes: &[(u16, &str)]| codes.iter().map(|(code, value)| (*code, value.to_string())).collect::<Vec<_>>();
+        let mut drawing = Drawing::default();
+        drawing.layers.push(Layer::new("NOTES", None));
+        drawing.layers[0].raw.push(RawEntity {
+            kind: "LEADER".to_string(),
+            codes: codes(&[(3, "STANDARD"), (40, "2.5"), (10, "1"), (20, "0"), (30, "7"), (10, "3"), (20, "2"), (211, "1"), (221, "0")]),
+        });
+        drawing.transform(&Transform::scale(2.0).then(&Transform::rotate_degrees(90.0)).then(&Transform::translate(10.0, 0.0)));
+        assert_eq!(
+            drawing.layers[0].raw[0].codes,
+            codes(&[(3, "STANDARD"), (40, "5"), (10, "10"), (20, "2"), (30, "7"), (10, "6"), (20, "6"), (211, "0"), (221, "2")])
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use crate::cleanup::snap_endpoints;
+use crate::dxf::*;
+use crate::dxf_process::DxfConfig;
+use crate::report::WeldReport;
+use crate::result::*;
+use std::collections::BTreeMap;
+
+// resolutions tried by `--auto-resolution`, finest first
+pub const SWEEP_RESOLUTIONS: [f64; 10] = [0.001, 0.002, 0.005, 0.01, 0.02, 0.05, 0.1, 0.2, 0.5, 1.0];
+
+pub struct ResolutionTrial {
+    pub resolution: f64,
+    pub drawing: Drawing,
+    pub report: WeldReport,
+}
+
+// Welds `drawing` once per resolution, each report starting from a copy of `base`.
+pub fn sweep(config: &DxfConfig, drawing: &Drawing, resolutions: &[f64], base: &WeldReport) -> Result<Vec<ResolutionTrial>> {
+    let welds = config.process_resolutions(drawing.clone(), resolutions, &mut base.clone())?;
+    Ok(resolutions.iter().zip(welds).map(|(resolution, (drawing, report))| ResolutionTrial {
+        resolution: *resolution,
+        drawing,
+        report,
+    }).collect())
+}
+
+// the trial with the best compression whose deviation stays within `max_deviation`, preferring the finer resolution on ties
+pub fn best_under(trials: &[ResolutionTrial], max_deviation: f64) -> Option<usize> {
+    let mut best: Option<usize> = None;
+    for (i, trial) in trials.iter().enumerate() {
+        if trial.report.max_residual() > max_deviation {
+            continue;
+        }
+        if best.map(|b| trial.report.compression_ratio() > trials[b].report.compression_ratio()).unwrap_or(true) {
+            best = Some(i);
+        }
+    }
+    best
+}
+
+pub fn format_table(trials: &[ResolutionTrial]) -> String {
+    let mut out = format!("{:>12} {:>10} {:>12} {:>14}\n", "resolution", "entities", "compression", "max deviation");
+    for trial in trials.iter() {
+        out.push_str(&format!(
+            "{:>12} {:>10} {:>12.2} {:>14.6}\n",
+            trial.resolution, trial.report.output_entities, trial.report.compression_ratio(), trial.report.max_residual(),
+        ));
+    }
+    out
+}
+
+// An interactive tuning session over one input, parsed once. Each weld is kept by its settings, so
+// stepping back to values already tried is instant, and the input is only re-joined when the join
+// tolerance changes.
+pub struct Session {
+    input: Drawing,
+    pub config: DxfConfig,
+    // line endpoints up to this far apart are snapped together before welding; 0 joins exact matches only
+    pub join_tolerance: f64,
+    joined: Option<(f64, Drawing)>,
+    welds: BTreeMap<(u64, u64), (Drawing, WeldReport)>,
+}
+
+impl Session {
+    pub fn new(input: Drawing, config: DxfConfig) -> Session {
+        Session {
+            input,
+            config,
+            join_tolerance: 0.0,
+            joined: None,
+            welds: BTreeMap::new(),
+        }
+    }
+
+    // the input welded with the current settings
+    pub fn weld(&mut self) -> Result<&(Drawing, WeldReport)> {
+        let key = (self.config.resolution.to_bits(), self.join_tolerance.to_bits());
+        if !self.welds.contains_key(&key) {
+            if self.join_tolerance > 0.0 && self.joined.as_ref().map(|(tolerance, _)| *tolerance != self.join_tolerance).unwrap_or(true) {
+                let mut joined = self.input.clone();
+                snap_endpoints(&mut joined, self.join_tolerance);
+                self.joined = Some((self.join_tolerance, joined));
+            }
+            let input = match &self.joined {
+                Some((_, joined)) if self.join_tolerance > 0.0 => joined,
+                _ => &self.input,
+            };
+            let mut report = WeldReport::default();
+            let welded = self.config.process_drawing(input.clone(), &mut report)?;
+            self.welds.insert(key, (welded, report));
+        }
+        Ok(&self.welds[&key])
+    }
+}
+
+// one line of statistics for a weld at these settings
+pub fn format_stats(resolution: f64, join_tolerance: f64, report: &WeldReport) -> String {
+    format!(
+        "resolution {} join {}: {} -> {} entities ({:.2}x), {} arcs, {} circles, max deviation {:.6}",
+        resolution, join_tolerance, report.input_entities, report.output_entities, report.compression_ratio(),
+        report.arcs().filter(|arc| !arc.circle).count(), report.arcs().filter(|arc| arc.circle).count(), report.max_residual(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sweep() {
+        let points = (0..=32).map(|i| Point { x: 0.0, y: 0.0 }.polar(10.0, 90.0 - i as f64 * 90.0 / 32.0)).collect::<Vec<_>>();
+        let drawing = Drawing::new(points.windows(2).map(|pair| Entity::Line(pair[0].clone(), pair[1].clone())).collect());
+        let trials = sweep(&DxfConfig::default(), &drawing, &SWEEP_RESOLUTIONS, &WeldReport::default()).unwrap();
+        assert_eq!(trials.len(), SWEEP_RESOLUTIONS.len());
+        let best = best_under(&trials, 0.05).unwrap();
+        assert!(trials[best].report.max_residual() <= 0.05);
+        assert!(trials[best].report.compression_ratio() > 1.0);
+        assert_eq!(format_table(&trials).lines().count(), SWEEP_RESOLUTIONS.len() + 1);
+    }
+
+    #[test]
+    fn test_session() {
+        // a quarter circle with a small break halfway along
+        let points = (0..=32).map(|i| Point { x: 0.0, y: 0.0 }.polar(10.0, 90.0 - i as f64 * 90.0 / 32.0)).collect::<Vec<_>>();
+        let lines = points.windows(2).enumerate().map(|(i, pair)| {
+            let from = if i == 16 { Point { x: pair[0].x + 0.002, y: pair[0].y } } else { pair[0].clone() };
+            Entity::Line(from, pair[1].clone())
+        }).collect();
+        let mut session = Session::new(Drawing::new(lines), DxfConfig::default());
+        assert_eq!(session.weld().unwrap().1.output_entities, 2);
+        session.join_tolerance = 0.01;
+        assert_eq!(session.weld().unwrap().1.output_entities, 1);
+        session.config.resolution = 0.01;
+        session.weld().unwrap();
+        assert_eq!(session.welds.len(), 3);
+        session.config.resolution = 0.05;
+        session.weld().unwrap();
+        assert_eq!(session.welds.len(), 3);
+        assert!(format_stats(0.05, 0.01, &session.weld().unwrap().1).starts_with("resolution 0.05 join 0.01: 32 -> 1 entities"));
+    }
+}
@@ -0,0 +1,187 @@
+use crate::dxf::*;
+use crate::tessellate::bounds;
+use crate::path::{find, snap_key};
+use crate::report::{IntersectionReport, WeldReport};
+use std::collections::{BTreeMap, HashSet};
+
+struct Span {
+    entity: usize,
+    from: Point,
+    to: Point,
+    min_x: f64,
+    max_x: f64,
+}
+
+// crossing point of two segments, ignoring segments that only touch end to end, and whether they
+// overlap from there on instead of crossing
+fn crossing(a: &Span, b: &Span) -> Option<(Point, bool)> {
+    let (dx1, dy1) = (a.to.x - a.from.x, a.to.y - a.from.y);
+    let (dx2, dy2) = (b.to.x - b.from.x, b.to.y - b.from.y);
+    let denom = dx1 * dy2 - dy1 * dx2;
+    let (ox, oy) = (b.from.x - a.from.x, b.from.y - a.from.y);
+    if denom.abs() < 1e-12 {
+        return overlap(a, b).map(|point| (point, true));
+    }
+    let t = (ox * dy2 - oy * dx2) / denom;
+    let u = (ox * dy1 - oy * dx1) / denom;
+    if !(0.0..=1.0).contains(&t) || !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let point = Point { x: a.from.x + t * dx1, y: a.from.y + t * dy1 };
+    let is_end = |span: &Span| point == span.from || point == span.to;
+    if is_end(a) && is_end(b) {
+        return None;
+    }
+    Some((point, false))
+}
+
+// where two parallel segments start running along each other, if they lie on one line and share
+// more than a point
+fn overlap(a: &Span, b: &Span) -> Option<Point> {
+    let (dx, dy) = (a.to.x - a.from.x, a.to.y - a.from.y);
+    let length = dx.hypot(dy);
+    if length < 1e-12 {
+        return None;
+    }
+    // distance of b from a's line
+    if ((b.from.x - a.from.x) * dy - (b.from.y - a.from.y) * dx).abs() / length >= 1e-5 {
+        return None;
+    }
+    let along = |p: &Point| ((p.x - a.from.x) * dx + (p.y - a.from.y) * dy) / (length * length);
+    let (t0, t1) = (along(&b.from), along(&b.to));
+    let (start, end) = (t0.min(t1).max(0.0), t0.max(t1).min(1.0));
+    if (end - start) * length < 1e-5 {
+        return None;
+    }
+    Some(Point { x: a.from.x + start * dx, y: a.from.y + start * dy })
+}
+
+fn layer_intersections(layer: &Layer, chord_error: f64) -> Vec<IntersectionReport> {
+    let outlines = layer.entities.iter().map(|e| e.tessellate(chord_error)).collect::<Vec<_>>();
+
+    // entities sharing an endpoint belong to the same chain
+    let mut chains = (0..outlines.len()).collect::<Vec<_>>();
+    let mut endpoints: BTreeMap<Point, usize> = BTreeMap::new();
+    for (i, outline) in outlines.iter().enumerate() {
+        for point in outline.first().into_iter().chain(outline.last()) {
+            let other = *endpoints.entry(point.clone()).or_insert(i);
+            let (a, b) = (find(&mut chains, other), find(&mut chains, i));
+            chains[a] = b;
+        }
+    }
+
+    let mut spans = outlines.iter().enumerate().flat_map(|(entity, outline)| {
+        outline.windows(2).map(move |pair| Span {
+            entity,
+            from: pair[0].clone(),
+            to: pair[1].clone(),
+            min_x: pair[0].x.min(pair[1].x),
+            max_x: pair[0].x.max(pair[1].x),
+        })
+    }).collect::<Vec<_>>();
+    spans.sort_by(|a, b| a.min_x.partial_cmp(&b.min_x).unwrap());
+
+    let mut found: Vec<IntersectionReport> = vec![];
+    let mut seen = HashSet::new();
+    for (i, a) in spans.iter().enumerate() {
+        for b in spans[i + 1..].iter().take_while(|b| b.min_x <= a.max_x) {
+            if let Some((point, overlap)) = crossing(a, b) {
+                if !seen.insert(snap_key(&point)) {
+                    continue;
+                }
+                found.push(IntersectionReport {
+                    layer: layer.name.clone(),
+                    point,
+                    same_chain: find(&mut chains, a.entity) == find(&mut chains, b.entity),
+                    overlap,
+                });
+            }
+        }
+    }
+    found
+}
+
+// Finds points where the drawing crosses itself or starts running along itself, either within one
+// chain of connected entities or between two chains on the same layer. Arcs and circles are tessellated to `chord_error`.
+pub fn intersections(drawing: &Drawing, chord_error: f64) -> Vec<IntersectionReport> {
+    drawing.layers.iter().flat_map(|layer| layer_intersections(layer, chord_error)).collect()
+}
+
+pub const DEVIATION_LAYER: &str = "DEVIATION";
+
+// Radial tick marks across each welded arc at its point of largest deviation, for every arc whose
+// residual exceeds `threshold`. Ticks are 2% of the drawing's diagonal long so they show at any scale.
+pub fn deviation_ticks(drawing: &Drawing, report: &WeldReport, threshold: f64, chord_error: f64) -> Vec<Entity> {
+    let size = match bounds(drawing, chord_error) {
+        Some((min, max)) => min.dist(&max) * 0.02,
+        None => return vec![],
+    };
+    report.arcs().filter(|arc| arc.residual > threshold).map(|arc| {
+        let degrees = arc.residual_at.angle(&arc.center).to_degrees();
+        Entity::Line(arc.center.polar(arc.radius - size / 2.0, degrees), arc.center.polar(arc.radius + size / 2.0, degrees))
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_intersections() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let drawing = Drawing::new(vec![
+            // a bow tie crossing itself at (5, 5)
+            Entity::Polyline { curve_type: 0, vertices: vec![p(0.0, 0.0), p(10.0, 10.0), p(10.0, 0.0), p(0.0, 10.0), p(0.0, 0.0)], widths: vec![] },
+            // a separate line crossing the bow tie's right edge
+            Entity::Line(p(8.0, 5.0), p(12.0, 5.0)),
+            // touching end to end is fine
+            Entity::Line(p(20.0, 0.0), p(25.0, 0.0)),
+            Entity::Line(p(25.0, 0.0), p(25.0, 5.0)),
+        ]);
+        let found = intersections(&drawing, 0.01);
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().any(|f| f.point == p(5.0, 5.0) && f.same_chain && !f.overlap));
+        assert!(found.iter().any(|f| f.point == p(10.0, 5.0) && !f.same_chain));
+    }
+
+    #[test]
+    fn test_overlaps() {
+        let p = |x: f64, y: f64| Point { x, y };
+        let drawing = Drawing::new(vec![
+            // two chains running along each other from (5, 0) to (10, 0)
+            Entity::Line(p(0.0, 0.0), p(10.0, 0.0)),
+            Entity::Line(p(15.0, 0.0), p(5.0, 0.0)),
+            // collinear but only touching end to end, and parallel but apart
+            Entity::Line(p(0.0, 10.0), p(10.0, 10.0)),
+            Entity::Line(p(10.0, 10.0), p(20.0, 10.0)),
+            Entity::Line(p(0.0, 11.0), p(20.0, 11.0)),
+        ]);
+        let found = intersections(&drawing, 0.01);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].point, p(5.0, 0.0));
+        assert!(found[0].overlap && !found[0].same_chain);
+    }
+
+    #[test]
+    fn test_deviation_ticks() {
+        let center = Point { x: 0.0, y: 0.0 };
+        let mut points = (0..=32).map(|i| center.polar(10.0, 90.0 - i as f64 * 90.0 / 32.0)).collect::<Vec<_>>();
+        points[8] = center.polar(10.03, 67.5);
+        let drawing = Drawing::new(points.windows(2).map(|pair| Entity::Line(pair[0].clone(), pair[1].clone())).collect());
+        let mut report = WeldReport::default();
+        let welded = crate::dxf_process::DxfConfig::default().process_drawing(drawing, &mut report).unwrap();
+        assert_eq!(welded.entity_count(), 1);
+
+        let ticks = deviation_ticks(&welded, &report, 0.01, 0.05);
+        assert_eq!(ticks.len(), 1);
+        match &ticks[0] {
+            Entity::Line(from, to) => {
+                // radial through the displaced point
+                assert!((from.angle(&center).to_degrees() - 67.5).abs() < 0.1);
+                assert!((to.angle(&center).to_degrees() - 67.5).abs() < 0.1);
+            },
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(deviation_ticks(&welded, &report, 0.05, 0.05).is_empty());
+    }
+}
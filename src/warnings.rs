@@ -0,0 +1,106 @@
+use crate::dxf::*;
+use serde::Serialize;
+use std::fmt;
+
+// coordinates past this are more likely the wrong units or a far-off origin than a real part
+pub const MAX_COORDINATE: f64 = 1e6;
+// share of the median segment length the resolution may reach before fits get loose
+pub const MAX_RESOLUTION_SHARE: f64 = 0.1;
+
+// A likely mistake in the input or settings that welding would otherwise carry through silently,
+// such as a drawing in micrometres welded at a resolution meant for millimetres.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Warning {
+    pub kind: &'static str,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "warning: {}", self.message)
+    }
+}
+
+fn extent(drawing: &Drawing) -> Option<(Point, Point)> {
+    drawing.entities().filter_map(Entity::bounding_box).reduce(|(min, max), (low, high)| (
+        Point { x: min.x.min(low.x), y: min.y.min(low.y) },
+        Point { x: max.x.max(high.x), y: max.y.max(high.y) },
+    ))
+}
+
+fn median_line_length(drawing: &Drawing) -> Option<f64> {
+    let mut lengths = drawing.entities().filter_map(|entity| match entity {
+        Entity::Line(from, to) if from != to => Some(from.dist(to)),
+        _ => None,
+    }).collect::<Vec<_>>();
+    if lengths.is_empty() {
+        return None;
+    }
+    let middle = lengths.len() / 2;
+    Some(*lengths.select_nth_unstable_by(middle, |a, b| a.total_cmp(b)).1)
+}
+
+// Checks a drawing about to be welded at `resolution` for magnitudes that suggest the wrong units, a
+// far-off origin or a resolution too coarse for its segments. Skips the resolution check when none
+// is given, as when a sweep picks it.
+pub fn check(drawing: &Drawing, resolution: Option<f64>) -> Vec<Warning> {
+    let mut warnings = vec![];
+    if let Some((min, max)) = extent(drawing) {
+        let size = (max.x - min.x).max(max.y - min.y);
+        let furthest = [min.x, min.y, max.x, max.y].iter().fold(0.0f64, |furthest, value| furthest.max(value.abs()));
+        if size > MAX_COORDINATE {
+            warnings.push(Warning {
+                kind: "units",
+                message: format!("the drawing spans {}, which suggests it is in smaller units than intended (e.g. micrometres instead of millimetres); rescale it with --scale", format_number(size)),
+            });
+        } else if furthest > MAX_COORDINATE {
+            warnings.push(Warning {
+                kind: "origin",
+                message: format!(
+                    "the drawing lies {} from the origin, where coordinates lose precision; move it back with --translate {},{}",
+                    format_number(furthest), format_number(-min.x), format_number(-min.y),
+                ),
+            });
+        }
+    }
+    if let (Some(resolution), Some(median)) = (resolution, median_line_length(drawing)) {
+        if resolution > median * MAX_RESOLUTION_SHARE {
+            warnings.push(Warning {
+                kind: "resolution",
+                message: format!(
+                    "the resolution {} is {:.0}% of the median segment length {}, so curves will be welded loosely; the drawing may be in smaller units than the resolution assumes, or needs a finer --preset",
+                    format_number(resolution), resolution / median * 100.0, format_number(median),
+                ),
+            });
+        }
+    }
+    warnings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn p(x: f64, y: f64) -> Point {
+        Point { x, y }
+    }
+
+    fn kinds(drawing: &Drawing, resolution: f64) -> Vec<&'static str> {
+        check(drawing, Some(resolution)).iter().map(|warning| warning.kind).collect()
+    }
+
+    #[test]
+    fn test_check() {
+        let steps = |origin: Point, step: f64| Drawing::new((0..10).map(|i| Entity::Line(
+            p(origin.x + i as f64 * step, origin.y),
+            p(origin.x + (i + 1) as f64 * step, origin.y),
+        )).collect());
+        assert!(kinds(&steps(p(0.0, 0.0), 1.0), 0.05).is_empty());
+        assert_eq!(kinds(&steps(p(0.0, 0.0), 1.0), 0.5), vec!["resolution"]);
+        assert_eq!(kinds(&steps(p(0.0, 0.0), 1e6), 0.05), vec!["units"]);
+        let far = steps(p(5e6, 2e6), 1.0);
+        assert_eq!(kinds(&far, 0.05), vec!["origin"]);
+        assert!(check(&far, Some(0.05))[0].message.contains("--translate -5000000,-2000000"));
+        assert!(check(&steps(p(0.0, 0.0), 1.0), None).is_empty());
+    }
+}
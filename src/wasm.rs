@@ -0,0 +1,13 @@
+use crate::dxf_process::DxfConfig;
+use wasm_bindgen::prelude::*;
+
+// `options_json` is a JSON object of DxfConfig fields, any of which may be omitted
+#[wasm_bindgen]
+pub fn weld(dxf_text: &str, options_json: &str) -> Result<String, JsValue> {
+    let config: DxfConfig = if options_json.trim().is_empty() {
+        DxfConfig::default()
+    } else {
+        serde_json::from_str(options_json).map_err(|e| JsValue::from_str(&e.to_string()))?
+    };
+    crate::weld_dxf(dxf_text, &config).map_err(|e| JsValue::from_str(&e.to_string()))
+}
@@ -0,0 +1,84 @@
+// Welds every drawing in tests/golden with each fitter and compares the output with the one recorded
+// in tests/golden/expected, so a fitter change can't move geometry unnoticed. After a change that
+// is meant to alter the output, re-record it with `cargo test --test golden -- --update-golden` and
+// review the diff of the expected files.
+use dxf_welder::diff::diff;
+use dxf_welder::dxf::Drawing;
+use dxf_welder::dxf_process::{DxfConfig, Fitter};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// how far welded geometry may move before it no longer matches the recorded output
+const TOLERANCE: f64 = 1e-6;
+
+const FITTERS: [(&str, Fitter); 2] = [("greedy", Fitter::Greedy), ("ransac", Fitter::Ransac)];
+
+fn inputs(dir: &Path) -> Vec<PathBuf> {
+    let mut inputs = fs::read_dir(dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "dxf"))
+        .collect::<Vec<_>>();
+    inputs.sort();
+    inputs
+}
+
+fn weld(input: &str, fitter: Fitter) -> String {
+    let config = DxfConfig { fitter, ..DxfConfig::default() };
+    dxf_welder::weld_dxf(input, &config).unwrap()
+}
+
+// whether `welded` matches the recorded output at `expected`, printing what differs when it doesn't
+fn check(case: &str, welded: &str, expected: &Path) -> bool {
+    let recorded = match fs::read_to_string(expected) {
+        Ok(recorded) => recorded,
+        Err(_) => {
+            println!("{}: no recorded output at {}", case, expected.display());
+            return false;
+        },
+    };
+    let diff = diff(&Drawing::parse(&recorded).unwrap(), &Drawing::parse(welded).unwrap(), TOLERANCE);
+    if diff.is_same(TOLERANCE) {
+        return true;
+    }
+    println!("{}: {} removed, {} added, hausdorff distance {}", case, diff.removed.len(), diff.added.len(), diff.hausdorff);
+    for (sign, unmatched) in diff.removed.iter().map(|u| ('-', u)).chain(diff.added.iter().map(|u| ('+', u))).take(10) {
+        println!("  {} [{}] {}", sign, unmatched.layer, serde_json::to_string(&unmatched.entity).unwrap());
+    }
+    false
+}
+
+fn main() {
+    // cargo passes libtest's own flags and filters along; only ours is looked at
+    let update = std::env::args().any(|arg| arg == "--update-golden");
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let mut failed = 0;
+    let mut cases = 0;
+    for input in inputs(&dir) {
+        let source = fs::read_to_string(&input).unwrap();
+        let stem = input.file_stem().unwrap().to_string_lossy().into_owned();
+        for (name, fitter) in FITTERS.iter() {
+            let case = format!("{} ({})", stem, name);
+            let expected = dir.join("expected").join(format!("{}.{}.dxf", stem, name));
+            let welded = weld(&source, *fitter);
+            cases += 1;
+            if update {
+                fs::write(&expected, &welded).unwrap();
+                println!("{}: recorded {}", case, expected.display());
+            } else if !check(&case, &welded, &expected) {
+                failed += 1;
+            }
+            // welded output, circles and all, welds again without moving further than the resolution
+            cases += 1;
+            let rewelded = diff(&Drawing::parse(&welded).unwrap(), &Drawing::parse(&weld(&welded, *fitter)).unwrap(), TOLERANCE);
+            if rewelded.hausdorff > DxfConfig::default().resolution {
+                println!("{} rewelded: hausdorff distance {}", case, rewelded.hausdorff);
+                failed += 1;
+            }
+        }
+    }
+    println!("golden: {} cases, {} failed", cases, failed);
+    if failed > 0 {
+        println!("re-record with `cargo test --test golden -- --update-golden` if the change is intended");
+        std::process::exit(1);
+    }
+}